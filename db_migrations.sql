@@ -194,3 +194,113 @@ insert into aadv_excluded_days values (to_date('2024-07-03', 'YYYY-MM-DD'), 'Ind
 -- Constrain speed limit
 -- this has been added to both test and production database
 alter table tc_header add constraint speedlimit_tc_header check (speedlimit > 0 and speedlimit < 90);
+
+-- Track the files that have been imported (by hash of their contents), to prevent importing the
+-- same file twice.
+create table import_file (
+    recordnum number not null,
+    hash varchar2(64) not null,
+    filename varchar2(255),
+    datetime timestamp default current_timestamp
+);
+create index import_file_hash on import_file (hash);
+
+-- Index the import log by the columns it's filtered by, so that querying it stays fast as it grows.
+create index import_log_recordnum_datetime on import_log (recordnum, datetime);
+create index import_log_datetime on import_log (datetime);
+
+-- The percentage of a class count's vehicles that are heavy vehicles (classes 4-13).
+alter table tc_header add pctheavy number(4,1);
+
+-- Axle correction factors (vehicles per axle) of counts made by counters counting axle hits.
+create table tc_axle_factor (
+    recordnum number primary key,
+    factor number(4,3) not null
+);
+
+-- Seasonal and day-of-week factors for estimating AADT from short counts, by factor group, and the
+-- rules assigning counts to factor groups (by functional class and/or MCD; null matches any).
+create table tc_adjustment_factor (
+    factor_group varchar2(50) not null,
+    month number(2) not null,
+    weekday varchar2(3) not null,
+    factor number(5,3) not null
+);
+create table tc_factor_group (
+    fc number,
+    mcd varchar2(10),
+    factor_group varchar2(50) not null
+);
+
+-- When each imported file was last modified and the number of rows inserted from it, so that files
+-- already fully imported can be skipped without reading them.
+alter table import_file add (mtime timestamp, rows_imported number);
+create index import_file_filename_mtime on import_file (filename, mtime);
+
+-- A structured record of every insert, update, and delete of a count's data: who did it (as
+-- which database user, and from which machine user), when, to which table, and how many rows.
+create table audit_log (
+    datetime timestamp default systimestamp not null,
+    username varchar2(128) not null,
+    os_user varchar2(128),
+    operation varchar2(10) not null,
+    tablename varchar2(30) not null,
+    recordnum number not null,
+    rows_affected number not null
+);
+create index audit_log_recordnum_datetime on audit_log (recordnum, datetime);
+create index audit_log_datetime on audit_log (datetime);
+
+-- The individual vehicles of counts imported with their raw data stored, so that they can be
+-- binned again (into other intervals or speed ranges) without their original files - along with
+-- the direction and lane each one's channel was mapped to, to bin them the same way.
+create table tc_rawvehicle (
+    recordnum number not null,
+    countdate date not null,
+    counttime date not null,
+    channel number(2,0) not null,
+    ctdir varchar2(10) not null check (
+        ctdir in ('north', 'east', 'west', 'south', 'northeast', 'northwest', 'southeast', 'southwest')
+    ),
+    countlane number(2,0) not null,
+    class number(2,0) not null,
+    speed number(5,1) not null
+);
+create index tc_rawvehicle_recordnum on tc_rawvehicle (recordnum, countdate);
+
+-- The manifest of each file processed by a run of the import program (as JSON: its hash, the
+-- rows extracted from it and inserted into each table, and the warnings raised), for
+-- reproducibility audits of the counts imported from it.
+create table import_manifest (
+    runstart timestamp not null,
+    recordnum number not null,
+    filename varchar2(255) not null,
+    hash varchar2(64),
+    imported number(1,0) not null,
+    manifest clob not null check (manifest is json)
+);
+create index import_manifest_recordnum on import_manifest (recordnum, runstart);
+
+-- Allow the intermediate directions (e.g. of a diagonal road) in the columns constrained to the
+-- cardinal ones above.
+alter table tc_clacount drop constraint ctdir_tc_clacount;
+alter table tc_clacount add constraint ctdir_tc_clacount check (ctdir in ('north', 'east', 'west', 'south', 'northeast', 'northwest', 'southeast', 'southwest') );
+alter table tc_specount drop constraint ctdir_tc_specount;
+alter table tc_specount add constraint ctdir_tc_specount check (ctdir in ('north', 'east', 'west', 'south', 'northeast', 'northwest', 'southeast', 'southwest') );
+alter table tc_volcount drop constraint cntdir_tc_volcount;
+alter table tc_volcount add constraint cntdir_tc_volcount check (cntdir in ('north', 'east', 'west', 'south', 'northeast', 'northwest', 'southeast', 'southwest') );
+alter table tc_15minvolcount drop constraint cntdir_tc_15minvolcount;
+alter table tc_15minvolcount add constraint cntdir_tc_15minvolcount check (cntdir in ('north', 'east', 'west', 'south', 'northeast', 'northwest', 'southeast', 'southwest') );
+
+-- The direction and lane each channel of a count imported with its raw vehicles stored was mapped
+-- to - including channels that recorded no vehicles - so that the count is binned again with the
+-- same lanes.
+create table tc_rawchannel (
+    recordnum number not null,
+    channel number(2,0) not null,
+    ctdir varchar2(10) not null check (
+        ctdir in ('north', 'east', 'west', 'south', 'northeast', 'northwest', 'southeast', 'southwest')
+    ),
+    countlane number(2,0) not null,
+    constraint tc_rawchannel_pk primary key (recordnum, channel)
+);
@@ -0,0 +1,312 @@
+//! Estimate AADT from short counts, with seasonal and day-of-week adjustment factors.
+//!
+//! The volume of a day of a short count depends on the month and day of the week it was counted
+//! in. To estimate the annual average daily traffic (AADT) from it, each full day's volume is
+//! multiplied by the adjustment factor for its month and day of the week, and the adjusted
+//! volumes are averaged. (Without this, only the raw average daily traffic can be given.)
+//!
+//! Factors differ between groups of roads, e.g. urban interstates and rural local roads. A count
+//! is assigned to a factor group by its location (MCD) or functional class, in its
+//! [metadata][Metadata]. A [`FactorTable`] - the factors of each group and the rules assigning
+//! counts to groups - is loaded either from the TC_ADJUSTMENT_FACTOR and TC_FACTOR_GROUP tables of
+//! the database or from two CSV files, like:
+//! ```csv
+//! group,month,weekday,factor
+//! urban,1,Mon,1.08
+//! urban,1,Tue,1.03
+//! ...
+//! ```
+//! and (with either `fc` or `mcd` left blank to match any, and both left blank for the default
+//! group):
+//! ```csv
+//! fc,mcd,group
+//! 1,,interstate
+//! ,4201703000,urban
+//! ,,urban
+//! ```
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use oracle::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{denormalize::NonNormalVolCount, CountError, Metadata};
+
+/// A rule assigning counts to a factor group.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GroupRule {
+    /// The functional class of counts in the group, or any if `None`.
+    pub fc: Option<u32>,
+    /// The location (MCD) of counts in the group, or any if `None`.
+    pub mcd: Option<String>,
+    pub group: String,
+}
+
+impl GroupRule {
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.fc.is_none_or(|v| metadata.fc == Some(v))
+            && self
+                .mcd
+                .as_ref()
+                .is_none_or(|v| metadata.mcd.as_ref() == Some(v))
+    }
+
+    /// How specific the rule is, with location more specific than functional class.
+    fn specificity(&self) -> u8 {
+        2 * self.mcd.is_some() as u8 + self.fc.is_some() as u8
+    }
+}
+
+/// A row of a CSV file of adjustment factors.
+#[derive(Debug, Deserialize)]
+struct FactorRow {
+    group: String,
+    month: u32,
+    weekday: String,
+    factor: f32,
+}
+
+/// Seasonal and day-of-week adjustment factors of each factor group, and the rules assigning
+/// counts to groups.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FactorTable {
+    factors: BTreeMap<(String, u32, u32), f32>,
+    rules: Vec<GroupRule>,
+}
+
+impl FactorTable {
+    /// Create a table from factors (by group, month, and day of the week) and group rules.
+    pub fn new(
+        factors: impl IntoIterator<Item = (String, u32, Weekday, f32)>,
+        rules: Vec<GroupRule>,
+    ) -> Result<Self, CountError> {
+        let mut table = Self {
+            factors: BTreeMap::new(),
+            rules,
+        };
+        for (group, month, weekday, factor) in factors {
+            if !(1..=12).contains(&month) || factor <= 0.0 {
+                return Err(CountError::AdjustmentFactorError(format!(
+                    "invalid factor {factor} for group {group}, month {month}"
+                )));
+            }
+            table
+                .factors
+                .insert((group, month, weekday.num_days_from_monday()), factor);
+        }
+        Ok(table)
+    }
+
+    /// Load a table from CSV files of factors and of group rules.
+    pub fn from_csv(factors: &Path, groups: &Path) -> Result<Self, CountError> {
+        let mut rows = vec![];
+        for row in csv::Reader::from_path(factors)?.deserialize() {
+            let row: FactorRow = row?;
+            rows.push((
+                row.group,
+                row.month,
+                parse_weekday(&row.weekday)?,
+                row.factor,
+            ));
+        }
+        let rules = csv::Reader::from_path(groups)?
+            .deserialize()
+            .collect::<Result<Vec<GroupRule>, _>>()?;
+        Self::new(rows, rules)
+    }
+
+    /// Load a table from the TC_ADJUSTMENT_FACTOR and TC_FACTOR_GROUP tables of the database.
+    pub fn from_db(conn: &Connection) -> Result<Self, CountError> {
+        let mut rows = vec![];
+        for row in conn.query_as::<(String, u32, String, f32)>(
+            "select factor_group, month, weekday, factor from tc_adjustment_factor",
+            &[],
+        )? {
+            let (group, month, weekday, factor) = row?;
+            rows.push((group, month, parse_weekday(&weekday)?, factor));
+        }
+        let mut rules = vec![];
+        for row in conn.query_as::<(Option<u32>, Option<String>, String)>(
+            "select fc, mcd, factor_group from tc_factor_group",
+            &[],
+        )? {
+            let (fc, mcd, group) = row?;
+            rules.push(GroupRule { fc, mcd, group });
+        }
+        Self::new(rows, rules)
+    }
+
+    /// Get the factor group of a count, from the most specific rule matching its metadata.
+    pub fn group(&self, metadata: &Metadata) -> Option<&str> {
+        self.rules
+            .iter()
+            .filter(|v| v.matches(metadata))
+            .max_by_key(|v| v.specificity())
+            .map(|v| v.group.as_str())
+    }
+
+    /// Get the factor of a group for a date.
+    pub fn factor(&self, group: &str, date: NaiveDate) -> Option<f32> {
+        self.factors
+            .get(&(
+                group.to_string(),
+                date.month(),
+                date.weekday().num_days_from_monday(),
+            ))
+            .copied()
+    }
+}
+
+fn parse_weekday(weekday: &str) -> Result<Weekday, CountError> {
+    Weekday::from_str(weekday.trim()).map_err(|_| {
+        CountError::AdjustmentFactorError(format!("invalid day of the week '{weekday}'"))
+    })
+}
+
+/// An estimate of the AADT of a count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AadtEstimate {
+    pub recordnum: u32,
+    pub group: String,
+    /// The number of full days of the count.
+    pub days: usize,
+    /// The average daily traffic, unadjusted.
+    pub adt: u32,
+    pub aadt: u32,
+}
+
+impl Display for AadtEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: AADT {} (ADT {} over {} days, factor group {})",
+            self.recordnum, self.aadt, self.adt, self.days, self.group
+        )
+    }
+}
+
+/// Get the total volume of each full day of a count, in all directions and lanes, from its
+/// [`NonNormalVolCount`]s.
+///
+/// A day is full if every hour of it has a volume in every direction and lane counted that day.
+pub fn daily_volumes(counts: &[NonNormalVolCount]) -> BTreeMap<NaiveDate, u32> {
+    let mut volumes: BTreeMap<NaiveDate, Option<u32>> = BTreeMap::new();
+    for count in counts {
+        let hours = count.hourly();
+        let total = hours.into_iter().sum::<Option<u32>>();
+        let volume = volumes.entry(count.date).or_insert(Some(0));
+        *volume = volume.zip(total).map(|(a, b)| a + b);
+    }
+    volumes
+        .into_iter()
+        .filter_map(|(date, volume)| volume.map(|v| (date, v)))
+        .collect()
+}
+
+/// Estimate the AADT of a count from the volumes of its full days.
+pub fn estimate_aadt(
+    metadata: &Metadata,
+    volumes: &BTreeMap<NaiveDate, u32>,
+    table: &FactorTable,
+) -> Result<AadtEstimate, CountError> {
+    let recordnum = metadata
+        .recordnum
+        .ok_or(CountError::MissingMetadata("recordnum"))?;
+    if volumes.is_empty() {
+        return Err(CountError::AdjustmentFactorError(format!(
+            "{recordnum}: no full days of counts"
+        )));
+    }
+    let group = table.group(metadata).ok_or_else(|| {
+        CountError::AdjustmentFactorError(format!("{recordnum}: no factor group"))
+    })?;
+
+    let mut adjusted = 0.0;
+    for (date, volume) in volumes {
+        let factor = table.factor(group, *date).ok_or_else(|| {
+            CountError::AdjustmentFactorError(format!(
+                "no factor for group {group} on {} in month {}",
+                date.weekday(),
+                date.month()
+            ))
+        })?;
+        adjusted += *volume as f32 * factor;
+    }
+    let days = volumes.len();
+    Ok(AadtEstimate {
+        recordnum,
+        group: group.to_string(),
+        days,
+        adt: (volumes.values().sum::<u32>() as f32 / days as f32).round() as u32,
+        aadt: (adjusted / days as f32).round() as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(fc: Option<u32>, mcd: Option<&str>, group: &str) -> GroupRule {
+        GroupRule {
+            fc,
+            mcd: mcd.map(String::from),
+            group: group.to_string(),
+        }
+    }
+
+    fn table() -> FactorTable {
+        FactorTable::new(
+            [
+                ("urban".to_string(), 4, Weekday::Mon, 1.1),
+                ("urban".to_string(), 4, Weekday::Tue, 0.9),
+                ("interstate".to_string(), 4, Weekday::Mon, 1.0),
+            ],
+            vec![
+                rule(None, None, "urban"),
+                rule(Some(1), None, "interstate"),
+                rule(Some(1), Some("4201703000"), "urban"),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn most_specific_group_selected() {
+        let table = table();
+        let mut metadata = Metadata {
+            recordnum: Some(123),
+            ..Default::default()
+        };
+        assert_eq!(table.group(&metadata), Some("urban"));
+        metadata.fc = Some(1);
+        assert_eq!(table.group(&metadata), Some("interstate"));
+        metadata.mcd = Some("4201703000".to_string());
+        assert_eq!(table.group(&metadata), Some("urban"));
+    }
+
+    #[test]
+    fn aadt_estimated_from_adjusted_days() {
+        let metadata = Metadata {
+            recordnum: Some(123),
+            ..Default::default()
+        };
+        let volumes = BTreeMap::from([
+            (NaiveDate::from_ymd_opt(2024, 4, 8).unwrap(), 1000),
+            (NaiveDate::from_ymd_opt(2024, 4, 9).unwrap(), 1200),
+        ]);
+        let estimate = estimate_aadt(&metadata, &volumes, &table()).unwrap();
+        assert_eq!(estimate.adt, 1100);
+        // (1000 * 1.1 + 1200 * 0.9) / 2
+        assert_eq!(estimate.aadt, 1090);
+
+        // No factor for Wednesday.
+        let volumes = BTreeMap::from([(NaiveDate::from_ymd_opt(2024, 4, 10).unwrap(), 1000)]);
+        assert!(matches!(
+            estimate_aadt(&metadata, &volumes, &table()),
+            Err(CountError::AdjustmentFactorError(_))
+        ));
+    }
+}
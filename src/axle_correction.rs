@@ -0,0 +1,319 @@
+//! Correct volume counts of axles to counts of vehicles.
+//!
+//! Some setups of tube counters count axle hits rather than vehicles. The
+//! [15-minute volume counts][FifteenMinuteVehicle] from them are corrected by multiplying them by
+//! an axle correction factor - the number of vehicles per axle - before they're inserted. A
+//! count's factor is, in order of preference:
+//!   - its record in the TC_AXLE_FACTOR table of the database
+//!   - its site in the [configuration file][AxleCorrectionConfig], either as a factor itself or
+//!     [derived][factor_from_class_count] from the classes of vehicles in a classification count
+//!     made nearby
+//!   - the default factor of the configuration file, if the count is from one of the counters it
+//!     lists as counting axles
+//!
+//! Counts without any factor (including all those from counters not known to count axles, without
+//! one of their own) are not corrected. The configuration file is the one set by the
+//! `AXLE_CORRECTION_CONFIG` env var, and is like:
+//! ```toml
+//! # The factor for counts from the counters below without their own (if any).
+//! default = 0.48
+//! # The counters (by ID) that count axles rather than vehicles.
+//! axle_counters = ["40972", "41223"]
+//! # The number of axles per vehicle of each class, 1-13, for factors derived from a
+//! # classification count (optional).
+//! axles = [2, 2, 2, 2.5, 2, 3, 4, 3.5, 5, 6, 5, 6, 7]
+//!
+//! [[sites]]
+//! recordnum = 166906
+//! factor = 0.45
+//!
+//! [[sites]]
+//! recordnum = 166907
+//! class_count = 166905
+//! ```
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use oracle::Connection;
+use serde::Deserialize;
+
+use crate::{
+    db, db::crud::Crud, CountError, FifteenMinuteVehicle, TimeBinnedVehicleClassCount, VehicleClass,
+};
+
+/// The typical number of axles per vehicle of each class, 1-13.
+pub const DEFAULT_AXLES: [f32; 13] = [
+    2.0, 2.0, 2.0, 2.5, 2.0, 3.0, 4.0, 3.5, 5.0, 6.0, 5.0, 6.0, 7.0,
+];
+
+/// Axle correction factors, from a TOML file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct AxleCorrectionConfig {
+    /// The factor for counts from [`axle_counters`][Self::axle_counters] without their own.
+    pub default: Option<f32>,
+    /// The IDs of the counters that count axles rather than vehicles.
+    #[serde(default)]
+    pub axle_counters: Vec<String>,
+    /// The number of axles per vehicle of each class, 1-13.
+    pub axles: Option<[f32; 13]>,
+    #[serde(default)]
+    pub sites: Vec<SiteFactor>,
+}
+
+/// The axle correction factor of a particular count.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SiteFactor {
+    pub recordnum: u32,
+    /// The factor itself.
+    pub factor: Option<f32>,
+    /// The classification count to derive the factor from.
+    pub class_count: Option<u32>,
+}
+
+impl AxleCorrectionConfig {
+    /// Get the configuration from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        for site in &config.sites {
+            if site.factor.is_some() == site.class_count.is_some() {
+                return Err(CountError::BadAxleFactor(format!(
+                    "{}: either factor or class_count must be given",
+                    site.recordnum
+                )));
+            }
+        }
+        let factors = config
+            .default
+            .iter()
+            .chain(config.sites.iter().filter_map(|v| v.factor.as_ref()));
+        for factor in factors {
+            validate(*factor)?;
+        }
+        Ok(config)
+    }
+
+    /// Get the configuration from the file set by the `AXLE_CORRECTION_CONFIG` env var, or the
+    /// default (no factors) if it isn't set.
+    pub fn from_env() -> Result<Self, CountError> {
+        match env::var("AXLE_CORRECTION_CONFIG") {
+            Ok(path) => Self::from_file(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Get the factor to correct a count from a counter with, if it has one.
+    pub fn factor(
+        &self,
+        conn: &Connection,
+        recordnum: u32,
+        counter_id: &str,
+    ) -> Result<Option<AxleFactor>, CountError> {
+        if let Some(factor) = db::get_axle_factor(conn, recordnum)? {
+            return Ok(Some(AxleFactor {
+                factor: validate(factor)?,
+                source: FactorSource::Database,
+            }));
+        }
+        if let Some(site) = self.sites.iter().find(|v| v.recordnum == recordnum) {
+            if let Some(factor) = site.factor {
+                return Ok(Some(AxleFactor {
+                    factor,
+                    source: FactorSource::Config,
+                }));
+            }
+            if let Some(class_count) = site.class_count {
+                let counts = TimeBinnedVehicleClassCount::select(conn, class_count)?;
+                let factor = factor_from_class_count(&counts, &self.axles.unwrap_or(DEFAULT_AXLES))
+                    .ok_or_else(|| {
+                        CountError::BadAxleFactor(format!(
+                            "{recordnum}: no vehicles in class count {class_count}"
+                        ))
+                    })?;
+                return Ok(Some(AxleFactor {
+                    factor,
+                    source: FactorSource::ClassCount(class_count),
+                }));
+            }
+        }
+        Ok(self.default_for(counter_id))
+    }
+
+    /// Get the default factor, if the counter is one that counts axles.
+    fn default_for(&self, counter_id: &str) -> Option<AxleFactor> {
+        if !self.axle_counters.iter().any(|v| v == counter_id) {
+            return None;
+        }
+        self.default.map(|factor| AxleFactor {
+            factor,
+            source: FactorSource::Default,
+        })
+    }
+}
+
+/// Check that a factor is plausible: more than 0, and no more than 1 vehicle per axle.
+fn validate(factor: f32) -> Result<f32, CountError> {
+    if factor > 0.0 && factor <= 1.0 {
+        Ok(factor)
+    } else {
+        Err(CountError::BadAxleFactor(format!(
+            "{factor} is not between 0 and 1"
+        )))
+    }
+}
+
+/// Where an [`AxleFactor`] came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FactorSource {
+    Database,
+    Config,
+    ClassCount(u32),
+    Default,
+}
+
+/// An axle correction factor (vehicles per axle) for a count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxleFactor {
+    pub factor: f32,
+    pub source: FactorSource,
+}
+
+impl AxleFactor {
+    /// Correct counts of axles to counts of vehicles.
+    pub fn apply(&self, counts: &mut [FifteenMinuteVehicle]) {
+        for count in counts {
+            count.count = (count.count as f32 * self.factor).round() as u16;
+        }
+    }
+}
+
+impl Display for AxleFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "axle correction factor {:.3} (", self.factor)?;
+        match self.source {
+            FactorSource::Database => write!(f, "from TC_AXLE_FACTOR)"),
+            FactorSource::Config => write!(f, "from the configuration of the site)"),
+            FactorSource::ClassCount(v) => write!(f, "from class count {v})"),
+            FactorSource::Default => write!(f, "default)"),
+        }
+    }
+}
+
+/// Derive an axle correction factor from the classes of vehicles in a classification count,
+/// given the number of axles per vehicle of each class, 1-13.
+///
+/// Unclassified vehicles are taken to be passenger cars, as they're included in class 2. Returns
+/// `None` if there are no vehicles.
+pub fn factor_from_class_count(
+    counts: &[TimeBinnedVehicleClassCount],
+    axles: &[f32; 13],
+) -> Option<f32> {
+    let mut vehicles = 0;
+    let mut total_axles = 0.0;
+    for count in counts {
+        // The classes are in order, so the unclassified vehicles (last) aren't counted again on
+        // their own: they're already in class 2.
+        for (class, per_vehicle) in VehicleClass::iter().zip(axles) {
+            let num = count.class(class);
+            vehicles += num;
+            total_axles += num as f32 * per_vehicle;
+        }
+    }
+    if vehicles == 0 {
+        None
+    } else {
+        Some(vehicles as f32 / total_axles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    #[test]
+    fn factor_derived_from_class_count() {
+        let time = NaiveDateTime::parse_from_str("2024-04-08 07:00", "%Y-%m-%d %H:%M").unwrap();
+        let count = TimeBinnedVehicleClassCount {
+            date: time.date(),
+            time,
+            lane: Some(1),
+            recordnum: 123,
+            direction: None,
+            c1: 0,
+            c2: 90,
+            c3: 0,
+            c4: 0,
+            c5: 0,
+            c6: 0,
+            c7: 0,
+            c8: 0,
+            c9: 10,
+            c10: 0,
+            c11: 0,
+            c12: 0,
+            c13: 0,
+            c15: Some(5),
+            total: 100,
+        };
+        // 100 vehicles (the 5 unclassified included in class 2) with 90 * 2 + 10 * 5 = 230 axles.
+        let factor = factor_from_class_count(&[count], &DEFAULT_AXLES).unwrap();
+        assert_eq!(factor, 100.0 / 230.0);
+        assert_eq!(factor_from_class_count(&[], &DEFAULT_AXLES), None);
+    }
+
+    #[test]
+    fn factor_applied_to_counts() {
+        let time = NaiveDateTime::parse_from_str("2024-04-08 07:00", "%Y-%m-%d %H:%M").unwrap();
+        let mut counts = vec![FifteenMinuteVehicle {
+            recordnum: 123,
+            date: time.date(),
+            time,
+            count: 101,
+            direction: None,
+            lane: Some(1),
+        }];
+        let factor = AxleFactor {
+            factor: 0.5,
+            source: FactorSource::Default,
+        };
+        factor.apply(&mut counts);
+        assert_eq!(counts[0].count, 51);
+        assert_eq!(factor.to_string(), "axle correction factor 0.500 (default)");
+    }
+
+    #[test]
+    fn config_validated() {
+        let config: AxleCorrectionConfig = toml::from_str(
+            r#"
+            default = 0.5
+            [[sites]]
+            recordnum = 166906
+            class_count = 166905
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.sites[0].class_count, Some(166905));
+        assert!(validate(2.0).is_err());
+    }
+
+    #[test]
+    fn default_only_for_axle_counters() {
+        let config: AxleCorrectionConfig = toml::from_str(
+            r#"
+            default = 0.5
+            axle_counters = ["40972"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.default_for("40972"),
+            Some(AxleFactor {
+                factor: 0.5,
+                source: FactorSource::Default,
+            })
+        );
+        assert_eq!(config.default_for("41223"), None);
+    }
+}
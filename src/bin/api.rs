@@ -0,0 +1,346 @@
+//! Serve count data from our database as JSON over HTTP.
+//!
+//! This is so that others (e.g. our web team) can get the import log, count metadata, and the
+//! results of data checks without reading the database's tables directly, and so without
+//! depending on its schema. It is only built with the `api` feature:
+//! `cargo run --bin api --features api`.
+//!
+//! The endpoints are:
+//!   - `GET /log` - the [import log][traffic_counts::db::ImportLogEntry], most recent first,
+//!     optionally for just one count (`?recordnum=<recordnum>`), at a minimum level
+//!     (`?level=<level>`), between two dates (`?from=<YYYY-MM-DD>&to=<YYYY-MM-DD>`), and a page at
+//!     a time (`?offset=<offset>&limit=<limit>`)
+//!   - `GET /metadata` - [metadata][traffic_counts::Metadata] of counts, most recent first, a page
+//!     at a time (`?offset=<offset>&limit=<limit>`, with a default limit of 100), with the total
+//!     number of them; [filtered][traffic_counts::db::MetadataQuery] by technician
+//!     (`?takenby=<initials>`), type of count (`?kind=<kind>`, e.g. "Class"), municipality
+//!     (`?mcd=<code>`) or county (`?county=<code>`), the last day counted
+//!     (`?from=<YYYY-MM-DD>&to=<YYYY-MM-DD>`), and status (`?status=<status>`), and sorted by
+//!     `?sort=recordnum`, `counted`, `imported`, or `created` (`&ascending=true` for oldest first)
+//!   - `GET /metadata/<recordnum>` - the metadata of a count, with its
+//!     [location][traffic_counts::location::Location] (road, municipality, county, and
+//!     coordinates) in a "location" field, filled in from the GeoJSON file set by
+//!     `LOCATIONS_GEOJSON`, if any
+//!   - `GET /check/<recordnum>` - the [report][traffic_counts::check_data::CheckReport] of the
+//!     data checks of a count
+//!   - `POST /records` - create new count records, returning their recordnums; the body is a JSON
+//!     object with the number of records to create ("number") and, optionally, the
+//!     [fields][traffic_counts::db::NewRecordFields] to fill in
+//!
+//! As with the import program, records are only created in the production database with
+//! `--confirm-prod` (or `DB_CONFIRM_PROD`), and only if changes can be recorded in its
+//! [audit log][traffic_counts::db::audit].
+//!
+//! Errors are returned as JSON objects with an "error" field, with a 404 status if the count
+//! doesn't exist, 400 if the request is invalid, 403 if it would write to the production
+//! database without that being confirmed, and 500 otherwise.
+//!
+//! The address to listen on is set with `--address` (or `API_ADDRESS`), by default
+//! 127.0.0.1:3000. Database credentials are read from the environment, as for the
+//! [import](../import/index.html) program, including those of the test schema with `--target
+//! test` (or `DB_TARGET`).
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::NaiveDate;
+use clap::Parser;
+use log::{error, Level, LevelFilter};
+use oracle::{pool::Pool, Connection};
+use serde::{Deserialize, Serialize};
+use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+
+use traffic_counts::{
+    check_data::{check_with, CheckReport, CheckRunner},
+    db::{
+        self, audit, retry::RetryPolicy, ConnectionSettings, DbTarget, ImportLogEntry,
+        ImportLogQuery, LocatedMetadata, MetadataPage, MetadataQuery, MetadataSort,
+        NewRecordFields,
+    },
+    location::Locations,
+    CountError, CountKind,
+};
+
+/// Serve count data from our database as JSON over HTTP.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// The address to listen on.
+    #[arg(long, env = "API_ADDRESS", default_value = "127.0.0.1:3000")]
+    address: SocketAddr,
+    /// The database to serve: "prod", or "test" (with the TEST_DB_* connection settings).
+    #[arg(long, env = "DB_TARGET", default_value_t = DbTarget::Prod)]
+    target: DbTarget,
+    /// Confirm that the production database is to be written to (by `POST /records`); it isn't
+    /// otherwise.
+    #[arg(long, env = "DB_CONFIRM_PROD")]
+    confirm_prod: bool,
+}
+
+/// The state shared by all handlers.
+struct AppState {
+    pool: Pool,
+    policy: RetryPolicy,
+    target: DbTarget,
+    confirm_prod: bool,
+    locations: Locations,
+    /// The data checks applied to counts.
+    checks: CheckRunner,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Load file containing environment variables, panic if it doesn't exist.
+    dotenvy::dotenv().expect("Unable to load .env file.");
+    let cli = Cli::parse();
+    TermLogger::init(
+        LevelFilter::Info,
+        Config::default(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    )
+    .expect("Unable to set up logging.");
+
+    let policy = RetryPolicy::from_env();
+    let pool = match connect(cli.target, &policy) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let locations = match Locations::from_env() {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Unable to load locations: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let state = Arc::new(AppState {
+        pool,
+        policy,
+        target: cli.target,
+        confirm_prod: cli.confirm_prod,
+        locations,
+        checks: CheckRunner::default(),
+    });
+
+    let app = Router::new()
+        .route("/log", get(import_log))
+        .route("/metadata", get(metadata_paginated))
+        .route("/metadata/:recordnum", get(metadata))
+        .route("/check/:recordnum", get(check_report))
+        .route("/records", post(create_records))
+        .with_state(state);
+
+    let listener = match tokio::net::TcpListener::bind(cli.address).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Unable to listen on {}: {e}", cli.address);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("{e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Create a connection pool to the target database, with the credentials in the environment.
+fn connect(target: DbTarget, policy: &RetryPolicy) -> Result<Pool, CountError> {
+    let settings = ConnectionSettings::from_env(target)?;
+    Ok(db::create_pool_with_retry(
+        &settings,
+        policy,
+        log::logger(),
+    )?)
+}
+
+/// Run a (blocking) database query with a connection from the pool, [retrying][db::retry] it if
+/// the connection is lost.
+///
+/// Each query is either a read or a single write on a new connection, so it's safe to retry.
+async fn query<T, F>(state: Arc<AppState>, f: F) -> Result<Json<T>, ApiError>
+where
+    T: Send + 'static,
+    F: Fn(&Connection) -> Result<T, CountError> + Send + 'static,
+{
+    let result = tokio::task::spawn_blocking(move || {
+        let mut conn = db::get_connection(&state.pool, &state.policy, log::logger())?;
+        db::retry_statement(&state.pool, &mut conn, &state.policy, log::logger(), &f)
+    })
+    .await
+    .map_err(|e| ApiError(CountError::DbError(e.to_string())))?;
+    Ok(Json(result?))
+}
+
+#[derive(Deserialize)]
+struct LogParams {
+    recordnum: Option<u32>,
+    level: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    offset: Option<u32>,
+    limit: Option<u32>,
+}
+
+async fn import_log(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<LogParams>,
+) -> Result<Json<Vec<ImportLogEntry>>, ApiError> {
+    let level = match params.level {
+        Some(level) => Some(
+            Level::from_str(&level)
+                .map_err(|_| ApiError(CountError::UnknownLogLevel(level.clone())))?,
+        ),
+        None => None,
+    };
+    let query_params = ImportLogQuery {
+        recordnum: params.recordnum,
+        level,
+        from: params.from,
+        to: params.to,
+        offset: params.offset,
+        limit: params.limit,
+    };
+    query(state, move |conn| {
+        Ok(db::get_import_log(conn, &query_params)?)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct MetadataParams {
+    takenby: Option<String>,
+    kind: Option<String>,
+    mcd: Option<String>,
+    county: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    status: Option<String>,
+    sort: Option<String>,
+    #[serde(default)]
+    ascending: bool,
+    offset: Option<u32>,
+    limit: Option<u32>,
+}
+
+async fn metadata_paginated(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<MetadataParams>,
+) -> Result<Json<MetadataPage>, ApiError> {
+    let count_kind = params.kind.map(|v| CountKind::from_str(&v)).transpose()?;
+    let sort = params
+        .sort
+        .map(|v| MetadataSort::from_str(&v))
+        .transpose()?
+        .unwrap_or_default();
+    let query_params = MetadataQuery {
+        takenby: params.takenby,
+        count_kind,
+        mcd: params.mcd,
+        county: params.county,
+        from: params.from,
+        to: params.to,
+        status: params.status,
+        sort,
+        ascending: params.ascending,
+        offset: params.offset,
+        limit: params.limit,
+    };
+    query(state, move |conn| {
+        db::get_metadata_paginated(conn, &query_params)
+    })
+    .await
+}
+
+async fn metadata(
+    State(state): State<Arc<AppState>>,
+    Path(recordnum): Path<u32>,
+) -> Result<Json<LocatedMetadata>, ApiError> {
+    let located = state.clone();
+    query(state, move |conn| {
+        db::get_located_metadata(conn, recordnum, &located.locations)
+    })
+    .await
+}
+
+async fn check_report(
+    State(state): State<Arc<AppState>>,
+    Path(recordnum): Path<u32>,
+) -> Result<Json<CheckReport>, ApiError> {
+    let checked = state.clone();
+    query(state, move |conn| {
+        // Make sure the count exists, so that a missing one is a 404.
+        db::get_metadata(conn, recordnum)?;
+        check_with(&checked.checks, recordnum, conn)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct NewRecords {
+    number: u32,
+    #[serde(flatten)]
+    fields: NewRecordFields,
+}
+
+async fn create_records(
+    State(state): State<Arc<AppState>>,
+    Json(new_records): Json<NewRecords>,
+) -> Result<Json<Vec<u32>>, ApiError> {
+    state.target.check_write(state.confirm_prod)?;
+    query(state, move |conn| {
+        audit::check_table(conn)?;
+        db::insert_empty_metadata(conn, new_records.number, &new_records.fields)
+    })
+    .await
+}
+
+/// An error, returned as JSON.
+struct ApiError(CountError);
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl<E: Into<CountError>> From<E> for ApiError {
+    fn from(value: E) -> Self {
+        ApiError(value.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            CountError::OracleError(ref e) if e.kind() == oracle::ErrorKind::NoDataFound => {
+                StatusCode::NOT_FOUND
+            }
+            CountError::InvalidMcd(_)
+            | CountError::InvalidField { .. }
+            | CountError::InvalidRecordNumber(_)
+            | CountError::UnknownLogLevel(_)
+            | CountError::UnknownCountType(_)
+            | CountError::UnknownMetadataSort(_) => StatusCode::BAD_REQUEST,
+            CountError::ProdWriteNotConfirmed => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        if status == StatusCode::INTERNAL_SERVER_ERROR {
+            error!("{}", self.0);
+        }
+        let body = ErrorBody {
+            error: self.0.to_string(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
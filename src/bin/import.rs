@@ -1,22 +1,303 @@
 //! Import traffic counts to our database from files.
-//! This program watches a directory for files to be uploaded to one of the following subdirectories:
+//! This program (with its `import` subcommand) watches a directory for files to be uploaded to one of the following subdirectories:
 //!   - vehicle/ - for raw, unbinned records of [individual vehicles][IndividualVehicle] containing vehicle class and speed, from STARneXt/JAMAR
+//!     or MetroCount (vehicle listings with speeds in mph and classes in the FHWA scheme)
 //!   - bicycle/ - for raw, unbinned records of [individual bicycles][IndividualBicycle] containing bicycle counts, from STARneXt/JAMAR
 //!   - 15minutevehicle/ - for [pre-binned, 15-minute volume counts][FifteenMinuteVehicle] from STARneXt/JAMAR
 //!   - 15minutebicycle/ - for [pre-binned, 15-minute bicycle counts][FifteenMinuteBicycle] from
 //!     Eco-Counter
 //!   - 15minutepedestrian/ - for [pre-binned, 15-minute pedestrian counts][FifteenMinutePedestrian]
 //!     from Eco-Counter
+//!   - hourlyclass/ - for [pre-binned, hourly class counts][TimeBinnedVehicleClassCount] from legacy
+//!     PEEK ADR counters
 //!
+//! The kind of count in a file is determined by the subdirectory it's in, and a file whose header
+//! doesn't match its subdirectory isn't imported. With `--detect-count-type` (or
+//! `IMPORT_DETECT_COUNT_TYPE`), it's [inferred from the header][InputCount::from_contents]
+//! instead, with the subdirectory only as a hint, and a file in the wrong one is imported with a
+//! warning. (Eco-Counter bicycle and pedestrian counts share a header, so must still be in the
+//! right subdirectory.)
+//!
+//! New files are noticed as soon as they are uploaded, via filesystem notifications. (The
+//! directory is also checked periodically regardless, in case a notification is missed.)
 //! When a file is found, the program verifies that it contains the correct/expected kind of data,
 //! derives the appropriate counts from it, and then inserts these into our database and removes
 //! the file.
 //!
-//! A [log][`LOG`] of the program's work is kept in the main directory.
+//! Several files may be uploaded at once in a zip archive, or a file compressed with gzip (.gz).
+//! Archives are [unpacked][traffic_counts::unpack] into the subdirectory they were uploaded to and
+//! then removed, and the files in them - whose names must follow the same specification as any
+//! other - are imported. An archive that can't be unpacked is renamed with ".failed" added to its
+//! name (e.g. "counts.zip.failed"), and left for someone to look at; any files unpacked from it
+//! before it failed are imported.
+//!
+//! A [log][`LOG`] of the program's work is kept in the log directory, in a file for each month
+//! (e.g. import-2024-06.log). With `--log-max-size` (or `IMPORT_LOG_MAX_SIZE`), in megabytes, a
+//! month's log is continued in numbered files (import-2024-06-1.log, ...) once it reaches that
+//! size, and with `--log-retention` (or `IMPORT_LOG_RETENTION`), only that many months of logs
+//! are kept. Warnings and errors about a count, including those about individual rows of its file
+//! that couldn't be parsed, are also entered into the database's import log (see the `log`
+//! subcommand), and every insert, update, and delete of a count's data is recorded in its
+//! [audit log][traffic_counts::db::audit] (without its table, the program refuses to start, as
+//! do the subcommands that change data). So that a long run isn't silent, the progress through
+//! the files of a run is logged as each is started, and the progress of inserting a file's
+//! records is shown in the terminal.
+//! Only one instance of the program imports from a data directory at once: a second one (e.g. a
+//! scheduled run while one started by hand is still going) exits with an error, leaving the files
+//! to the first. This is done with a lock on a file in the data directory, `.import.lock`.
 //! The program is able to log most errors and continue its execution,
 //! so that an error in one file will not prevent it from successfully processing another.
 //! The program itself should only fail if it is misconfigured, meaning that,
 //! once started successfully, it should run indefinitely.
+//! On SIGINT (Ctrl-C) or SIGTERM, it finishes the file it's importing, summarizes the run, and
+//! exits with code 130 (for SIGINT) or 143 (for SIGTERM); the next run picks up with the files
+//! left in the data directory. A second signal stops it immediately, with the same code. As all
+//! the tables of a count are committed together, that doesn't leave the count of the current
+//! file partly imported: if it wasn't yet committed, none of it is, and its file is imported
+//! again by the next run.
+//! If the connection to the database fails or is lost, connecting is [retried][db::retry], with
+//! increasing delays between attempts, before giving up on a file. A file given up on for such a
+//! reason - or because it was locked, e.g. while OneDrive synced it - is left in place (rather
+//! than cleaned up) and tried again at the end of the run, and then, if need be, by the next run
+//! - unless some of its records were already committed, as trying it again would only fail.
+//!
+//! A file for a count that already has data in the database is not imported, so that data is
+//! never inserted twice. To replace the existing data of a count (e.g. with a corrected file),
+//! use the `--replace` flag (or set the `IMPORT_REPLACE` environment variable to "true"), which
+//! deletes it before inserting. Similarly, a hash of the contents of every imported file is
+//! stored, and a file identical to one already imported is refused unless replacing (or with
+//! `--force`/`IMPORT_FORCE`). Files left in the data directory once imported are skipped on later
+//! runs, without being read again, unless they have been modified since or `--force` is set.
+//! Records within a file of individual vehicles that are exact duplicates of another (same time,
+//! lane, class, and speed) are removed before the data is processed.
+//!
+//! For counters still in the field, whose export file keeps growing, use `--append` (or
+//! `IMPORT_APPEND`) rather than replacing. A count's records are then imported from the last day of it already
+//! in the database on - that day is replaced, as it may have been partial - so the same file can
+//! be imported again and again as it grows.
+//!
+//! The speed limit and directions in a filename are sometimes mistyped. To cross-check them
+//! with those in the count's TC_HEADER record, set `--header-check` (or `IMPORT_HEADER_CHECK`) to
+//! "warn" (log any differences), "error" (don't import the count), or "source" (use those in
+//! TC_HEADER instead - except for directions a count can't have, e.g. north and east, in which
+//! case the filename's are kept and a warning logged). The default is "off".
+//!
+//! Counts rarely start and end on the boundaries of the periods they're binned into. By default,
+//! everything is imported, including the partial periods at the start and end of a count. To drop
+//! those, set `--partial-periods` (or `IMPORT_PARTIAL_PERIODS`) to "periods" (drop partial
+//! 15-minute periods from 15-minute counts and partial hours from hourly ones) or "days" (drop
+//! partial first and last days, for those counts used for AADT).
+//!
+//! The legacy reports read the rows of both directions combined (without a direction or lane) of
+//! the 15-minute and hourly volume tables. To create these alongside the rows of each direction
+//! of bidirectional counts, use `--combined-directions` (or `IMPORT_COMBINED_DIRECTIONS`); see
+//! [combined directions][traffic_counts::combined_directions].
+//!
+//! Only the binned counts created from individual vehicles are stored by default. To also store
+//! the individual vehicles themselves (their time, lane, class, and speed) in the TC_RAWVEHICLE
+//! table, so that a count can later be binned again - into other intervals or speed ranges -
+//! without finding its original file, use `--store-raw` (or `IMPORT_STORE_RAW`). When a count is
+//! replaced or appended to without it, any raw vehicles stored before are deleted, as they would
+//! no longer match the count.
+//!
+//! Files are imported in the order the filesystem lists them in. When many are waiting, set
+//! `--order` (or `IMPORT_ORDER`) to "count-date" to import the oldest counts first, or to
+//! "recordnum" to import them by recordnum, and `--priority-dirs` (`IMPORT_PRIORITY_DIRS`) to
+//! subdirectories of the data directory (comma-separated, e.g. "vehicle,15minutevehicle") whose
+//! files are imported before all others, in that order. See
+//! [import order][traffic_counts::import_order].
+//!
+//! To import only some of the files in the data directory, set `--include` (or `IMPORT_INCLUDE`)
+//! to glob patterns of them, relative to the data directory (e.g. "vehicle/**/rc-*.txt"), and/or
+//! `--exclude` (`IMPORT_EXCLUDE`) to patterns of those to skip (e.g. "vehicle/consultant/**").
+//! Files not imported are left where they are. An archive is matched by its own path (e.g.
+//! "vehicle/*.zip"), before it's unpacked; all the files in one that passes are imported, and
+//! one that doesn't is left packed. See [path filter][traffic_counts::path_filter].
+//!
+//! For counts of individual vehicles, the percentage of them that are
+//! [heavy vehicles][traffic_counts::heavy_vehicles] (classes 4-13) is recorded in the count's
+//! TC_HEADER record, and the directional split and K- and D-factors of each day of them are
+//! [logged][traffic_counts::peak_hour::DesignFactors], as is the share of them over the speed
+//! limit (see [speed compliance][traffic_counts::speed_compliance]). When appending, the
+//! percentage of heavy vehicles and design factors are of the whole count in the database, not
+//! only the records appended.
+//!
+//! Some counters count axles rather than vehicles. 15-minute volume counts are corrected with the
+//! count's [axle correction factor][traffic_counts::axle_correction], if it has one - from the
+//! TC_AXLE_FACTOR table or the configuration file set by `AXLE_CORRECTION_CONFIG` (whose default
+//! factor is only for the counters it lists as counting axles) - and the factor applied is logged.
+//!
+//! The times of each count's records are checked before anything is inserted: a count is not
+//! imported if they go backwards (as when a counter's clock is reset), are in the future or
+//! more than 18 months old, or don't match the span of time the file's header claims it covers.
+//! To import counts older than that (e.g. a backlog of them), use `--allow-old` (or
+//! `IMPORT_ALLOW_OLD`).
+//!
+//! The counter of each count is checked against the [inventory][traffic_counts::counter_inventory]
+//! of counters in the file set by `COUNTER_INVENTORY`, if any: a count from a counter not in it
+//! is not imported, and warnings are logged if the counter's calibration had lapsed by the time
+//! the count was taken or it can't produce the type of count in the count's TC_HEADER record.
+//!
+//! Files that have been successfully imported can be archived, rather than removed (with
+//! `--cleanup`/`IMPORT_CLEANUP_FILES`) or left in the data directory to be processed again. To
+//! do so, set `--archive-dir` (or `IMPORT_ARCHIVE_DIR`) to a directory outside of the data
+//! directory, e.g. "imported". Each file (and its sidecar metadata file, if it has one) is moved
+//! to a subdirectory of it for the year and month it was imported, e.g. "imported/2024/06/".
+//! Files that are not successfully imported are still removed or left in place.
+//!
+//! Files can also be [fetched][traffic_counts::source] from wherever they're uploaded to, rather
+//! than copied into the data directory by hand. Set `--source` (or `IMPORT_SOURCE`) to a directory
+//! with the same subdirectories as the data directory - e.g. a network share - or, with the `sftp`
+//! feature, an SFTP URL (sftp://user@host[:port]/path). New files in it are copied into the data
+//! directory before each run. Once imported, they are deleted from the source with
+//! `--source-delete` (`IMPORT_SOURCE_DELETE`), or moved to a directory there with
+//! `--source-archive-dir` (`IMPORT_SOURCE_ARCHIVE_DIR`); otherwise they are left in place, and
+//! recorded (in `.import-fetched.log` in the data directory) so that they aren't fetched and
+//! imported again unless their contents change.
+//!
+//! To check files before importing them, use the `--dry-run` flag. This does
+//! a [dry run][traffic_counts::dry_run] of every file in the data directory - everything except
+//! writing to the database - prints a summary of what would be inserted and any issues found,
+//! and exits. The files are left in place.
+//!
+//! The 15-minute and hourly class and speed counts created from raw vehicle records (and the
+//! 15-minute [headways][traffic_counts::headway] between vehicles and hourly speed compliance)
+//! can also be
+//! [exported][traffic_counts::export] to files, for those without access to our database. To do
+//! so, set `--export-dir` (or the `EXPORT_DIR` environment variable) to the directory they should
+//! be written to, and optionally `--export-format` (`EXPORT_FORMAT`) to "csv" (the default),
+//! "json", or, with the `parquet` feature, "arrow" or "parquet" (for reading directly into Python
+//! or duckdb). Class counts are exported by FHWA class unless `--export-class-scheme`
+//! (`EXPORT_CLASS_SCHEME`) is set to another [classification scheme][traffic_counts::class_scheme]:
+//! "six-bin" or the path to a file of a custom one. The [location][traffic_counts::location] of
+//! each count - from its TC_HEADER record and the GeoJSON file set by `LOCATIONS_GEOJSON`, if
+//! any - is exported with it.
+//!
+//! After each run through the files in the data directory, a
+//! [summary][traffic_counts::import_summary] of it - the files processed, those skipped and why,
+//! the records inserted into each table, and the warnings raised - is logged. To also write it
+//! to a file, set `--summary-dir` (or `IMPORT_SUMMARY_DIR`) to a directory outside of the data
+//! directory, and optionally `--summary-format` (`IMPORT_SUMMARY_FORMAT`) to "json" (the
+//! default) or "csv". With the `email` feature, it can also be [emailed][traffic_counts::email]
+//! to a list of addresses (set by `NOTIFY_TO`, with the SMTP server's settings in `SMTP_HOST`,
+//! etc.), along with an alert if the program stops because of an error.
+//!
+//! For reproducibility audits, a [manifest][traffic_counts::manifest] of each run - each file
+//! processed, the hash of its contents, its recordnum, the rows extracted from it and inserted
+//! into each table, and the warnings raised - can be written as JSON to a directory, set by
+//! `--manifest-dir` (or `IMPORT_MANIFEST_DIR`), and/or, with `--store-manifest`
+//! (`IMPORT_STORE_MANIFEST`), stored in the IMPORT_MANIFEST table.
+//!
+//! To track the program's health over time, set `--metrics-file` (or `IMPORT_METRICS_FILE`) to a
+//! file in the directory of the Prometheus node exporter's textfile collector. After each run,
+//! [metrics][traffic_counts::metrics] of the files processed, records inserted, rows that couldn't
+//! be parsed, round-trip time to the database, and duration of runs are written to it.
+//!
+//! ## Running as a service
+//!
+//! The program is meant to run continuously, rather than be started by the Task Scheduler. Its
+//! [status][traffic_counts::status] - what it's doing, and a summary of its last run - can be
+//! served as JSON at `/status` (and its metrics at `/metrics`) by setting `--status-address` (or
+//! `IMPORT_STATUS_ADDRESS`), e.g. to "127.0.0.1:9184". On SIGHUP, it reloads the .env file before
+//! its next run (the database's retry policy, the axle correction config, the counter inventory,
+//! and email settings, but not its flags).
+//!
+//! On Windows, it can be installed as a service that runs the `import` subcommand, with the
+//! `service` subcommand (and the same flags), e.g.:
+//!
+//! ```text
+//! sc create traffic-counts-import binPath= "C:\traffic-counts\import.exe service" start= auto
+//! ```
+//!
+//! The service reads the .env file in the program's directory. Stopping it stops the program as
+//! SIGTERM would, and changing its parameters (`sc control traffic-counts-import paramchange`)
+//! reloads its configuration as SIGHUP would. If the program stops on its own (because of an
+//! error it can't carry on from, e.g. being misconfigured), the service reports exit code 1, so
+//! that it can be restarted by its recovery actions:
+//!
+//! ```text
+//! sc failure traffic-counts-import reset= 86400 actions= restart/60000
+//! sc failureflag traffic-counts-import 1
+//! ```
+//!
+//! Each count is [checked][traffic_counts::check_data] as it's imported. With `--check-interval
+//! <hours>` (or `IMPORT_CHECK_INTERVAL`), the counts imported in the last `--check-days` days
+//! (`IMPORT_CHECK_DAYS`, 30 by default) are also checked again every that many hours, starting
+//! when the program does, between runs, with what's found logged as when they were imported. This
+//! works the same when the `import` subcommand isn't run as a service. To check a single count,
+//! run the `check` subcommand.
+//!
+//! ## Usage
+//!
+//! The above is the `import` subcommand. Every flag of a subcommand can also be set by the
+//! environment variable shown by `--help`, including in a .env file.
+//!
+//! Every subcommand uses the production database, with the credentials in `DB_USERNAME` and
+//! `DB_PASSWORD`, unless `--target test` (or `DB_TARGET`) is set, in which case it uses the test
+//! schema, with those in `TEST_DB_USERNAME` and `TEST_DB_PASSWORD`. (Either can also be on a
+//! database other than the default one, set by `DB_CONNECT_STRING`/`TEST_DB_CONNECT_STRING`.) To
+//! guard against writing to production by mistake, the `import` (other than a dry run),
+//! `create-records`, `purge`, and `rebin` subcommands refuse to do so without `--confirm-prod`
+//! (`DB_CONFIRM_PROD`).
+//!
+//! Every subcommand exits with a non-zero status if it fails (for `import`, if it stops because
+//! of an error it can't carry on from), so that scripts can tell.
+//!
+//! The other subcommands are:
+//!   - `check <recordnum>` - [check the data][traffic_counts::check_data] of a count already in
+//!     the database again, without re-importing it (with `--json`, printing the report of every
+//!     check rather than logging the issues found)
+//!   - `aadt <recordnum>` - [estimate the AADT][traffic_counts::aadt] of a count in the database
+//!     with seasonal and day-of-week adjustment factors, from the database or (with `--factors`
+//!     and `--factor-groups`) CSV files
+//!   - `profile <recordnum>` - show the [average volume][traffic_counts::volume_profile] of a
+//!     count by hour of the day, in each direction, on weekdays and weekends; with `--dir`, it's
+//!     written to a file there instead, in the format set by `--format` ("csv" or "json")
+//!   - `days-of-week <recordnum>` - show the [average daily traffic][traffic_counts::day_of_week]
+//!     of a count on weekdays, weekends, and each day of the week, excluding the holidays in the
+//!     file set by `HOLIDAYS_CONFIG` (with `--json`, as JSON)
+//!   - `location <recordnum>` - show [where][traffic_counts::location] a count was taken: its
+//!     road, municipality, county, and coordinates, from its TC_HEADER record and the GeoJSON
+//!     file set by `LOCATIONS_GEOJSON`, if any (with `--json`, as JSON)
+//!   - `report <recordnum> --dir <dir>` - write the summary [report][traffic_counts::report] of a
+//!     count (its metadata, daily volumes, hourly profile, classes, speeds, and data check
+//!     findings) to an HTML file in the directory; with `--pdf`, it's also converted to PDF
+//!   - `log [recordnum]` - show the import log, for all counts or just one, most recent first;
+//!     `--level`, `--from`, and `--to` filter it by level and date, and `--offset` and `--limit`
+//!     page through it
+//!   - `create-records <number>` - create new, empty count records (or, with `--from
+//!     <recordnum>`, copies of an existing one), printing their recordnums; with `--kind`, `--mcd`,
+//!     and `--taken-by`, their type of count, municipality, and technician are filled in
+//!   - `purge <recordnum>` - delete all the imported data of a count (e.g. a bad import), its
+//!     AADV, and the record of the files it was imported from, and reset its metadata to not
+//!     imported, so that it can be imported again; this is entered into the import log
+//!   - `rebin <recordnum>` - replace the class, speed, and hourly volume counts of a count
+//!     imported with `--store-raw` with ones [binned again][traffic_counts::rebin] from its raw
+//!     vehicles (e.g. after the speed ranges change), with its channels mapped to the directions
+//!     and lanes they were when it was imported, and its partial periods dropped as they were
+//!     (with the same `--partial-periods`); this is entered into the import log
+//!   - `export <paths>...` - export the class and speed counts from files of individual vehicles,
+//!     without importing them (and, with `--vehicles`, the individual vehicles themselves),
+//!     along with their locations in the GeoJSON file set by `LOCATIONS_GEOJSON`, if any
+//!   - `export-tmg <recordnums>... --output <file>` - export counts in the database to a file of
+//!     [FHWA TMG][traffic_counts::tmg] records, e.g. to submit them to PennDOT/TMAS: hourly
+//!     volume records, or (with `--records class` or `--records speed`) vehicle classification
+//!     or speed records
+//!   - `export-xlsx <recordnums>... --dir <dir>` - export counts in the database as Excel
+//!     [workbooks][traffic_counts::workbook], one per count, with a summary sheet and sheets of
+//!     its 15-minute class counts, speed counts, and hourly volumes
+//!   - `store-local <paths>... --db <file>` - create the class and speed counts and hourly
+//!     average speeds from files of individual vehicles, as they are when imported, and store
+//!     them in a local SQLite database rather than ours (replacing any stored before), for
+//!     those without access to it (only with the `sqlite` feature)
+//!   - `filename <recordnum> <directions> <counter-id>` - print the name a file of a count
+//!     should have, to the filename specification below (with `--speed-limit`, if known, and
+//!     `--extension`, if not "csv"), after checking each part of it, including that the counter
+//!     is in the inventory in the file set by `COUNTER_INVENTORY`, if any
+//!   - `pull-eco-counter [recordnum]` - pull the counts of the sites configured for the
+//!     [Eco-Counter API][traffic_counts::eco_counter] (or just one of them) into the data
+//!     directory, where they are imported like any other file (only with the `eco-counter`
+//!     feature)
 //!
 //! ## Filename specification
 //!
@@ -61,10 +342,45 @@
 //!   - nnn
 //!   - sss
 //!
+//! Intermediate directions (ne, nw, se, sw) can be used in the same way, e.g. "ne", "nesw",
+//! "swne", "nene", or "nenene". A count can have one direction, two that are the same or
+//! opposite, or three that are the same.
+//!
 //! Note that for bicycle and pedestrian counts that are unidirectional, the program will use
 //! the total for each period, capturing both in/out directions and thus any wrong-way travel.
 //! In terms of the filename, this would mean using a single direction in that position.
 //!
+//! ### Sidecar metadata files
+//!
+//! Alternatively, the metadata can be provided in a JSON file alongside the data file, in which
+//! case the data file can be named anything. This is either a file with the same name but a .json
+//! extension (for one data file) or a `metadata.json` file (for all data files in its directory).
+//! It contains the same components, with the directions in the same form as above, e.g.:
+//!
+//! ```json
+//! {"recordnum": 166905, "directions": "ew", "counter_id": "40972", "speed_limit": 35}
+//! ```
+//!
+//! "speed_limit" can be omitted if it's unknown/not available. If a sidecar file is present,
+//! the filename is not used for metadata at all.
+//!
+//! By default, channel 1 of the counter is the first direction, in lane 1, channel 2 the second,
+//! in lane 2, and so on. Channels that aren't used are skipped with an "x" in place of their
+//! direction, e.g. "166905-xe-40972-35.csv" for a count of one direction, east, that was set up
+//! on channel 2. A file of individual vehicles none of which are on a channel with a direction is
+//! not imported. Otherwise, those vehicles on channels without a direction are dropped, and how
+//! many there were on each such channel (and when the first was) is logged; to refuse such files
+//! instead, use `--strict-channels` (or `IMPORT_STRICT_CHANNELS`).
+//!
+//! For counters with more channels (e.g. four lanes, two in each direction), the direction and
+//! lane of each channel can be set in the sidecar file:
+//!
+//! ```json
+//! {"recordnum": 166905, "directions": "ns", "counter_id": "40972", "speed_limit": 45,
+//!  "channels": {"1": {"direction": "n", "lane": 1}, "2": {"direction": "n", "lane": 2},
+//!               "3": {"direction": "s", "lane": 3}, "4": {"direction": "s", "lane": 4}}}
+//! ```
+//!
 //! ## Exporting from STARneXt
 //!
 //! To begin, open the STARneXt app from JAMAR and then open a .snj or .tf2 file. From there, it
@@ -128,54 +444,1015 @@
 //!     both toggled on and that both directions (in/out) are included.
 //!   - click on the **Download** (⤓) button, choosing *Spreadsheet (CSV)* as the format, comma   //!     as the delimiter, and save locally.
 
+use std::collections::{HashSet, VecDeque};
 use std::env;
-use std::fs::{self, OpenOptions};
+use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time;
+use std::time::{Duration, Instant};
 
-use log::{error, Level, LevelFilter};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use clap::{Args, Parser, Subcommand};
+use log::{error, info, warn, Level, LevelFilter, Log, Record};
+use notify::{event::ModifyKind, Event, EventKind, RecursiveMode, Watcher};
+use oracle::{pool::Pool, Connection};
+#[cfg(unix)]
+use signal_hook::{
+    consts::{SIGHUP, SIGTERM},
+    iterator::Signals,
+};
 use simplelog::{
-    ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode, WriteLogger,
+    ColorChoice, CombinedLogger, Config, ConfigBuilder, TermLogger, TerminalMode, WriteLogger,
 };
 
+#[cfg(feature = "email")]
+use traffic_counts::email::Notifier;
 use traffic_counts::{
-    check_data::check,
+    aadt::{daily_volumes, estimate_aadt, FactorTable},
+    axle_correction::AxleCorrectionConfig,
+    check_data::{check_and_log_with, check_with, CheckRunner},
+    class_scheme::ClassScheme,
+    combined_directions::{combine_fifteen_minute_counts, combine_hourly_counts},
+    counter_inventory::CounterInventory,
     create_binned_bicycle_vol_count, create_speed_and_class_count,
-    db::{self, crud::Crud},
+    day_of_week::{summarize_days_of_week, Holidays},
+    db::{
+        self,
+        audit::{self, Operation},
+        crud::{self, Crud, DEFAULT_BATCH_SIZE},
+        record_log::RecordLog,
+        retry::{RetryPolicy, Retryable},
+        ConnectionSettings, DbTarget, ImportLogQuery, NewRecordFields,
+    },
     denormalize::{Denormalize, *},
-    extract_from_file::{Extract, InputCount},
-    log_msg, FieldMetadata, FifteenMinuteBicycle, FifteenMinutePedestrian, FifteenMinuteVehicle,
-    IndividualBicycle, IndividualVehicle, TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
+    dry_run::dry_run,
+    export::{export, export_individual_vehicles, export_vehicle_counts, ExportFormat},
+    extract_from_file::{
+        check_times, file_hash, num_nondata_rows, ClaimedSpan, Extract, InputCount,
+    },
+    heavy_vehicles::create_heavy_vehicle_summary,
+    import_order::{order_paths, ImportOrder},
+    import_summary::{ImportSummary, SummaryLog},
+    location::Locations,
+    log_file::RotatingLogFile,
+    log_msg,
+    manifest::Manifest,
+    metadata_builder::FieldMetadataBuilder,
+    metrics::Metrics,
+    path_filter::PathFilter,
+    peak_hour::create_design_factors,
+    rebin::rebin,
+    report::{create_report, write_html, write_pdf},
+    source::Ingestion,
+    speed_compliance::create_speed_compliance,
+    status::{self, Activity, Status},
+    tmg::{self, TmgRecordType},
+    unpack::{is_archive, unpack},
+    volume_profile::create_volume_profile,
+    workbook::export_workbook,
+    CountError, CountKind, Dedup, FieldMetadata, FifteenMinuteBicycle, FifteenMinutePedestrian,
+    FifteenMinuteVehicle, GetDate, HeaderCheck, IndividualBicycle, IndividualVehicle,
+    PartialPeriods, RawChannel, RawVehicle, TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
     TimeInterval,
 };
 
-const LOG: &str = "import.log";
+/// Without the `email` feature, there's no one to notify.
+#[cfg(not(feature = "email"))]
+struct Notifier;
+
+#[cfg(not(feature = "email"))]
+impl Notifier {
+    fn from_env() -> Result<Option<Self>, CountError> {
+        Ok(None)
+    }
+
+    fn send_summary(&self, _summary: &ImportSummary) -> Result<(), CountError> {
+        Ok(())
+    }
+
+    fn send_alert(&self, _message: &str) -> Result<(), CountError> {
+        Ok(())
+    }
+}
+
+const LOG: &str = "import";
 const TIME_BETWEEN_LOOPS: u64 = 20;
+const TIME_TO_SETTLE: u64 = 5;
+const LOCK_FILE: &str = ".import.lock";
+/// The extension added to archives that couldn't be unpacked, to leave them out of later runs.
+const FAILED_ARCHIVE_EXTENSION: &str = "failed";
+/// The file in the data directory that files fetched from a source are recorded in.
+const FETCHED_FILE: &str = ".import-fetched.log";
+/// The exit code of the `import` subcommand when it stops because of SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+/// The exit code of the `import` subcommand when it stops because of SIGTERM.
+const EXIT_TERMINATED: i32 = 143;
+/// The exit code the service reports when the `import` subcommand stops without being asked to,
+/// i.e. because of an error it couldn't carry on from.
+#[cfg(windows)]
+const EXIT_SERVICE_FAILED: u32 = 1;
+
+/// Whether the program has been asked to stop, by SIGINT (Ctrl-C), SIGTERM, or (as a service)
+/// the Service Control Manager.
+static STOPPING: AtomicBool = AtomicBool::new(false);
+/// The code to exit with once stopped, for the signal the program was last asked to stop by.
+static EXIT_CODE: AtomicI32 = AtomicI32::new(EXIT_INTERRUPTED);
+/// Whether the program has been asked to reload its configuration, by SIGHUP or (as a service)
+/// the Service Control Manager.
+static RELOAD: AtomicBool = AtomicBool::new(false);
+/// Whether a subcommand failed (or, for the `import` subcommand, stopped because of an error it
+/// couldn't carry on from), so that the program exits with a non-zero status.
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Print an error to stderr, as `eprintln!` does, and exit with a non-zero status once done.
+macro_rules! fail {
+    ($($arg:tt)*) => {{
+        eprintln!($($arg)*);
+        FAILED.store(true, Ordering::SeqCst);
+    }};
+}
+
+/// Import traffic counts to our database from files, and manage them once imported.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// The database to use: "prod", or "test" (with the TEST_DB_* connection settings).
+    #[arg(long, global = true, env = "DB_TARGET", default_value_t = DbTarget::Prod)]
+    target: DbTarget,
+    /// Confirm that the production database is to be written to; it isn't otherwise.
+    #[arg(long, global = true, env = "DB_CONFIRM_PROD")]
+    confirm_prod: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Watch the data directory and import files as they are uploaded.
+    Import(Box<ImportArgs>),
+    /// Run the `import` subcommand as a Windows service (when started by the Service Control
+    /// Manager).
+    #[cfg(windows)]
+    Service(Box<ImportArgs>),
+    /// Check the data of a count already in the database, logging any issues found.
+    Check {
+        recordnum: u32,
+        /// Print the report of all checks as JSON, rather than logging the issues found.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Estimate the AADT of a count with seasonal and day-of-week adjustment factors.
+    Aadt {
+        recordnum: u32,
+        /// A CSV file of adjustment factors, rather than those in the database.
+        #[arg(long, env = "ADJUSTMENT_FACTORS", requires = "factor_groups")]
+        factors: Option<PathBuf>,
+        /// A CSV file of the rules assigning counts to factor groups, rather than those in the
+        /// database.
+        #[arg(long, env = "FACTOR_GROUPS", requires = "factors")]
+        factor_groups: Option<PathBuf>,
+    },
+    /// Show the average volume of a count by hour of the day, on weekdays and weekends.
+    Profile {
+        recordnum: u32,
+        /// The directory to write the profile to, rather than printing it.
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// The format to write the profile in: "csv" or "json".
+        #[arg(long, env = "EXPORT_FORMAT", default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
+    /// Show the average daily traffic of a count by day of the week, excluding holidays.
+    DaysOfWeek {
+        recordnum: u32,
+        /// Print the summary as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show where a count was taken.
+    Location {
+        recordnum: u32,
+        /// Print the location as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write the summary report of a count as an HTML file (and optionally PDF).
+    Report {
+        recordnum: u32,
+        /// The directory to write the report to.
+        #[arg(long, env = "REPORT_DIR")]
+        dir: PathBuf,
+        /// Also convert the report to PDF.
+        #[arg(long)]
+        pdf: bool,
+    },
+    /// Show the import log, for all counts or just one.
+    Log {
+        recordnum: Option<u32>,
+        /// Only show entries at this level or a more severe one, e.g. "warn".
+        #[arg(long)]
+        level: Option<Level>,
+        /// Only show entries from this day (YYYY-MM-DD) onward.
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only show entries up to and including this day (YYYY-MM-DD).
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// The number of entries to skip, for paging through the log.
+        #[arg(long)]
+        offset: Option<u32>,
+        /// The maximum number of entries to show, most recent first.
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+    },
+    /// Create new, empty count records, printing their recordnums.
+    CreateRecords {
+        number: u32,
+        /// Copy the fields of this existing record into the new ones.
+        #[arg(long)]
+        from: Option<u32>,
+        /// The type of count, e.g. "Class" or "15 min Volume".
+        #[arg(long)]
+        kind: Option<CountKind>,
+        /// The 10-digit code of the municipality (MCD) of the count.
+        #[arg(long)]
+        mcd: Option<String>,
+        /// The technician taking the count.
+        #[arg(long)]
+        taken_by: Option<String>,
+    },
+    /// Delete all the imported data of a count, so that it can be imported again.
+    Purge { recordnum: u32 },
+    /// Replace the class, speed, and hourly volume counts of a count with ones binned again from
+    /// its stored raw vehicles.
+    Rebin {
+        recordnum: u32,
+        /// What to do with partial periods at the start and end of the count: "keep", "periods"
+        /// (drop partial 15-minute periods), or "days" (drop partial days).
+        #[arg(long, env = "IMPORT_PARTIAL_PERIODS", default_value_t = PartialPeriods::Keep)]
+        partial_periods: PartialPeriods,
+        /// Also insert rows of both directions combined into the hourly volume table.
+        #[arg(long, env = "IMPORT_COMBINED_DIRECTIONS")]
+        combined_directions: bool,
+    },
+    /// Export counts in the database as FHWA TMG records.
+    ExportTmg {
+        /// The recordnums of the counts to export.
+        #[arg(required = true)]
+        recordnums: Vec<u32>,
+        /// The type of records to export: "volume", "class", or "speed".
+        #[arg(long, default_value_t = TmgRecordType::Volume)]
+        records: TmgRecordType,
+        /// The file to write the records to.
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Export counts in the database as Excel workbooks, one per count.
+    ExportXlsx {
+        /// The recordnums of the counts to export.
+        #[arg(required = true)]
+        recordnums: Vec<u32>,
+        /// The directory to write the workbooks to.
+        #[arg(long, env = "EXPORT_DIR")]
+        dir: PathBuf,
+    },
+    /// Export the class and speed counts created from files of individual vehicles.
+    Export {
+        /// Files, or directories of them, to export counts from.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// The directory to write the exported files to.
+        #[arg(long, env = "EXPORT_DIR")]
+        dir: PathBuf,
+        /// The format to export to: "csv" or "json" (or, with the `parquet` feature, "arrow" or
+        /// "parquet").
+        #[arg(long, env = "EXPORT_FORMAT", default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// The classification scheme to export class counts in: "fhwa", "six-bin", or the path
+        /// to a file of a custom one.
+        #[arg(long, env = "EXPORT_CLASS_SCHEME", default_value_t = ClassScheme::Fhwa)]
+        class_scheme: ClassScheme,
+        /// Also export the individual vehicles the counts are created from.
+        #[arg(long)]
+        vehicles: bool,
+    },
+    /// Store the counts created from files of individual vehicles in a local SQLite database,
+    /// rather than ours.
+    #[cfg(feature = "sqlite")]
+    StoreLocal {
+        /// Files, or directories of them, to store counts from.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// The SQLite database file to store them in (created if it doesn't exist).
+        #[arg(long, env = "SQLITE_DB")]
+        db: PathBuf,
+    },
+    /// Print the name a file of a count should have, to the filename specification.
+    Filename {
+        recordnum: u32,
+        /// The direction(s) of the count, e.g. "ew", preceded by an "x" for each unused channel.
+        directions: String,
+        /// The counter the count was taken with.
+        counter_id: String,
+        /// The speed limit, if known.
+        #[arg(long)]
+        speed_limit: Option<u8>,
+        /// The extension of the file.
+        #[arg(long, default_value = "csv")]
+        extension: String,
+    },
+    /// Pull the counts of the configured sites from the Eco-Counter API into the data directory.
+    #[cfg(feature = "eco-counter")]
+    PullEcoCounter {
+        /// Only pull the count with this recordnum.
+        recordnum: Option<u32>,
+        /// The directory the import program watches for files.
+        #[arg(long, env = "DATA_DIR")]
+        data_dir: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct ImportArgs {
+    /// The directory to watch for files.
+    #[arg(long, env = "DATA_DIR")]
+    data_dir: PathBuf,
+    /// The directory to keep the log in.
+    #[arg(long, env = "LOG_DIR")]
+    log_dir: PathBuf,
+    /// The maximum size of a log file, in megabytes, after which the month's log is continued
+    /// in a new file.
+    #[arg(long, env = "IMPORT_LOG_MAX_SIZE")]
+    log_max_size: Option<u64>,
+    /// The number of months of logs to keep, including the current one (by default, all).
+    #[arg(long, env = "IMPORT_LOG_RETENTION")]
+    log_retention: Option<u32>,
+    /// Remove files once they've been processed.
+    #[arg(long, env = "IMPORT_CLEANUP_FILES")]
+    cleanup: bool,
+    /// Replace the existing data of a count, rather than refusing to import it.
+    #[arg(long, env = "IMPORT_REPLACE")]
+    replace: bool,
+    /// Import files again even if they've already been fully imported (with the same path and
+    /// modification time), rather than skipping them.
+    #[arg(long, env = "IMPORT_FORCE")]
+    force: bool,
+    /// Import counts with records more than 18 months old (e.g. a backlog of old counts), rather
+    /// than refusing them as implausible.
+    #[arg(long, env = "IMPORT_ALLOW_OLD")]
+    allow_old: bool,
+    /// Append the records of a count from the last day of it already in the database on, rather
+    /// than refusing to import it, so that a file still growing can be imported repeatedly.
+    #[arg(long, env = "IMPORT_APPEND", conflicts_with = "replace")]
+    append: bool,
+    /// Infer the kind of count in a file from its header, using the directory it's in only as a
+    /// hint, rather than refusing files in the wrong directory.
+    #[arg(long, env = "IMPORT_DETECT_COUNT_TYPE")]
+    detect_count_type: bool,
+    /// What to do when the speed limit or directions in a filename differ from those in the
+    /// count's TC_HEADER record: "off", "warn", "error", or "source" (use TC_HEADER's).
+    #[arg(long, env = "IMPORT_HEADER_CHECK", default_value_t = HeaderCheck::Off)]
+    header_check: HeaderCheck,
+    /// What to do with partial periods at the start and end of counts: "keep", "periods"
+    /// (drop partial 15-minute periods/hours), or "days" (drop partial days).
+    #[arg(long, env = "IMPORT_PARTIAL_PERIODS", default_value_t = PartialPeriods::Keep)]
+    partial_periods: PartialPeriods,
+    /// Also insert rows of both directions combined into the 15-minute and hourly volume tables
+    /// of bidirectional counts, for the legacy reports.
+    #[arg(long, env = "IMPORT_COMBINED_DIRECTIONS")]
+    combined_directions: bool,
+    /// Refuse files of individual vehicles with any on channels that no direction is mapped to,
+    /// rather than dropping those vehicles.
+    #[arg(long, env = "IMPORT_STRICT_CHANNELS")]
+    strict_channels: bool,
+    /// Also store the individual vehicles of counts in the raw vehicle table, so that they can be
+    /// binned again without their files.
+    #[arg(long, env = "IMPORT_STORE_RAW")]
+    store_raw: bool,
+    /// The order to import files in: "filesystem", "count-date" (oldest first), or "recordnum".
+    #[arg(long, env = "IMPORT_ORDER", default_value_t = ImportOrder::Filesystem)]
+    order: ImportOrder,
+    /// Subdirectories of the data directory (comma-separated) whose files are imported before
+    /// all others, in the order given.
+    #[arg(long, env = "IMPORT_PRIORITY_DIRS", value_delimiter = ',')]
+    priority_dirs: Vec<PathBuf>,
+    /// Glob patterns (comma-separated) of the files to import, relative to the data directory,
+    /// e.g. "vehicle/**/rc-*.txt". Without any, all files are imported.
+    #[arg(long, env = "IMPORT_INCLUDE", value_delimiter = ',')]
+    include: Vec<String>,
+    /// Glob patterns (comma-separated) of files not to import, relative to the data directory,
+    /// e.g. "vehicle/consultant/**".
+    #[arg(long, env = "IMPORT_EXCLUDE", value_delimiter = ',')]
+    exclude: Vec<String>,
+    /// The directory to move files to once they've been imported, if any. This should not be
+    /// within the data directory.
+    #[arg(long, env = "IMPORT_ARCHIVE_DIR")]
+    archive_dir: Option<PathBuf>,
+    /// Where else to fetch files to import from, if anywhere: a directory (including a network
+    /// share) or, with the `sftp` feature, an SFTP URL (sftp://user@host[:port]/path).
+    #[arg(long, env = "IMPORT_SOURCE")]
+    source: Option<String>,
+    /// Delete files from the source once they've been imported.
+    #[arg(long, env = "IMPORT_SOURCE_DELETE")]
+    source_delete: bool,
+    /// The directory of the source to move files to once they've been imported, if any
+    /// (relative to the source, unless absolute).
+    #[arg(long, env = "IMPORT_SOURCE_ARCHIVE_DIR")]
+    source_archive_dir: Option<PathBuf>,
+    /// The number of records to insert into the database at once.
+    #[arg(long, env = "IMPORT_BATCH_SIZE", default_value_t = DEFAULT_BATCH_SIZE)]
+    batch_size: NonZeroUsize,
+    /// The directory to export class and speed counts to, if any.
+    #[arg(long, env = "EXPORT_DIR")]
+    export_dir: Option<PathBuf>,
+    /// The format to export to: "csv" or "json" (or, with the `parquet` feature, "arrow" or
+    /// "parquet").
+    #[arg(long, env = "EXPORT_FORMAT", default_value_t = ExportFormat::Csv)]
+    export_format: ExportFormat,
+    /// The classification scheme to export class counts in: "fhwa", "six-bin", or the path to a
+    /// file of a custom one.
+    #[arg(long, env = "EXPORT_CLASS_SCHEME", default_value_t = ClassScheme::Fhwa)]
+    export_class_scheme: ClassScheme,
+    /// The directory to write a summary of each run to, if any (it is always logged). This
+    /// should not be the data directory.
+    #[arg(long, env = "IMPORT_SUMMARY_DIR")]
+    summary_dir: Option<PathBuf>,
+    /// The format to write the summary in: "csv" or "json".
+    #[arg(long, env = "IMPORT_SUMMARY_FORMAT", default_value_t = ExportFormat::Json)]
+    summary_format: ExportFormat,
+    /// The directory to write a JSON manifest of the inputs and outputs of each run to, if any.
+    /// This should not be the data directory.
+    #[arg(long, env = "IMPORT_MANIFEST_DIR")]
+    manifest_dir: Option<PathBuf>,
+    /// Also store the manifest of each run in the import manifest table.
+    #[arg(long, env = "IMPORT_STORE_MANIFEST")]
+    store_manifest: bool,
+    /// The file to write metrics of the program's health to after each run, for Prometheus's
+    /// node exporter textfile collector, if any (e.g. "/var/lib/node_exporter/import.prom").
+    #[arg(long, env = "IMPORT_METRICS_FILE")]
+    metrics_file: Option<PathBuf>,
+    /// The address to serve the program's status (and metrics) on, if any, e.g. "127.0.0.1:9184".
+    #[arg(long, env = "IMPORT_STATUS_ADDRESS")]
+    status_address: Option<SocketAddr>,
+    /// Check the data of the counts imported recently again every this many hours, if set.
+    #[arg(long, env = "IMPORT_CHECK_INTERVAL", value_parser = clap::value_parser!(u64).range(1..))]
+    check_interval: Option<u64>,
+    /// The number of days back that counts imported in are checked again (see --check-interval).
+    #[arg(long, env = "IMPORT_CHECK_DAYS", default_value_t = 30)]
+    check_days: u32,
+    /// Summarize what would be imported from the files in the data directory, without using
+    /// the database, and exit.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+fn main() -> ExitCode {
+    run_command();
+    if FAILED.load(Ordering::SeqCst) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Run the subcommand the program was invoked with.
+fn run_command() {
+    // A Windows service is started in the system directory, so use the program's own directory
+    // instead, for the .env file and any relative paths in it.
+    #[cfg(windows)]
+    if env::args().any(|arg| arg == "service") {
+        if let Some(dir) = env::current_exe()
+            .ok()
+            .and_then(|v| v.parent().map(Path::to_owned))
+        {
+            let _ = env::set_current_dir(dir);
+        }
+    }
 
-fn main() {
     // Load file containing environment variables, panic if it doesn't exist.
     dotenvy::dotenv().expect("Unable to load .env file.");
 
-    // Get env var for path where CSVs will be, panic if it doesn't exist.
-    let data_dir =
-        env::var("DATA_DIR").expect("Unable to load data directory path from .env file.");
+    let Cli {
+        target,
+        confirm_prod,
+        command,
+    } = Cli::parse();
+    match command {
+        Command::Import(args) => {
+            import(*args, target, confirm_prod);
+            if STOPPING.load(Ordering::SeqCst) {
+                process::exit(EXIT_CODE.load(Ordering::SeqCst));
+            }
+        }
+        #[cfg(windows)]
+        Command::Service(args) => {
+            let run = move || {
+                import(*args, target, confirm_prod);
+                if STOPPING.load(Ordering::SeqCst) {
+                    0
+                } else {
+                    EXIT_SERVICE_FAILED
+                }
+            };
+            if let Err(e) = traffic_counts::service::run(run, stop, reload) {
+                fail!("{e}");
+            }
+        }
+        Command::Check { recordnum, json } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let runner = CheckRunner::default();
+            let report = if json {
+                check_with(&runner, recordnum, &conn)
+            } else {
+                check_and_log_with(&runner, recordnum, &conn)
+            };
+            match report {
+                Ok(v) if json => match serde_json::to_string_pretty(&v) {
+                    Ok(v) => println!("{v}"),
+                    Err(e) => fail!("Unable to serialize report: {e}"),
+                },
+                Ok(_) => (),
+                Err(e) => fail!("An error occurred while checking data: {e}"),
+            }
+        }
+        Command::Aadt {
+            recordnum,
+            factors,
+            factor_groups,
+        } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let table = match (factors, factor_groups) {
+                (Some(factors), Some(groups)) => FactorTable::from_csv(&factors, &groups),
+                _ => FactorTable::from_db(&conn),
+            };
+            let estimate = table.and_then(|table| {
+                let metadata = db::get_metadata(&conn, recordnum)?;
+                let volumes = daily_volumes(&db::get_volume_count(&conn, recordnum)?);
+                estimate_aadt(&metadata, &volumes, &table)
+            });
+            match estimate {
+                Ok(v) => println!("{v}"),
+                Err(e) => fail!("{e}"),
+            }
+        }
+        Command::Profile {
+            recordnum,
+            dir,
+            format,
+        } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let profile = match db::get_volume_count(&conn, recordnum) {
+                Ok(v) => create_volume_profile(&v),
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            match (profile, dir) {
+                (None, _) => fail!("No hourly volumes found for {recordnum}."),
+                (Some(v), None) => println!("{v}"),
+                (Some(v), Some(dir)) => {
+                    match export(&v.hours, &dir, recordnum, "volume-profile", format) {
+                        Ok(v) => println!("Volume profile written to {}.", v.display()),
+                        Err(e) => fail!("Unable to write volume profile: {e}"),
+                    }
+                }
+            }
+        }
+        Command::DaysOfWeek { recordnum, json } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let summary = Holidays::from_env().and_then(|holidays| {
+                let volumes = daily_volumes(&db::get_volume_count(&conn, recordnum)?);
+                Ok(summarize_days_of_week(recordnum, &volumes, &holidays))
+            });
+            match summary {
+                Ok(v) if json => match serde_json::to_string_pretty(&v) {
+                    Ok(v) => println!("{v}"),
+                    Err(e) => fail!("Unable to serialize summary: {e}"),
+                },
+                Ok(v) => println!("{v}"),
+                Err(e) => fail!("{e}"),
+            }
+        }
+        Command::Location { recordnum, json } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let location = Locations::from_env().and_then(|locations| {
+                Ok(db::get_located_metadata(&conn, recordnum, &locations)?.location)
+            });
+            match location {
+                Ok(v) if json => match serde_json::to_string_pretty(&v) {
+                    Ok(v) => println!("{v}"),
+                    Err(e) => fail!("Unable to serialize location: {e}"),
+                },
+                Ok(v) => println!("{v}"),
+                Err(e) => fail!("{e}"),
+            }
+        }
+        Command::Report {
+            recordnum,
+            dir,
+            pdf,
+        } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let written = Locations::from_env()
+                .and_then(|locations| create_report(&conn, recordnum, &locations))
+                .and_then(|report| write_html(&report, &dir))
+                .and_then(|html| {
+                    println!("Report written to {}", html.display());
+                    if pdf {
+                        let pdf = write_pdf(&html)?;
+                        println!("Report written to {}", pdf.display());
+                    }
+                    Ok(())
+                });
+            if let Err(e) = written {
+                fail!("{e}");
+            }
+        }
+        Command::Log {
+            recordnum,
+            level,
+            from,
+            to,
+            offset,
+            limit,
+        } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let query = ImportLogQuery {
+                recordnum,
+                level,
+                from,
+                to,
+                offset,
+                limit: Some(limit),
+            };
+            match db::get_import_log(&conn, &query) {
+                Ok(v) => {
+                    for entry in v.iter() {
+                        println!(
+                            "{} {} {}: {}",
+                            entry.datetime.map(|v| v.to_string()).unwrap_or_default(),
+                            entry.level,
+                            entry.recordnum,
+                            entry.msg
+                        );
+                    }
+                }
+                Err(e) => fail!("Unable to get import log: {e}"),
+            }
+        }
+        Command::CreateRecords {
+            number,
+            from,
+            kind,
+            mcd,
+            taken_by,
+        } => {
+            if let Err(e) = target.check_write(confirm_prod) {
+                fail!("{e}");
+                return;
+            }
+            let (_pool, conn) = match connect_to_write(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let fields = NewRecordFields {
+                count_kind: kind,
+                mcd,
+                takenby: taken_by,
+            };
+            let created = match from {
+                Some(recordnum) => db::get_metadata(&conn, recordnum).and_then(|mut metadata| {
+                    fields.apply(&mut metadata)?;
+                    db::insert_metadata_from_existing(&conn, number, metadata)
+                }),
+                None => db::insert_empty_metadata(&conn, number, &fields),
+            };
+            match created {
+                Ok(v) => {
+                    for recordnum in v {
+                        println!("{recordnum}");
+                    }
+                }
+                Err(e) => fail!("Unable to create records: {e}"),
+            }
+        }
+        Command::Purge { recordnum } => {
+            if let Err(e) = target.check_write(confirm_prod) {
+                fail!("{e}");
+                return;
+            }
+            let (_pool, conn) = match connect_to_write(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            match crud::purge(&conn, recordnum) {
+                Ok(v) => {
+                    let purged = v
+                        .iter()
+                        .map(|(table, n)| format!("{n} from {table}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let msg = format!("Purged imported data: {purged}");
+                    println!("{msg}");
+                    log_msg(recordnum, terminal_log(), Level::Info, &msg, &conn);
+                }
+                Err(e) => fail!("Unable to purge count: {e}"),
+            }
+        }
+        Command::Rebin {
+            recordnum,
+            partial_periods,
+            combined_directions,
+        } => {
+            if let Err(e) = target.check_write(confirm_prod) {
+                fail!("{e}");
+                return;
+            }
+            let (_pool, conn) = match connect_to_write(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let rebinned = rebin(
+                &conn,
+                recordnum,
+                partial_periods,
+                combined_directions,
+                DEFAULT_BATCH_SIZE,
+            );
+            match rebinned {
+                Ok(v) => {
+                    let rebinned = v
+                        .iter()
+                        .map(|(table, deleted, inserted)| {
+                            format!("{deleted} replaced by {inserted} in {table}")
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let msg = format!("Binned again from raw vehicles: {rebinned}");
+                    println!("{msg}");
+                    log_msg(recordnum, terminal_log(), Level::Info, &msg, &conn);
+                }
+                Err(e) => fail!("Unable to bin count again: {e}"),
+            }
+        }
+        Command::ExportTmg {
+            recordnums,
+            records,
+            output,
+        } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            match tmg::export_records(&conn, records, &recordnums, &output) {
+                Ok(v) => println!("{v} records written to {}", output.display()),
+                Err(e) => fail!("Unable to export TMG records: {e}"),
+            }
+        }
+        Command::ExportXlsx { recordnums, dir } => {
+            let (_pool, conn) = match connect(target, &terminal_log()) {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            let locations = match Locations::from_env() {
+                Ok(v) => v,
+                Err(e) => {
+                    fail!("{e}");
+                    return;
+                }
+            };
+            for recordnum in recordnums {
+                match export_workbook(&conn, recordnum, &locations, &dir) {
+                    Ok(v) => println!("Workbook written to {}", v.display()),
+                    Err(e) => fail!("Unable to export {recordnum}: {e}"),
+                }
+            }
+        }
+        Command::Export {
+            paths,
+            dir,
+            format,
+            class_scheme,
+            vehicles,
+        } => export_files(paths, &dir, format, &class_scheme, vehicles),
+        #[cfg(feature = "sqlite")]
+        Command::StoreLocal { paths, db } => store_local(paths, &db),
+        Command::Filename {
+            recordnum,
+            directions,
+            counter_id,
+            speed_limit,
+            extension,
+        } => {
+            let filename = CounterInventory::from_env().and_then(|inventory| {
+                FieldMetadataBuilder::new()
+                    .recordnum(recordnum)
+                    .directions(&directions)
+                    .counter_id(&counter_id)
+                    .speed_limit(speed_limit)
+                    .build(&inventory)?
+                    .filename(&extension)
+            });
+            match filename {
+                Ok(v) => println!("{v}"),
+                Err(e) => fail!("Unable to create filename: {e}"),
+            }
+        }
+        #[cfg(feature = "eco-counter")]
+        Command::PullEcoCounter {
+            recordnum,
+            data_dir,
+        } => pull_eco_counter(recordnum, &data_dir),
+    }
+}
+
+/// Pull the counts of the configured sites from the Eco-Counter API, writing them to the data
+/// directory to be imported.
+#[cfg(feature = "eco-counter")]
+fn pull_eco_counter(recordnum: Option<u32>, data_dir: &Path) {
+    use traffic_counts::eco_counter::{self, Client, EcoCounterConfig};
+
+    let (config, client) = match EcoCounterConfig::from_env().and_then(|config| {
+        let client = Client::from_env()?;
+        Ok((config, client))
+    }) {
+        Ok(v) => v,
+        Err(e) => {
+            fail!("{e}");
+            return;
+        }
+    };
+    let sites = config
+        .sites
+        .iter()
+        .filter(|site| recordnum.is_none_or(|v| v == site.recordnum));
+    for site in sites {
+        match client
+            .pull(site)
+            .and_then(|rows| eco_counter::write_file(site, &rows, data_dir))
+        {
+            Ok(v) => println!("{}: written to {}", site.recordnum, v.display()),
+            Err(e) => fail!("{}: not pulled: {e}", site.recordnum),
+        }
+    }
+}
+
+/// Connect to the target database, with the credentials in the environment,
+/// [retrying][db::retry] (and logging each retry to `log`) if unable to.
+fn connect(target: DbTarget, log: &impl Log) -> Result<(Pool, Connection), CountError> {
+    let settings = ConnectionSettings::from_env(target)?;
+    let policy = RetryPolicy::from_env();
+    let pool = db::create_pool_with_retry(&settings, &policy, log)?;
+    let conn = db::get_connection(&pool, &policy, log)?;
+    Ok((pool, conn))
+}
+
+/// Connect to the target database to change data in it, as [`connect`] does, after checking that
+/// changes can be recorded in its [audit log][audit].
+fn connect_to_write(target: DbTarget, log: &impl Log) -> Result<(Pool, Connection), CountError> {
+    let (pool, conn) = connect(target, log)?;
+    audit::check_table(&conn)?;
+    Ok((pool, conn))
+}
 
-    // Get env var for path where log will be, panic if it doesn't exist.
-    let log_dir = env::var("LOG_DIR").expect("Unable to load log directory path from .env file.");
+/// A log for subcommands other than `import`, which only logs warnings and errors to the terminal.
+fn terminal_log() -> Box<TermLogger> {
+    TermLogger::new(
+        LevelFilter::Warn,
+        Config::default(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    )
+}
 
-    // Get env var for whether or not to clean up files.
-    // (When run in production, we want to remove the data files after they've been processed.)
-    let cleanup_files = match env::var("IMPORT_CLEANUP_FILES") {
-        Ok(v) if v == "true" => true,
-        Ok(_) => false,
-        Err(_) => false,
+/// Watch the data directory and import files as they are uploaded.
+fn import(args: ImportArgs, target: DbTarget, confirm_prod: bool) {
+    let ImportArgs {
+        data_dir,
+        log_dir,
+        log_max_size,
+        log_retention,
+        cleanup: cleanup_files,
+        replace,
+        force,
+        allow_old,
+        append,
+        detect_count_type,
+        header_check,
+        partial_periods,
+        combined_directions,
+        strict_channels,
+        store_raw,
+        order,
+        priority_dirs,
+        include,
+        exclude,
+        archive_dir,
+        source,
+        source_delete,
+        source_archive_dir,
+        batch_size,
+        export_dir,
+        export_format,
+        export_class_scheme,
+        summary_dir,
+        summary_format,
+        manifest_dir,
+        store_manifest,
+        metrics_file,
+        status_address,
+        check_interval,
+        check_days,
+        dry_run: is_dry_run,
+    } = args;
+    let options = ImportOptions {
+        replace,
+        allow_old,
+        append,
+        partial_periods,
+        combined_directions,
+        strict_channels,
+        store_raw,
+        batch_size,
+        export_dir,
+        export_format,
+        export_class_scheme,
     };
+    let priority_dirs = priority_dirs
+        .iter()
+        .map(|v| data_dir.join(v))
+        .collect::<Vec<_>>();
 
     // Set up logging, panic if it fails.
     let import_config = ConfigBuilder::new().set_time_format_rfc3339().build();
-    let import_log = CombinedLogger::new(vec![
+    // (Warnings and errors are also kept, to summarize each run and to enter those about a count
+    // into the database.)
+    let import_log = SummaryLog::new(RecordLog::new(CombinedLogger::new(vec![
         TermLogger::new(
             LevelFilter::Debug,
             import_config.clone(),
@@ -185,523 +1462,646 @@ fn main() {
         WriteLogger::new(
             LevelFilter::Info,
             import_config,
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(format!("{log_dir}/{LOG}"))
-                .expect("Could not open log file."),
+            RotatingLogFile::new(
+                &log_dir,
+                LOG,
+                log_max_size.map(|v| v * 1024 * 1024),
+                log_retention,
+            )
+            .expect("Could not open log file."),
         ),
-    ]);
+    ])));
+    // Also use it for messages logged with the `log` macros, e.g. about rows of files that
+    // couldn't be parsed.
+    let import_log: &'static _ = Box::leak(Box::new(import_log));
+    if log::set_logger(import_log).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
 
-    // The database env vars aren't needed for a while, but if they aren't available, return
-    // early before doing any work.
-    let username = match env::var("DB_USERNAME") {
-        Ok(v) => v,
-        Err(e) => {
-            error!("Unable to load username from .env file: {e}.");
-            return;
-        }
-    };
-    let password = match env::var("DB_PASSWORD") {
+    let path_filter = match PathFilter::new(&include, &exclude) {
         Ok(v) => v,
         Err(e) => {
-            error!("Unable to load password from .env file: {e}.");
+            error!("Unable to filter files to import: {e}");
+            FAILED.store(true, Ordering::SeqCst);
             return;
         }
     };
-    let pool = db::create_pool(username, password).unwrap();
-    let conn = pool.get().unwrap();
 
-    loop {
-        // Recreate the logs in case they somehow get deleted.
-        let _ = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(format!("{log_dir}/{LOG}"))
-            .expect("Could not open log file.");
-
-        // Get all the paths of the files that need to be processed.
+    // With the --dry-run flag, summarize what would be imported from the files currently in the
+    // data directory, without using the database, and then exit.
+    if is_dry_run {
         let mut paths = vec![];
-        let paths = match collect_paths(data_dir.clone().into(), &mut paths) {
+        let collected = collect_paths(&data_dir, data_dir.clone(), &mut paths, false, &path_filter);
+        let paths = match collected {
             Ok(v) => v,
             Err(e) => {
                 error!("{e}");
+                FAILED.store(true, Ordering::SeqCst);
                 return;
             }
         };
-
-        // Iterate through all paths, extacting the data from the files, transforming it into the
-        // desired shape, and inserting it into the database.
-        // Exactly how the data is processed depends on what `InputCount` it is.
-        'paths_loop: for path in paths {
-            // Don't try to process the log files.
-            if path.extension().is_some_and(|x| x == "log") {
-                continue;
-            }
-            let count_type = match InputCount::from_parent_dir(path) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("{path:?} not processed: {e}");
-                    cleanup(cleanup_files, path);
-                    continue;
-                }
-            };
-
-            let metadata = match FieldMetadata::from_path(path) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("{path:?} not processed: {e}");
-                    cleanup(cleanup_files, path);
-                    continue;
-                }
-            };
-            let recordnum = metadata.clone().recordnum;
-
-            // Check that the count is already included in meta table in database - abort otherwise.
-            if conn
-                .query_row_as::<Option<String>>(
-                    "select recordnum from tc_header where recordnum = :1",
-                    &[&recordnum],
-                )
-                .is_err()
-            {
-                log_msg(
-                    recordnum,
-                    &import_log,
-                    Level::Error,
-                    "Not processed: recordnum not found in TC_HEADER table",
-                    &conn,
-                );
-                cleanup(cleanup_files, path);
-                continue;
+        order_paths(paths, order, &priority_dirs);
+        for path in paths {
+            match dry_run(path) {
+                Ok(v) => println!("{v}"),
+                Err(e) => println!("{}: would not be processed: {e}\n", path.display()),
             }
+        }
+        return;
+    }
 
-            // Process the file according to InputCount.
-            log_msg(
-                recordnum,
+    // Email a summary of each run, and an alert if the program stops because of an error, if
+    // configured to.
+    let mut notifier = match Notifier::from_env() {
+        Ok(v) => v,
+        Err(e) => {
+            log_error(
                 &import_log,
-                Level::Info,
-                &format!("Extracting data from {path:?}, a {count_type:?} count"),
-                &conn,
+                &format!("Unable to set up email notifications: {e}"),
             );
-            match count_type {
-                InputCount::IndividualVehicle => {
-                    // Extract data from CSV/text file.
-                    let individual_vehicles = match IndividualVehicle::extract(path) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!("Not processed: {e}"),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    };
+            FAILED.store(true, Ordering::SeqCst);
+            return;
+        }
+    };
 
-                    // Create two counts from this: 15-minute speed count and 15-minute class count
-                    let (speed_range_count, vehicle_class_count) = create_speed_and_class_count(
-                        TimeInterval::FifteenMin,
-                        metadata.clone(),
-                        individual_vehicles.clone(),
-                    );
+    if let Err(e) = target.check_write(confirm_prod) {
+        log_fatal(&import_log, notifier.as_ref(), &e.to_string());
+        return;
+    }
 
-                    // Create records for the non-normalized TC_SPESUM table (another one with
-                    // specific hourly fields, this time for average speed/hour).
-                    let non_normal_speedavg_count =
-                        create_non_normal_speedavg_count(metadata.clone(), individual_vehicles);
-
-                    // Delete existing records from db.
-                    TimeBinnedVehicleClassCount::delete(&conn, recordnum).unwrap();
-                    TimeBinnedSpeedRangeCount::delete(&conn, recordnum).unwrap();
-                    NonNormalAvgSpeedCount::delete(&conn, recordnum).unwrap();
-                    NonNormalVolCount::delete(&conn, recordnum).unwrap();
-
-                    // Create prepared statements and use them to insert counts.
-                    let mut prepared = TimeBinnedVehicleClassCount::prepare_insert(&conn).unwrap();
-                    for count in vehicle_class_count {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <TimeBinnedVehicleClassCount as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(
-                                recordnum, &import_log, Level::Info, &format!("Successfully committed class data insert to database ({table} table)"), &conn);
-                        }
-                        Err(e) => {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error committing class data insert to database ({table} table): {e}"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    }
+    // On SIGINT or SIGTERM, finish the current file and then stop (see `stop`), and on SIGHUP,
+    // reload the configuration before the next run.
+    if let Err(e) = ctrlc::set_handler(stop)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            #[cfg(unix)]
+            handle_unix_signals().map_err(|e| e.to_string())?;
+            Ok(())
+        })
+    {
+        log_fatal(
+            &import_log,
+            notifier.as_ref(),
+            &format!("Unable to handle signals: {e}"),
+        );
+        return;
+    }
 
-                    let mut prepared = TimeBinnedSpeedRangeCount::prepare_insert(&conn).unwrap();
-                    for count in speed_range_count {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <TimeBinnedSpeedRangeCount as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(recordnum, &import_log, Level::Info, &format!("Successfully committed speed range data insert to database ({table} table)"), &conn);
-                        }
-                        Err(e) => {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error committing speed range data insert to database ({table} table): {e}"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    }
+    // Only one instance of the program can import from the data directory at once, so that the
+    // same files aren't imported twice (e.g. by a scheduled run while one started by hand is still
+    // going). The lock is held until the program exits.
+    let _lock = match lock_data_dir(&data_dir) {
+        Ok(v) => v,
+        Err(e) => {
+            log_error(&import_log, &format!("Not importing: {e}"));
+            FAILED.store(true, Ordering::SeqCst);
+            return;
+        }
+    };
+
+    // The database isn't needed for a while, but if it isn't available, return early before
+    // doing any work.
+    let (pool, mut conn) = match connect_to_write(target, &import_log) {
+        Ok(v) => v,
+        Err(e) => {
+            log_fatal(
+                &import_log,
+                notifier.as_ref(),
+                &format!("Unable to connect to database: {e}"),
+            );
+            return;
+        }
+    };
+    let mut retry_policy = RetryPolicy::from_env();
+    // The data checks applied to each count once imported.
+    let check_runner = CheckRunner::default();
+    // Where counts were taken, to export with them.
+    let locations = match Locations::from_env() {
+        Ok(v) => v,
+        Err(e) => {
+            log_fatal(
+                &import_log,
+                notifier.as_ref(),
+                &format!("Unable to load locations: {e}"),
+            );
+            return;
+        }
+    };
+    let mut axle_correction = match AxleCorrectionConfig::from_env() {
+        Ok(v) => v,
+        Err(e) => {
+            log_fatal(
+                &import_log,
+                notifier.as_ref(),
+                &format!("Unable to load axle correction config: {e}"),
+            );
+            return;
+        }
+    };
+    let mut counter_inventory = match CounterInventory::from_env() {
+        Ok(v) => v,
+        Err(e) => {
+            log_fatal(
+                &import_log,
+                notifier.as_ref(),
+                &format!("Unable to load counter inventory: {e}"),
+            );
+            return;
+        }
+    };
 
-                    // Denormalize this data to insert into tc_volcount table.
-                    let denormalized_volcount =
-                        TimeBinnedVehicleClassCount::denormalize_vol_count(recordnum, &conn)
-                            .unwrap();
+    // Watch the data directory, so that files can be imported as soon as they are uploaded.
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).expect("Unable to create watcher for data directory.");
+    watcher
+        .watch(&data_dir, RecursiveMode::Recursive)
+        .expect("Unable to watch data directory.");
 
-                    // Create prepared statements and use them to insert counts.
-                    let mut prepared = NonNormalVolCount::prepare_insert(&conn).unwrap();
-                    for count in denormalized_volcount {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <NonNormalVolCount as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(recordnum, &import_log, Level::Info, &format!("Successfully committed denormalized class data insert to database ({table} table)"), &conn);
-                        }
-                        Err(e) => {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error committing denormalized class data insert to database ({table} table): {e}"), &conn);
+    let mut ingestion = match source
+        .map(|source| {
+            Ingestion::new(
+                source,
+                source_delete,
+                source_archive_dir,
+                data_dir.join(FETCHED_FILE),
+            )
+        })
+        .transpose()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            log_fatal(
+                &import_log,
+                notifier.as_ref(),
+                &format!("Unable to load the record of files fetched from source: {e}"),
+            );
+            return;
+        }
+    };
 
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    }
+    // Serve the status of the program, if configured to.
+    let status = Arc::new(Mutex::new(Status::new()));
+    if let Some(status_address) = status_address {
+        if let Err(e) = status::serve(status_address, status.clone()) {
+            log_fatal(
+                &import_log,
+                notifier.as_ref(),
+                &format!("Unable to serve status on {status_address}: {e}"),
+            );
+            return;
+        }
+    }
 
-                    let mut prepared = NonNormalAvgSpeedCount::prepare_insert(&conn).unwrap();
-                    for count in non_normal_speedavg_count {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <NonNormalAvgSpeedCount as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(recordnum, &import_log, Level::Info, &format!("Successfully committed denormalized speed data insert to database ({table} table)"), &conn);
-                        }
-                        Err(e) => {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error committing denormalized speed data insert to database ({table} table): {e}"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    }
+    let mut metrics = Metrics::new();
+    // When the counts imported recently were last checked again, if they have been.
+    let mut last_checked: Option<Instant> = None;
+
+    loop {
+        let run_start = Instant::now();
+
+        // Reload the configuration in the environment (including the .env file), if asked to.
+        // (Only that not set by the program's flags: the database's retry policy, the axle
+        // correction config, the counter inventory, and email notifications.)
+        if RELOAD.swap(false, Ordering::SeqCst) {
+            if let Err(e) = dotenvy::dotenv_override() {
+                log_error(&import_log, &format!("Unable to reload .env file: {e}"));
+            }
+            retry_policy = RetryPolicy::from_env();
+            match AxleCorrectionConfig::from_env() {
+                Ok(v) => axle_correction = v,
+                Err(e) => log_error(
+                    &import_log,
+                    &format!("Unable to reload axle correction config, keeping the old one: {e}"),
+                ),
+            }
+            match CounterInventory::from_env() {
+                Ok(v) => counter_inventory = v,
+                Err(e) => log_error(
+                    &import_log,
+                    &format!("Unable to reload counter inventory, keeping the old one: {e}"),
+                ),
+            }
+            match Notifier::from_env() {
+                Ok(v) => notifier = v,
+                Err(e) => log_error(
+                    &import_log,
+                    &format!("Unable to reload email notifications, keeping the old ones: {e}"),
+                ),
+            }
+            info!("Configuration reloaded");
+            status::update(&status, |v| v.config_loaded = Local::now().naive_local());
+        }
+
+        // Copy any new files from the source into the data directory.
+        if let Some(ingestion) = &mut ingestion {
+            if let Err(e) = ingestion.fetch_new(&data_dir) {
+                log_error(
+                    &import_log,
+                    &format!("Unable to fetch files from source: {e}"),
+                );
+            }
+        }
+
+        // Get all the paths of the files that need to be processed, in the order to process
+        // them.
+        let mut paths = vec![];
+        let collected = collect_paths(&data_dir, data_dir.clone(), &mut paths, true, &path_filter);
+        let paths = match collected {
+            Ok(v) => v,
+            Err(e) => {
+                log_fatal(
+                    &import_log,
+                    notifier.as_ref(),
+                    &format!("Unable to read data directory: {e}"),
+                );
+                return;
+            }
+        };
+        order_paths(paths, order, &priority_dirs);
+
+        // Iterate through all paths, extacting the data from the files, transforming it into the
+        // desired shape, and inserting it into the database.
+        // Exactly how the data is processed depends on what `InputCount` it is.
+        let mut summary = ImportSummary::new();
+        import_log.take_messages();
+        let mut queue = Queue::new(std::mem::take(paths));
+        while let Some(path) = queue.next() {
+            let path = &path;
+            if STOPPING.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // Don't try to process the log files.
+            if path.extension().is_some_and(|x| x == "log") {
+                continue;
+            }
+
+            // Skip files already fully imported (left in the data directory), without reading
+            // them again. (If this can't be checked, the file's hash is checked below.)
+            let mtime = modified(path);
+            if let Some(mtime) = mtime {
+                let imported =
+                    db::retry_statement(&pool, &mut conn, &retry_policy, import_log, |conn| {
+                        Ok(db::is_file_imported(conn, &path.to_string_lossy(), mtime)?)
+                    });
+                if !force && imported.unwrap_or(false) {
+                    continue;
                 }
-                InputCount::IndividualBicycle => {
-                    // Extract data from CSV/text file.
-                    let counts = match IndividualBicycle::extract(path) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!("Not processed: {e}"),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    };
+            }
+            summary.start_file(path, import_log.take_messages());
 
-                    // Create aggregated 15-minute bicycle count from this.
-                    let fifteen_min_volcount = create_binned_bicycle_vol_count(
-                        TimeInterval::FifteenMin,
-                        metadata.clone(),
-                        counts,
-                    );
+            // Report progress, since a run through many files (e.g. a full season's) can take a
+            // long time.
+            info!(
+                "Processing file {} of {} ({} remaining, {}s elapsed): {path:?}",
+                queue.done,
+                queue.total,
+                queue.total - queue.done,
+                run_start.elapsed().as_secs()
+            );
+            status::update(&status, |v| {
+                v.activity = Activity::Importing;
+                v.current_file = Some(path.clone());
+            });
 
-                    // Delete existing records from db.
-                    FifteenMinuteBicycle::delete(&conn, recordnum).unwrap();
+            // Anything logged from here until the recordnum is known isn't about the previous
+            // file's count.
+            import_log.inner().set_recordnum(None);
 
-                    // Create prepared statements and use them to insert counts.
-                    let mut prepared = FifteenMinuteBicycle::prepare_insert(&conn).unwrap();
-                    for count in fifteen_min_volcount {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum,  &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
+            // Get a new connection if the current one has been lost (e.g. the VPN dropped).
+            let ping_start = Instant::now();
+            if conn.ping().is_ok() {
+                metrics.db_latency(ping_start.elapsed());
+            } else {
+                conn = match db::get_connection(&pool, &retry_policy, import_log) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log_error(
+                            &import_log,
+                            &format!(
+                                "{path:?} not processed: unable to reconnect to database: {e}"
+                            ),
+                        );
+                        queue.retry(path);
+                        continue;
                     }
-                    let table = <FifteenMinuteBicycle as Crud>::COUNT_TABLE;
+                };
+            }
 
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Info,
-                                &format!(
-                                "Successfully committed data insert to database ({table} table)"
-                            ),
-                                &conn,
-                            );
-                        }
-                        Err(e) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!(
-                                    "Error committing data insert to database ({table} table): {e}"
-                                ),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue;
+            // Enter the warnings and errors logged about the previous file's count into the
+            // database.
+            import_log.inner().write_to_db(&conn);
+
+            // Get the kind of count from the file's location, and verify that the file contains
+            // that kind of data - or, if configured to, infer it from the file's header.
+            let location_type = InputCount::from_parent_dir(path);
+            let hint = location_type.as_ref().ok().copied();
+            let count_type = if detect_count_type {
+                InputCount::from_contents(path, hint)
+            } else {
+                location_type.and_then(|v| v.check_header(path).map(|_| v))
+            };
+            let count_type = match count_type {
+                Ok(v) => v,
+                Err(e) => {
+                    log_error(&import_log, &format!("{path:?} not processed: {e}"));
+                    queue.failed(&e, &summary, cleanup_files, path);
+                    continue;
+                }
+            };
+            // (Logged once the recordnum is known.)
+            let location_warning = match hint {
+                Some(v) if v == count_type => None,
+                _ => Some(format!(
+                    "File is not in the directory for a {count_type:?} count, as its header \
+                    indicates it is"
+                )),
+            };
+
+            let mut metadata = match FieldMetadata::from_path(path) {
+                Ok(v) => v,
+                Err(e) => {
+                    log_error(&import_log, &format!("{path:?} not processed: {e}"));
+                    queue.failed(&e, &summary, cleanup_files, path);
+                    continue;
+                }
+            };
+            let recordnum = metadata.clone().recordnum;
+            summary.set_recordnum(recordnum);
+            import_log.inner().set_recordnum(Some(recordnum));
+
+            if let Some(warning) = &location_warning {
+                log_msg(recordnum, import_log, Level::Warn, warning, &conn);
+            }
+
+            // Check that the count is already included in meta table in database - abort otherwise.
+            // (This and the other checks before importing only read, so they're retried if the
+            // connection is lost.)
+            match db::retry_statement(&pool, &mut conn, &retry_policy, import_log, |conn| {
+                Ok(conn.query_row_as::<Option<String>>(
+                    "select recordnum from tc_header where recordnum = :1",
+                    &[&recordnum],
+                )?)
+            }) {
+                Ok(_) => (),
+                Err(e) if e.is_transient() => {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Error,
+                        &format!("Not processed: unable to check TC_HEADER table: {e}"),
+                        &conn,
+                    );
+                    queue.retry(path);
+                    continue;
+                }
+                Err(_) => {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Error,
+                        "Not processed: recordnum not found in TC_HEADER table",
+                        &conn,
+                    );
+                    cleanup(cleanup_files, path);
+                    continue;
+                }
+            }
+
+            // Check the counter against the inventory, and the count's type against what it can
+            // produce. Its calibration is checked as of the date the count was taken: the start
+            // the file claims, or else the date last counted in TC_HEADER, or else today.
+            if !counter_inventory.counters.is_empty() {
+                let count_date = match ClaimedSpan::from_file(path).ok().flatten() {
+                    Some(span) => span.start.date(),
+                    None => {
+                        db::retry_statement(&pool, &mut conn, &retry_policy, import_log, |conn| {
+                            db::get_metadata(conn, recordnum)
+                        })
+                        .ok()
+                        .and_then(|v| v.datelastcounted)
+                        .unwrap_or_else(|| Local::now().date_naive())
+                    }
+                };
+                let checked =
+                    db::retry_statement(&pool, &mut conn, &retry_policy, import_log, |conn| {
+                        db::get_count_kind(conn, recordnum)
+                    })
+                    .and_then(|count_kind| {
+                        counter_inventory.check(
+                            &metadata.counter_id,
+                            count_date,
+                            count_kind.as_ref(),
+                        )
+                    });
+                match checked {
+                    Ok(warnings) => {
+                        for warning in warnings {
+                            log_msg(recordnum, import_log, Level::Warn, &warning, &conn);
                         }
                     }
+                    Err(e) => {
+                        log_msg(
+                            recordnum,
+                            import_log,
+                            Level::Error,
+                            &format!("Not processed: {e}"),
+                            &conn,
+                        );
+                        queue.failed(&e, &summary, cleanup_files, path);
+                        continue;
+                    }
                 }
-                InputCount::FifteenMinuteVehicle => {
-                    // Extract data from CSV/text file.
-                    let fifteen_min_volcount = match FifteenMinuteVehicle::extract(path) {
+            }
+
+            // Cross-check the speed limit and directions with those in TC_HEADER.
+            if header_check != HeaderCheck::Off {
+                let header =
+                    match db::retry_statement(&pool, &mut conn, &retry_policy, import_log, |conn| {
+                        db::get_metadata(conn, recordnum)
+                    }) {
                         Ok(v) => v,
                         Err(e) => {
                             log_msg(
                                 recordnum,
-                                &import_log,
+                                import_log,
                                 Level::Error,
-                                &format!("Not processed: {e}"),
+                                &format!("Not processed: unable to get TC_HEADER record: {e}"),
                                 &conn,
                             );
-                            cleanup(cleanup_files, path);
+                            queue.failed(&e, &summary, cleanup_files, path);
                             continue;
                         }
                     };
-
-                    // As they are already binned by 15-minute period, these need no further
-                    // processing; just insert into database.
-                    FifteenMinuteVehicle::delete(&conn, recordnum).unwrap();
-                    let mut prepared = FifteenMinuteVehicle::prepare_insert(&conn).unwrap();
-                    for count in fifteen_min_volcount {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum,  &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <FifteenMinuteVehicle as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Info,
-                                &format!(
-                                "Successfully committed data insert to database ({table} table)"
-                            ),
-                                &conn,
-                            );
-                        }
-                        Err(e) => {
+                let mismatches = metadata.header_mismatches(&header);
+                if !mismatches.is_empty() {
+                    let mismatches = mismatches.join("; ");
+                    match header_check {
+                        HeaderCheck::Error => {
                             log_msg(
                                 recordnum,
-                                &import_log,
+                                import_log,
                                 Level::Error,
                                 &format!(
-                                    "Error committing data insert to database ({table} table): {e}"
+                                    "Not processed: metadata differs from TC_HEADER: {mismatches}"
                                 ),
                                 &conn,
                             );
                             cleanup(cleanup_files, path);
                             continue;
                         }
-                    }
-
-                    // Denormalize this data to insert into tc_volcount table.
-                    let denormalized_volcount =
-                        FifteenMinuteVehicle::denormalize_vol_count(recordnum, &conn).unwrap();
-
-                    // Delete existing records from db.
-                    NonNormalVolCount::delete(&conn, recordnum).unwrap();
-
-                    // Create prepared statements and use them to insert counts.
-                    let mut prepared = NonNormalVolCount::prepare_insert(&conn).unwrap();
-                    for count in denormalized_volcount {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <NonNormalVolCount as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(recordnum, &import_log, Level::Info, &format!("Successfully committed denormalized data insert to database ({table} table)"), &conn);
-                        }
-                        Err(e) => {
-                            log_msg(recordnum, &import_log, Level::Error,&format!("Error committing denormalized data insert to database ({table} table): {e}"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    }
-                }
-                InputCount::FifteenMinuteBicycle => {
-                    // Extract data from CSV/text file.
-                    let fifteen_min_volcount = match FifteenMinuteBicycle::extract(path) {
-                        Ok(v) => v,
-                        Err(e) => {
+                        HeaderCheck::Source => {
                             log_msg(
                                 recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!("Not processed: {e}"),
+                                import_log,
+                                Level::Warn,
+                                &format!(
+                                    "Metadata differs from TC_HEADER; using TC_HEADER's: \
+                                    {mismatches}"
+                                ),
                                 &conn,
                             );
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    };
-
-                    // As they are already binned by 15-minute period, these need no further
-                    // processing; just insert into database.
-                    FifteenMinuteBicycle::delete(&conn, recordnum).unwrap();
-                    let mut prepared = FifteenMinuteBicycle::prepare_insert(&conn).unwrap();
-                    for count in fifteen_min_volcount {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(recordnum, &import_log, Level::Error, &format!("Error inserting count {count:?}: {e}; further processing has been abandoned"), &conn);
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <FifteenMinuteBicycle as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Info,
-                                &format!(
-                                "Successfully committed data insert to database ({table} table)"
-                            ),
-                                &conn,
-                            );
-                        }
-                        Err(e) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!(
-                                    "Error committing data insert to database ({table} table): {e}"
-                                ),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue;
+                            if let Err(e) = metadata.source_from_header(&header) {
+                                log_msg(
+                                    recordnum,
+                                    import_log,
+                                    Level::Warn,
+                                    &format!("Using the filename's directions: {e}"),
+                                    &conn,
+                                );
+                            }
                         }
+                        _ => log_msg(
+                            recordnum,
+                            import_log,
+                            Level::Warn,
+                            &format!("Metadata differs from TC_HEADER: {mismatches}"),
+                            &conn,
+                        ),
                     }
                 }
-                InputCount::FifteenMinutePedestrian => {
-                    // Extract data from CSV/text file.
-                    let fifteen_min_volcount = match FifteenMinutePedestrian::extract(path) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!("Not processed: {e}"),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    };
+            }
 
-                    // As they are already binned by 15-minute period, these need no further
-                    // processing; just insert into database.
-                    FifteenMinutePedestrian::delete(&conn, recordnum).unwrap();
-                    let mut prepared = FifteenMinutePedestrian::prepare_insert(&conn).unwrap();
-                    for count in fifteen_min_volcount {
-                        if let Err(e) = count.insert(&mut prepared) {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!("Error inserting count {count:?}: {e}"),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue 'paths_loop;
-                        }
-                    }
-                    let table = <FifteenMinutePedestrian as Crud>::COUNT_TABLE;
-                    match conn.commit() {
-                        Ok(()) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Info,
-                                &format!(
-                                "Successfully committed data insert to database ({table} table)"
-                            ),
-                                &conn,
-                            );
-                        }
-                        Err(e) => {
-                            log_msg(
-                                recordnum,
-                                &import_log,
-                                Level::Error,
-                                &format!(
-                                    "Error committing data insert to database ({table} table): {e}"
-                                ),
-                                &conn,
-                            );
-                            cleanup(cleanup_files, path);
-                            continue;
-                        }
-                    }
+            // Refuse a file that has already been imported, unless replacing existing data.
+            let hash = match file_hash(path) {
+                Ok(v) => v,
+                Err(e) => {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Error,
+                        &format!("Not processed: unable to hash file: {e}"),
+                        &conn,
+                    );
+                    queue.failed(&e, &summary, cleanup_files, path);
+                    continue;
+                }
+            };
+            summary.set_hash(&hash);
+            match db::retry_statement(&pool, &mut conn, &retry_policy, import_log, |conn| {
+                Ok(db::get_imported_file(conn, &hash)?)
+            }) {
+                Ok(Some(v)) if !replace && !force => {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Error,
+                        &format!(
+                            "Not processed: an identical file was already imported (recordnum \
+                            {v}); use --force to import it again"
+                        ),
+                        &conn,
+                    );
+                    cleanup(cleanup_files, path);
+                    continue;
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Warn,
+                        &format!("Unable to check whether file was already imported: {e}"),
+                        &conn,
+                    );
                 }
             }
 
+            // Process the file according to InputCount.
+            let skipped_rows = num_nondata_rows(path)
+                .map(|n| format!(", skipping the {n} rows before its data"))
+                .unwrap_or_default();
+            log_msg(
+                recordnum,
+                import_log,
+                Level::Info,
+                &format!("Extracting data from {path:?}, a {count_type:?} count{skipped_rows}"),
+                &conn,
+            );
+            let imported = {
+                let mut file = FileImport {
+                    recordnum,
+                    path,
+                    metadata: &metadata,
+                    conn: &conn,
+                    import_log,
+                    options: &options,
+                    locations: &locations,
+                    axle_correction: &axle_correction,
+                    summary: &mut summary,
+                    metrics: &mut metrics,
+                    inserted: vec![],
+                };
+                match count_type {
+                    InputCount::IndividualVehicle => import_individual_vehicles(&mut file),
+                    InputCount::IndividualBicycle => import_individual_bicycles(&mut file),
+                    InputCount::FifteenMinuteVehicle => import_fifteen_min_volume(&mut file),
+                    InputCount::HourlyClass => import_hourly_class(&mut file),
+                    InputCount::FifteenMinuteBicycle => import_fifteen_min_bicycle(&mut file),
+                    InputCount::FifteenMinutePedestrian => import_fifteen_min_pedestrian(&mut file),
+                }
+            };
+            if let Err(e) = imported {
+                // Leave the count's tables as they were, rather than with only some of its
+                // records deleted or inserted.
+                if let Err(e) = conn.rollback() {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Error,
+                        &format!("Unable to roll back changes to database: {e}"),
+                        &conn,
+                    );
+                }
+                queue.failed(&e, &summary, cleanup_files, path);
+                continue;
+            }
+
             // Update metadata table in db.
-            if let Err(e) = conn.execute(
-                "update tc_header SET
+            if let Err(e) = conn
+                .execute(
+                    "update tc_header SET
                 importdatadate = (select current_date from dual),
                 status = :1,
                 counterid = :2,
                 speedlimit = :3
                 where recordnum = :4",
-                &[
-                    &"imported",
-                    &metadata.counter_id,
-                    &metadata.speed_limit,
-                    &recordnum,
-                ],
-            ) {
+                    &[
+                        &"imported",
+                        &metadata.counter_id,
+                        &metadata.speed_limit,
+                        &recordnum,
+                    ],
+                )
+                .and_then(|stmt| {
+                    let rows = stmt.row_count()?;
+                    audit::record(&conn, Operation::Update, "tc_header", recordnum, rows)
+                })
+            {
                 log_msg(
                     recordnum,
-                    &import_log,
+                    import_log,
                     Level::Error,
                     &format!("Error updating metadata (tc_header table): {e}"),
                     &conn,
@@ -711,7 +2111,7 @@ fn main() {
             match conn.commit() {
                 Ok(()) => log_msg(
                     recordnum,
-                    &import_log,
+                    import_log,
                     Level::Info,
                     "Metadata updated (tc_header table)",
                     &conn,
@@ -719,7 +2119,7 @@ fn main() {
                 Err(e) => {
                     log_msg(
                         recordnum,
-                        &import_log,
+                        import_log,
                         Level::Error,
                         &format!("Error updating metadata (tc_header table): {e}"),
                         &conn,
@@ -732,7 +2132,7 @@ fn main() {
                 Ok(_) => {
                     log_msg(
                         recordnum,
-                        &import_log,
+                        import_log,
                         Level::Info,
                         "Intermediate table TC_COUNTDATE updated",
                         &conn,
@@ -741,7 +2141,7 @@ fn main() {
                 Err(e) => {
                     log_msg(
                         recordnum,
-                        &import_log,
+                        import_log,
                         Level::Error,
                         &format!("Failed to update intermediate table TC_COUNTDATE: {e}"),
                         &conn,
@@ -754,7 +2154,7 @@ fn main() {
                 Ok(_) => {
                     log_msg(
                         recordnum,
-                        &import_log,
+                        import_log,
                         Level::Info,
                         "Field SETDATE updated",
                         &conn,
@@ -763,7 +2163,7 @@ fn main() {
                 Err(e) => {
                     log_msg(
                         recordnum,
-                        &import_log,
+                        import_log,
                         Level::Error,
                         &format!("Failed to update field SETDATE: {e}"),
                         &conn,
@@ -780,7 +2180,7 @@ fn main() {
                     Ok(_) => {
                         log_msg(
                             recordnum,
-                            &import_log,
+                            import_log,
                             Level::Info,
                             "AADV calculated and inserted",
                             &conn,
@@ -789,7 +2189,7 @@ fn main() {
                     Err(e) => {
                         log_msg(
                             recordnum,
-                            &import_log,
+                            import_log,
                             Level::Error,
                             &format!("Failed to calculate/insert AADV: {e}"),
                             &conn,
@@ -798,41 +2198,1266 @@ fn main() {
                 }
             }
 
+            if let Err(e) = db::insert_imported_file(
+                &conn,
+                recordnum,
+                &hash,
+                &path.to_string_lossy(),
+                mtime,
+                summary.current_inserted(),
+            ) {
+                log_msg(
+                    recordnum,
+                    import_log,
+                    Level::Warn,
+                    &format!("Unable to record file as imported: {e}"),
+                    &conn,
+                );
+            }
+
+            summary.imported();
+
+            // Delete or archive the file in the source it was fetched from, if configured to.
+            if let Some(ingestion) = &mut ingestion {
+                if let Err(e) = ingestion.imported(path) {
+                    log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Warn,
+                        &format!("Unable to delete or archive file in source: {e}"),
+                        &conn,
+                    );
+                }
+            }
+
             // Check for potential issues with data, after it has been inserted into the database,
-            // and log them for review.
-            log_msg(recordnum, &import_log, Level::Info, "Checking data", &conn);
+            // and log them for review. (The data checks of class counts assume 15-minute bins, so
+            // aren't run on hourly ones.)
+            if count_type != InputCount::HourlyClass {
+                log_msg(recordnum, import_log, Level::Info, "Checking data", &conn);
+
+                if let Err(e) = check_and_log_with(&check_runner, recordnum, &conn) {
+                    log_msg(recordnum,  import_log, Level::Error, &format!("An error occurred while checking data: {e}; warnings likely to be incomplete or incorrect."), &conn);
+                }
+            }
+
+            // Archive the file, if configured to, or otherwise clean it up.
+            match &archive_dir {
+                Some(archive_dir) => match archive(archive_dir, path) {
+                    Ok(v) => log_msg(
+                        recordnum,
+                        import_log,
+                        Level::Info,
+                        &format!("File archived to {v:?}"),
+                        &conn,
+                    ),
+                    Err(e) => {
+                        log_msg(
+                            recordnum,
+                            import_log,
+                            Level::Warn,
+                            &format!("Unable to archive file: {e}"),
+                            &conn,
+                        );
+                        cleanup(cleanup_files, path);
+                    }
+                },
+                None => cleanup(cleanup_files, path),
+            }
+        }
+
+        import_log.inner().write_to_db(&conn);
+        import_log.inner().set_recordnum(None);
+
+        // Summarize the run, if there was anything to summarize.
+        summary.finish_file(import_log.take_messages());
+        if !summary.files.is_empty() {
+            import_log.log(
+                &Record::builder()
+                    .args(format_args!("{summary}"))
+                    .level(Level::Info)
+                    .build(),
+            );
+            if let Some(summary_dir) = &summary_dir {
+                if let Err(e) = summary.write(summary_dir, summary_format) {
+                    log_error(&import_log, &format!("Unable to write import summary: {e}"));
+                }
+            }
+            if manifest_dir.is_some() || store_manifest {
+                let manifest = Manifest::new(&summary);
+                if let Some(manifest_dir) = &manifest_dir {
+                    if let Err(e) = manifest.write(manifest_dir) {
+                        log_error(
+                            &import_log,
+                            &format!("Unable to write import manifest: {e}"),
+                        );
+                    }
+                }
+                if store_manifest {
+                    if let Err(e) = manifest.store(&conn) {
+                        log_error(
+                            &import_log,
+                            &format!("Unable to store import manifest: {e}"),
+                        );
+                    }
+                }
+            }
+            if let Some(notifier) = &notifier {
+                if let Err(e) = notifier.send_summary(&summary) {
+                    log_error(&import_log, &format!("Unable to email import summary: {e}"));
+                }
+            }
+        }
+
+        // Update the metrics of the program's health, and write them, if configured to.
+        metrics.run(&summary, run_start.elapsed());
+        if let Some(metrics_file) = &metrics_file {
+            if let Err(e) = metrics.write(metrics_file) {
+                log_error(&import_log, &format!("Unable to write metrics: {e}"));
+            }
+        }
+        status::update(&status, |v| {
+            v.activity = Activity::Waiting;
+            v.current_file = None;
+            v.metrics = metrics.clone();
+            if !summary.files.is_empty() {
+                v.last_summary = Some(summary);
+            }
+        });
+        if stopped() {
+            return;
+        }
+
+        // Check the counts imported recently again, if configured to and it's time to.
+        if let Some(hours) = check_interval {
+            let interval = Duration::from_secs(hours * 60 * 60);
+            if last_checked.is_none_or(|v| v.elapsed() >= interval) {
+                status::update(&status, |v| v.activity = Activity::Checking);
+                check_recent_counts(&conn, &check_runner, check_days, &import_log);
+                last_checked = Some(Instant::now());
+                status::update(&status, |v| v.activity = Activity::Waiting);
+                if stopped() {
+                    return;
+                }
+            }
+        }
+
+        // Wait for new files to try again.
+        wait_for_new_files(&rx);
+        if stopped() {
+            return;
+        }
+    }
+}
+
+/// Check the data of the counts imported in the last `days` days again, logging what's found as
+/// when they were imported.
+fn check_recent_counts(conn: &Connection, runner: &CheckRunner, days: u32, log: &impl Log) {
+    let since = Local::now().date_naive() - TimeDelta::days(days.into());
+    let recordnums = match db::get_counts_imported_since(conn, since) {
+        Ok(v) => v,
+        Err(e) => {
+            log_error(
+                log,
+                &format!("Unable to get the counts to check again: {e}"),
+            );
+            return;
+        }
+    };
+    info!(
+        "Checking the data of the {} counts imported since {since} again",
+        recordnums.len()
+    );
+    for recordnum in recordnums {
+        if STOPPING.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = check_and_log_with(runner, recordnum, conn) {
+            log_msg(
+                recordnum,
+                log,
+                Level::Error,
+                &format!(
+                    "An error occurred while checking data again: {e}; warnings likely to be \
+                    incomplete or incorrect."
+                ),
+                conn,
+            );
+        }
+    }
+}
+
+/// Ask the program to stop once it has finished the current file, as on SIGINT.
+fn stop() {
+    stop_with(EXIT_INTERRUPTED);
+}
+
+/// Ask the program to stop once it has finished the current file, as on SIGTERM.
+#[cfg(unix)]
+fn terminate() {
+    stop_with(EXIT_TERMINATED);
+}
+
+/// Ask the program to stop once it has finished the current file, and then exit with `code`. If
+/// it has already been asked to, exit immediately: what of the current file hasn't been
+/// committed is lost, but what has is left in the database.
+fn stop_with(code: i32) {
+    EXIT_CODE.store(code, Ordering::SeqCst);
+    if STOPPING.swap(true, Ordering::SeqCst) {
+        process::exit(code);
+    }
+    info!("Stopping once the current file is finished (signal again to stop immediately)");
+}
+
+/// Ask the program to reload its configuration before the next run.
+fn reload() {
+    RELOAD.store(true, Ordering::SeqCst);
+    info!("Reloading configuration before the next run");
+}
 
-            if let Err(e) = check(recordnum, &conn) {
-                log_msg(recordnum,  &import_log, Level::Error, &format!("An error occurred while checking data: {e}; warnings likely to be incomplete or incorrect."), &conn);
+/// Stop on SIGTERM and reload the configuration on SIGHUP. (SIGINT is handled by `ctrlc` on all
+/// platforms.)
+#[cfg(unix)]
+fn handle_unix_signals() -> io::Result<()> {
+    let mut signals = Signals::new([SIGTERM, SIGHUP])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGHUP => reload(),
+                _ => terminate(),
             }
+        }
+    });
+    Ok(())
+}
+
+/// Whether the program has been asked to stop, in which case it's logged that it's stopping.
+///
+/// Nothing needs to be saved to resume where it left off: files that weren't processed are still
+/// in the data directory, and those that were are skipped by the next run.
+fn stopped() -> bool {
+    if !STOPPING.load(Ordering::SeqCst) {
+        return false;
+    }
+    info!("Stopped; files not yet processed will be imported by the next run");
+    log::logger().flush();
+    true
+}
+
+/// The options of a run that affect how the records of each count are imported.
+struct ImportOptions {
+    replace: bool,
+    allow_old: bool,
+    append: bool,
+    partial_periods: PartialPeriods,
+    combined_directions: bool,
+    strict_channels: bool,
+    store_raw: bool,
+    batch_size: NonZeroUsize,
+    export_dir: Option<PathBuf>,
+    export_format: ExportFormat,
+    export_class_scheme: ClassScheme,
+}
+
+/// The import of the records of a count from a file, and what's needed to do it.
+///
+/// Each kind of count is imported by a function of its own (e.g. [`import_individual_vehicles`]),
+/// which logs why the file wasn't processed, or what went wrong part way through importing it,
+/// before returning the error.
+struct FileImport<'a, L: Log> {
+    recordnum: u32,
+    path: &'a Path,
+    metadata: &'a FieldMetadata,
+    conn: &'a Connection,
+    import_log: &'a L,
+    options: &'a ImportOptions,
+    locations: &'a Locations,
+    axle_correction: &'a AxleCorrectionConfig,
+    summary: &'a mut ImportSummary,
+    metrics: &'a mut Metrics,
+    /// What's been inserted but not yet committed: a description of the data, its table, and
+    /// the number of records.
+    inserted: Vec<(&'static str, &'static str, usize)>,
+}
+
+impl<L: Log> FileImport<'_, L> {
+    /// Log a message about the count.
+    fn log(&self, level: Level, message: &str) {
+        log_msg(self.recordnum, self.import_log, level, message, self.conn);
+    }
+
+    /// Log why the file isn't processed, returning the error.
+    fn not_processed(&self, e: CountError) -> CountError {
+        self.log(Level::Error, &format!("Not processed: {e}"));
+        e
+    }
+
+    /// Log the number of records dropped from partial periods, if any.
+    fn trimmed(&self, trimmed: usize) {
+        if trimmed > 0 {
+            self.log(
+                Level::Info,
+                &format!("{trimmed} records in partial periods were dropped"),
+            );
+        }
+    }
+
+    /// Extract the records of the file, logging how many rows couldn't be parsed, and check that
+    /// their times are plausible (see [`check_times`]).
+    fn extract<T: Extract>(
+        &mut self,
+        time: impl Fn(&T::Item) -> NaiveDateTime,
+    ) -> Result<Vec<T::Item>, CountError> {
+        let (records, skipped) =
+            T::extract_with_skipped(self.path).map_err(|e| self.not_processed(e))?;
+
+        self.metrics.parse_errors(skipped);
+        self.summary.extracted(records.len(), skipped);
+        if skipped > 0 {
+            self.log(
+                Level::Warn,
+                &format!("{skipped} rows could not be parsed and were skipped"),
+            );
+        }
+
+        check_times(
+            self.path,
+            records.iter().map(time),
+            Local::now().naive_local(),
+            self.options.allow_old,
+        )
+        .map_err(|e| self.not_processed(e))?;
+        Ok(records)
+    }
+
+    /// When appending, keep only the records of the count from the last day of it already in
+    /// `T`'s table on (that day is imported again, as it may have been partial), and log how many
+    /// are kept.
+    ///
+    /// Returns that day, or `None` if not appending or there are no records of the count yet.
+    fn keep_appended<T: Crud, R>(
+        &self,
+        records: &mut Vec<R>,
+        date: impl Fn(&R) -> NaiveDate,
+    ) -> Result<Option<NaiveDate>, CountError> {
+        if !self.options.append {
+            return Ok(None);
+        }
+        let from = match T::last_date(self.conn, self.recordnum) {
+            Ok(Some(from)) => from,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(self.not_processed(e)),
+        };
+        records.retain(|v| date(v) >= from);
+        if records.is_empty() {
+            return Err(self.not_processed(CountError::NothingToAppend(from)));
+        }
+        self.log(
+            Level::Info,
+            &format!("Appending {} records from {from} on", records.len()),
+        );
+        Ok(Some(from))
+    }
+
+    /// Prepare `T`'s table for the records of the count: when appending from a day on (see
+    /// [`keep_appended`](FileImport::keep_appended)), by deleting those from that day on, and
+    /// otherwise as with [`Crud::prepare_for_import`].
+    fn prepare<T: Crud>(&self, append_from: Option<NaiveDate>) -> Result<(), CountError> {
+        match append_from {
+            Some(from) => T::delete_from(self.conn, self.recordnum, from).map_err(CountError::from),
+            None => T::prepare_for_import(self.conn, self.recordnum, self.options.replace),
+        }
+        .map_err(|e| self.not_processed(e))
+    }
+
+    /// Insert records into `T`'s table in batches, without committing them (see
+    /// [`commit`](FileImport::commit)).
+    fn insert<T: Crud>(&mut self, records: &[T], data: &'static str) -> Result<(), CountError> {
+        let table = T::COUNT_TABLE;
+        if let Err(e) = T::insert_batch(self.conn, records, self.options.batch_size) {
+            self.log(
+                Level::Error,
+                &format!(
+                    "Error inserting {data} into {table} table: {e}; further processing has \
+                    been abandoned"
+                ),
+            );
+            return Err(e.into());
+        }
+        self.inserted.push((data, table, records.len()));
+        Ok(())
+    }
+
+    /// Commit the records inserted, together with those deleted to make way for them, logging
+    /// whether they were. Either all of the count's tables are changed, or none are.
+    fn commit(&mut self) -> Result<(), CountError> {
+        if let Err(e) = self.conn.commit() {
+            let tables = self.inserted.iter().map(|(_, table, _)| *table);
+            self.log(
+                Level::Error,
+                &format!(
+                    "Error committing data insert to database ({} tables): {e}",
+                    tables.collect::<Vec<_>>().join(", ")
+                ),
+            );
+            return Err(e.into());
+        }
+        for (data, table, rows) in std::mem::take(&mut self.inserted) {
+            self.log(
+                Level::Info,
+                &format!("Successfully committed {data} insert to database ({table} table)"),
+            );
+            self.summary.inserted(table, rows);
+        }
+        Ok(())
+    }
 
+    /// Denormalize the hourly volumes of the count from its records in `T`'s table, and insert
+    /// them into the TC_VOLCOUNT table.
+    fn insert_denormalized<T: Denormalize>(
+        &mut self,
+        append_from: Option<NaiveDate>,
+        data: &'static str,
+    ) -> Result<(), CountError> {
+        let table = T::NORMALIZED_TABLE;
+        let mut denormalized_volcount = T::denormalize_vol_count(self.recordnum, self.conn)
+            .map_err(|e| {
+                self.log(
+                    Level::Error,
+                    &format!(
+                        "Error denormalizing counts from {table} table: {e}; further processing \
+                        has been abandoned"
+                    ),
+                );
+                e
+            })?;
+
+        // (All of the count's data is denormalized, but only that of the days appended is
+        // inserted.)
+        if let Some(from) = append_from {
+            denormalized_volcount.retain(|v| v.date >= from);
+        }
+        if self.options.combined_directions {
+            denormalized_volcount.extend(combine_hourly_counts(&denormalized_volcount));
+        }
+        self.insert(&denormalized_volcount, data)
+    }
+
+    /// Create hourly volume counts from the 15-minute ones of a bicycle count (dropping partial
+    /// hours, if configured to), and insert them into the same table as those of motor vehicles.
+    fn insert_bicycle_hourly(
+        &mut self,
+        fifteen_min_volcount: &[FifteenMinuteBicycle],
+    ) -> Result<(), CountError> {
+        let mut hourly_volcount = fifteen_min_volcount.to_vec();
+        let trimmed = self.options.partial_periods.trim(
+            &mut hourly_volcount,
+            TimeInterval::Hour,
+            Some(TimeInterval::FifteenMin),
+            |v| v.time,
+        );
+        self.trimmed(trimmed);
+        let denormalized_volcount =
+            create_non_normal_bicycle_vol_count(self.metadata, &hourly_volcount);
+        self.insert(&denormalized_volcount, "denormalized data")
+    }
+}
+
+/// Import a file of individual vehicles: the 15-minute class and speed counts created from them,
+/// their hourly volumes and average speeds, and, if configured to, the vehicles themselves.
+fn import_individual_vehicles(file: &mut FileImport<impl Log>) -> Result<(), CountError> {
+    let recordnum = file.recordnum;
+    let metadata = file.metadata;
+    let options = file.options;
+
+    let mut individual_vehicles = file.extract::<IndividualVehicle>(|v| v.time)?;
+
+    // Refuse a file none of whose vehicles could be assigned a direction.
+    metadata
+        .check_channels(individual_vehicles.iter().map(|v| v.lane))
+        .map_err(|e| file.not_processed(e))?;
+
+    // Drop (and report) the vehicles on channels without a direction, or refuse the file if they
+    // aren't to be dropped.
+    let unmapped = metadata.unmapped_channels(individual_vehicles.iter().map(|v| (v.lane, v.time)));
+    if options.strict_channels && !unmapped.is_empty() {
+        let channels = unmapped.iter().map(|v| v.channel.to_string());
+        let e = CountError::UnmappedChannels(channels.collect::<Vec<_>>().join(", "));
+        let unmapped = unmapped.iter().map(|v| v.to_string());
+        file.log(
+            Level::Error,
+            &format!(
+                "Not processed: {e} ({})",
+                unmapped.collect::<Vec<_>>().join("; ")
+            ),
+        );
+        return Err(e);
+    }
+    for channel in &unmapped {
+        file.log(
+            Level::Warn,
+            &format!("Dropped {channel}, as no direction is mapped to it"),
+        );
+    }
+    individual_vehicles.retain(|v| metadata.channels.contains_key(&v.lane));
+
+    let duplicates = IndividualVehicle::remove_duplicates(&mut individual_vehicles);
+    if duplicates > 0 {
+        file.log(
+            Level::Warn,
+            &format!("{duplicates} duplicate records were removed"),
+        );
+    }
+
+    let append_from =
+        file.keep_appended::<TimeBinnedVehicleClassCount, _>(&mut individual_vehicles, |v| v.date)?;
+
+    // Keep the raw vehicles to store, if configured to, before any are dropped, with the
+    // direction and lane of their channels. (Those on channels without any have already been
+    // dropped.)
+    let raw_vehicles = options.store_raw.then(|| {
+        individual_vehicles
+            .iter()
+            .filter_map(|v| {
+                let channel = metadata.channels.get(&v.lane)?;
+                Some(RawVehicle::new(recordnum, v, *channel))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // Drop partial periods, if configured to: 15-minute ones for the binned counts, and hours
+    // for the hourly average speeds.
+    let partial_periods = options.partial_periods;
+    let mut hourly_vehicles = individual_vehicles.clone();
+    let trimmed = partial_periods.trim(
+        &mut individual_vehicles,
+        TimeInterval::FifteenMin,
+        None,
+        |v| v.time,
+    ) + partial_periods
+        .trim(&mut hourly_vehicles, TimeInterval::Hour, None, |v| v.time);
+    file.trimmed(trimmed);
+
+    // Create two counts from this: 15-minute speed count and 15-minute class count
+    let (speed_range_count, vehicle_class_count) = create_speed_and_class_count(
+        TimeInterval::FifteenMin,
+        metadata.clone(),
+        individual_vehicles.clone(),
+    );
+
+    // Export the binned counts (and hourly ones), if configured to.
+    if let Some(export_dir) = &options.export_dir {
+        // (Without a location, if the count's TC_HEADER record can't be read.)
+        let location = db::get_located_metadata(file.conn, recordnum, file.locations)
+            .map(|v| v.location)
+            .ok();
+        if let Err(e) = export_vehicle_counts(
+            metadata,
+            &individual_vehicles,
+            export_dir,
+            options.export_format,
+            &options.export_class_scheme,
+            location.as_ref(),
+        ) {
+            file.log(Level::Warn, &format!("Error exporting counts: {e}"));
+        }
+    }
+
+    // Create records for the non-normalized TC_SPESUM table (another one with specific hourly
+    // fields, this time for average speed/hour).
+    let non_normal_speedavg_count =
+        create_non_normal_speedavg_count(metadata.clone(), hourly_vehicles);
+
+    // Delete existing records from db (or abort if not replacing them).
+    file.prepare::<TimeBinnedVehicleClassCount>(append_from)?;
+    file.prepare::<TimeBinnedSpeedRangeCount>(append_from)?;
+    file.prepare::<NonNormalAvgSpeedCount>(append_from)?;
+    file.prepare::<NonNormalVolCount>(append_from)?;
+    match raw_vehicles {
+        Some(_) => file.prepare::<RawVehicle>(append_from)?,
+        // Raw vehicles stored by an earlier import would no longer match the count, and would
+        // overwrite it if it were rebinned, so they are deleted - all of them, as those of any
+        // days appended aren't being stored.
+        None if options.replace || append_from.is_some() => {
+            RawVehicle::delete(file.conn, recordnum).map_err(|e| file.not_processed(e.into()))?;
+        }
+        None => (),
+    }
+    // The channels stored with the raw vehicles are replaced (and deleted with them) as a whole,
+    // as the mapping is of the whole count.
+    if raw_vehicles.is_some() || options.replace || append_from.is_some() {
+        RawChannel::delete(file.conn, recordnum).map_err(|e| file.not_processed(e.into()))?;
+    }
+
+    // How well vehicles complied with the speed limit (logged once the count is committed).
+    let speed_compliance = create_speed_compliance(metadata, &individual_vehicles);
+
+    file.insert(&vehicle_class_count, "class data")?;
+    file.insert(&speed_range_count, "speed range data")?;
+    if let Some(raw_vehicles) = &raw_vehicles {
+        file.insert(raw_vehicles, "raw vehicle data")?;
+        file.insert(&RawChannel::from_metadata(metadata), "raw vehicle channels")?;
+    }
+    file.insert_denormalized::<TimeBinnedVehicleClassCount>(
+        append_from,
+        "denormalized class data",
+    )?;
+    file.insert(&non_normal_speedavg_count, "denormalized speed data")?;
+    file.commit()?;
+
+    if let Some(report) = speed_compliance {
+        file.log(Level::Info, &report.to_string());
+    }
+
+    // The share of heavy vehicles and design factors are of the whole count, so when appending,
+    // they're of its class counts in the database, not only those of the days appended.
+    let whole_count = match append_from {
+        Some(_) => TimeBinnedVehicleClassCount::select(file.conn, recordnum),
+        None => Ok(vehicle_class_count),
+    };
+    let whole_count = match whole_count {
+        Ok(v) => v,
+        Err(e) => {
+            file.log(
+                Level::Warn,
+                &format!("Unable to get class counts of the whole count: {e}"),
+            );
+            vec![]
+        }
+    };
+
+    // Record the share of heavy vehicles in the metadata, now that all the count's tables have
+    // been committed.
+    if let Some(heavy_vehicles) = create_heavy_vehicle_summary(&whole_count) {
+        let updated = heavy_vehicles.overall.percent().map(|percent| {
+            db::update_heavy_vehicle_percent(file.conn, recordnum, percent)
+                .and_then(|_| file.conn.commit().map_err(CountError::from))
+        });
+        match updated {
+            Some(Err(e)) => file.log(
+                Level::Error,
+                &format!("Error updating heavy vehicle percentage: {e}"),
+            ),
+            _ => file.log(Level::Info, &heavy_vehicles.to_string()),
+        }
+    }
+
+    // Log the design factors of each full day.
+    for factors in create_design_factors(&whole_count) {
+        file.log(Level::Info, &factors.to_string());
+    }
+    Ok(())
+}
+
+/// Import a file of individual bicycles: the 15-minute counts created from them, and their hourly
+/// volumes.
+fn import_individual_bicycles(file: &mut FileImport<impl Log>) -> Result<(), CountError> {
+    let mut counts = file.extract::<IndividualBicycle>(|v| v.time)?;
+    let append_from = file.keep_appended::<FifteenMinuteBicycle, _>(&mut counts, |v| v.date)?;
+
+    // Drop partial 15-minute periods, if configured to.
+    let trimmed =
+        file.options
+            .partial_periods
+            .trim(&mut counts, TimeInterval::FifteenMin, None, |v| v.time);
+    file.trimmed(trimmed);
+
+    // Create aggregated 15-minute bicycle count from this.
+    let fifteen_min_volcount =
+        create_binned_bicycle_vol_count(TimeInterval::FifteenMin, file.metadata.clone(), counts);
+
+    // Delete existing records from db (or abort if not replacing them).
+    file.prepare::<FifteenMinuteBicycle>(append_from)?;
+    file.prepare::<NonNormalVolCount>(append_from)?;
+
+    file.insert(&fifteen_min_volcount, "data")?;
+    file.insert_bicycle_hourly(&fifteen_min_volcount)?;
+    file.commit()
+}
+
+/// Import a file of 15-minute motor vehicle volumes, and their hourly volumes.
+fn import_fifteen_min_volume(file: &mut FileImport<impl Log>) -> Result<(), CountError> {
+    let mut fifteen_min_volcount = file.extract::<FifteenMinuteVehicle>(|v| v.time)?;
+    let append_from =
+        file.keep_appended::<FifteenMinuteVehicle, _>(&mut fifteen_min_volcount, |v| v.date)?;
+
+    // Drop partial days, if configured to (15-minute periods are always whole).
+    let trimmed = file.options.partial_periods.trim(
+        &mut fifteen_min_volcount,
+        TimeInterval::FifteenMin,
+        Some(TimeInterval::FifteenMin),
+        |v| v.time,
+    );
+    file.trimmed(trimmed);
+
+    // Correct counts of axles to counts of vehicles, if the count has a factor.
+    match file
+        .axle_correction
+        .factor(file.conn, file.recordnum, &file.metadata.counter_id)
+    {
+        Ok(Some(factor)) => {
+            factor.apply(&mut fifteen_min_volcount);
+            file.log(Level::Info, &format!("Volumes corrected with {factor}"));
+        }
+        Ok(None) => (),
+        Err(e) => return Err(file.not_processed(e)),
+    }
+
+    // Add the counts of both directions combined, for the legacy reports.
+    if file.options.combined_directions {
+        let combined = combine_fifteen_minute_counts(&fifteen_min_volcount);
+        fifteen_min_volcount.extend(combined);
+    }
+
+    // Delete existing records from db (or abort if not replacing them).
+    file.prepare::<FifteenMinuteVehicle>(append_from)?;
+    file.prepare::<NonNormalVolCount>(append_from)?;
+
+    // As they are already binned by 15-minute period, these need no further processing; just
+    // insert into database.
+    file.insert(&fifteen_min_volcount, "data")?;
+    file.insert_denormalized::<FifteenMinuteVehicle>(append_from, "denormalized data")?;
+    file.commit()
+}
+
+/// Import a file of hourly class counts, and their hourly volumes.
+fn import_hourly_class(file: &mut FileImport<impl Log>) -> Result<(), CountError> {
+    let mut vehicle_class_count = file.extract::<TimeBinnedVehicleClassCount>(|v| v.time)?;
+    let append_from =
+        file.keep_appended::<TimeBinnedVehicleClassCount, _>(&mut vehicle_class_count, |v| v.date)?;
+
+    // Drop partial days, if configured to (hours are always whole).
+    let trimmed = file.options.partial_periods.trim(
+        &mut vehicle_class_count,
+        TimeInterval::Hour,
+        Some(TimeInterval::Hour),
+        |v| v.time,
+    );
+    file.trimmed(trimmed);
+
+    // Delete existing records from db (or abort if not replacing them).
+    file.prepare::<TimeBinnedVehicleClassCount>(append_from)?;
+    file.prepare::<NonNormalVolCount>(append_from)?;
+
+    // As they are already binned by hour, these need no further processing; just insert into
+    // database.
+    file.insert(&vehicle_class_count, "class data")?;
+    file.insert_denormalized::<TimeBinnedVehicleClassCount>(
+        append_from,
+        "denormalized class data",
+    )?;
+    file.commit()
+}
+
+/// Import a file of 15-minute bicycle volumes, and their hourly volumes.
+fn import_fifteen_min_bicycle(file: &mut FileImport<impl Log>) -> Result<(), CountError> {
+    let mut fifteen_min_volcount = file.extract::<FifteenMinuteBicycle>(|v| v.time)?;
+    let append_from =
+        file.keep_appended::<FifteenMinuteBicycle, _>(&mut fifteen_min_volcount, |v| v.date)?;
+
+    // Drop partial days, if configured to (15-minute periods are always whole).
+    let trimmed = file.options.partial_periods.trim(
+        &mut fifteen_min_volcount,
+        TimeInterval::FifteenMin,
+        Some(TimeInterval::FifteenMin),
+        |v| v.time,
+    );
+    file.trimmed(trimmed);
+
+    // Delete existing records from db (or abort if not replacing them).
+    file.prepare::<FifteenMinuteBicycle>(append_from)?;
+    file.prepare::<NonNormalVolCount>(append_from)?;
+
+    // As they are already binned by 15-minute period, these need no further processing; just
+    // insert into database.
+    file.insert(&fifteen_min_volcount, "data")?;
+    file.insert_bicycle_hourly(&fifteen_min_volcount)?;
+    file.commit()
+}
+
+/// Import a file of 15-minute pedestrian volumes.
+fn import_fifteen_min_pedestrian(file: &mut FileImport<impl Log>) -> Result<(), CountError> {
+    let mut fifteen_min_volcount = file.extract::<FifteenMinutePedestrian>(|v| v.time)?;
+    let append_from =
+        file.keep_appended::<FifteenMinutePedestrian, _>(&mut fifteen_min_volcount, |v| v.date)?;
+
+    // Drop partial days, if configured to (15-minute periods are always whole).
+    let trimmed = file.options.partial_periods.trim(
+        &mut fifteen_min_volcount,
+        TimeInterval::FifteenMin,
+        Some(TimeInterval::FifteenMin),
+        |v| v.time,
+    );
+    file.trimmed(trimmed);
+
+    // Delete existing records from db (or abort if not replacing them).
+    file.prepare::<FifteenMinutePedestrian>(append_from)?;
+
+    // As they are already binned by 15-minute period, these need no further processing; just
+    // insert into database.
+    file.insert(&fifteen_min_volcount, "data")?;
+    file.commit()?;
+
+    // Pedestrian counts may not have had their type set when their metadata was created, nor do
+    // they get the last date counted from elsewhere.
+    if let Some(last_date) = fifteen_min_volcount.iter().map(|c| c.get_date()).max() {
+        if let Err(e) = db::update_count_kind_and_last_date(
+            file.conn,
+            file.recordnum,
+            CountKind::Pedestrian,
+            last_date,
+        ) {
+            file.log(
+                Level::Error,
+                &format!("Error updating metadata (tc_header table): {e}"),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Log an error that isn't (yet) associated with a recordnum.
+fn log_error(log: &impl Log, message: &str) {
+    log.log(
+        &Record::builder()
+            .args(format_args!("{message}"))
+            .level(Level::Error)
+            .build(),
+    );
+}
+
+/// The files of a run still to be processed.
+///
+/// A file that isn't imported because of a [transient][Retryable::is_transient] error (e.g. the
+/// database was unreachable, or the file was locked while OneDrive synced it) is tried again once
+/// at the end of the run, as long as none of its records were committed. If that fails too, it's
+/// left in the data directory for the next run.
+struct Queue {
+    paths: VecDeque<PathBuf>,
+    retried: HashSet<PathBuf>,
+    /// The number of files in the run, including those tried again.
+    total: usize,
+    /// The number of files taken from the queue.
+    done: usize,
+}
+
+impl Queue {
+    fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            total: paths.len(),
+            paths: paths.into(),
+            retried: HashSet::new(),
+            done: 0,
+        }
+    }
+
+    /// Take the next file to process.
+    fn next(&mut self) -> Option<PathBuf> {
+        let path = self.paths.pop_front()?;
+        self.done += 1;
+        Some(path)
+    }
+
+    /// Try a file again at the end of the run, unless it already has been.
+    fn retry(&mut self, path: &Path) {
+        if self.retried.insert(path.to_owned()) {
+            info!("{path:?} will be tried again at the end of the run");
+            self.paths.push_back(path.to_owned());
+            self.total += 1;
+        } else {
+            info!("{path:?} will be tried again by the next run");
+        }
+    }
+
+    /// Handle a file that wasn't imported because of `error`: try it again if the error is
+    /// transient and none of its records have been committed yet, and otherwise clean it up.
+    ///
+    /// (Once some of a count's tables have been committed, trying it again would only fail,
+    /// as its records would already be in them.)
+    fn failed(
+        &mut self,
+        error: &impl Retryable,
+        summary: &ImportSummary,
+        cleanup_files: bool,
+        path: &PathBuf,
+    ) {
+        if !error.is_transient() {
             cleanup(cleanup_files, path);
+        } else if summary.current_inserted() > 0 {
+            warn!(
+                "{path:?} won't be tried again, as some of its records were already committed; \
+                its records should be purged before it is imported again"
+            );
+            cleanup(cleanup_files, path);
+        } else {
+            self.retry(path);
+        }
+    }
+}
+
+/// Log an error that stops the program, and send an alert of it, if configured to.
+fn log_fatal(log: &impl Log, notifier: Option<&Notifier>, message: &str) {
+    log_error(log, message);
+    FAILED.store(true, Ordering::SeqCst);
+    if let Some(notifier) = notifier {
+        if let Err(e) = notifier.send_alert(message) {
+            log_error(log, &format!("Unable to send alert: {e}"));
+        }
+    }
+}
+
+/// Export the class and speed counts created from files of individual vehicles.
+fn export_files(
+    paths: Vec<PathBuf>,
+    dir: &Path,
+    format: ExportFormat,
+    scheme: &ClassScheme,
+    vehicles: bool,
+) {
+    let locations = match Locations::from_env() {
+        Ok(v) => v,
+        Err(e) => {
+            fail!("Unable to load locations: {e}");
+            return;
+        }
+    };
+    let mut files = vec![];
+    let filter = PathFilter::default();
+    for path in paths {
+        if path.is_dir() {
+            if let Err(e) = collect_paths(&path, path.clone(), &mut files, false, &filter) {
+                fail!("{}: not exported: {e}", path.display());
+            }
+        } else if is_archive(&path) {
+            files.extend(unpack_archive(&path, false));
+        } else {
+            files.push(path);
+        }
+    }
+
+    for path in files {
+        match InputCount::from_parent_dir(&path) {
+            Ok(InputCount::IndividualVehicle) => (),
+            Ok(count_type) => {
+                fail!(
+                    "{}: not exported: {count_type:?} counts cannot be exported",
+                    path.display()
+                );
+                continue;
+            }
+            Err(e) => {
+                fail!("{}: not exported: {e}", path.display());
+                continue;
+            }
+        }
+        let exported = InputCount::IndividualVehicle
+            .check_header(&path)
+            .and_then(|_| FieldMetadata::from_path(&path))
+            .and_then(|metadata| {
+                let mut individual_vehicles = IndividualVehicle::extract(&path)?;
+                IndividualVehicle::remove_duplicates(&mut individual_vehicles);
+                let mut paths = export_vehicle_counts(
+                    &metadata,
+                    &individual_vehicles,
+                    dir,
+                    format,
+                    scheme,
+                    locations.get(metadata.recordnum),
+                )?;
+                if vehicles {
+                    paths.push(export_individual_vehicles(
+                        &metadata,
+                        &individual_vehicles,
+                        dir,
+                        format,
+                    )?);
+                }
+                Ok(paths)
+            });
+        match exported {
+            Ok(v) => {
+                for exported_path in v {
+                    println!("{}", exported_path.display());
+                }
+            }
+            Err(e) => fail!("{}: not exported: {e}", path.display()),
         }
-        // Wait to try again
-        thread::sleep(time::Duration::from_secs(TIME_BETWEEN_LOOPS));
     }
 }
 
-/// Collect all the file paths to extract data from.
-fn collect_paths(dir: PathBuf, paths: &mut Vec<PathBuf>) -> io::Result<&mut Vec<PathBuf>> {
-    for entry in fs::read_dir(dir)? {
-        let path = entry?.path();
+/// Store the counts created from files of individual vehicles in a SQLite database.
+#[cfg(feature = "sqlite")]
+fn store_local(paths: Vec<PathBuf>, db: &Path) {
+    use traffic_counts::db::{sqlite::SqliteStore, store::store_vehicle_counts};
 
+    let store = match SqliteStore::open(db) {
+        Ok(v) => v,
+        Err(e) => {
+            fail!("Unable to open {}: {e}", db.display());
+            return;
+        }
+    };
+    let mut files = vec![];
+    let filter = PathFilter::default();
+    for path in paths {
         if path.is_dir() {
-            collect_paths(path, paths)?;
-        } else if let Some(v) = path.file_name() {
-            if v != LOG {
-                paths.push(path)
+            if let Err(e) = collect_paths(&path, path.clone(), &mut files, false, &filter) {
+                fail!("{}: not stored: {e}", path.display());
+            }
+        } else if is_archive(&path) {
+            files.extend(unpack_archive(&path, false));
+        } else {
+            files.push(path);
+        }
+    }
+
+    for path in files {
+        match InputCount::from_parent_dir(&path) {
+            Ok(InputCount::IndividualVehicle) => (),
+            Ok(count_type) => {
+                fail!(
+                    "{}: not stored: {count_type:?} counts cannot be stored locally",
+                    path.display()
+                );
+                continue;
+            }
+            Err(e) => {
+                fail!("{}: not stored: {e}", path.display());
+                continue;
+            }
+        }
+        let stored = InputCount::IndividualVehicle
+            .check_header(&path)
+            .and_then(|_| FieldMetadata::from_path(&path))
+            .and_then(|metadata| {
+                let mut individual_vehicles = IndividualVehicle::extract(&path)?;
+                IndividualVehicle::remove_duplicates(&mut individual_vehicles);
+                store_vehicle_counts(&store, &metadata, &individual_vehicles)
+            });
+        match stored {
+            Ok(v) => {
+                for (table, num) in v {
+                    println!("{}: stored {num} records in {table}", path.display());
+                }
             }
+            Err(e) => fail!("{}: not stored: {e}", path.display()),
+        }
+    }
+}
+
+/// Wait until a file is added to the data directory, or until `TIME_BETWEEN_LOOPS` seconds have
+/// passed (in case a notification was missed).
+///
+/// Once a file is added, wait `TIME_TO_SETTLE` seconds longer for its upload - and the upload of
+/// any others with it - to finish.
+///
+/// Stop waiting (within a second) if the program is asked to stop.
+fn wait_for_new_files(rx: &Receiver<notify::Result<Event>>) {
+    let timeout = Duration::from_secs(TIME_BETWEEN_LOOPS);
+    let start = Instant::now();
+    loop {
+        if STOPPING.load(Ordering::SeqCst) {
+            return;
+        }
+        let remaining = match timeout.checked_sub(start.elapsed()) {
+            Some(v) => v.min(Duration::from_secs(1)),
+            None => return,
+        };
+        match rx.recv_timeout(remaining) {
+            Ok(Ok(event)) if is_new_file(&event) => break,
+            Ok(Ok(_)) => continue,
+            Ok(Err(e)) => error!("Error watching data directory: {e}"),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(remaining),
+        }
+    }
+    thread::sleep(Duration::from_secs(TIME_TO_SETTLE));
+
+    // Discard notifications from the files that were added while waiting.
+    while rx.try_recv().is_ok() {}
+}
+
+/// Determine if a filesystem event is a (non-log) file being created in or moved into the data
+/// directory.
+fn is_new_file(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+    ) && event
+        .paths
+        .iter()
+        .any(|path| !path.extension().is_some_and(|x| x == "log" || x == "json"))
+}
+
+/// Collect all the file paths to extract data from in `dir`, a directory within `root`, that
+/// pass `filter`.
+///
+/// Archives are matched by their own paths relative to `root`, before they're unpacked, so that
+/// those that don't pass are left as they are (and a dry run doesn't match against the
+/// temporary directory they'd be unpacked into).
+fn collect_paths<'a>(
+    root: &Path,
+    dir: PathBuf,
+    paths: &'a mut Vec<PathBuf>,
+    in_place: bool,
+    filter: &PathFilter,
+) -> io::Result<&'a mut Vec<PathBuf>> {
+    // List the directory before unpacking any archives into it, so their files aren't listed too.
+    let entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|v| v.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    for path in entries {
+        if path.is_dir() {
+            collect_paths(root, path, paths, in_place, filter)?;
+        } else if !filter.is_match_within(root, &path) {
+            continue;
+        } else if is_archive(&path) {
+            paths.extend(unpack_archive(&path, in_place));
+        } else if !is_skipped(&path) {
+            paths.push(path)
         }
     }
     Ok(paths)
 }
 
+/// Get the time a file was last modified, to the second (as it's stored in the database).
+fn modified(path: &Path) -> Option<NaiveDateTime> {
+    let modified = fs::metadata(path).and_then(|v| v.modified()).ok()?;
+    DateTime::<Local>::from(modified)
+        .naive_local()
+        .with_nanosecond(0)
+}
+
+/// Log, sidecar metadata, and lock files, and archives that couldn't be unpacked, are skipped
+/// when collecting paths.
+fn is_skipped(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|x| x == "log" || x == "json" || x == "lock" || x == FAILED_ARCHIVE_EXTENSION)
+}
+
+/// Lock the data directory, so that no other instance of the program imports from it at the same
+/// time, returning the lock file. The lock is released when the file is closed - including if the
+/// program crashes, so a stale lock is never left behind.
+fn lock_data_dir(data_dir: &Path) -> Result<fs::File, CountError> {
+    let path = data_dir.join(LOCK_FILE);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(fs::TryLockError::WouldBlock) => Err(CountError::AlreadyRunning(path)),
+        Err(fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Unpack an archive, returning the paths of the files in it to process.
+///
+/// With `in_place`, its files are unpacked into the directory it's in and it is removed, as if
+/// they had been uploaded instead. Otherwise (e.g. for a dry run), they're unpacked into a
+/// temporary directory with the same name as that directory (which determines the kind of count),
+/// and the archive is left in place.
+///
+/// An archive that can't be unpacked in place is moved aside, by adding
+/// [`FAILED_ARCHIVE_EXTENSION`] to its name, so that it isn't tried again on every run.
+fn unpack_archive(path: &Path, in_place: bool) -> Vec<PathBuf> {
+    let Some(dir) = path.parent() else {
+        return vec![];
+    };
+    let dest = if in_place {
+        dir.to_path_buf()
+    } else {
+        let dest = env::temp_dir()
+            .join("traffic_counts_unpacked")
+            .join(path.file_name().unwrap_or_default())
+            .join(dir.file_name().unwrap_or_default());
+        // Remove anything unpacked from the archive before.
+        let _ = fs::remove_dir_all(&dest);
+        dest
+    };
+    match unpack(path, &dest) {
+        Ok(files) => {
+            if in_place {
+                if let Err(e) = fs::remove_file(path) {
+                    error!("Unable to delete unpacked archive {path:?} {e}");
+                }
+            }
+            files.into_iter().filter(|v| !is_skipped(v)).collect()
+        }
+        Err(e) => {
+            error!("Unable to unpack archive {path:?}: {e}");
+            if in_place {
+                let mut failed = path.as_os_str().to_owned();
+                failed.push(format!(".{FAILED_ARCHIVE_EXTENSION}"));
+                match fs::rename(path, &failed) {
+                    Ok(()) => warn!("Moved archive {path:?} aside to {failed:?}"),
+                    Err(e) => error!("Unable to move aside archive {path:?}: {e}"),
+                }
+            }
+            vec![]
+        }
+    }
+}
+
+/// Move an imported file (and its sidecar metadata file, if it has one) to a subdirectory of
+/// `archive_dir` for the current year and month, returning its new path.
+///
+/// If a file with the same name has already been archived that month, a number is added to the
+/// end of the name.
+fn archive(archive_dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    let now = Local::now();
+    let dir = archive_dir
+        .join(now.format("%Y").to_string())
+        .join(now.format("%m").to_string());
+    fs::create_dir_all(&dir)?;
+
+    let (Some(stem), Some(name)) = (path.file_stem(), path.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no file name in {path:?}"),
+        ));
+    };
+    let ext = path
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+    let mut archived = dir.join(name);
+    let mut n = 1;
+    while archived.exists() {
+        archived = dir.join(format!("{}-{n}{ext}", stem.to_string_lossy()));
+        n += 1;
+    }
+
+    move_file(path, &archived)?;
+    let sidecar = path.with_extension("json");
+    if sidecar.is_file() {
+        move_file(&sidecar, &archived.with_extension("json"))?;
+    }
+    Ok(archived)
+}
+
+/// Move a file, copying and then removing it if it can't simply be renamed (e.g. because it's
+/// being moved to a different filesystem).
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
 fn cleanup(cleanup_files: bool, path: &PathBuf) {
     if cleanup_files {
         if let Err(e) = fs::remove_file(path) {
             error!("Unable to delete file {path:?} {e}");
         }
+        // Remove a sidecar metadata file specific to this file, but not one for its directory.
+        let sidecar = path.with_extension("json");
+        if sidecar.is_file() {
+            if let Err(e) = fs::remove_file(&sidecar) {
+                error!("Unable to delete file {sidecar:?} {e}");
+            }
+        }
     }
 }
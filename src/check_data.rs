@@ -1,21 +1,78 @@
 //! Checks on data integrity/validity.
-use std::fmt::Write;
-use std::fs::OpenOptions;
+//!
+//! Among others, counts are checked for [gaps][find_gaps] - long periods with nothing counted,
+//! as when a counter's battery fails. How long a period must be to be reported can be set, in
+//! minutes, with the `CHECK_GAP_THRESHOLD` environment variable. They are also checked for
+//! [runs of the same count][find_stuck_runs] in consecutive periods, as when a sensor is stuck,
+//! for [hours in a row][find_direction_dropouts] in which one direction of a bidirectional count
+//! recorded almost nothing, as when one side's tube is disconnected (which the proportions of
+//! the whole count's directions can mask), and for [coverage of full days][DayCoverage], as
+//! partial first and last days skew daily averages. The class, speed, and hourly volume counts
+//! of a class count, all created from the same vehicles, are checked to have the same total in
+//! each hour. The [average daily traffic][crate::day_of_week] of weekdays and weekends is
+//! reported, along with any full days excluded from it for being holidays.
+//!
+//! [`check`] returns a [`CheckReport`] of the results of all checks applied to a count, which
+//! can be serialized (e.g. to JSON); [`check_and_log`] also logs the issues found.
+//!
+//! Each check implements the [`Check`] trait - its name, the kinds of counts it applies to, the
+//! severity of the issues it finds, and how it's applied - and is applied by a [`CheckRunner`].
+//! Checks specific to an agency's counts can be [registered][CheckRunner::register] with a
+//! runner alongside the built-in ones, rather than added to them, and are applied by
+//! [`check_with`] and [`check_and_log_with`]. Checks that can also be applied to counts created
+//! from a file before they're inserted into the database (as in a [dry run][crate::dry_run]) do
+//! so by [`Check::run_pending`].
+//!
+//! The thresholds beyond which the checks report an issue can be set in a TOML file, whose path
+//! is set with the `CHECK_CONFIG` environment variable (see [`CheckConfig`]). Any not set there
+//! use their defaults. They can be set for all counts, for counts in certain months of the year,
+//! and for each type of count, each taking precedence over the last:
+//!
+//! ```toml
+//! [default]
+//! dir_proportion_lower_bound = 0.35
+//!
+//! [[seasons]]
+//! months = [6, 7, 8]
+//! bike_count_max = 30
+//!
+//! [count_types."15 min Volume"]
+//! gap_threshold = 180
+//! ```
+//!
+//! Checks can be disabled (or enabled again) in the same way, by name:
+//!
+//! ```toml
+//! [default.checks]
+//! days_of_week = false
+//!
+//! [count_types."Bicycle 6".checks]
+//! excessive_bicycles = false
+//! ```
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
-use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
 use std::str::FromStr;
 
-use chrono::{NaiveDate, NaiveDateTime};
-use log::{Level, LevelFilter};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use log::{Level, LevelFilter, Log};
 use oracle::Connection;
+use serde::{Deserialize, Serialize, Serializer};
 use simplelog::{
     ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode, WriteLogger,
 };
 
 use crate::{
-    log_msg,
-    db,
-    CountError, CountKind, LaneDirection,
+    aadt::daily_volumes,
+    bin_time, create_time_bins,
+    day_of_week::{summarize_days_of_week, DayOfWeekSummary, Holidays},
+    db::{self, crud::Crud},
+    denormalize::NonNormalVolCount,
+    log_msg, CountError, CountKind, FifteenMinuteBicycle, FifteenMinutePedestrian,
+    FifteenMinuteVehicle, LaneDirection, TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
+    TimeInterval,
 };
 
 // If a count is bidirectional, the totals for both directions should be relatively proportional.
@@ -23,12 +80,407 @@ use crate::{
 const DIR_PROPORTION_LOWER_BOUND: f32 = 0.40;
 // Unusually high count for bicycles in a 15-minute period.
 const BIKE_COUNT_MAX: u32 = 20;
+// Class 2 vehicles being less than this percentage of the total is considered abnormal.
+const CLASS2_MIN_PERCENT: f32 = 75.0;
+// Unclassed vehicles being more than this percentage of the total is considered abnormal.
+const UNCLASSED_MAX_PERCENT: f32 = 10.0;
+// Default length (in minutes) beyond which consecutive 15-minute periods with nothing counted
+// are considered a gap in the data. Can be overridden with the CHECK_GAP_THRESHOLD env var.
+const DEFAULT_GAP_THRESHOLD: i64 = 120;
+// Number of consecutive 15-minute periods with the same, non-zero count beyond which a counter
+// is considered stuck.
+const STUCK_PERIODS: usize = 12;
+// Speeds this far (in mph) over the speed limit are considered implausible.
+const SPEED_EXCESS_OVER_LIMIT: f32 = 30.0;
+// More than this share of vehicles going implausibly fast is considered abnormal.
+const SPEED_EXCESS_MAX_SHARE: f32 = 0.01;
+// The 85th percentile speed being further than this (in mph) from the speed limit is considered
+// abnormal.
+const SPEED_P85_MAX_DIFFERENCE: f32 = 20.0;
+// The minimum number of full (24-hour) days a count should include.
+const MIN_FULL_DAYS: usize = 2;
+// In an hour of a bidirectional count, one direction having less than this share of the total
+// is considered to have recorded essentially nothing.
+const DIR_DROPOUT_MAX_SHARE: f32 = 0.05;
+// Number of consecutive hours with one direction recording essentially nothing at or beyond
+// which it is considered to have stopped counting.
+const DIR_DROPOUT_HOURS: usize = 3;
+// Hours with fewer vehicles than this (in all directions) are too quiet (e.g. overnight) to
+// judge the share of each direction by.
+const DIR_DROPOUT_MIN_VOLUME: u32 = 20;
+// The lower bound (in mph) of each speed range (s1-s14) of a TimeBinnedSpeedRangeCount.
+pub(crate) const SPEED_RANGE_LOWER_BOUNDS: [f32; 14] = [
+    0.0, 15.0, 20.0, 25.0, 30.0, 35.0, 40.0, 45.0, 50.0, 55.0, 60.0, 65.0, 70.0, 75.0,
+];
+// The upper bound (in mph) assumed for the last speed range, for estimating percentiles.
+const SPEED_RANGE_MAX: f32 = 80.0;
+
+// Names of the checks.
+const SHARE_UNCLASSED_VEHICLES: &str = "share_unclassed_vehicles";
+const SHARE_CLASS2_VEHICLES: &str = "share_class2_vehicles";
+const VEHICLE_DIR_PROPORTIONALITY: &str = "vehicle_dir_proportionality";
+const BIKE_DIR_PROPORTIONALITY: &str = "bike_dir_proportionality";
+const EXCESSIVE_BICYCLES: &str = "excessive_bicycles";
+const GAPS: &str = "gaps";
+const STUCK_COUNTER: &str = "stuck_counter";
+const FULL_DAYS: &str = "full_days";
+const SPEED_OUTLIERS: &str = "speed_outliers";
+const DAYS_OF_WEEK: &str = "days_of_week";
+const TABLE_CONSISTENCY: &str = "table_consistency";
+const HOURLY_DIR_PROPORTIONALITY: &str = "hourly_dir_proportionality";
 
 /// Result of a particular check.
-#[derive(Debug)]
-struct CheckResult {
-    level: Level,
-    message: String,
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    /// The severity of the result: `Warn` if an issue was found, `Info` if not, or `Error` if
+    /// the check couldn't be done.
+    #[serde(serialize_with = "serialize_level")]
+    pub level: Level,
+    /// The name of the check.
+    pub check: &'static str,
+    pub message: String,
+    /// Values measured by the check, by name.
+    pub metrics: BTreeMap<&'static str, f64>,
+}
+
+impl CheckResult {
+    pub fn new(level: Level, check: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            check,
+            message: message.into(),
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    /// Add a value measured by the check.
+    pub fn metric(mut self, name: &'static str, value: f64) -> Self {
+        self.metrics.insert(name, value);
+        self
+    }
+}
+
+fn serialize_level<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+/// The results of all checks applied to a count.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    pub recordnum: u32,
+    pub results: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    /// The results that are issues with the data (warnings) or checks that couldn't be done
+    /// (errors).
+    pub fn findings(&self) -> impl Iterator<Item = &CheckResult> {
+        self.results
+            .iter()
+            .filter(|result| result.level <= Level::Warn)
+    }
+
+    /// Log the [findings][CheckReport::findings], including to the import log in the database.
+    pub fn log(&self, log: &impl Log, conn: &Connection) {
+        for result in self.findings() {
+            log_msg(self.recordnum, log, result.level, &result.message, conn);
+        }
+    }
+}
+
+/// The thresholds beyond which data checks report an issue.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckThresholds {
+    /// In a bidirectional count, one direction having less than this share of the total is
+    /// considered abnormal.
+    pub dir_proportion_lower_bound: f32,
+    /// More bicycles than this in a 15-minute period is considered excessive.
+    pub bike_count_max: u32,
+    /// Class 2 vehicles being less than this percentage of the total is considered abnormal.
+    pub class2_min_percent: f32,
+    /// Unclassed vehicles being more than this percentage of the total is considered abnormal.
+    pub unclassed_max_percent: f32,
+    /// Length (in minutes) beyond which consecutive 15-minute periods with nothing counted are
+    /// considered a gap in the data.
+    pub gap_threshold: i64,
+    /// Number of consecutive 15-minute periods with the same, non-zero count at or beyond which
+    /// a counter is considered stuck.
+    pub stuck_periods: usize,
+    /// Speeds this far (in mph) over the speed limit are considered implausible.
+    pub speed_excess_over_limit: f32,
+    /// More than this share of vehicles going implausibly fast is considered abnormal.
+    pub speed_excess_max_share: f32,
+    /// The 85th percentile speed being further than this (in mph) from the speed limit is
+    /// considered abnormal.
+    pub speed_p85_max_difference: f32,
+    /// The minimum number of full (24-hour) days a count should include.
+    pub min_full_days: usize,
+    /// In an hour of a bidirectional count, one direction having less than this share of the
+    /// total is considered to have recorded essentially nothing.
+    pub dir_dropout_max_share: f32,
+    /// Number of consecutive hours with one direction recording essentially nothing at or
+    /// beyond which it is considered to have stopped counting.
+    pub dir_dropout_hours: usize,
+    /// Hours with fewer vehicles than this (in all directions) aren't judged by the share of
+    /// each direction.
+    pub dir_dropout_min_volume: u32,
+    /// The names of the checks not to apply.
+    pub disabled: BTreeSet<String>,
+}
+
+impl CheckThresholds {
+    /// Whether a check is to be applied.
+    pub fn is_enabled(&self, check: &str) -> bool {
+        !self.disabled.contains(check)
+    }
+}
+
+impl Default for CheckThresholds {
+    fn default() -> Self {
+        Self {
+            dir_proportion_lower_bound: DIR_PROPORTION_LOWER_BOUND,
+            bike_count_max: BIKE_COUNT_MAX,
+            class2_min_percent: CLASS2_MIN_PERCENT,
+            unclassed_max_percent: UNCLASSED_MAX_PERCENT,
+            gap_threshold: gap_threshold().num_minutes(),
+            stuck_periods: STUCK_PERIODS,
+            speed_excess_over_limit: SPEED_EXCESS_OVER_LIMIT,
+            speed_excess_max_share: SPEED_EXCESS_MAX_SHARE,
+            speed_p85_max_difference: SPEED_P85_MAX_DIFFERENCE,
+            min_full_days: MIN_FULL_DAYS,
+            dir_dropout_max_share: DIR_DROPOUT_MAX_SHARE,
+            dir_dropout_hours: DIR_DROPOUT_HOURS,
+            dir_dropout_min_volume: DIR_DROPOUT_MIN_VOLUME,
+            disabled: BTreeSet::new(),
+        }
+    }
+}
+
+/// [`CheckThresholds`] to use instead of those otherwise used, where set.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ThresholdOverrides {
+    pub dir_proportion_lower_bound: Option<f32>,
+    pub bike_count_max: Option<u32>,
+    pub class2_min_percent: Option<f32>,
+    pub unclassed_max_percent: Option<f32>,
+    pub gap_threshold: Option<i64>,
+    pub stuck_periods: Option<usize>,
+    pub speed_excess_over_limit: Option<f32>,
+    pub speed_excess_max_share: Option<f32>,
+    pub speed_p85_max_difference: Option<f32>,
+    pub min_full_days: Option<usize>,
+    pub dir_dropout_max_share: Option<f32>,
+    pub dir_dropout_hours: Option<usize>,
+    pub dir_dropout_min_volume: Option<u32>,
+    /// Whether to apply checks, by name.
+    #[serde(default)]
+    pub checks: BTreeMap<String, bool>,
+}
+
+impl ThresholdOverrides {
+    fn apply(&self, thresholds: &mut CheckThresholds) {
+        if let Some(v) = self.dir_proportion_lower_bound {
+            thresholds.dir_proportion_lower_bound = v;
+        }
+        if let Some(v) = self.bike_count_max {
+            thresholds.bike_count_max = v;
+        }
+        if let Some(v) = self.class2_min_percent {
+            thresholds.class2_min_percent = v;
+        }
+        if let Some(v) = self.unclassed_max_percent {
+            thresholds.unclassed_max_percent = v;
+        }
+        if let Some(v) = self.gap_threshold {
+            thresholds.gap_threshold = v;
+        }
+        if let Some(v) = self.stuck_periods {
+            thresholds.stuck_periods = v;
+        }
+        if let Some(v) = self.speed_excess_over_limit {
+            thresholds.speed_excess_over_limit = v;
+        }
+        if let Some(v) = self.speed_excess_max_share {
+            thresholds.speed_excess_max_share = v;
+        }
+        if let Some(v) = self.speed_p85_max_difference {
+            thresholds.speed_p85_max_difference = v;
+        }
+        if let Some(v) = self.min_full_days {
+            thresholds.min_full_days = v;
+        }
+        if let Some(v) = self.dir_dropout_max_share {
+            thresholds.dir_dropout_max_share = v;
+        }
+        if let Some(v) = self.dir_dropout_hours {
+            thresholds.dir_dropout_hours = v;
+        }
+        if let Some(v) = self.dir_dropout_min_volume {
+            thresholds.dir_dropout_min_volume = v;
+        }
+        for (check, enabled) in &self.checks {
+            if *enabled {
+                thresholds.disabled.remove(check);
+            } else {
+                thresholds.disabled.insert(check.clone());
+            }
+        }
+    }
+}
+
+/// Thresholds for counts in certain months of the year.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Season {
+    /// The months (1-12) of the season.
+    pub months: Vec<u32>,
+    #[serde(flatten)]
+    pub thresholds: ThresholdOverrides,
+}
+
+/// Configuration of the thresholds used by data checks, from a TOML file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct CheckConfig {
+    /// Thresholds for all counts.
+    #[serde(default)]
+    pub default: ThresholdOverrides,
+    /// Thresholds for counts in certain months of the year, by the date they started.
+    #[serde(default)]
+    pub seasons: Vec<Season>,
+    /// Thresholds for each type of count, by its name in the database (e.g. "Bicycle 1").
+    #[serde(default)]
+    pub count_types: BTreeMap<String, ThresholdOverrides>,
+}
+
+impl CheckConfig {
+    /// Get the configuration from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        for count_type in config.count_types.keys() {
+            CountKind::from_str(count_type)?;
+        }
+        Ok(config)
+    }
+
+    /// Get the configuration from the file set by the `CHECK_CONFIG` env var, or the default if
+    /// it isn't set.
+    pub fn from_env() -> Result<Self, CountError> {
+        match env::var("CHECK_CONFIG") {
+            Ok(path) => Self::from_file(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Get the thresholds for a type of count started on a date, if known.
+    pub fn thresholds(
+        &self,
+        count_kind: Option<&CountKind>,
+        date: Option<NaiveDate>,
+    ) -> CheckThresholds {
+        let mut thresholds = CheckThresholds::default();
+        self.default.apply(&mut thresholds);
+        if let Some(date) = date {
+            for season in &self.seasons {
+                if season.months.contains(&date.month()) {
+                    season.thresholds.apply(&mut thresholds);
+                }
+            }
+        }
+        if let Some(overrides) =
+            count_kind.and_then(|count_kind| self.count_types.get(&count_kind.to_string()))
+        {
+            overrides.apply(&mut thresholds);
+        }
+        thresholds
+    }
+}
+
+/// A period of a count in which nothing was counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// The start of the first 15-minute period with nothing counted.
+    pub start: NaiveDateTime,
+    /// The end of the last 15-minute period with nothing counted.
+    pub end: NaiveDateTime,
+}
+
+impl Gap {
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+}
+
+/// A run of consecutive 15-minute periods of a count with the same, non-zero count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuckRun {
+    /// The start of the first 15-minute period of the run.
+    pub start: NaiveDateTime,
+    /// The end of the last 15-minute period of the run.
+    pub end: NaiveDateTime,
+    /// The count in each period.
+    pub volume: u32,
+}
+
+/// The days that a count covers, fully or partially.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayCoverage {
+    /// Days covered from midnight to midnight.
+    pub full_days: Vec<NaiveDate>,
+    /// Days covered only in part, with the length of time covered.
+    pub partial_days: Vec<(NaiveDate, TimeDelta)>,
+}
+
+impl DayCoverage {
+    /// Get the days covered by a count, from the first to the end of the last of its 15-minute
+    /// periods (regardless of any gaps in between).
+    pub fn from_volumes(volumes: &BTreeMap<NaiveDateTime, u32>) -> Self {
+        let mut coverage = Self {
+            full_days: vec![],
+            partial_days: vec![],
+        };
+        let (Some((&first, _)), Some((&last, _))) =
+            (volumes.first_key_value(), volumes.last_key_value())
+        else {
+            return coverage;
+        };
+        let end = last + TimeDelta::minutes(15);
+
+        let mut day = first.date();
+        while day.and_time(NaiveTime::MIN) < end {
+            let day_start = day.and_time(NaiveTime::MIN);
+            let day_end = day_start + TimeDelta::days(1);
+            let covered = end.min(day_end) - first.max(day_start);
+            if covered == TimeDelta::days(1) {
+                coverage.full_days.push(day);
+            } else {
+                coverage.partial_days.push((day, covered));
+            }
+            match day.succ_opt() {
+                Some(v) => day = v,
+                None => break,
+            }
+        }
+        coverage
+    }
+}
+
+impl StuckRun {
+    /// The number of 15-minute periods in the run.
+    pub fn periods(&self) -> usize {
+        ((self.end - self.start).num_minutes() / 15) as usize
+    }
+}
+
+/// Consecutive hours of a bidirectional count in which one direction recorded essentially
+/// nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectionDropout {
+    pub direction: LaneDirection,
+    /// The start of the first hour.
+    pub start: NaiveDateTime,
+    /// The end of the last hour.
+    pub end: NaiveDateTime,
+    /// The number of hours judged (those too quiet to judge aren't included).
+    pub hours: usize,
 }
 
 /// Used for checking shares by class.
@@ -42,17 +494,26 @@ pub struct ClassCountCheck {
     total: u32,
 }
 
-/// Apply various data checks and log any issues found.
-pub fn check(recordnum: u32, conn: &Connection) -> Result<(), CountError> {
+/// Apply various data checks and log any issues found, returning the [`CheckReport`].
+///
+/// The log is written to data_check.log in the directory set by the `LOG_DIR` env var.
+pub fn check_and_log(recordnum: u32, conn: &Connection) -> Result<CheckReport, CountError> {
+    check_and_log_with(&CheckRunner::default(), recordnum, conn)
+}
+
+/// As [`check_and_log`], but applying the checks registered with `runner`.
+pub fn check_and_log_with(
+    runner: &CheckRunner,
+    recordnum: u32,
+    conn: &Connection,
+) -> Result<CheckReport, CountError> {
     // Load file containing environment variables, panic if it doesn't exist.
     dotenvy::dotenv().expect("Unable to load .env file.");
 
     // Get env var for path where log will be, panic if it doesn't exist.
     let log_dir = env::var("LOG_DIR").expect("Unable to load log directory path from .env file.");
     // Set up logging, panic if it fails.
-    let check_config = ConfigBuilder::new()
-        .set_time_format_rfc3339()
-        .build();
+    let check_config = ConfigBuilder::new().set_time_format_rfc3339().build();
     let data_check_log = CombinedLogger::new(vec![
         TermLogger::new(
             LevelFilter::Debug,
@@ -71,154 +532,1270 @@ pub fn check(recordnum: u32, conn: &Connection) -> Result<(), CountError> {
         ),
     ]);
 
-    // Determine what kind of count this is, in order to run the appropriate checks.
-    let count_kind = match db::get_count_kind(conn, recordnum) {
-        Ok(Some(v)) => v,
-        Ok(None) => {
-            return Err(CountError::DataCheckError(
-                "unable to identify type of count".to_string(),
-            ));
-        }
-        Err(e) => {
-            return Err(CountError::DbError(format!("{e}")));
-        }
-    };
+    let report = check_with(runner, recordnum, conn)?;
+    report.log(&data_check_log, conn);
+    Ok(report)
+}
+
+/// Apply the [data checks][CheckRunner::default] configured (see [`CheckConfig`]) to a count,
+/// returning a report of their results.
+pub fn check(recordnum: u32, conn: &Connection) -> Result<CheckReport, CountError> {
+    check_with(&CheckRunner::default(), recordnum, conn)
+}
+
+/// As [`check`], but applying the checks registered with `runner`.
+pub fn check_with(
+    runner: &CheckRunner,
+    recordnum: u32,
+    conn: &Connection,
+) -> Result<CheckReport, CountError> {
+    let config = CheckConfig::from_env()?;
+    runner.verify_config(&config)?;
+    runner.run(recordnum, conn, &config)
+}
+
+/// A count in the database to apply a [`Check`] to, and the thresholds for it.
+pub struct CheckContext<'a> {
+    pub recordnum: u32,
+    pub count_kind: &'a CountKind,
+    pub conn: &'a Connection,
+    pub thresholds: &'a CheckThresholds,
+}
+
+/// Counts not (yet) in the database, as created from a file, to apply [`Check`]s to.
+pub enum PendingCounts<'a> {
+    /// Speed counts, and the speed limit of the count they're of, if known.
+    SpeedRanges {
+        counts: &'a [TimeBinnedSpeedRangeCount],
+        speed_limit: Option<u8>,
+    },
+    VehicleClasses(&'a [TimeBinnedVehicleClassCount]),
+    FifteenMinuteVehicles(&'a [FifteenMinuteVehicle]),
+    /// Bicycle counts, and whether the count they're of has two directions.
+    Bicycles {
+        counts: &'a [FifteenMinuteBicycle],
+        bidirectional: bool,
+    },
+}
+
+impl PendingCounts<'_> {
+    /// The class counts, if these are them, to check the shares of classes with.
+    fn class_counts(&self) -> Option<Vec<ClassCountCheck>> {
+        let PendingCounts::VehicleClasses(counts) = self else {
+            return None;
+        };
+        let class_counts = counts
+            .iter()
+            .filter_map(|count| {
+                Some(ClassCountCheck {
+                    datetime: count.time,
+                    lane: count.lane?,
+                    dir: count.direction?,
+                    c2: count.c2,
+                    c15: count.c15.unwrap_or(0),
+                    total: count.total,
+                })
+            })
+            .collect();
+        Some(class_counts)
+    }
+
+    /// The total of each direction, if these are counts of motor vehicles.
+    fn count_by_dir(&self) -> Option<HashMap<String, u32>> {
+        let mut count_by_dir = HashMap::new();
+        match self {
+            PendingCounts::VehicleClasses(_) => {
+                for count in self.class_counts()? {
+                    *count_by_dir.entry(count.dir.to_string()).or_insert(0) += count.total;
+                }
+            }
+            PendingCounts::FifteenMinuteVehicles(counts) => {
+                for count in counts.iter() {
+                    if let Some(direction) = count.direction {
+                        *count_by_dir.entry(direction.to_string()).or_insert(0) +=
+                            count.count as u32;
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(count_by_dir)
+    }
+
+    /// The total volume of each period, if these are counts of volumes.
+    fn volumes(&self) -> Option<BTreeMap<NaiveDateTime, u32>> {
+        let mut volumes = BTreeMap::new();
+        match self {
+            PendingCounts::VehicleClasses(_) => {
+                for count in self.class_counts()? {
+                    *volumes.entry(count.datetime).or_insert(0) += count.total;
+                }
+            }
+            PendingCounts::FifteenMinuteVehicles(counts) => {
+                for count in counts.iter() {
+                    *volumes.entry(count.time).or_insert(0) += count.count as u32;
+                }
+            }
+            PendingCounts::Bicycles { counts, .. } => {
+                for count in counts.iter() {
+                    *volumes.entry(count.time).or_insert(0) += count.total as u32;
+                }
+            }
+            PendingCounts::SpeedRanges { .. } => return None,
+        }
+        Some(volumes)
+    }
+}
+
+/// A data check of counts in the database.
+///
+/// Checks are applied by a [`CheckRunner`], which has the built-in ones by default. Others (e.g.
+/// ones specific to an agency's counts) can be [registered][CheckRunner::register] with it.
+pub trait Check: Send + Sync {
+    /// The name of the check, as in its results and in the [configuration][CheckConfig] that
+    /// enables or disables it.
+    fn name(&self) -> &'static str;
+
+    /// Whether the check applies to a kind of count.
+    fn applies_to(&self, count_kind: &CountKind) -> bool;
+
+    /// The level that issues found by the check are reported at: `Warn` by default, but an
+    /// issue can be made an error, or only informational.
+    fn severity(&self) -> Level {
+        Level::Warn
+    }
+
+    /// Apply the check to a count, returning a result at the `Warn` level if an issue was found.
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError>;
+
+    /// Apply the check to counts not (yet) in the database, if it can be applied to them,
+    /// returning a result at the `Warn` level if an issue was found. By default, it can't be.
+    fn run_pending(
+        &self,
+        _counts: &PendingCounts,
+        _thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        None
+    }
+}
+
+/// Applies the registered [`Check`]s to counts, in the order they were registered.
+pub struct CheckRunner {
+    checks: Vec<Box<dyn Check>>,
+}
+
+impl Default for CheckRunner {
+    /// A runner with the built-in checks.
+    fn default() -> Self {
+        let mut runner = Self::empty();
+        runner
+            .register(SpeedOutliers)
+            .register(ShareUnclassedVehicles)
+            .register(ShareClass2Vehicles)
+            .register(TableConsistency)
+            .register(VehicleDirProportionality)
+            .register(HourlyDirProportionality)
+            .register(DaysOfWeek)
+            .register(BikeDirProportionality)
+            .register(ExcessiveBicycles)
+            .register(Gaps)
+            .register(FullDays)
+            .register(StuckCounter);
+        /*
+        TODO: after table normalized (for both vehicles and bicycles), for class and 15-minute
+        volume counts: check_vehicle_0_hours.
+        */
+        runner
+    }
+}
+
+impl CheckRunner {
+    /// A runner without any checks.
+    pub fn empty() -> Self {
+        Self { checks: vec![] }
+    }
+
+    /// Register a check, to be applied after those already registered.
+    pub fn register(&mut self, check: impl Check + 'static) -> &mut Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// The names of the registered checks.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.checks.iter().map(|check| check.name())
+    }
+
+    /// Verify that every check enabled or disabled in a configuration is registered.
+    pub fn verify_config(&self, config: &CheckConfig) -> Result<(), CountError> {
+        let overrides = std::iter::once(&config.default)
+            .chain(config.seasons.iter().map(|season| &season.thresholds))
+            .chain(config.count_types.values());
+        for name in overrides.flat_map(|v| v.checks.keys()) {
+            if !self.names().any(|v| v == name.as_str()) {
+                return Err(CountError::DataCheckError(format!(
+                    "unknown check '{name}'"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the checks that apply to a count, and aren't disabled for it, returning a report of
+    /// their results.
+    ///
+    /// A check that can't be done has a result at the `Error` level, rather than stopping the
+    /// others.
+    pub fn run(
+        &self,
+        recordnum: u32,
+        conn: &Connection,
+        config: &CheckConfig,
+    ) -> Result<CheckReport, CountError> {
+        // Determine what kind of count this is, in order to run the appropriate checks.
+        let count_kind = match db::get_count_kind(conn, recordnum) {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                return Err(CountError::DataCheckError(
+                    "unable to identify type of count".to_string(),
+                ));
+            }
+            Err(e) => {
+                return Err(CountError::DbError(format!("{e}")));
+            }
+        };
+
+        let thresholds =
+            &config.thresholds(Some(&count_kind), first_date(recordnum, &count_kind, conn));
+        let ctx = CheckContext {
+            recordnum,
+            count_kind: &count_kind,
+            conn,
+            thresholds,
+        };
+
+        let mut results = vec![];
+        for check in &self.checks {
+            if !check.applies_to(&count_kind) || !thresholds.is_enabled(check.name()) {
+                continue;
+            }
+            // Keep the result of a check, or that it couldn't be done.
+            let mut result = check.run(&ctx).unwrap_or_else(|e| {
+                CheckResult::new(
+                    Level::Error,
+                    check.name(),
+                    format!("Unable to check data: {e}"),
+                )
+            });
+            if result.level == Level::Warn {
+                result.level = check.severity();
+            }
+            results.push(result);
+        }
+
+        Ok(CheckReport { recordnum, results })
+    }
+
+    /// Apply the checks that can be applied to counts not (yet) in the database, and aren't
+    /// disabled, returning the messages of any issues found.
+    pub fn run_pending(&self, counts: &PendingCounts, thresholds: &CheckThresholds) -> Vec<String> {
+        self.checks
+            .iter()
+            .filter(|check| thresholds.is_enabled(check.name()))
+            .filter_map(|check| check.run_pending(counts, thresholds))
+            .filter(|result| result.level == Level::Warn)
+            .map(|result| result.message)
+            .collect()
+    }
+}
+
+/// Whether a count is of motor vehicles, with hourly volumes in TC_VOLCOUNT.
+fn is_motor_vehicle(count_kind: &CountKind) -> bool {
+    matches!(
+        count_kind,
+        CountKind::Class | CountKind::Volume | CountKind::FifteenMinVolume
+    )
+}
+
+/// Whether a count is of bicycles.
+fn is_bicycle(count_kind: &CountKind) -> bool {
+    matches!(
+        count_kind,
+        CountKind::Bicycle1
+            | CountKind::Bicycle2
+            | CountKind::Bicycle3
+            | CountKind::Bicycle4
+            | CountKind::Bicycle5
+            | CountKind::Bicycle6,
+    )
+}
+
+/// Warn about speeds implausible given the speed limit, as from a mis-calibrated counter or the
+/// wrong speed limit.
+pub struct SpeedOutliers;
+
+impl Check for SpeedOutliers {
+    fn name(&self) -> &'static str {
+        SPEED_OUTLIERS
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        *count_kind == CountKind::Class
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_speed_outliers(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        let PendingCounts::SpeedRanges {
+            counts,
+            speed_limit,
+        } = counts
+        else {
+            return None;
+        };
+        Some(speed_outliers(
+            &speed_ranges(counts),
+            *speed_limit,
+            thresholds,
+        ))
+    }
+}
+
+/// Warn about too large a share of unclassed vehicles.
+pub struct ShareUnclassedVehicles;
+
+impl Check for ShareUnclassedVehicles {
+    fn name(&self) -> &'static str {
+        SHARE_UNCLASSED_VEHICLES
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        *count_kind == CountKind::Class
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_share_unclassed_vehicles(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        Some(share_unclassed_vehicles(
+            &counts.class_counts()?,
+            thresholds,
+        ))
+    }
+}
+
+/// Warn about too small a share of class 2 vehicles.
+pub struct ShareClass2Vehicles;
+
+impl Check for ShareClass2Vehicles {
+    fn name(&self) -> &'static str {
+        SHARE_CLASS2_VEHICLES
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        *count_kind == CountKind::Class
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_share_class2_vehicles(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        Some(share_class2_vehicles(&counts.class_counts()?, thresholds))
+    }
+}
+
+/// Warn about class, speed, and volume totals that disagree in any hour.
+pub struct TableConsistency;
+
+impl Check for TableConsistency {
+    fn name(&self) -> &'static str {
+        TABLE_CONSISTENCY
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        *count_kind == CountKind::Class
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_table_consistency(ctx.recordnum, ctx.conn)
+    }
+}
+
+/// Warn about disproportionate totals of the directions of motor vehicles.
+pub struct VehicleDirProportionality;
+
+impl Check for VehicleDirProportionality {
+    fn name(&self) -> &'static str {
+        VEHICLE_DIR_PROPORTIONALITY
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        is_motor_vehicle(count_kind)
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_vehicle_dir_proportionality(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        Some(vehicle_dir_proportionality(
+            counts.count_by_dir()?,
+            thresholds,
+        ))
+    }
+}
+
+/// Warn about hours on end in which one direction of motor vehicles recorded almost nothing.
+pub struct HourlyDirProportionality;
+
+impl Check for HourlyDirProportionality {
+    fn name(&self) -> &'static str {
+        HOURLY_DIR_PROPORTIONALITY
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        is_motor_vehicle(count_kind)
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_hourly_dir_proportionality(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+}
+
+/// Report weekday and weekend average daily traffic of motor vehicles, and any holidays left
+/// out of it.
+pub struct DaysOfWeek;
+
+impl Check for DaysOfWeek {
+    fn name(&self) -> &'static str {
+        DAYS_OF_WEEK
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        is_motor_vehicle(count_kind)
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_days_of_week(ctx.recordnum, ctx.conn)
+    }
+}
+
+/// Warn about disproportionate totals of the directions of bicycles.
+pub struct BikeDirProportionality;
+
+impl Check for BikeDirProportionality {
+    fn name(&self) -> &'static str {
+        BIKE_DIR_PROPORTIONALITY
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        is_bicycle(count_kind)
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_bike_dir_proportionality(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        let PendingCounts::Bicycles {
+            counts,
+            bidirectional: true,
+        } = counts
+        else {
+            return None;
+        };
+        if counts.is_empty() {
+            return None;
+        }
+        let total = counts.iter().map(|c| c.total as u32).sum();
+        let incount = counts.iter().map(|c| c.indir.unwrap_or(0) as u32).sum();
+        let outcount = counts.iter().map(|c| c.outdir.unwrap_or(0) as u32).sum();
+        Some(bike_dir_proportionality(
+            total, incount, outcount, thresholds,
+        ))
+    }
+}
+
+/// Warn about an excessive number of bicycles in any 15-minute period.
+pub struct ExcessiveBicycles;
+
+impl Check for ExcessiveBicycles {
+    fn name(&self) -> &'static str {
+        EXCESSIVE_BICYCLES
+    }
+
+    fn applies_to(&self, count_kind: &CountKind) -> bool {
+        is_bicycle(count_kind)
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_excessive_bicycles(ctx.recordnum, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        let PendingCounts::Bicycles { counts, .. } = counts else {
+            return None;
+        };
+        let periods = counts
+            .iter()
+            .map(|c| {
+                (
+                    c.date,
+                    c.time,
+                    c.indir.unwrap_or(0) as u32,
+                    c.outdir.unwrap_or(0) as u32,
+                )
+            })
+            .collect::<Vec<_>>();
+        Some(excessive_bicycles(&periods, thresholds))
+    }
+}
+
+/// Warn about gaps in the data, as from a counter's battery failing.
+pub struct Gaps;
+
+impl Check for Gaps {
+    fn name(&self) -> &'static str {
+        GAPS
+    }
+
+    fn applies_to(&self, _count_kind: &CountKind) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_gaps(ctx.recordnum, ctx.count_kind, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        Some(gaps(&counts.volumes()?, thresholds))
+    }
+}
+
+/// Warn about too few full days, or partial ones that skew daily averages.
+pub struct FullDays;
+
+impl Check for FullDays {
+    fn name(&self) -> &'static str {
+        FULL_DAYS
+    }
+
+    fn applies_to(&self, _count_kind: &CountKind) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_full_days(ctx.recordnum, ctx.count_kind, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        Some(full_days(&counts.volumes()?, thresholds))
+    }
+}
+
+/// Warn about runs of the same count, as from a stuck tube or sensor.
+pub struct StuckCounter;
+
+impl Check for StuckCounter {
+    fn name(&self) -> &'static str {
+        STUCK_COUNTER
+    }
+
+    fn applies_to(&self, _count_kind: &CountKind) -> bool {
+        true
+    }
+
+    fn run(&self, ctx: &CheckContext) -> Result<CheckResult, CountError> {
+        check_stuck_counter(ctx.recordnum, ctx.count_kind, ctx.conn, ctx.thresholds)
+    }
+
+    fn run_pending(
+        &self,
+        counts: &PendingCounts,
+        thresholds: &CheckThresholds,
+    ) -> Option<CheckResult> {
+        Some(stuck_counter(&counts.volumes()?, thresholds))
+    }
+}
+
+/// Find gaps in a count - consecutive 15-minute periods with nothing counted, which together are
+/// longer than `threshold`.
+///
+/// `volumes` is the total volume (of all lanes/directions) per 15-minute period. Periods between
+/// the first and last that are missing are considered to have nothing counted.
+pub fn find_gaps(volumes: &BTreeMap<NaiveDateTime, u32>, threshold: TimeDelta) -> Vec<Gap> {
+    let (first, last) = match (volumes.first_key_value(), volumes.last_key_value()) {
+        (Some(first), Some(last)) => (*first.0, *last.0),
+        _ => return vec![],
+    };
+
+    let mut gaps = vec![];
+    let mut gap_start = None;
+    for period in create_time_bins(first, last, TimeInterval::FifteenMin) {
+        let empty = volumes.get(&period).is_none_or(|volume| *volume == 0);
+        match (empty, gap_start) {
+            (true, None) => gap_start = Some(period),
+            (false, Some(start)) => {
+                gaps.push(Gap { start, end: period });
+                gap_start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(start) = gap_start {
+        gaps.push(Gap {
+            start,
+            end: last + TimeDelta::minutes(15),
+        });
+    }
+
+    gaps.retain(|gap| gap.duration() > threshold);
+    gaps
+}
+
+/// Find runs of the same, non-zero count in at least `min_periods` consecutive 15-minute
+/// periods of a count, which are unlikely to have actually been counted.
+///
+/// `volumes` is the volume per 15-minute period. Missing periods end a run.
+pub fn find_stuck_runs(
+    volumes: &BTreeMap<NaiveDateTime, u32>,
+    min_periods: usize,
+) -> Vec<StuckRun> {
+    let mut runs: Vec<StuckRun> = vec![];
+    let mut current: Option<StuckRun> = None;
+    for (&period, &volume) in volumes {
+        if let Some(run) = current.as_mut() {
+            if run.end == period && run.volume == volume {
+                run.end = period + TimeDelta::minutes(15);
+                continue;
+            }
+        }
+        runs.extend(current.take());
+        if volume != 0 {
+            current = Some(StuckRun {
+                start: period,
+                end: period + TimeDelta::minutes(15),
+                volume,
+            });
+        }
+    }
+    runs.extend(current);
+
+    runs.retain(|run| run.periods() >= min_periods.max(2));
+    runs
+}
+
+/// Find runs of consecutive hours of a bidirectional count in which one direction had less than
+/// `max_share` of the total, lasting at least `min_hours`.
+///
+/// `volumes` is the volume of each direction per hour. Hours with less than `min_volume` in all
+/// directions are too quiet to judge, and so neither extend nor end a run; missing hours end it.
+pub fn find_direction_dropouts(
+    volumes: &BTreeMap<NaiveDateTime, BTreeMap<LaneDirection, u32>>,
+    max_share: f32,
+    min_volume: u32,
+    min_hours: usize,
+) -> Vec<DirectionDropout> {
+    let directions = volumes
+        .values()
+        .flat_map(|v| v.keys().copied())
+        .collect::<BTreeSet<_>>();
+    if directions.len() < 2 {
+        return vec![];
+    }
+
+    let mut dropouts: Vec<DirectionDropout> = vec![];
+    let mut current: Option<DirectionDropout> = None;
+    let mut last_hour = None;
+    for (&hour, by_direction) in volumes {
+        if last_hour.is_some_and(|v| v + TimeDelta::hours(1) != hour) {
+            dropouts.extend(current.take());
+        }
+        last_hour = Some(hour);
+
+        let total = by_direction.values().sum::<u32>();
+        if total < min_volume {
+            continue;
+        }
+        let Some((direction, volume)) = directions
+            .iter()
+            .map(|v| (*v, by_direction.get(v).copied().unwrap_or(0)))
+            .min_by_key(|(_, volume)| *volume)
+        else {
+            continue;
+        };
+        if (volume as f32 / total as f32) >= max_share {
+            dropouts.extend(current.take());
+            continue;
+        }
+        match current.as_mut() {
+            Some(dropout) if dropout.direction == direction => {
+                dropout.end = hour + TimeDelta::hours(1);
+                dropout.hours += 1;
+            }
+            _ => {
+                dropouts.extend(current.take());
+                current = Some(DirectionDropout {
+                    direction,
+                    start: hour,
+                    end: hour + TimeDelta::hours(1),
+                    hours: 1,
+                });
+            }
+        }
+    }
+    dropouts.extend(current);
+
+    dropouts.retain(|dropout| dropout.hours >= min_hours.max(1));
+    dropouts
+}
+
+/// Get the threshold for gaps in the data to be reported, from env var or the default.
+fn gap_threshold() -> TimeDelta {
+    let minutes = env::var("CHECK_GAP_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_GAP_THRESHOLD);
+    TimeDelta::minutes(minutes)
+}
+
+/// Get the table, recordnum field, and total volume field of the 15-minute data of a kind of
+/// count, if it has such data.
+fn fifteen_minute_table(
+    count_kind: &CountKind,
+) -> Option<(&'static str, &'static str, &'static str)> {
+    let table = match count_kind {
+        CountKind::Class => (
+            <TimeBinnedVehicleClassCount as Crud>::COUNT_TABLE,
+            <TimeBinnedVehicleClassCount as Crud>::COUNT_RECORDNUM_FIELD,
+            "total",
+        ),
+        CountKind::FifteenMinVolume => (
+            <FifteenMinuteVehicle as Crud>::COUNT_TABLE,
+            <FifteenMinuteVehicle as Crud>::COUNT_RECORDNUM_FIELD,
+            "volcount",
+        ),
+        CountKind::Bicycle1
+        | CountKind::Bicycle2
+        | CountKind::Bicycle3
+        | CountKind::Bicycle4
+        | CountKind::Bicycle5
+        | CountKind::Bicycle6 => (
+            <FifteenMinuteBicycle as Crud>::COUNT_TABLE,
+            <FifteenMinuteBicycle as Crud>::COUNT_RECORDNUM_FIELD,
+            "total",
+        ),
+        CountKind::Pedestrian | CountKind::Pedestrian2 => (
+            <FifteenMinutePedestrian as Crud>::COUNT_TABLE,
+            <FifteenMinutePedestrian as Crud>::COUNT_RECORDNUM_FIELD,
+            "total",
+        ),
+        _ => return None,
+    };
+    Some(table)
+}
+
+/// Get the first date of the 15-minute data of a count, if it has any.
+fn first_date(recordnum: u32, count_kind: &CountKind, conn: &Connection) -> Option<NaiveDate> {
+    let (table, recordnum_field, _) = fifteen_minute_table(count_kind)?;
+    conn.query_row_as::<Option<NaiveDate>>(
+        &format!("select min(countdate) from {table} where {recordnum_field} = :1"),
+        &[&recordnum],
+    )
+    .ok()
+    .flatten()
+}
+
+/// Get the total volume (of all lanes/directions) per 15-minute period of a count, if it is a
+/// 15-minute count.
+fn fifteen_minute_volumes(
+    recordnum: u32,
+    count_kind: &CountKind,
+    conn: &Connection,
+) -> Result<Option<BTreeMap<NaiveDateTime, u32>>, CountError> {
+    let Some((table, recordnum_field, volume_field)) = fifteen_minute_table(count_kind) else {
+        return Ok(None);
+    };
+
+    // (Rows of both directions combined are left out of 15-minute volume counts.)
+    let combined = if table == <FifteenMinuteVehicle as Crud>::COUNT_TABLE {
+        " and cntdir is not null"
+    } else {
+        ""
+    };
+    let results = conn.query_as::<(NaiveDate, NaiveDateTime, u32)>(
+        &format!(
+            "select countdate, counttime, {volume_field} from {table} \
+            where {recordnum_field} = :1{combined}"
+        ),
+        &[&recordnum],
+    )?;
+
+    let mut volumes = BTreeMap::new();
+    for result in results {
+        let (countdate, counttime, volume) = result?;
+        *volumes
+            .entry(NaiveDateTime::new(countdate, counttime.time()))
+            .or_insert(0) += volume;
+    }
+
+    Ok(Some(volumes))
+}
+
+/// Check if there are gaps in the data of a count.
+fn check_gaps(
+    recordnum: u32,
+    count_kind: &CountKind,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
+    match fifteen_minute_volumes(recordnum, count_kind, conn)? {
+        Some(volumes) => Ok(gaps(&volumes, thresholds)),
+        None => Ok(CheckResult::new(
+            Level::Info,
+            GAPS,
+            "Skipping gap check - not a 15-minute count.",
+        )),
+    }
+}
+
+/// Check if a count covers enough full days, and whether it has partial ones.
+fn check_full_days(
+    recordnum: u32,
+    count_kind: &CountKind,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
+    match fifteen_minute_volumes(recordnum, count_kind, conn)? {
+        Some(volumes) => Ok(full_days(&volumes, thresholds)),
+        None => Ok(CheckResult::new(
+            Level::Info,
+            FULL_DAYS,
+            "Skipping full day check - not a 15-minute count.",
+        )),
+    }
+}
+
+/// Check if a count has runs of the same count, as from a stuck counter.
+fn check_stuck_counter(
+    recordnum: u32,
+    count_kind: &CountKind,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
+    match fifteen_minute_volumes(recordnum, count_kind, conn)? {
+        Some(volumes) => Ok(stuck_counter(&volumes, thresholds)),
+        None => Ok(CheckResult::new(
+            Level::Info,
+            STUCK_COUNTER,
+            "Skipping stuck counter check - not a 15-minute count.",
+        )),
+    }
+}
 
-    if count_kind == CountKind::Class {
-        match check_share_unclassed_vehicles(recordnum, conn) {
-            Ok(v) if v.level == Level::Warn => {
-                log_msg(recordnum, &data_check_log, Level::Warn, &v.message, conn);
-            }
-            _ => (),            
-        }
-        match check_share_class2_vehicles(recordnum, conn) {
-            Ok(v) if v.level == Level::Warn => {
-                log_msg(recordnum, &data_check_log, Level::Warn, &v.message, conn);
-            }
-            _ => (),            
-        }
+/// Check if there are gaps in the data of a count, from its volume per 15-minute period.
+fn gaps(volumes: &BTreeMap<NaiveDateTime, u32>, thresholds: &CheckThresholds) -> CheckResult {
+    let threshold = TimeDelta::minutes(thresholds.gap_threshold);
+    let gaps = find_gaps(volumes, threshold);
+
+    let longest = gaps
+        .iter()
+        .map(|gap| gap.duration().num_minutes())
+        .max()
+        .unwrap_or(0);
+
+    let result = if gaps.is_empty() {
+        CheckResult::new(Level::Info, GAPS, "No gaps in data")
+    } else {
+        let gaps = gaps.iter().fold(String::new(), |mut output, gap| {
+            let _ = write!(
+                output,
+                "{} to {} ({} minutes); ",
+                gap.start,
+                gap.end,
+                gap.duration().num_minutes()
+            );
+            output
+        });
+        CheckResult::new(
+            Level::Warn,
+            GAPS,
+            format!(
+                "Found periods longer than {} minutes with nothing counted: {gaps}",
+                threshold.num_minutes()
+            ),
+        )
+    };
+    result
+        .metric("num_gaps", gaps.len() as f64)
+        .metric("longest_gap_minutes", longest as f64)
+}
 
+/// Check if a count covers enough full days, and whether it has partial ones, from its volume per
+/// 15-minute period.
+fn full_days(volumes: &BTreeMap<NaiveDateTime, u32>, thresholds: &CheckThresholds) -> CheckResult {
+    let coverage = DayCoverage::from_volumes(volumes);
+    if coverage.full_days.is_empty() && coverage.partial_days.is_empty() {
+        return CheckResult::new(Level::Info, FULL_DAYS, "Count is empty");
     }
 
-    if matches!(
-        count_kind,
-        CountKind::Class | CountKind::Volume | CountKind::FifteenMinVolume
-    ) {
-        match check_vehicle_dir_proportionality(recordnum, conn) {
-            Ok(v) if v.level == Level::Warn => {
-                log_msg(recordnum, &data_check_log, Level::Warn, &v.message, conn);
-            }
-            _ => (),            
-        }
+    let mut issues = vec![];
+    if coverage.full_days.len() < thresholds.min_full_days {
+        issues.push(format!(
+            "only {} full days counted (expected at least {})",
+            coverage.full_days.len(),
+            thresholds.min_full_days
+        ));
+    }
+    for (day, covered) in &coverage.partial_days {
+        issues.push(format!(
+            "{day} is partial ({:.2} hours), which will skew daily averages",
+            covered.num_minutes() as f32 / 60.0
+        ));
     }
 
-    if matches!(
-        count_kind,
-        CountKind::Bicycle1
-            | CountKind::Bicycle2
-            | CountKind::Bicycle3
-            | CountKind::Bicycle4
-            | CountKind::Bicycle5
-            | CountKind::Bicycle6,
-    ) {
-        match check_bike_dir_proportionality(recordnum, conn) {
-            Ok(v) if v.level == Level::Warn => {
-                log_msg(recordnum, &data_check_log, Level::Warn, &v.message, conn);
-            }
-            _ => (),            
+    let result = if issues.is_empty() {
+        CheckResult::new(Level::Info, FULL_DAYS, "Count covers only full days")
+    } else {
+        CheckResult::new(
+            Level::Warn,
+            FULL_DAYS,
+            format!("Incomplete days counted: {}.", issues.join("; ")),
+        )
+    };
+    result
+        .metric("full_days", coverage.full_days.len() as f64)
+        .metric("partial_days", coverage.partial_days.len() as f64)
+}
+
+/// Check if a count has runs of the same count, as from a stuck counter, from its volume per
+/// 15-minute period.
+fn stuck_counter(
+    volumes: &BTreeMap<NaiveDateTime, u32>,
+    thresholds: &CheckThresholds,
+) -> CheckResult {
+    let runs = find_stuck_runs(volumes, thresholds.stuck_periods);
+    let longest = runs.iter().map(|run| run.periods()).max().unwrap_or(0);
+
+    let result = if runs.is_empty() {
+        CheckResult::new(Level::Info, STUCK_COUNTER, "No runs of the same count")
+    } else {
+        let runs = runs.iter().fold(String::new(), |mut output, run| {
+            let _ = write!(
+                output,
+                "{} to {} ({} each); ",
+                run.start, run.end, run.volume
+            );
+            output
+        });
+        CheckResult::new(
+            Level::Warn,
+            STUCK_COUNTER,
+            format!(
+                "Found {} or more consecutive 15-minute periods with the same count, \
+                possibly from a stuck counter: {runs}",
+                thresholds.stuck_periods
+            ),
+        )
+    };
+    result
+        .metric("num_runs", runs.len() as f64)
+        .metric("longest_run_periods", longest as f64)
+}
+
+/// Check if speeds are implausible given the speed limit, as from a mis-calibrated counter or the
+/// wrong speed limit.
+fn check_speed_outliers(
+    recordnum: u32,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
+    let speed_limit = conn.query_row_as::<Option<u8>>(
+        "select speedlimit from tc_header where recordnum = :1",
+        &[&recordnum],
+    )?;
+    let counts = TimeBinnedSpeedRangeCount::select(conn, recordnum)?;
+    Ok(speed_outliers(
+        &speed_ranges(&counts),
+        speed_limit,
+        thresholds,
+    ))
+}
+
+/// Get the total count in each speed range (s1-s14).
+pub(crate) fn speed_ranges(counts: &[TimeBinnedSpeedRangeCount]) -> [u32; 14] {
+    let mut ranges = [0; 14];
+    for count in counts {
+        let count_ranges = [
+            count.s1, count.s2, count.s3, count.s4, count.s5, count.s6, count.s7, count.s8,
+            count.s9, count.s10, count.s11, count.s12, count.s13, count.s14,
+        ];
+        for (range, count) in ranges.iter_mut().zip(count_ranges) {
+            *range += count;
         }
     }
+    ranges
+}
 
-    /*
-    TODO: after table normalized (for both vehicles and bicycles)
-    if matches!(count_kind, CountKind::Class | CountKind::FifteenMinVolume) {
-        match check_vehicle_0_hours(recordnum, conn) {
-            Ok(v) if v.level == Level::Warn => {
-                log_msg(recordnum, &data_check_log, Level::Warn, &v.message, conn);
-            }
-            _ => (),            
+/// Estimate a percentile (0-1) of speeds from the total count in each speed range, assuming
+/// speeds are evenly distributed within each range.
+pub(crate) fn speed_percentile(ranges: &[u32; 14], percentile: f32) -> Option<f32> {
+    let total = ranges.iter().sum::<u32>();
+    if total == 0 {
+        return None;
+    }
+    let target = total as f32 * percentile;
+    let mut cumulative = 0.0;
+    for (i, &count) in ranges.iter().enumerate() {
+        if count > 0 && cumulative + count as f32 >= target {
+            let lower = SPEED_RANGE_LOWER_BOUNDS[i];
+            let upper = SPEED_RANGE_LOWER_BOUNDS
+                .get(i + 1)
+                .copied()
+                .unwrap_or(SPEED_RANGE_MAX);
+            return Some(lower + (upper - lower) * (target - cumulative) / count as f32);
         }
+        cumulative += count as f32;
     }
-    */
+    None
+}
 
-    // Warn about bicycle counts having more than 20 in any 15-minute period.
-    if matches!(
-        count_kind,
-        CountKind::Bicycle1
-            | CountKind::Bicycle2
-            | CountKind::Bicycle3
-            | CountKind::Bicycle4
-            | CountKind::Bicycle5
-            | CountKind::Bicycle6,
-    ) {
-        match check_excessive_bicycles(recordnum, conn) {
-            Ok(v) if v.level == Level::Warn => {
-                log_msg(recordnum, &data_check_log, Level::Warn, &v.message, conn);
-            }
-            _ => (),            
-        }
+/// Check if speeds are implausible given the speed limit, from the total count in each speed
+/// range.
+fn speed_outliers(
+    ranges: &[u32; 14],
+    speed_limit: Option<u8>,
+    thresholds: &CheckThresholds,
+) -> CheckResult {
+    let Some(speed_limit) = speed_limit else {
+        return CheckResult::new(
+            Level::Info,
+            SPEED_OUTLIERS,
+            "Skipping speed outlier check - no speed limit.",
+        );
+    };
+    let Some(p85) = speed_percentile(ranges, 0.85) else {
+        return CheckResult::new(Level::Info, SPEED_OUTLIERS, "Count is empty");
+    };
+
+    // Only ranges entirely above the limit for excessive speeds are counted.
+    let excess_speed = speed_limit as f32 + thresholds.speed_excess_over_limit;
+    let excess = SPEED_RANGE_LOWER_BOUNDS
+        .iter()
+        .zip(ranges)
+        .filter(|(lower, _)| **lower >= excess_speed)
+        .map(|(_, count)| count)
+        .sum::<u32>();
+    let excess_share = excess as f32 / ranges.iter().sum::<u32>() as f32;
+
+    let mut issues = vec![];
+    if excess_share > thresholds.speed_excess_max_share {
+        issues.push(format!(
+            "{:.1}% of vehicles were recorded going more than {excess_speed} mph",
+            excess_share * 100_f32
+        ));
+    }
+    if (p85 - speed_limit as f32).abs() > thresholds.speed_p85_max_difference {
+        issues.push(format!(
+            "the 85th percentile speed ({p85:.1} mph) is more than {} mph from the speed limit",
+            thresholds.speed_p85_max_difference
+        ));
     }
 
-    Ok(())
+    let result = if issues.is_empty() {
+        CheckResult::new(
+            Level::Info,
+            SPEED_OUTLIERS,
+            "Speeds are within expectations",
+        )
+    } else {
+        CheckResult::new(
+            Level::Warn,
+            SPEED_OUTLIERS,
+            format!(
+                "Implausible speeds for a speed limit of {speed_limit} mph (check the counter's \
+                calibration and the speed limit): {}.",
+                issues.join("; ")
+            ),
+        )
+    };
+    result
+        .metric("speed_limit", speed_limit as f64)
+        .metric("excess_share", excess_share as f64)
+        .metric("p85_speed", p85 as f64)
+}
+
+/// Apply the [data checks][CheckRunner::run_pending] for speed counts to ones not (yet) in the
+/// database, returning the messages of any issues found.
+pub fn check_speed_range_counts(
+    counts: &[TimeBinnedSpeedRangeCount],
+    speed_limit: Option<u8>,
+    thresholds: &CheckThresholds,
+) -> Vec<String> {
+    CheckRunner::default().run_pending(
+        &PendingCounts::SpeedRanges {
+            counts,
+            speed_limit,
+        },
+        thresholds,
+    )
+}
+
+/// Apply the [data checks][CheckRunner::run_pending] for class counts to ones not (yet) in the
+/// database, returning the messages of any issues found.
+pub fn check_vehicle_class_counts(
+    counts: &[TimeBinnedVehicleClassCount],
+    thresholds: &CheckThresholds,
+) -> Vec<String> {
+    CheckRunner::default().run_pending(&PendingCounts::VehicleClasses(counts), thresholds)
+}
+
+/// Apply the [data checks][CheckRunner::run_pending] for 15-minute volume counts to ones not
+/// (yet) in the database, returning the messages of any issues found.
+pub fn check_fifteen_minute_vehicle_counts(
+    counts: &[FifteenMinuteVehicle],
+    thresholds: &CheckThresholds,
+) -> Vec<String> {
+    CheckRunner::default().run_pending(&PendingCounts::FifteenMinuteVehicles(counts), thresholds)
+}
+
+/// Apply the [data checks][CheckRunner::run_pending] for bicycle counts to ones not (yet) in the
+/// database, returning the messages of any issues found.
+pub fn check_bicycle_counts(
+    counts: &[FifteenMinuteBicycle],
+    bidirectional: bool,
+    thresholds: &CheckThresholds,
+) -> Vec<String> {
+    CheckRunner::default().run_pending(
+        &PendingCounts::Bicycles {
+            counts,
+            bidirectional,
+        },
+        thresholds,
+    )
 }
 
 /// Check if share of class 2 vehicles is too low.
 fn check_share_class2_vehicles(
     recordnum: u32,
     conn: &Connection,
+    thresholds: &CheckThresholds,
 ) -> Result<CheckResult, CountError> {
-
     let counts = get_c2_c15_total_counts(recordnum, conn)?;
+    Ok(share_class2_vehicles(&counts, thresholds))
+}
 
+/// Check if share of class 2 vehicles is too low, from class counts.
+fn share_class2_vehicles(counts: &[ClassCountCheck], thresholds: &CheckThresholds) -> CheckResult {
     // Check share of class 2 of total.
     let c2_sum = counts.iter().map(|count| count.c2).sum::<u32>();
     let total_sum = counts.iter().map(|count| count.total).sum::<u32>();
 
     let c2_percent = c2_sum as f32 / total_sum as f32 * 100.0;
 
-    if c2_percent < 75.0 {
-        Ok(CheckResult {
-            level: Level::Warn,
-            message: format!("Class 2 vehicles are less than 75% ({c2_percent:.1}%) of total.")
-        })
+    let result = if c2_percent < thresholds.class2_min_percent {
+        CheckResult::new(
+            Level::Warn,
+            SHARE_CLASS2_VEHICLES,
+            format!(
+                "Class 2 vehicles are less than {}% ({c2_percent:.1}%) of total.",
+                thresholds.class2_min_percent
+            ),
+        )
     } else {
-        Ok(CheckResult {
-            level: Level::Info,
-            message: "Share of class 2 vehicles is within expectations".to_string(),
-        })
-    }
+        CheckResult::new(
+            Level::Info,
+            SHARE_CLASS2_VEHICLES,
+            "Share of class 2 vehicles is within expectations",
+        )
+    };
+    result.metric("c2_percent", c2_percent as f64)
 }
 
 /// Check if share of unclassed vehicles is too high.
 fn check_share_unclassed_vehicles(
     recordnum: u32,
     conn: &Connection,
+    thresholds: &CheckThresholds,
 ) -> Result<CheckResult, CountError> {
     let counts = get_c2_c15_total_counts(recordnum, conn)?;
+    Ok(share_unclassed_vehicles(&counts, thresholds))
+}
 
+/// Check if share of unclassed vehicles is too high, from class counts.
+fn share_unclassed_vehicles(
+    counts: &[ClassCountCheck],
+    thresholds: &CheckThresholds,
+) -> CheckResult {
     // Check share of class 15 of total.
     let c15_sum = counts.iter().map(|count| count.c15).sum::<u32>();
     let total_sum = counts.iter().map(|count| count.total).sum::<u32>();
 
     let c15_percent = c15_sum as f32 / total_sum as f32 * 100.0;
 
-    if c15_percent > 10.0 {
-        Ok(CheckResult {
-            level: Level::Warn,
-            message:format!("Unclassed vehicles are greater than 10% ({c15_percent:.1}%) of total."),
-        })
+    let result = if c15_percent > thresholds.unclassed_max_percent {
+        CheckResult::new(
+            Level::Warn,
+            SHARE_UNCLASSED_VEHICLES,
+            format!(
+                "Unclassed vehicles are greater than {}% ({c15_percent:.1}%) of total.",
+                thresholds.unclassed_max_percent
+            ),
+        )
     } else {
-        Ok(CheckResult {
-            level: Level::Info,
-            message: "Share of unclassed vehicles is within expectations".to_string(),
-        })
-    }
+        CheckResult::new(
+            Level::Info,
+            SHARE_UNCLASSED_VEHICLES,
+            "Share of unclassed vehicles is within expectations",
+        )
+    };
+    result.metric("c15_percent", c15_percent as f64)
 }
 
 /// Check if motor vehicle counts have relatively even proportion of total per direction.
-fn check_vehicle_dir_proportionality(recordnum: u32, conn: &Connection) -> Result<CheckResult, CountError> {
+fn check_vehicle_dir_proportionality(
+    recordnum: u32,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
     let results = conn.query_as::<(u32, String)>(
-        "select totalcount, cntdir from tc_volcount where recordnum = :1",
+        "select totalcount, cntdir from tc_volcount where recordnum = :1 and cntdir is not null",
         &[&recordnum],
     )?;
 
@@ -228,11 +1805,17 @@ fn check_vehicle_dir_proportionality(recordnum: u32, conn: &Connection) -> Resul
         *count_by_dir.entry(direction).or_insert(total) += total;
     }
 
+    Ok(vehicle_dir_proportionality(count_by_dir, thresholds))
+}
+
+/// Check if motor vehicle counts have relatively even proportion of total per direction, from
+/// the total per direction.
+fn vehicle_dir_proportionality(
+    count_by_dir: HashMap<String, u32>,
+    thresholds: &CheckThresholds,
+) -> CheckResult {
     if count_by_dir.is_empty() {
-        return Ok(CheckResult {
-            level: Level::Info,
-            message: "Count is empty".to_string()
-        })
+        return CheckResult::new(Level::Info, VEHICLE_DIR_PROPORTIONALITY, "Count is empty");
     }
 
     let larger = count_by_dir.iter().max_by(|a, b| a.1.cmp(b.1)).unwrap();
@@ -242,36 +1825,274 @@ fn check_vehicle_dir_proportionality(recordnum: u32, conn: &Connection) -> Resul
         let total = smaller.1 + larger.1;
         let smaller_share = *smaller.1 as f32 / total as f32;
         let larger_share = *larger.1 as f32 / total as f32;
-        if smaller_share < DIR_PROPORTION_LOWER_BOUND {
+        let lower_bound = thresholds.dir_proportion_lower_bound;
+        if smaller_share < lower_bound {
             let msg =  format!("Abnormal direction proportions: {} has {:.1}% of total, {} has {:.1}%. (Expectation is that proportions are no less/more than {}%/{}%.)",
                 smaller.0,
                 smaller_share * 100_f32,
                 larger.0,
                 larger_share * 100_f32,
-                DIR_PROPORTION_LOWER_BOUND * 100_f32,
-                100_f32 - DIR_PROPORTION_LOWER_BOUND * 100_f32);
-            Ok(CheckResult {
-                level: Level::Warn,
-                message: msg,                    
-            })
+                lower_bound * 100_f32,
+                100_f32 - lower_bound * 100_f32);
+            CheckResult::new(Level::Warn, VEHICLE_DIR_PROPORTIONALITY, msg)
+                .metric("smaller_share", smaller_share as f64)
         } else {
-            Ok(CheckResult {
-                level: Level::Info,
-                message: "Direction proportions is within expectations".to_string(),
-            })
+            CheckResult::new(
+                Level::Info,
+                VEHICLE_DIR_PROPORTIONALITY,
+                "Direction proportions is within expectations",
+            )
+            .metric("smaller_share", smaller_share as f64)
         }
     } else {
-        Ok(CheckResult {
-            level: Level::Info,
-            message: "Skipping disproportional directionality check - count only one direction.".to_string(),
-        })
+        CheckResult::new(
+            Level::Info,
+            VEHICLE_DIR_PROPORTIONALITY,
+            "Skipping disproportional directionality check - count only one direction.",
+        )
+    }
+}
+
+/// Check if the class, speed, and hourly volume counts of a class count (the TC_CLACOUNT,
+/// TC_SPECOUNT, and TC_VOLCOUNT tables) have the same total in each hour.
+fn check_table_consistency(recordnum: u32, conn: &Connection) -> Result<CheckResult, CountError> {
+    let class = db::get_class_count(conn, recordnum)?
+        .iter()
+        .map(|v| (v.date, v.time, v.total))
+        .collect::<Vec<_>>();
+    let speed = db::get_speed_count(conn, recordnum)?
+        .iter()
+        .map(|v| (v.date, v.time, v.total))
+        .collect::<Vec<_>>();
+    Ok(table_consistency(
+        &hourly_totals(class),
+        &hourly_totals(speed),
+        &hourly_volumes(&db::get_volume_count(conn, recordnum)?),
+    ))
+}
+
+/// The total of counts (by date, time, and total) in each hour.
+fn hourly_totals(
+    counts: impl IntoIterator<Item = (NaiveDate, NaiveDateTime, u32)>,
+) -> BTreeMap<NaiveDateTime, u32> {
+    let mut totals = BTreeMap::new();
+    for (date, time, total) in counts {
+        let hour = NaiveDateTime::new(date, bin_time(time.time(), TimeInterval::Hour));
+        *totals.entry(hour).or_insert(0) += total;
+    }
+    totals
+}
+
+/// The total volume of hourly volume counts (of all lanes) in each hour counted.
+///
+/// Rows of [both directions combined][crate::combined_directions] are left out of days that also
+/// have rows of each direction (as by [`db::get_volume_count`]).
+fn hourly_volumes(counts: &[NonNormalVolCount]) -> BTreeMap<NaiveDateTime, u32> {
+    let mut volumes = BTreeMap::new();
+    for count in counts {
+        let hours = count.hourly();
+        for (hour, volume) in (0..).zip(hours) {
+            if let Some(volume) = volume {
+                let hour = count.date.and_time(NaiveTime::MIN) + TimeDelta::hours(hour);
+                *volumes.entry(hour).or_insert(0) += volume;
+            }
+        }
+    }
+    volumes
+}
+
+/// Check if class, speed, and volume totals agree in each hour, from the totals in each hour.
+///
+/// Speed totals are only compared if there are any, as counts imported already binned by class
+/// don't have speeds. Hours missing from a table are considered to have nothing counted.
+fn table_consistency(
+    class: &BTreeMap<NaiveDateTime, u32>,
+    speed: &BTreeMap<NaiveDateTime, u32>,
+    volume: &BTreeMap<NaiveDateTime, u32>,
+) -> CheckResult {
+    if class.is_empty() && volume.is_empty() {
+        return CheckResult::new(Level::Info, TABLE_CONSISTENCY, "Count is empty");
+    }
+    let has_speeds = !speed.is_empty();
+    let hours = class
+        .keys()
+        .chain(speed.keys())
+        .chain(volume.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut discrepancies = vec![];
+    for hour in hours {
+        let class_total = class.get(hour).copied().unwrap_or(0);
+        let speed_total = speed.get(hour).copied().unwrap_or(0);
+        let volume_total = volume.get(hour).copied().unwrap_or(0);
+        if class_total != volume_total || (has_speeds && class_total != speed_total) {
+            let mut msg = format!("{} (class {class_total}", hour.format("%Y-%m-%d %H:%M"));
+            if has_speeds {
+                let _ = write!(msg, ", speed {speed_total}");
+            }
+            let _ = write!(msg, ", volume {volume_total})");
+            discrepancies.push(msg);
+        }
+    }
+
+    let total = |totals: &BTreeMap<NaiveDateTime, u32>| totals.values().sum::<u32>() as f64;
+    let result = if discrepancies.is_empty() {
+        CheckResult::new(
+            Level::Info,
+            TABLE_CONSISTENCY,
+            "Class, speed, and volume totals agree",
+        )
+    } else {
+        let shown = discrepancies.iter().take(5).cloned().collect::<Vec<_>>();
+        let more = discrepancies.len() - shown.len();
+        let mut msg = format!(
+            "Class, speed, and volume totals disagree in {} hour(s), though they're created from \
+            the same vehicles: {}",
+            discrepancies.len(),
+            shown.join("; ")
+        );
+        if more > 0 {
+            let _ = write!(msg, "; and {more} more");
+        }
+        CheckResult::new(Level::Warn, TABLE_CONSISTENCY, msg)
+    };
+    result
+        .metric("inconsistent_hours", discrepancies.len() as f64)
+        .metric("class_total", total(class))
+        .metric("speed_total", total(speed))
+        .metric("volume_total", total(volume))
+}
+
+/// Check if one direction of a bidirectional motor vehicle count recorded essentially nothing
+/// for hours on end, as when one side's tube is disconnected.
+fn check_hourly_dir_proportionality(
+    recordnum: u32,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
+    let mut volumes: BTreeMap<NaiveDateTime, BTreeMap<LaneDirection, u32>> = BTreeMap::new();
+    for count in db::get_volume_count(conn, recordnum)? {
+        let Some(direction) = count.direction else {
+            continue;
+        };
+        let hours = count.hourly();
+        for (hour, volume) in (0..).zip(hours) {
+            if let Some(volume) = volume {
+                let hour = count.date.and_time(NaiveTime::MIN) + TimeDelta::hours(hour);
+                *volumes
+                    .entry(hour)
+                    .or_default()
+                    .entry(direction)
+                    .or_insert(0) += volume;
+            }
+        }
+    }
+    Ok(hourly_dir_proportionality(&volumes, thresholds))
+}
+
+/// Check if one direction of a bidirectional count recorded essentially nothing for hours on
+/// end, from the volume of each direction per hour.
+fn hourly_dir_proportionality(
+    volumes: &BTreeMap<NaiveDateTime, BTreeMap<LaneDirection, u32>>,
+    thresholds: &CheckThresholds,
+) -> CheckResult {
+    let directions = volumes
+        .values()
+        .flat_map(|v| v.keys())
+        .collect::<BTreeSet<_>>();
+    if directions.len() < 2 {
+        return CheckResult::new(
+            Level::Info,
+            HOURLY_DIR_PROPORTIONALITY,
+            "Skipping hourly directionality check - count only one direction.",
+        );
+    }
+
+    let dropouts = find_direction_dropouts(
+        volumes,
+        thresholds.dir_dropout_max_share,
+        thresholds.dir_dropout_min_volume,
+        thresholds.dir_dropout_hours,
+    );
+    let longest = dropouts.iter().map(|v| v.hours).max().unwrap_or(0);
+
+    let result = if dropouts.is_empty() {
+        CheckResult::new(
+            Level::Info,
+            HOURLY_DIR_PROPORTIONALITY,
+            "Each direction recorded vehicles throughout the count",
+        )
+    } else {
+        let dropouts = dropouts.iter().fold(String::new(), |mut output, dropout| {
+            let _ = write!(
+                output,
+                "{} from {} to {} ({} hours); ",
+                dropout.direction, dropout.start, dropout.end, dropout.hours
+            );
+            output
+        });
+        CheckResult::new(
+            Level::Warn,
+            HOURLY_DIR_PROPORTIONALITY,
+            format!(
+                "Found {} or more consecutive hours in which one direction had less than {:.1}% \
+                of vehicles, possibly from a disconnected tube: {dropouts}",
+                thresholds.dir_dropout_hours,
+                thresholds.dir_dropout_max_share * 100_f32
+            ),
+        )
+    };
+    result
+        .metric("num_dropouts", dropouts.len() as f64)
+        .metric("longest_dropout_hours", longest as f64)
+}
+
+/// Summarize the full days of a motor vehicle count by day of the week.
+fn check_days_of_week(recordnum: u32, conn: &Connection) -> Result<CheckResult, CountError> {
+    let volumes = daily_volumes(&db::get_volume_count(conn, recordnum)?);
+    let summary = summarize_days_of_week(recordnum, &volumes, &Holidays::from_env()?);
+    Ok(days_of_week(&summary))
+}
+
+/// Report weekday and weekend average daily traffic, warning if any full days counted were
+/// holidays (which are excluded from it).
+fn days_of_week(summary: &DayOfWeekSummary) -> CheckResult {
+    let full_days = summary.days_of_week.iter().map(|v| v.days).sum::<u32>();
+    if full_days == 0 && summary.holidays.is_empty() {
+        return CheckResult::new(Level::Info, DAYS_OF_WEEK, "No full days counted");
+    }
+
+    let mut result = if summary.holidays.is_empty() {
+        CheckResult::new(Level::Info, DAYS_OF_WEEK, summary.to_string())
+    } else {
+        let holidays = summary
+            .holidays
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        CheckResult::new(
+            Level::Warn,
+            DAYS_OF_WEEK,
+            format!(
+                "Holidays counted, which are excluded from daily averages: {}.",
+                holidays.join(", ")
+            ),
+        )
+    };
+    if let Some(v) = summary.weekday_average {
+        result = result.metric("weekday_average", v as f64);
+    }
+    if let Some(v) = summary.weekend_average {
+        result = result.metric("weekend_average", v as f64);
     }
+    result.metric("holidays", summary.holidays.len() as f64)
 }
 
 /// Check if bicycle counts have relatively even proportion of total per direction.
 fn check_bike_dir_proportionality(
     recordnum: u32,
     conn: &Connection,
+    thresholds: &CheckThresholds,
 ) -> Result<CheckResult, CountError> {
     // Check to see if count is bidirectional.
     let results = conn.query_row_as::<String>(
@@ -284,31 +2105,46 @@ fn check_bike_dir_proportionality(
             "select sum(total), sum(incount), sum(outcount) from tc_bikecount where dvrpcnum = :1",
             &[&recordnum],
         )?;
+        Ok(bike_dir_proportionality(
+            total, incount, outcount, thresholds,
+        ))
+    } else {
+        Ok(CheckResult::new(
+            Level::Info,
+            BIKE_DIR_PROPORTIONALITY,
+            "Skipping disproportional directionality check - count only one direction.",
+        ))
+    }
+}
 
-        let incount_share = incount as f32 / total as f32;
-        let outcount_share = outcount as f32 / total as f32;
-
-        if incount_share < DIR_PROPORTION_LOWER_BOUND || outcount_share < DIR_PROPORTION_LOWER_BOUND
-        {
+/// Check if bicycle counts have relatively even proportion of total per direction, from the
+/// total and the total per direction of a bidirectional count.
+fn bike_dir_proportionality(
+    total: u32,
+    incount: u32,
+    outcount: u32,
+    thresholds: &CheckThresholds,
+) -> CheckResult {
+    let incount_share = incount as f32 / total as f32;
+    let outcount_share = outcount as f32 / total as f32;
+    let lower_bound = thresholds.dir_proportion_lower_bound;
 
-            Ok(CheckResult { level: Level::Warn, message: format!("Abnormal direction proportions: INCOUNT has {:.1}% of total, OUTCOUNT has {:.1}%. (Expectation is that proportions are no less/more than {}%/{}%.)",
-                            incount_share * 100_f32,
-                            outcount_share * 100_f32,
-                            DIR_PROPORTION_LOWER_BOUND * 100_f32,
-                            100_f32 - DIR_PROPORTION_LOWER_BOUND * 100_f32) 
-            })
-        } else {
-            Ok(CheckResult {
-                level: Level::Info,
-                message: "Direction proportions is within expectations".to_string(),
-            })
-        }
+    let result = if incount_share < lower_bound || outcount_share < lower_bound {
+        CheckResult::new(Level::Warn, BIKE_DIR_PROPORTIONALITY, format!("Abnormal direction proportions: INCOUNT has {:.1}% of total, OUTCOUNT has {:.1}%. (Expectation is that proportions are no less/more than {}%/{}%.)",
+                        incount_share * 100_f32,
+                        outcount_share * 100_f32,
+                        lower_bound * 100_f32,
+                        100_f32 - lower_bound * 100_f32))
     } else {
-        Ok(CheckResult {
-            level: Level::Info,
-            message: "Skipping disproportional directionality check - count only one direction.".to_string(),
-        })
-    }
+        CheckResult::new(
+            Level::Info,
+            BIKE_DIR_PROPORTIONALITY,
+            "Direction proportions is within expectations",
+        )
+    };
+    result
+        .metric("incount_share", incount_share as f64)
+        .metric("outcount_share", outcount_share as f64)
 }
 
 // Check if more than 1 consecutive 0-count/hour between 4am and 10pm for motor vehicles.
@@ -342,44 +2178,78 @@ fn check_vehicle_0_hours(recordnum: u32, conn: &Connection) -> Result<CheckResul
 */
 
 /// Check if there is an excessive number of bicycles in any 15-minute period.
-fn check_excessive_bicycles(recordnum: u32,conn: &Connection) -> Result<CheckResult, CountError> {   
+fn check_excessive_bicycles(
+    recordnum: u32,
+    conn: &Connection,
+    thresholds: &CheckThresholds,
+) -> Result<CheckResult, CountError> {
     let results = conn.query_as::<(NaiveDate, NaiveDateTime, u32, u32)>(
         "select countdate, counttime, incount, outcount from tc_bikecount where dvrpcnum = :1 order by countdate, counttime",
         &[&recordnum],
     )?;
 
+    let mut counts = vec![];
+    for result in results {
+        counts.push(result?);
+    }
+
+    Ok(excessive_bicycles(&counts, thresholds))
+}
+
+/// Check if there is an excessive number of bicycles in any 15-minute period, from the date,
+/// time, incount and outcount of each period.
+fn excessive_bicycles(
+    counts: &[(NaiveDate, NaiveDateTime, u32, u32)],
+    thresholds: &CheckThresholds,
+) -> CheckResult {
+    let bike_count_max = thresholds.bike_count_max;
     let mut excessive_bicycles = vec![];
 
-    for result in results {
-        let (countdate, counttime, incount, outcount) = result?;
-        if incount > BIKE_COUNT_MAX {
+    for &(countdate, counttime, incount, outcount) in counts {
+        if incount > bike_count_max {
             excessive_bicycles.push((countdate, counttime.time(), incount, "incount"))
         }
-        if outcount > BIKE_COUNT_MAX {
+        if outcount > bike_count_max {
             excessive_bicycles.push((countdate, counttime.time(), outcount, "outcount"))
-            
         }
     }
 
-    if excessive_bicycles.is_empty() {
-        Ok(CheckResult {
-            level: Level::Info,
-            message: "All counts under excessive threshold".to_string()
-        })        
+    let max = counts
+        .iter()
+        .map(|&(_, _, incount, outcount)| incount.max(outcount))
+        .max()
+        .unwrap_or(0);
+
+    let result = if excessive_bicycles.is_empty() {
+        CheckResult::new(
+            Level::Info,
+            EXCESSIVE_BICYCLES,
+            "All counts under excessive threshold",
+        )
     } else {
-        let excessive_bicycles = excessive_bicycles.iter().fold(String::new(), |mut output, count| {
-            let _ = write!(output, "{} {}: {} ({}); ", count.0, count.1, count.2, count.3);
-            output
-        });
+        let excessive_bicycles =
+            excessive_bicycles
+                .iter()
+                .fold(String::new(), |mut output, count| {
+                    let _ = write!(
+                        output,
+                        "{} {}: {} ({}); ",
+                        count.0, count.1, count.2, count.3
+                    );
+                    output
+                });
 
-        let message = format!("Found more than {BIKE_COUNT_MAX} bicycles counted in the following periods: {excessive_bicycles}");
-        Ok(CheckResult {
-            level: Level::Warn,
-            message,
-        })
-    }
+        let message = format!("Found more than {bike_count_max} bicycles counted in the following periods: {excessive_bicycles}");
+        CheckResult::new(Level::Warn, EXCESSIVE_BICYCLES, message)
+    };
+    result
+        .metric("num_excessive", excessive_bicycles.len() as f64)
+        .metric("max_count", max as f64)
 }
-fn get_c2_c15_total_counts(recordnum: u32, conn: &Connection) -> Result<Vec<ClassCountCheck>, CountError> {
+fn get_c2_c15_total_counts(
+    recordnum: u32,
+    conn: &Connection,
+) -> Result<Vec<ClassCountCheck>, CountError> {
     let results = conn.query_as::<(NaiveDate, NaiveDateTime, u8, String, u32, u32, u32)>(
     "select countdate, counttime, countlane, ctdir, total, cars_and_tlrs, unclassified from tc_clacount where recordnum = :1",
     &[&recordnum],
@@ -392,7 +2262,7 @@ fn get_c2_c15_total_counts(recordnum: u32, conn: &Connection) -> Result<Vec<Clas
         counts.push(ClassCountCheck {
             datetime,
             lane,
-            dir: LaneDirection::from_str(&direction).unwrap(),
+            dir: direction.parse()?,
             c2,
             c15,
             total,
@@ -407,28 +2277,414 @@ mod test {
     use super::*;
     use crate::db;
 
+    fn datetime(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn gaps_found_when_longer_than_threshold() {
+        let mut volumes = BTreeMap::new();
+        volumes.insert(datetime("2024-04-08 07:00"), 10);
+        // 07:15 - 07:45 missing
+        volumes.insert(datetime("2024-04-08 08:00"), 10);
+        volumes.insert(datetime("2024-04-08 08:15"), 0);
+        volumes.insert(datetime("2024-04-08 08:30"), 10);
+
+        let gaps = find_gaps(&volumes, TimeDelta::minutes(15));
+        assert_eq!(
+            gaps,
+            vec![Gap {
+                start: datetime("2024-04-08 07:15"),
+                end: datetime("2024-04-08 08:00"),
+            }]
+        );
+        assert_eq!(gaps[0].duration(), TimeDelta::minutes(45));
+
+        let gaps = find_gaps(&volumes, TimeDelta::minutes(0));
+        assert_eq!(gaps.len(), 2);
+        assert_eq!(gaps[1].start, datetime("2024-04-08 08:15"));
+
+        assert!(find_gaps(&volumes, TimeDelta::minutes(45)).is_empty());
+    }
+
+    #[test]
+    fn holidays_counted_reported() {
+        let volumes = BTreeMap::from([
+            (NaiveDate::from_ymd_opt(2024, 7, 3).unwrap(), 1200),
+            (NaiveDate::from_ymd_opt(2024, 7, 4).unwrap(), 400),
+        ]);
+        let holidays = Holidays {
+            dates: [NaiveDate::from_ymd_opt(2024, 7, 4).unwrap()].into(),
+        };
+
+        let result = days_of_week(&summarize_days_of_week(1, &volumes, &holidays));
+        assert_eq!(result.level, Level::Warn);
+        assert_eq!(result.metrics["weekday_average"], 1200.0);
+        assert_eq!(result.metrics["holidays"], 1.0);
+
+        let result = days_of_week(&summarize_days_of_week(1, &volumes, &Holidays::default()));
+        assert_eq!(result.level, Level::Info);
+        assert_eq!(result.metrics["weekday_average"], 800.0);
+    }
+
+    #[test]
+    fn gaps_found_at_end_of_count() {
+        let mut volumes = BTreeMap::new();
+        volumes.insert(datetime("2024-04-08 07:00"), 10);
+        volumes.insert(datetime("2024-04-08 07:15"), 0);
+        volumes.insert(datetime("2024-04-08 07:30"), 0);
+
+        let gaps = find_gaps(&volumes, TimeDelta::minutes(15));
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].end, datetime("2024-04-08 07:45"));
+    }
+
+    #[test]
+    fn stuck_runs_found_when_long_enough() {
+        let mut volumes = BTreeMap::new();
+        for (i, volume) in [5, 7, 7, 7, 7, 3, 0, 0, 0, 0, 3, 3].into_iter().enumerate() {
+            volumes.insert(
+                datetime("2024-04-08 07:00") + TimeDelta::minutes(15 * i as i64),
+                volume,
+            );
+        }
+        // 10:00 missing
+        volumes.insert(datetime("2024-04-08 10:15"), 3);
+
+        let runs = find_stuck_runs(&volumes, 4);
+        assert_eq!(
+            runs,
+            vec![StuckRun {
+                start: datetime("2024-04-08 07:15"),
+                end: datetime("2024-04-08 08:15"),
+                volume: 7,
+            }]
+        );
+        assert_eq!(runs[0].periods(), 4);
+
+        // Zeros are left to the gap check, and missing periods end a run.
+        assert_eq!(find_stuck_runs(&volumes, 2).len(), 2);
+        assert!(find_stuck_runs(&volumes, 5).is_empty());
+    }
+
+    #[test]
+    fn direction_dropouts_found_when_sustained() {
+        let thresholds = CheckThresholds::default();
+        let mut volumes = BTreeMap::new();
+        for (time, east, west) in [
+            ("2024-04-08 06:00", 50, 30),
+            ("2024-04-08 07:00", 50, 30),
+            ("2024-04-08 08:00", 50, 0),
+            // Too quiet to judge.
+            ("2024-04-08 09:00", 5, 0),
+            ("2024-04-08 10:00", 50, 1),
+            ("2024-04-08 11:00", 50, 0),
+            ("2024-04-08 12:00", 50, 30),
+        ] {
+            volumes.insert(
+                datetime(time),
+                BTreeMap::from([(LaneDirection::East, east), (LaneDirection::West, west)]),
+            );
+        }
+
+        let result = hourly_dir_proportionality(&volumes, &thresholds);
+        assert_eq!(result.level, Level::Warn);
+        assert_eq!(
+            find_direction_dropouts(&volumes, 0.05, 20, 3),
+            vec![DirectionDropout {
+                direction: LaneDirection::West,
+                start: datetime("2024-04-08 08:00"),
+                end: datetime("2024-04-08 12:00"),
+                hours: 3,
+            }]
+        );
+        assert!(find_direction_dropouts(&volumes, 0.05, 20, 4).is_empty());
+
+        // Missing hours end a run.
+        volumes.remove(&datetime("2024-04-08 09:00"));
+        assert!(find_direction_dropouts(&volumes, 0.05, 20, 3).is_empty());
+
+        // Nor are counts of one direction checked.
+        for by_direction in volumes.values_mut() {
+            by_direction.remove(&LaneDirection::West);
+        }
+        assert_eq!(
+            hourly_dir_proportionality(&volumes, &thresholds).level,
+            Level::Info
+        );
+    }
+
+    #[test]
+    fn class_speed_and_volume_totals_compared_by_hour() {
+        let hours = |totals: &[(&str, u32)]| {
+            hourly_totals(totals.iter().map(|(time, total)| {
+                let time = datetime(time);
+                (time.date(), time, *total)
+            }))
+        };
+        let class = hours(&[
+            ("2024-04-08 07:00", 10),
+            ("2024-04-08 07:45", 5),
+            ("2024-04-08 08:00", 3),
+        ]);
+        assert_eq!(class[&datetime("2024-04-08 07:00")], 15);
+
+        let volume = hours(&[("2024-04-08 07:00", 15), ("2024-04-08 08:00", 3)]);
+        let result = table_consistency(&class, &BTreeMap::new(), &volume);
+        assert_eq!(result.level, Level::Info);
+
+        // Volume missing an hour, and speed disagreeing in another.
+        let speed = hours(&[("2024-04-08 07:00", 14), ("2024-04-08 08:00", 3)]);
+        let volume = hours(&[("2024-04-08 07:00", 15)]);
+        let result = table_consistency(&class, &speed, &volume);
+        assert_eq!(result.level, Level::Warn);
+        assert_eq!(result.metrics["inconsistent_hours"], 2.0);
+        assert!(result
+            .message
+            .contains("2024-04-08 08:00 (class 3, speed 3, volume 0)"));
+    }
+
+    #[test]
+    fn speed_outliers_found_from_speed_ranges() {
+        let thresholds = CheckThresholds::default();
+        // Mostly 30-40 mph, as expected for a limit of 35.
+        let mut ranges = [0, 0, 5, 20, 40, 40, 20, 5, 0, 0, 0, 0, 0, 0];
+        let p85 = speed_percentile(&ranges, 0.85).unwrap();
+        assert!((40.0..=45.0).contains(&p85));
+        assert_eq!(
+            speed_outliers(&ranges, Some(35), &thresholds).level,
+            Level::Info
+        );
+
+        // Wrong speed limit.
+        let result = speed_outliers(&ranges, Some(65), &thresholds);
+        assert_eq!(result.level, Level::Warn);
+        assert!(result.message.contains("85th percentile"));
+
+        // Some vehicles recorded going more than 30 mph over the limit.
+        ranges[13] = 10;
+        let result = speed_outliers(&ranges, Some(35), &thresholds);
+        assert_eq!(result.level, Level::Warn);
+        assert_eq!(result.metrics["excess_share"], (10.0_f32 / 140.0) as f64);
+
+        assert_eq!(
+            speed_outliers(&ranges, None, &thresholds).level,
+            Level::Info
+        );
+    }
+
+    #[test]
+    fn day_coverage_has_full_and_partial_days() {
+        let mut volumes = BTreeMap::new();
+        for period in create_time_bins(
+            datetime("2024-04-08 10:00"),
+            datetime("2024-04-10 23:45"),
+            TimeInterval::FifteenMin,
+        ) {
+            volumes.insert(period, 1);
+        }
+
+        let coverage = DayCoverage::from_volumes(&volumes);
+        assert_eq!(
+            coverage.full_days,
+            vec![
+                NaiveDate::from_ymd_opt(2024, 4, 9).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 4, 10).unwrap()
+            ]
+        );
+        assert_eq!(
+            coverage.partial_days,
+            vec![(
+                NaiveDate::from_ymd_opt(2024, 4, 8).unwrap(),
+                TimeDelta::hours(14)
+            )]
+        );
+
+        let result = full_days(&volumes, &CheckThresholds::default());
+        assert_eq!(result.level, Level::Warn);
+        assert!(!result.message.contains("only"));
+        volumes.remove(&datetime("2024-04-08 10:00"));
+        assert_eq!(
+            DayCoverage::from_volumes(&volumes).partial_days[0].1,
+            TimeDelta::minutes(13 * 60 + 45)
+        );
+    }
+
+    #[test]
+    fn bicycle_counts_excessive_and_disproportionate_found() {
+        let time = NaiveDateTime::parse_from_str("2024-04-08 07:00", "%Y-%m-%d %H:%M").unwrap();
+        let counts =
+            vec![FifteenMinuteBicycle::new(123, time.date(), time, 25, Some(24), Some(1)).unwrap()];
+
+        let warnings = check_bicycle_counts(&counts, true, &CheckThresholds::default());
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].starts_with("Abnormal direction proportions"));
+        assert!(warnings[1].starts_with("Found more than 20 bicycles"));
+        assert!(warnings[2].starts_with("Incomplete days counted"));
+
+        // Not bidirectional, so proportions aren't checked.
+        let warnings = check_bicycle_counts(&counts, false, &CheckThresholds::default());
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn check_report_has_findings_with_metrics_and_serializes() {
+        let time = datetime("2024-04-08 07:00");
+        let report = CheckReport {
+            recordnum: 123,
+            results: vec![
+                bike_dir_proportionality(25, 24, 1, &CheckThresholds::default()),
+                excessive_bicycles(&[(time.date(), time, 10, 1)], &CheckThresholds::default()),
+            ],
+        };
+        let findings = report.findings().collect::<Vec<_>>();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, BIKE_DIR_PROPORTIONALITY);
+        assert_eq!(findings[0].metrics["outcount_share"], 0.04f32 as f64);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["results"][0]["level"], "WARN");
+        assert_eq!(json["results"][1]["check"], EXCESSIVE_BICYCLES);
+        assert_eq!(json["results"][1]["metrics"]["max_count"], 10.0);
+    }
+
+    #[test]
+    fn check_config_thresholds_by_season_and_count_type() {
+        let config: CheckConfig = toml::from_str(
+            r#"
+            [default]
+            bike_count_max = 25
+
+            [[seasons]]
+            months = [6, 7, 8]
+            bike_count_max = 30
+            gap_threshold = 60
+
+            [count_types."Bicycle 2"]
+            bike_count_max = 40
+            "#,
+        )
+        .unwrap();
+        let june = NaiveDate::from_ymd_opt(2024, 6, 3);
+        let january = NaiveDate::from_ymd_opt(2024, 1, 8);
+
+        let thresholds = config.thresholds(Some(&CountKind::Bicycle1), january);
+        assert_eq!(thresholds.bike_count_max, 25);
+        assert_eq!(
+            thresholds.dir_proportion_lower_bound,
+            DIR_PROPORTION_LOWER_BOUND
+        );
+        let thresholds = config.thresholds(Some(&CountKind::Bicycle1), june);
+        assert_eq!(thresholds.bike_count_max, 30);
+        assert_eq!(thresholds.gap_threshold, 60);
+        let thresholds = config.thresholds(Some(&CountKind::Bicycle2), june);
+        assert_eq!(thresholds.bike_count_max, 40);
+        assert_eq!(thresholds.gap_threshold, 60);
+
+        let time = datetime("2024-06-03 07:00");
+        let counts = [(time.date(), time, 35, 0)];
+        assert_eq!(excessive_bicycles(&counts, &thresholds).level, Level::Info);
+        assert_eq!(
+            excessive_bicycles(&counts, &CheckThresholds::default()).level,
+            Level::Warn
+        );
+    }
+
+    #[test]
+    fn checks_disabled_by_config_and_verified_by_runner() {
+        let config: CheckConfig = toml::from_str(
+            r#"
+            [default.checks]
+            stuck_counter = false
+            gaps = false
+
+            [count_types."Bicycle 2".checks]
+            gaps = true
+            "#,
+        )
+        .unwrap();
+        let thresholds = config.thresholds(Some(&CountKind::Bicycle1), None);
+        assert!(!thresholds.is_enabled(STUCK_COUNTER));
+        assert!(!thresholds.is_enabled(GAPS));
+        assert!(thresholds.is_enabled(FULL_DAYS));
+        let thresholds = config.thresholds(Some(&CountKind::Bicycle2), None);
+        assert!(thresholds.is_enabled(GAPS));
+
+        let runner = CheckRunner::default();
+        assert_eq!(runner.names().count(), 12);
+        assert!(runner.verify_config(&config).is_ok());
+        let config: CheckConfig = toml::from_str(
+            "[default.checks]
+some_check = false",
+        )
+        .unwrap();
+        assert!(runner.verify_config(&config).is_err());
+
+        // Checks registered alongside the built-in ones can be configured too.
+        struct SomeCheck;
+        impl Check for SomeCheck {
+            fn name(&self) -> &'static str {
+                "some_check"
+            }
+            fn applies_to(&self, _count_kind: &CountKind) -> bool {
+                true
+            }
+            fn run(&self, _ctx: &CheckContext) -> Result<CheckResult, CountError> {
+                Ok(CheckResult::new(Level::Info, self.name(), "ok"))
+            }
+            fn run_pending(
+                &self,
+                _counts: &PendingCounts,
+                _thresholds: &CheckThresholds,
+            ) -> Option<CheckResult> {
+                Some(CheckResult::new(Level::Warn, self.name(), "not ok"))
+            }
+        }
+        let mut runner = CheckRunner::default();
+        runner.register(SomeCheck);
+        assert!(runner.verify_config(&config).is_ok());
+        // And are applied to counts not yet in the database.
+        let thresholds = CheckThresholds::default();
+        assert_eq!(
+            runner.run_pending(&PendingCounts::VehicleClasses(&[]), &thresholds),
+            ["not ok"]
+        );
+
+        // Disabled checks are left out of those of counts not yet in the database, too.
+        let time = datetime("2024-06-03 07:00");
+        let counts =
+            [FifteenMinuteBicycle::new(1, time.date(), time, 40, Some(40), Some(0)).unwrap()];
+        let thresholds = CheckThresholds::default();
+        let all = check_bicycle_counts(&counts, false, &thresholds).len();
+        let mut thresholds = CheckThresholds::default();
+        thresholds.disabled.insert(EXCESSIVE_BICYCLES.to_string());
+        assert_eq!(
+            check_bicycle_counts(&counts, false, &thresholds).len(),
+            all - 1
+        );
+    }
+
     #[ignore]
     #[test]
     fn fifteen_min_bicycle_disproportionate_direction_found() {
-        let (username, password) = db::get_creds();         
+        let (username, password) = db::get_creds();
         let pool = db::create_pool(username, password).unwrap();
         let conn = pool.get().unwrap();
 
-        let result = check_bike_dir_proportionality(158971, &conn).unwrap();
+        let result =
+            check_bike_dir_proportionality(158971, &conn, &CheckThresholds::default()).unwrap();
         assert!(matches!(result.level, Level::Warn))
     }
 
     #[ignore]
     #[test]
     fn fifteen_min_bicycle_exessive() {
-        let (username, password) = db::get_creds();         
+        let (username, password) = db::get_creds();
         let pool = db::create_pool(username, password).unwrap();
         let conn = pool.get().unwrap();
 
-        let result = check_excessive_bicycles(111722, &conn).unwrap();
+        let result = check_excessive_bicycles(111722, &conn, &CheckThresholds::default()).unwrap();
         dbg!(&result);
         assert!(matches!(result.level, Level::Warn))
-
-        
     }
 }
@@ -0,0 +1,246 @@
+//! Vehicle classification schemes that FHWA classes can be collapsed into.
+//!
+//! Counts are always binned and stored by the 13 [FHWA classes][VehicleClass], but some
+//! deliverables (e.g. those for NJDOT) call for fewer, broader classes. A [`ClassScheme`] maps
+//! each FHWA class to one of its own, so that [class counts][TimeBinnedVehicleClassCount] can be
+//! [aggregated][ClassSchemeCount::from_count] under it. The schemes are:
+//!   - "fhwa" - the 13 FHWA classes themselves
+//!   - "six-bin" - bikes (1), cars (2), light trucks (3), buses (4), single-unit trucks (5-7),
+//!     and combination trucks (8-13)
+//!   - the path to a TOML file of a custom mapping, like:
+//! ```toml
+//! [[classes]]
+//! name = "light"
+//! fhwa = [1, 2, 3]
+//!
+//! [[classes]]
+//! name = "heavy"
+//! fhwa = [4, 5, 6, 7, 8, 9, 10, 11, 12, 13]
+//! ```
+//! Unclassified vehicles, and any FHWA classes not in a custom mapping, are only included in the
+//! total.
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{CountError, LaneDirection, TimeBinnedVehicleClassCount, VehicleClass};
+
+/// A class of a [`ClassScheme`], and the FHWA classes in it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SchemeClass {
+    pub name: String,
+    pub fhwa: Vec<u8>,
+}
+
+impl SchemeClass {
+    fn new(name: &str, fhwa: &[u8]) -> Self {
+        Self {
+            name: name.to_string(),
+            fhwa: fhwa.to_vec(),
+        }
+    }
+}
+
+/// A custom class scheme, from a TOML file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct CustomScheme {
+    classes: Vec<SchemeClass>,
+}
+
+/// A vehicle classification scheme.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClassScheme {
+    Fhwa,
+    SixBin,
+    Custom {
+        path: PathBuf,
+        classes: Vec<SchemeClass>,
+    },
+}
+
+impl ClassScheme {
+    /// Get a custom scheme from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        let scheme: CustomScheme = toml::from_str(&fs::read_to_string(path)?)?;
+        let mut fhwa = scheme
+            .classes
+            .iter()
+            .flat_map(|v| &v.fhwa)
+            .collect::<Vec<_>>();
+        if let Some(class) = fhwa.iter().find(|v| !(1..=13).contains(**v)) {
+            return Err(CountError::BadVehicleClass(**class));
+        }
+        let num_mapped = fhwa.len();
+        fhwa.sort();
+        fhwa.dedup();
+        if fhwa.len() != num_mapped {
+            return Err(CountError::UnknownClassScheme(format!(
+                "{}: an FHWA class is in more than one class",
+                path.display()
+            )));
+        }
+        Ok(ClassScheme::Custom {
+            path: path.to_path_buf(),
+            classes: scheme.classes,
+        })
+    }
+
+    /// The classes of the scheme, in order.
+    pub fn classes(&self) -> Vec<SchemeClass> {
+        match self {
+            ClassScheme::Fhwa => (1..=13)
+                .map(|v| SchemeClass::new(&format!("c{v}"), &[v]))
+                .collect(),
+            ClassScheme::SixBin => vec![
+                SchemeClass::new("bikes", &[1]),
+                SchemeClass::new("cars", &[2]),
+                SchemeClass::new("light_trucks", &[3]),
+                SchemeClass::new("buses", &[4]),
+                SchemeClass::new("single_unit", &[5, 6, 7]),
+                SchemeClass::new("combination", &[8, 9, 10, 11, 12, 13]),
+            ],
+            ClassScheme::Custom { classes, .. } => classes.clone(),
+        }
+    }
+
+    /// Get the index of the scheme's class that an FHWA class is in, if any.
+    pub fn class_of(&self, class: &VehicleClass) -> Option<usize> {
+        let num = class.as_num();
+        self.classes().iter().position(|v| v.fhwa.contains(&num))
+    }
+}
+
+impl FromStr for ClassScheme {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fhwa" => Ok(ClassScheme::Fhwa),
+            "six-bin" => Ok(ClassScheme::SixBin),
+            _ if Path::new(s).is_file() => ClassScheme::from_file(Path::new(s)),
+            _ => Err(CountError::UnknownClassScheme(s.to_string())),
+        }
+    }
+}
+
+impl Display for ClassScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClassScheme::Fhwa => write!(f, "fhwa"),
+            ClassScheme::SixBin => write!(f, "six-bin"),
+            ClassScheme::Custom { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Count of vehicles by the classes of a [`ClassScheme`], binned into the same interval as the
+/// [`TimeBinnedVehicleClassCount`] it was aggregated from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassSchemeCount {
+    pub date: NaiveDate,
+    pub time: NaiveDateTime,
+    pub lane: Option<u8>,
+    pub recordnum: u32,
+    pub direction: Option<LaneDirection>,
+    /// The number of vehicles in each of the scheme's classes, in order.
+    pub classes: Vec<u32>,
+    pub total: u32,
+}
+
+impl ClassSchemeCount {
+    /// Aggregate an FHWA class count under a scheme.
+    pub fn from_count(count: &TimeBinnedVehicleClassCount, scheme: &ClassScheme) -> Self {
+        let classes = scheme
+            .classes()
+            .iter()
+            .map(|class| {
+                class
+                    .fhwa
+                    .iter()
+                    .filter_map(|v| VehicleClass::from_num(*v).ok())
+                    .map(|v| count.class(v))
+                    .sum::<u32>()
+            })
+            .collect();
+        Self {
+            date: count.date,
+            time: count.time,
+            lane: count.lane,
+            recordnum: count.recordnum,
+            direction: count.direction,
+            classes,
+            total: count.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_from_file::Extract, *};
+
+    #[test]
+    fn six_bin_scheme_totals_match_fhwa() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let vehicles = IndividualVehicle::extract(path).unwrap();
+        let (_, class_counts) =
+            create_speed_and_class_count(TimeInterval::FifteenMin, metadata, vehicles);
+
+        for count in &class_counts {
+            let six_bin = ClassSchemeCount::from_count(count, &ClassScheme::SixBin);
+            assert_eq!(six_bin.classes.len(), 6);
+            assert_eq!(six_bin.classes[1], count.c2);
+            assert_eq!(six_bin.classes[4], count.c5 + count.c6 + count.c7);
+            assert_eq!(
+                six_bin.classes.iter().sum::<u32>(),
+                ClassSchemeCount::from_count(count, &ClassScheme::Fhwa)
+                    .classes
+                    .iter()
+                    .sum::<u32>()
+            );
+        }
+        assert_eq!(
+            ClassScheme::SixBin.class_of(&VehicleClass::FiveAxleSingleTrailerTrucks),
+            Some(5)
+        );
+        assert_eq!(
+            ClassScheme::SixBin.class_of(&VehicleClass::UnclassifiedVehicle),
+            None
+        );
+    }
+
+    #[test]
+    fn custom_scheme_from_file() {
+        let path = std::env::temp_dir().join("traffic_counts_class_scheme.toml");
+        fs::write(
+            &path,
+            "[[classes]]\nname = \"light\"\nfhwa = [1, 2, 3]\n\n\
+             [[classes]]\nname = \"heavy\"\nfhwa = [4, 5, 6, 7, 8, 9, 10, 11, 12, 13]\n",
+        )
+        .unwrap();
+        let scheme = ClassScheme::from_str(path.to_str().unwrap()).unwrap();
+        assert_eq!(scheme.classes()[1].name, "heavy");
+        assert_eq!(scheme.class_of(&VehicleClass::Buses), Some(1));
+
+        fs::write(
+            &path,
+            "[[classes]]\nname = \"a\"\nfhwa = [1, 2]\n\n[[classes]]\nname = \"b\"\nfhwa = [2]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            ClassScheme::from_file(&path),
+            Err(CountError::UnknownClassScheme(_))
+        ));
+        assert!(matches!(
+            ClassScheme::from_str("seven-bin"),
+            Err(CountError::UnknownClassScheme(_))
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+}
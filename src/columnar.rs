@@ -0,0 +1,108 @@
+//! Write records to Apache Arrow and Parquet files (only with the `parquet` feature).
+//!
+//! These are for multi-year analyses that read columnar files directly (e.g. into Python or
+//! duckdb) rather than querying our database. The schema of a file is traced from the type of
+//! its records, rather than the records themselves, so that every file of a kind of count has
+//! the same schema - including one with no records at all. Each field is a column, optional
+//! ones are nullable, and enums (like [`VehicleClass`][crate::VehicleClass]), dates, and times
+//! are strings (the latter two in ISO 8601 format).
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use arrow::datatypes::FieldRef;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+
+use crate::CountError;
+
+/// Convert records to an Arrow record batch, with the schema of their type.
+///
+/// There may be no records, in which case the batch is empty.
+pub fn record_batch<T: Serialize + DeserializeOwned>(
+    records: &[T],
+) -> Result<RecordBatch, CountError> {
+    let options = TracingOptions::default()
+        .allow_null_fields(true)
+        .enums_without_data_as_strings(true);
+    let fields = Vec::<FieldRef>::from_type::<T>(options)
+        .map_err(|e| CountError::ColumnarError(e.to_string()))?;
+    serde_arrow::to_record_batch(&fields, &records)
+        .map_err(|e| CountError::ColumnarError(e.to_string()))
+}
+
+/// Write records to an Arrow IPC file at `path`.
+pub fn write_arrow<T: Serialize + DeserializeOwned>(
+    records: &[T],
+    path: &Path,
+) -> Result<(), CountError> {
+    let batch = record_batch(records)?;
+    let file = BufWriter::new(File::create(path)?);
+    let mut writer = FileWriter::try_new(file, &batch.schema())
+        .map_err(|e| CountError::ColumnarError(e.to_string()))?;
+    writer
+        .write(&batch)
+        .and_then(|_| writer.finish())
+        .map_err(|e| CountError::ColumnarError(e.to_string()))
+}
+
+/// Write records to a Parquet file at `path`.
+pub fn write_parquet<T: Serialize + DeserializeOwned>(
+    records: &[T],
+    path: &Path,
+) -> Result<(), CountError> {
+    let batch = record_batch(records)?;
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None)
+        .map_err(|e| CountError::ColumnarError(e.to_string()))?;
+    writer
+        .write(&batch)
+        .map_err(|e| CountError::ColumnarError(e.to_string()))?;
+    writer
+        .close()
+        .map_err(|e| CountError::ColumnarError(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_from_file::Extract, *};
+
+    #[test]
+    fn vehicles_and_binned_counts_written_to_parquet_and_arrow() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+
+        let batch = record_batch(&counted_vehicles).unwrap();
+        assert_eq!(batch.num_rows(), counted_vehicles.len());
+        assert!(batch.schema().field_with_name("class").is_ok());
+
+        let (_, class_counts) =
+            create_speed_and_class_count(TimeInterval::Hour, metadata, counted_vehicles.clone());
+        let dir = std::env::temp_dir();
+        let parquet_path = dir.join("166905-hourly-class.parquet");
+        write_parquet(&class_counts, &parquet_path).unwrap();
+        let arrow_path = dir.join("166905-vehicles.arrow");
+        write_arrow(&counted_vehicles, &arrow_path).unwrap();
+
+        assert!(std::fs::read(&parquet_path).unwrap().starts_with(b"PAR1"));
+        assert!(std::fs::read(&arrow_path).unwrap().starts_with(b"ARROW1"));
+
+        // Files with no records have the same schema as those with some.
+        let empty = record_batch::<IndividualVehicle>(&[]).unwrap();
+        assert_eq!(empty.num_rows(), 0);
+        assert_eq!(empty.schema(), batch.schema());
+        let empty_path = dir.join("166905-empty.parquet");
+        write_parquet::<TimeBinnedVehicleClassCount>(&[], &empty_path).unwrap();
+        assert!(std::fs::read(&empty_path).unwrap().starts_with(b"PAR1"));
+
+        std::fs::remove_file(parquet_path).unwrap();
+        std::fs::remove_file(arrow_path).unwrap();
+        std::fs::remove_file(empty_path).unwrap();
+    }
+}
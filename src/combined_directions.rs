@@ -0,0 +1,171 @@
+//! Volume counts of both directions combined.
+//!
+//! By the database's convention, a row of a volume table without a direction or lane is of both
+//! directions combined, and the legacy reports read those rather than adding up the directions
+//! themselves. For bidirectional counts, these rows can be created alongside the per-direction
+//! ones: [15-minute][combine_fifteen_minute_counts] (for the TC_15MINVOLCOUNT table) and
+//! [hourly][combine_hourly_counts] (for the TC_VOLCOUNT table) totals of every direction and lane.
+//!
+//! Anything that adds up the rows of a count itself should leave the combined ones out, as they
+//! would otherwise be counted twice (see [`is_combined`]).
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{denormalize::NonNormalVolCount, FifteenMinuteVehicle, LaneDirection};
+
+/// Whether a row is of both directions combined, from its direction.
+pub fn is_combined(direction: Option<LaneDirection>) -> bool {
+    direction.is_none()
+}
+
+/// Whether counts are of more than one direction.
+fn is_bidirectional(directions: impl IntoIterator<Item = Option<LaneDirection>>) -> bool {
+    directions
+        .into_iter()
+        .flatten()
+        .collect::<BTreeSet<_>>()
+        .len()
+        > 1
+}
+
+/// Create the combined 15-minute counts of all directions and lanes of a bidirectional count.
+///
+/// Returns none if the counts aren't of more than one direction.
+pub fn combine_fifteen_minute_counts(counts: &[FifteenMinuteVehicle]) -> Vec<FifteenMinuteVehicle> {
+    if !is_bidirectional(counts.iter().map(|v| v.direction)) {
+        return vec![];
+    }
+    let mut combined: BTreeMap<(u32, NaiveDate, NaiveDateTime), u16> = BTreeMap::new();
+    for count in counts.iter().filter(|v| !is_combined(v.direction)) {
+        let total = combined
+            .entry((count.recordnum, count.date, count.time))
+            .or_default();
+        *total = total.saturating_add(count.count);
+    }
+    combined
+        .into_iter()
+        .map(|((recordnum, date, time), count)| FifteenMinuteVehicle {
+            recordnum,
+            date,
+            time,
+            count,
+            direction: None,
+            lane: None,
+        })
+        .collect()
+}
+
+/// Create the combined hourly counts of all directions and lanes of a bidirectional count.
+///
+/// An hour of a day is only combined if every direction and lane counted that day has it, so
+/// that partial hours at the start and end of a count aren't mistaken for low volumes. Returns
+/// none if the counts aren't of more than one direction.
+pub fn combine_hourly_counts(counts: &[NonNormalVolCount]) -> Vec<NonNormalVolCount> {
+    if !is_bidirectional(counts.iter().map(|v| v.direction)) {
+        return vec![];
+    }
+    // The total and hourly volumes of each day (none where any direction/lane is missing them).
+    type Hours = (Option<u32>, [Option<u32>; 24]);
+    let mut combined: BTreeMap<(u32, NaiveDate), Hours> = BTreeMap::new();
+    for count in counts.iter().filter(|v| !is_combined(v.direction)) {
+        let hours = count.hourly();
+        let (totalcount, volumes) = combined
+            .entry((count.recordnum, count.date))
+            .or_insert((Some(0), [Some(0); 24]));
+        *totalcount = totalcount.zip(count.totalcount).map(|(a, b)| a + b);
+        for (volume, lane_volume) in volumes.iter_mut().zip(hours) {
+            *volume = volume.zip(lane_volume).map(|(a, b)| a + b);
+        }
+    }
+    combined
+        .into_iter()
+        .map(
+            |((recordnum, date), (totalcount, hours))| NonNormalVolCount {
+                recordnum,
+                date,
+                direction: None,
+                lane: None,
+                setflag: None,
+                totalcount,
+                am12: hours[0],
+                am1: hours[1],
+                am2: hours[2],
+                am3: hours[3],
+                am4: hours[4],
+                am5: hours[5],
+                am6: hours[6],
+                am7: hours[7],
+                am8: hours[8],
+                am9: hours[9],
+                am10: hours[10],
+                am11: hours[11],
+                pm12: hours[12],
+                pm1: hours[13],
+                pm2: hours[14],
+                pm3: hours[15],
+                pm4: hours[16],
+                pm5: hours[17],
+                pm6: hours[18],
+                pm7: hours[19],
+                pm8: hours[20],
+                pm9: hours[21],
+                pm10: hours[22],
+                pm11: hours[23],
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn datetime(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn fifteen_minute(time: &str, count: u16, direction: LaneDirection) -> FifteenMinuteVehicle {
+        let time = datetime(time);
+        FifteenMinuteVehicle::new(123, time.date(), time, count, Some(direction), Some(1)).unwrap()
+    }
+
+    #[test]
+    fn fifteen_minute_counts_combined_when_bidirectional() {
+        let counts = vec![
+            fifteen_minute("2024-04-08 07:00", 10, LaneDirection::East),
+            fifteen_minute("2024-04-08 07:00", 7, LaneDirection::West),
+            fifteen_minute("2024-04-08 07:15", 12, LaneDirection::East),
+        ];
+        let combined = combine_fifteen_minute_counts(&counts);
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].count, 17);
+        assert_eq!(combined[1].count, 12);
+        assert!(combined
+            .iter()
+            .all(|v| v.direction.is_none() && v.lane.is_none()));
+
+        assert!(combine_fifteen_minute_counts(&counts[..1]).is_empty());
+    }
+
+    #[test]
+    fn hourly_counts_combined_only_where_every_direction_counted() {
+        let counts: Vec<NonNormalVolCount> = serde_json::from_value(json!([
+            {"recordnum": 123, "date": "2024-04-08", "direction": "East", "lane": 1,
+             "totalcount": 30, "am7": 10, "am8": 20},
+            {"recordnum": 123, "date": "2024-04-08", "direction": "West", "lane": 2,
+             "totalcount": 5, "am8": 5},
+        ]))
+        .unwrap();
+        let combined = combine_hourly_counts(&counts);
+        assert_eq!(combined.len(), 1);
+        assert_eq!(combined[0].direction, None);
+        assert_eq!(combined[0].totalcount, Some(35));
+        assert_eq!(combined[0].am7, None);
+        assert_eq!(combined[0].am8, Some(25));
+
+        assert!(combine_hourly_counts(&counts[..1]).is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! The inventory of counters that counts are taken with.
+//!
+//! The counter of each input count (its [`counter_id`][crate::FieldMetadata::counter_id]) is
+//! [checked][CounterInventory::check] against the inventory, which is a TOML file set by the
+//! `COUNTER_INVENTORY` env var, like:
+//! ```toml
+//! # How long a calibration is good for, in days (by default, a year).
+//! calibration_days = 365
+//!
+//! [counters.40972]
+//! model = "JAMAR TRAX Apollyon"
+//! device = "tube"
+//! calibrated = 2024-03-01
+//! count_kinds = ["Class", "Speed", "Volume", "FifteenMinVolume"]
+//!
+//! [counters.101]
+//! device = "eco-counter"
+//! ```
+//! Counters not in the inventory are refused, unless it's empty (or not set). The model, type of
+//! device, calibration date, and kinds of counts a counter can produce are all optional; those
+//! that are set are checked. The calibration is checked against the date the count was taken,
+//! not the date it's imported.
+//!
+//! Inventories that only list the IDs of counters, like `counter_ids = ["40972", "101"]`, are
+//! still read, each ID being a counter with nothing else set. Any other unknown field is an
+//! error, so that a misspelled one isn't silently ignored.
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use chrono::{NaiveDate, TimeDelta};
+use serde::Deserialize;
+
+use crate::{toml_date, CountError, CountKind};
+
+/// The type of device a counter is.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Device {
+    /// Pneumatic road tubes.
+    Tube,
+    Radar,
+    EcoCounter,
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let device = match self {
+            Device::Tube => "tube",
+            Device::Radar => "radar",
+            Device::EcoCounter => "Eco-Counter",
+        };
+        write!(f, "{}", device)
+    }
+}
+
+/// A counter in the inventory.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Counter {
+    pub model: Option<String>,
+    pub device: Option<Device>,
+    /// The date the counter was last calibrated.
+    #[serde(default, deserialize_with = "toml_date::option::deserialize")]
+    pub calibrated: Option<NaiveDate>,
+    /// The kinds of counts the counter can produce; any, if none are set.
+    #[serde(default)]
+    pub count_kinds: Vec<CountKind>,
+}
+
+/// The counters that counts can be taken with, by ID.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "InventoryFile")]
+pub struct CounterInventory {
+    /// How long a calibration is good for, in days.
+    pub calibration_days: u32,
+    pub counters: BTreeMap<String, Counter>,
+}
+
+/// The inventory as it is in its file, including the IDs of counters listed the older way.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InventoryFile {
+    #[serde(default = "default_calibration_days")]
+    calibration_days: u32,
+    #[serde(default)]
+    counter_ids: Vec<String>,
+    #[serde(default)]
+    counters: BTreeMap<String, Counter>,
+}
+
+impl From<InventoryFile> for CounterInventory {
+    fn from(file: InventoryFile) -> Self {
+        let mut counters = file.counters;
+        for counter_id in file.counter_ids {
+            counters.entry(counter_id).or_default();
+        }
+        Self {
+            calibration_days: file.calibration_days,
+            counters,
+        }
+    }
+}
+
+fn default_calibration_days() -> u32 {
+    365
+}
+
+impl Default for CounterInventory {
+    fn default() -> Self {
+        Self {
+            calibration_days: default_calibration_days(),
+            counters: BTreeMap::new(),
+        }
+    }
+}
+
+impl CounterInventory {
+    /// Get the inventory from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Get the inventory from the file set by the `COUNTER_INVENTORY` env var, or an empty one
+    /// if it isn't set.
+    pub fn from_env() -> Result<Self, CountError> {
+        match env::var("COUNTER_INVENTORY") {
+            Ok(path) => Self::from_file(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Whether a counter is in the inventory. Any counter is, if the inventory is empty.
+    pub fn contains(&self, counter_id: &str) -> bool {
+        self.counters.is_empty() || self.counters.contains_key(counter_id)
+    }
+
+    /// Check a counter used on `date` for a kind of count (if known).
+    ///
+    /// Returns an error if the counter isn't in the inventory, and otherwise a warning for each
+    /// problem with it: that its calibration had lapsed, or that it can't produce that kind of
+    /// count.
+    pub fn check(
+        &self,
+        counter_id: &str,
+        date: NaiveDate,
+        count_kind: Option<&CountKind>,
+    ) -> Result<Vec<String>, CountError> {
+        if !self.contains(counter_id) {
+            return Err(CountError::UnknownCounter(counter_id.to_string()));
+        }
+        let Some(counter) = self.counters.get(counter_id) else {
+            return Ok(vec![]);
+        };
+        let name = match (&counter.model, counter.device) {
+            (Some(model), _) => format!("counter {counter_id} ({model})"),
+            (None, Some(device)) => format!("counter {counter_id} ({device})"),
+            (None, None) => format!("counter {counter_id}"),
+        };
+
+        let mut warnings = vec![];
+        if let Some(calibrated) = counter.calibrated {
+            let lapsed = calibrated + TimeDelta::days(self.calibration_days as i64);
+            if lapsed < date {
+                warnings.push(format!(
+                    "the calibration of {name} lapsed on {lapsed} (it was calibrated on \
+                    {calibrated})"
+                ));
+            }
+        }
+        if let Some(count_kind) = count_kind {
+            if !counter.count_kinds.is_empty() && !counter.count_kinds.contains(count_kind) {
+                warnings.push(format!("{name} can't produce {count_kind} counts"));
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn counters_checked_against_inventory() {
+        let inventory: CounterInventory = toml::from_str(
+            r#"
+            calibration_days = 180
+
+            [counters.40972]
+            model = "JAMAR TRAX Apollyon"
+            device = "tube"
+            calibrated = 2024-03-01
+            count_kinds = ["Class", "Speed"]
+
+            [counters.101]
+            device = "eco-counter"
+            "#,
+        )
+        .unwrap();
+
+        assert!(inventory
+            .check("40972", date("2024-06-01"), Some(&CountKind::Class))
+            .unwrap()
+            .is_empty());
+        let warnings = inventory
+            .check("40972", date("2024-09-01"), Some(&CountKind::Bicycle1))
+            .unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("lapsed on 2024-08-28"));
+        assert!(warnings[1].contains("can't produce Bicycle 1 counts"));
+        assert!(inventory
+            .check("101", date("2030-01-01"), Some(&CountKind::Bicycle1))
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            inventory.check("40973", date("2024-06-01"), None),
+            Err(CountError::UnknownCounter(_))
+        ));
+
+        assert!(CounterInventory::default()
+            .check("40973", date("2024-06-01"), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn counter_ids_still_read() {
+        let inventory: CounterInventory = toml::from_str(
+            r#"
+            counter_ids = ["40972", "101"]
+
+            [counters.101]
+            device = "eco-counter"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(inventory.counters.len(), 2);
+        assert_eq!(inventory.counters["40972"], Counter::default());
+        assert_eq!(inventory.counters["101"].device, Some(Device::EcoCounter));
+        assert!(inventory.contains("40972"));
+        assert!(!inventory.contains("40973"));
+    }
+
+    #[test]
+    fn unknown_fields_refused() {
+        assert!(toml::from_str::<CounterInventory>("counter_id = [\"40972\"]").is_err());
+        assert!(toml::from_str::<CounterInventory>(
+            r#"
+            [counters.40972]
+            calibration = 2024-03-01
+            "#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Daily volumes by day of the week, and average weekday and weekend daily traffic.
+//!
+//! Published count summaries report the average daily traffic on weekdays and on weekends, and
+//! the volume of each day of the week. These are [summarized][summarize_days_of_week] from the
+//! volumes of a count's full days (see [`daily_volumes`][crate::aadt::daily_volumes]). Traffic on
+//! holidays isn't typical of either, so days that are [holidays][Holidays] are left out of the
+//! summary (and reported as such). The holidays are those in the TOML file set by the
+//! `HOLIDAYS_CONFIG` env var, like:
+//! ```toml
+//! dates = ["2024-01-01", "2024-05-27", "2024-07-04", "2024-09-02"]
+//! ```
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{volume_profile::DayType, CountError};
+
+/// Days excluded from summaries of days of the week.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Holidays {
+    #[serde(default)]
+    pub dates: BTreeSet<NaiveDate>,
+}
+
+impl Holidays {
+    /// Get the holidays from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Get the holidays from the file set by the `HOLIDAYS_CONFIG` env var, or none if it isn't
+    /// set.
+    pub fn from_env() -> Result<Self, CountError> {
+        match env::var("HOLIDAYS_CONFIG") {
+            Ok(path) => Self::from_file(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.dates.contains(&date)
+    }
+}
+
+/// The volume of the full days of a count on one day of the week.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayOfWeekVolume {
+    pub day: Weekday,
+    /// The number of full days counted on this day of the week.
+    pub days: u32,
+    pub total: u32,
+    pub average: Option<f32>,
+}
+
+/// A count's daily volumes by day of the week, excluding holidays.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayOfWeekSummary {
+    pub recordnum: u32,
+    /// Each day of the week, from Monday to Sunday.
+    pub days_of_week: Vec<DayOfWeekVolume>,
+    /// The average daily traffic on weekdays, if any were counted.
+    pub weekday_average: Option<f32>,
+    /// The average daily traffic on weekends, if any were counted.
+    pub weekend_average: Option<f32>,
+    /// The full days counted that were excluded for being holidays.
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl DayOfWeekSummary {
+    /// The average daily traffic on weekends as a share of that on weekdays.
+    pub fn weekend_ratio(&self) -> Option<f32> {
+        match (self.weekend_average, self.weekday_average) {
+            (Some(weekend), Some(weekday)) if weekday > 0.0 => Some(weekend / weekday),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DayOfWeekSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let average = |v: Option<f32>| {
+            v.map(|v| format!("{v:.0}"))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        write!(
+            f,
+            "{} average daily traffic: weekday {}, weekend {}",
+            self.recordnum,
+            average(self.weekday_average),
+            average(self.weekend_average)
+        )?;
+        for day in &self.days_of_week {
+            write!(
+                f,
+                "; {} {} ({} days)",
+                day.day,
+                average(day.average),
+                day.days
+            )?;
+        }
+        if !self.holidays.is_empty() {
+            let holidays = self
+                .holidays
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>();
+            write!(f, "; excluding holidays {}", holidays.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarize the volumes of a count's full days by day of the week, excluding holidays.
+pub fn summarize_days_of_week(
+    recordnum: u32,
+    volumes: &BTreeMap<NaiveDate, u32>,
+    holidays: &Holidays,
+) -> DayOfWeekSummary {
+    // The number of days and total volume of each day of the week, from Monday.
+    let mut days_of_week = [(0, 0); 7];
+    let mut day_types: BTreeMap<DayType, (u32, u32)> = BTreeMap::new();
+    let mut excluded = vec![];
+
+    for (date, volume) in volumes {
+        if holidays.contains(*date) {
+            excluded.push(*date);
+            continue;
+        }
+        let day = &mut days_of_week[date.weekday().num_days_from_monday() as usize];
+        day.0 += 1;
+        day.1 += volume;
+        let day_type = day_types.entry(DayType::from_date(*date)).or_default();
+        day_type.0 += 1;
+        day_type.1 += volume;
+    }
+
+    let average = |(days, total): (u32, u32)| (days > 0).then(|| total as f32 / days as f32);
+    let mut day = Weekday::Mon;
+    let days_of_week = days_of_week
+        .into_iter()
+        .map(|(days, total)| {
+            let volume = DayOfWeekVolume {
+                day,
+                days,
+                total,
+                average: average((days, total)),
+            };
+            day = day.succ();
+            volume
+        })
+        .collect();
+
+    DayOfWeekSummary {
+        recordnum,
+        days_of_week,
+        weekday_average: day_types.get(&DayType::Weekday).copied().and_then(average),
+        weekend_average: day_types.get(&DayType::Weekend).copied().and_then(average),
+        holidays: excluded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn weekdays_and_weekends_averaged_without_holidays() {
+        let holidays: Holidays = toml::from_str(r#"dates = ["2024-07-04"]"#).unwrap();
+        let volumes = BTreeMap::from([
+            (date("2024-07-02"), 1000), // Tuesday
+            (date("2024-07-03"), 1200), // Wednesday
+            (date("2024-07-04"), 400),  // Thursday, Independence Day
+            (date("2024-07-06"), 600),  // Saturday
+            (date("2024-07-09"), 1100), // Tuesday
+        ]);
+        let summary = summarize_days_of_week(123, &volumes, &holidays);
+
+        assert_eq!(summary.weekday_average, Some(1100.0));
+        assert_eq!(summary.weekend_average, Some(600.0));
+        assert_eq!(summary.holidays, vec![date("2024-07-04")]);
+        assert_eq!(summary.days_of_week.len(), 7);
+        let tuesday = &summary.days_of_week[1];
+        assert_eq!(tuesday.day, Weekday::Tue);
+        assert_eq!((tuesday.days, tuesday.total), (2, 2100));
+        assert_eq!(tuesday.average, Some(1050.0));
+        assert_eq!(summary.days_of_week[3].days, 0);
+        assert_eq!(summary.days_of_week[3].average, None);
+        assert_eq!(summary.days_of_week[6].day, Weekday::Sun);
+
+        let summary = summarize_days_of_week(123, &volumes, &Holidays::default());
+        assert_eq!(summary.weekday_average, Some(925.0));
+        assert!(summary.holidays.is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! A structured record of every operation that changes the data of counts.
+//!
+//! The [import log][super::ImportLogEntry] says what happened while importing a count, in prose.
+//! The audit log records, for every insert, update, and delete of a count's data (or its
+//! TC_HEADER record), who did it - both the database user and the user of the machine it was done
+//! from - when, to which table, and how many rows were affected, so that exactly when and how a
+//! count was loaded (or changed, or removed) can be shown later. See [`get_audit_log`].
+//!
+//! Entries are recorded in the same transaction as the operation, and so are only kept if it is
+//! committed. An operation fails if its entry can't be recorded, so programs that change data
+//! [check][check_table] that the audit_log table exists before starting, rather than failing
+//! partway through (e.g. after some of a count's tables have been inserted into).
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use oracle::{sql_type::ToSql, Connection, RowValue};
+use serde::{Deserialize, Serialize};
+
+use crate::CountError;
+
+/// The kind of operation an [`AuditEntry`] records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl FromStr for Operation {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "insert" => Ok(Operation::Insert),
+            "update" => Ok(Operation::Update),
+            "delete" => Ok(Operation::Delete),
+            _ => Err(CountError::UnknownOperation(s.to_string())),
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operation = match self {
+            Operation::Insert => "insert",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+        };
+        write!(f, "{}", operation)
+    }
+}
+
+/// An entry in the audit log, corresponding to a row of its "audit_log" table.
+#[derive(Debug, Clone, PartialEq, RowValue, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub datetime: NaiveDateTime,
+    /// The database user the operation was done as.
+    pub username: String,
+    /// The user of the machine the operation was done from, if known.
+    pub os_user: Option<String>,
+    pub operation: String,
+    #[row_value(rename = "tablename")]
+    pub table: String,
+    pub recordnum: u32,
+    #[row_value(rename = "rows_affected")]
+    pub rows: u64,
+}
+
+/// Record an operation on the data of a count, without committing it (so that it's committed, or
+/// not, along with the operation).
+pub fn record(
+    conn: &Connection,
+    operation: Operation,
+    table: &str,
+    recordnum: u32,
+    rows: u64,
+) -> Result<(), oracle::Error> {
+    conn.execute(
+        "insert into audit_log (username, os_user, operation, tablename, recordnum, \
+        rows_affected) values (user, sys_context('USERENV', 'OS_USER'), :1, :2, :3, :4)",
+        &[&operation.to_string(), &table, &recordnum, &rows],
+    )?;
+    Ok(())
+}
+
+/// Check that the audit_log table exists, with an error saying how to create it if it doesn't.
+pub fn check_table(conn: &Connection) -> Result<(), CountError> {
+    match conn.query_row_as::<u32>("select count(*) from audit_log where 1 = 0", &[]) {
+        Ok(_) => Ok(()),
+        // ORA-00942: table or view does not exist
+        Err(e) if e.oci_code() == Some(942) => Err(CountError::DbError(
+            "the audit_log table does not exist; create it by running the migrations in \
+            db_migrations.sql"
+                .to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Filters and pagination for [`get_audit_log`].
+///
+/// Any filter that is `None` isn't applied, and all entries are returned if `limit` is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    pub recordnum: Option<u32>,
+    pub operation: Option<Operation>,
+    /// The first day to include entries from.
+    pub from: Option<NaiveDate>,
+    /// The last day to include entries from.
+    pub to: Option<NaiveDate>,
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+/// Get [audit log entries](AuditEntry), most recent first.
+pub fn get_audit_log(
+    conn: &Connection,
+    query: &AuditQuery,
+) -> Result<Vec<AuditEntry>, oracle::Error> {
+    let mut conditions = vec![];
+    let mut params: Vec<&dyn ToSql> = vec![];
+
+    if let Some(recordnum) = &query.recordnum {
+        params.push(recordnum);
+        conditions.push(format!("recordnum = :{}", params.len()));
+    }
+    let operation = query.operation.map(|v| v.to_string());
+    if let Some(operation) = &operation {
+        params.push(operation);
+        conditions.push(format!("operation = :{}", params.len()));
+    }
+    let from = query.from.map(|v| v.and_time(NaiveTime::MIN));
+    if let Some(from) = &from {
+        params.push(from);
+        conditions.push(format!("datetime >= :{}", params.len()));
+    }
+    let to = query
+        .to
+        .and_then(|v| v.succ_opt())
+        .map(|v| v.and_time(NaiveTime::MIN));
+    if let Some(to) = &to {
+        params.push(to);
+        conditions.push(format!("datetime < :{}", params.len()));
+    }
+
+    let mut sql = "select * from audit_log".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(&format!(" where {}", conditions.join(" and ")));
+    }
+    sql.push_str(" order by datetime desc");
+    if let Some(offset) = &query.offset {
+        params.push(offset);
+        sql.push_str(&format!(" offset :{} rows", params.len()));
+    }
+    if let Some(limit) = &query.limit {
+        params.push(limit);
+        sql.push_str(&format!(" fetch first :{} rows only", params.len()));
+    }
+
+    let mut entries = vec![];
+    for row in conn.query_as::<AuditEntry>(&sql, &params)? {
+        entries.push(row?);
+    }
+    Ok(entries)
+}
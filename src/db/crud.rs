@@ -2,14 +2,25 @@
 //!
 //! See the [Crud trait implementors][Crud#implementors] for kinds of counts and associated tables.
 
-use oracle::{Connection, Statement};
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use log::debug;
+use oracle::{sql_type::ToSql, Batch, Connection, Statement};
 
 use crate::{
+    db::audit::{self, Operation},
     denormalize::{NonNormalAvgSpeedCount, NonNormalVolCount},
-    CountError, FifteenMinuteBicycle, FifteenMinutePedestrian, FifteenMinuteVehicle,
-    TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
+    CountError, FifteenMinuteBicycle, FifteenMinutePedestrian, FifteenMinuteVehicle, RawChannel,
+    RawVehicle, TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
 };
 
+/// The default number of records sent to the database at once by [`Crud::insert_batch`].
+pub const DEFAULT_BATCH_SIZE: NonZeroUsize = NonZeroUsize::new(1000).unwrap();
+
+/// The [order][Crud::insert_order] records are inserted in.
+pub type InsertOrder = (u32, NaiveDateTime, Option<u8>);
+
 /// A trait for handling basic CRUD db operations on count data tables.
 pub trait Crud {
     /// The name of the table in the database that this count type corresponds to.
@@ -17,6 +28,9 @@ pub trait Crud {
     /// Field in COUNT_TABLE with recordnum.
     const COUNT_RECORDNUM_FIELD: &'static str = "recordnum";
 
+    /// The recordnum of the count that a record belongs to.
+    fn recordnum(&self) -> u32;
+
     /// Select all records from the table.
     fn select(conn: &Connection, recordnum: u32) -> Result<Vec<Self>, CountError>
     where
@@ -37,29 +51,180 @@ pub trait Crud {
         Ok(data)
     }
 
-    /// Delete all records in the table with a particular recordnum.
-    fn delete(conn: &Connection, recordnum: u32) -> Result<(), oracle::Error> {
+    /// Delete all records in the table with a particular recordnum, returning the number deleted.
+    ///
+    /// This does not commit, so that the deletion can be committed together with any records
+    /// replacing them (or rolled back, if they can't be inserted).
+    fn delete(conn: &Connection, recordnum: u32) -> Result<u64, oracle::Error> {
         let sql = &format!(
             "delete from {} where {} = :1",
             &Self::COUNT_TABLE,
             &Self::COUNT_RECORDNUM_FIELD
         );
-        conn.execute(sql, &[&recordnum])?;
-        conn.commit()
+        let stmt = conn.execute(sql, &[&recordnum])?;
+        let rows = stmt.row_count()?;
+        audit::record(conn, Operation::Delete, Self::COUNT_TABLE, recordnum, rows)?;
+        Ok(rows)
+    }
+
+    /// Delete the records in the table with a particular recordnum from a day on.
+    ///
+    /// As with [`delete`](Crud::delete), this does not commit.
+    fn delete_from(
+        conn: &Connection,
+        recordnum: u32,
+        date: NaiveDate,
+    ) -> Result<(), oracle::Error> {
+        let sql = &format!(
+            "delete from {} where {} = :1 and countdate >= :2",
+            &Self::COUNT_TABLE,
+            &Self::COUNT_RECORDNUM_FIELD
+        );
+        let stmt = conn.execute(sql, &[&recordnum, &date])?;
+        audit::record(
+            conn,
+            Operation::Delete,
+            Self::COUNT_TABLE,
+            recordnum,
+            stmt.row_count()?,
+        )
+    }
+
+    /// The last day of a count with records in the table, if it has any.
+    fn last_date(conn: &Connection, recordnum: u32) -> Result<Option<NaiveDate>, CountError> {
+        let sql = &format!(
+            "select max(countdate) from {} where {} = :1",
+            &Self::COUNT_TABLE,
+            &Self::COUNT_RECORDNUM_FIELD
+        );
+        Ok(conn.query_row_as::<Option<NaiveDate>>(sql, &[&recordnum])?)
+    }
+
+    /// Prepare the table for the records of a count to be inserted.
+    ///
+    /// When `replace` is true, any existing records of the count are deleted (without
+    /// committing). Otherwise, it is an error for any to exist, so that a count isn't inserted
+    /// twice.
+    fn prepare_for_import(
+        conn: &Connection,
+        recordnum: u32,
+        replace: bool,
+    ) -> Result<(), CountError> {
+        if replace {
+            Self::delete(conn, recordnum)?;
+            return Ok(());
+        }
+        let sql = &format!(
+            "select count(*) from {} where {} = :1",
+            &Self::COUNT_TABLE,
+            &Self::COUNT_RECORDNUM_FIELD
+        );
+        let existing = conn.query_row_as::<u32>(sql, &[&recordnum])?;
+        if existing > 0 {
+            return Err(CountError::DbError(format!(
+                "{existing} records for {recordnum} already in {} table; \
+                use --replace to replace them",
+                &Self::COUNT_TABLE
+            )));
+        }
+        Ok(())
     }
 
+    /// The key records are inserted in order of: their recordnum, date and time (midnight, for
+    /// tables with a row per day), and lane.
+    ///
+    /// Some legacy queries (and the old Access reports) assume that the rows of a count are
+    /// stored in chronological order, so records are inserted in this order regardless of the
+    /// order they were extracted or created in. Rows without a lane (e.g. those of both
+    /// directions combined) come before those of each lane at the same time.
+    fn insert_order(&self) -> InsertOrder;
+
+    /// The SQL statement used to insert a record into the table.
+    fn insert_sql() -> String;
+
+    /// The values of a record, in the order of the fields in [`insert_sql`](Crud::insert_sql).
+    fn insert_values(&self) -> Vec<&dyn ToSql>;
+
     /// Create prepared statement to use for insert.
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error>;
+    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
+        conn.statement(&Self::insert_sql()).build()
+    }
 
     /// Insert a record into the table using prepared statement.
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error>;
+    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
+        stmt.execute(&self.insert_values())
+    }
+
+    /// Create batch to use for inserting many records at once.
+    ///
+    /// Records are sent to the database each time `batch_size` of them have been appended to
+    /// the batch, and any remaining ones when the batch is executed.
+    fn prepare_batch_insert(
+        conn: &Connection,
+        batch_size: NonZeroUsize,
+    ) -> Result<Batch<'_>, oracle::Error> {
+        conn.batch(&Self::insert_sql(), batch_size.get()).build()
+    }
+
+    /// Insert records into the table in batches, rather than one statement per record.
+    ///
+    /// As with [`insert`](Crud::insert), this does not commit. Unlike it, the records inserted
+    /// (of each count) are recorded in the [audit log][audit], so a caller inserting records one
+    /// at a time must record them itself.
+    ///
+    /// When there is more than one batch, progress is logged (at the debug level) after each.
+    fn insert_batch(
+        conn: &Connection,
+        records: &[Self],
+        batch_size: NonZeroUsize,
+    ) -> Result<(), oracle::Error>
+    where
+        Self: std::marker::Sized,
+    {
+        let mut batch = Self::prepare_batch_insert(conn, batch_size)?;
+        let mut inserted = BTreeMap::new();
+        for (i, record) in in_insert_order(records).into_iter().enumerate() {
+            batch.append_row(&record.insert_values())?;
+            *inserted.entry(record.recordnum()).or_insert(0) += 1;
+            if (i + 1) % batch_size.get() == 0 && i + 1 < records.len() {
+                debug!(
+                    "{}: {} of {} records inserted",
+                    Self::COUNT_TABLE,
+                    i + 1,
+                    records.len()
+                );
+            }
+        }
+        batch.execute()?;
+        for (recordnum, rows) in inserted {
+            audit::record(conn, Operation::Insert, Self::COUNT_TABLE, recordnum, rows)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records in the [order][Crud::insert_order] to insert them in.
+///
+/// Records with the same key stay in the order they were in.
+pub fn in_insert_order<T: Crud>(records: &[T]) -> Vec<&T> {
+    let mut ordered = records.iter().collect::<Vec<_>>();
+    ordered.sort_by_key(|v| v.insert_order());
+    ordered
 }
 
 impl Crud for TimeBinnedVehicleClassCount {
     const COUNT_TABLE: &'static str = "tc_clacount";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (self.recordnum, self.time, self.lane)
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {} (recordnum, countdate, counttime, countlane, total, ctdir, \
             bikes, cars_and_tlrs, ax2_long, buses, ax2_6_tire, ax3_single, ax4_single, \
             lt_5_ax_double, ax5_double, gt_5_ax_double, lt_6_ax_multi, ax6_multi, gt_6_ax_multi, \
@@ -68,11 +233,10 @@ impl Crud for TimeBinnedVehicleClassCount {
             (:1, :2, :3, :4, :5, :6, :7, :8, :9, :10, :11, :12, :13, :14, :15, :16, :17, :18, 
             :19, :20)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.time,
@@ -93,14 +257,22 @@ impl Crud for TimeBinnedVehicleClassCount {
             &self.c12,
             &self.c13,
             &self.c15,
-        ])
+        ]
     }
 }
 impl Crud for TimeBinnedSpeedRangeCount {
     const COUNT_TABLE: &'static str = "tc_specount";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (self.recordnum, self.time, self.lane)
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {} (
             recordnum, countdate, counttime, countlane, total, ctdir, \
             s1, s2, s3, s4, s5, s6, s7, s8, s9, s10, s11, s12, s13, s14)
@@ -108,12 +280,11 @@ impl Crud for TimeBinnedSpeedRangeCount {
             (:1, :2, :3, :4, :5, :6, :7, :8, :9, :10, :11, :12, :13, :14, :15, :16, :17, :18, 
             :19, :20)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
 
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.time,
@@ -134,15 +305,27 @@ impl Crud for TimeBinnedSpeedRangeCount {
             &self.s12,
             &self.s13,
             &self.s14,
-        ])
+        ]
     }
 }
 
 impl Crud for NonNormalAvgSpeedCount {
     const COUNT_TABLE: &'static str = "tc_spesum";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (
+            self.recordnum,
+            self.date.and_time(NaiveTime::MIN),
+            self.lane,
+        )
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {}
             (recordnum, countdate, ctdir, countlane, \
             am12, am1, am2, am3, am4, am5, am6, am7, am8, am9, am10, am11, pm12, \
@@ -151,12 +334,11 @@ impl Crud for NonNormalAvgSpeedCount {
             (:1, :2, :3, :4, :5, :6, :7, :8, :9, :10, :11, :12, :13, :14, :15, :16, :17, :18, 
             :19, :20, :21, :22, :23, :24, :25, :26, :27, :28)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
 
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.direction,
@@ -185,15 +367,27 @@ impl Crud for NonNormalAvgSpeedCount {
             &self.pm9,
             &self.pm10,
             &self.pm11,
-        ])
+        ]
     }
 }
 
 impl Crud for NonNormalVolCount {
     const COUNT_TABLE: &'static str = "tc_volcount";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (
+            self.recordnum,
+            self.date.and_time(NaiveTime::MIN),
+            self.lane,
+        )
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {}
             (recordnum, countdate, totalcount, cntdir, countlane, \
             am12, am1, am2, am3, am4, am5, am6, am7, am8, am9, am10, am11, pm12, \
@@ -202,12 +396,11 @@ impl Crud for NonNormalVolCount {
             (:1, :2, :3, :4, :5, :6, :7, :8, :9, :10, :11, :12, :13, :14, :15, :16, :17, :18, 
             :19, :20, :21, :22, :23, :24, :25, :26, :27, :28, :29)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
 
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.totalcount,
@@ -238,32 +431,39 @@ impl Crud for NonNormalVolCount {
             &self.pm9,
             &self.pm10,
             &self.pm11,
-        ])
+        ]
     }
 }
 
 impl Crud for FifteenMinuteVehicle {
     const COUNT_TABLE: &'static str = "tc_15minvolcount";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (self.recordnum, self.time, self.lane)
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {}
             (recordnum, countdate, counttime, volcount, cntdir, countlane) \
             VALUES (:1, :2, :3, :4, :5, :6)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
 
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.time,
             &self.count,
             &self.direction,
             &self.lane,
-        ])
+        ]
     }
 }
 
@@ -271,25 +471,32 @@ impl Crud for FifteenMinuteBicycle {
     const COUNT_TABLE: &'static str = "tc_bikecount";
     const COUNT_RECORDNUM_FIELD: &'static str = "dvrpcnum";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (self.recordnum, self.time, None)
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {}
             (dvrpcnum, countdate, counttime, total, incount, outcount) \
             VALUES (:1, :2, :3, :4, :5, :6)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
 
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.time,
             &self.total,
             &self.indir,
             &self.outdir,
-        ])
+        ]
     }
 }
 
@@ -297,24 +504,186 @@ impl Crud for FifteenMinutePedestrian {
     const COUNT_TABLE: &'static str = "tc_pedcount";
     const COUNT_RECORDNUM_FIELD: &'static str = "dvrpcnum";
 
-    fn prepare_insert(conn: &Connection) -> Result<Statement, oracle::Error> {
-        let sql = &format!(
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (self.recordnum, self.time, None)
+    }
+
+    fn insert_sql() -> String {
+        format!(
             "insert into {}
             (dvrpcnum, countdate, counttime, total, \"IN\", \"OUT\") \
             VALUES (:1, :2, :3, :4, :5, :6)",
             &Self::COUNT_TABLE,
-        );
-        conn.statement(sql).build()
+        )
     }
 
-    fn insert(&self, stmt: &mut Statement) -> Result<(), oracle::Error> {
-        stmt.execute(&[
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
             &self.recordnum,
             &self.date,
             &self.time,
             &self.total,
             &self.indir,
             &self.outdir,
-        ])
+        ]
+    }
+}
+
+impl Crud for RawVehicle {
+    const COUNT_TABLE: &'static str = "tc_rawvehicle";
+
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        (self.recordnum, self.time, Some(self.lane))
+    }
+
+    fn insert_sql() -> String {
+        format!(
+            "insert into {}
+            (recordnum, countdate, counttime, channel, ctdir, countlane, class, speed) \
+            VALUES (:1, :2, :3, :4, :5, :6, :7, :8)",
+            &Self::COUNT_TABLE,
+        )
+    }
+
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![
+            &self.recordnum,
+            &self.date,
+            &self.time,
+            &self.channel,
+            &self.direction,
+            &self.lane,
+            &self.class,
+            &self.speed,
+        ]
+    }
+}
+
+impl Crud for RawChannel {
+    const COUNT_TABLE: &'static str = "tc_rawchannel";
+
+    fn recordnum(&self) -> u32 {
+        self.recordnum
+    }
+
+    fn insert_order(&self) -> InsertOrder {
+        // (Channels aren't of any time, so are only ordered by channel.)
+        (self.recordnum, NaiveDateTime::default(), Some(self.channel))
+    }
+
+    fn insert_sql() -> String {
+        format!(
+            "insert into {}
+            (recordnum, channel, ctdir, countlane) \
+            VALUES (:1, :2, :3, :4)",
+            &Self::COUNT_TABLE,
+        )
+    }
+
+    fn insert_values(&self) -> Vec<&dyn ToSql> {
+        vec![&self.recordnum, &self.channel, &self.direction, &self.lane]
+    }
+}
+
+/// The number of records of a count deleted from each table by [`purge`].
+pub type Purged = Vec<(&'static str, u64)>;
+
+/// Delete all the imported records of a count, from every count data table, so that it can be
+/// imported again.
+///
+/// Everything the import derived from them goes too: the count's rows in the intermediate
+/// TC_COUNTDATE table and the AADV table are deleted, and its [`Metadata`][crate::Metadata] is
+/// reset to not imported (no status, date imported, percent heavy vehicles or AADV). The dates
+/// counted and type of count are left, as they may have been entered before the count was
+/// imported. The record of the files it was imported from (in the IMPORT_FILE table) is deleted
+/// too, so that they aren't refused as already imported.
+///
+/// Everything is done in a single transaction: if any of it fails, none of it is. The changes are
+/// recorded in the [audit log][audit].
+pub fn purge(conn: &Connection, recordnum: u32) -> Result<Purged, CountError> {
+    match purge_uncommitted(conn, recordnum) {
+        Ok(v) => {
+            conn.commit()?;
+            Ok(v)
+        }
+        Err(e) => {
+            conn.rollback()?;
+            Err(e.into())
+        }
+    }
+}
+
+fn purge_uncommitted(conn: &Connection, recordnum: u32) -> Result<Purged, oracle::Error> {
+    let mut purged = vec![
+        (
+            TimeBinnedVehicleClassCount::COUNT_TABLE,
+            TimeBinnedVehicleClassCount::delete(conn, recordnum)?,
+        ),
+        (
+            TimeBinnedSpeedRangeCount::COUNT_TABLE,
+            TimeBinnedSpeedRangeCount::delete(conn, recordnum)?,
+        ),
+        (
+            NonNormalAvgSpeedCount::COUNT_TABLE,
+            NonNormalAvgSpeedCount::delete(conn, recordnum)?,
+        ),
+        (
+            NonNormalVolCount::COUNT_TABLE,
+            NonNormalVolCount::delete(conn, recordnum)?,
+        ),
+        (
+            FifteenMinuteVehicle::COUNT_TABLE,
+            FifteenMinuteVehicle::delete(conn, recordnum)?,
+        ),
+        (
+            FifteenMinuteBicycle::COUNT_TABLE,
+            FifteenMinuteBicycle::delete(conn, recordnum)?,
+        ),
+        (
+            FifteenMinutePedestrian::COUNT_TABLE,
+            FifteenMinutePedestrian::delete(conn, recordnum)?,
+        ),
+        (
+            RawVehicle::COUNT_TABLE,
+            RawVehicle::delete(conn, recordnum)?,
+        ),
+        (
+            RawChannel::COUNT_TABLE,
+            RawChannel::delete(conn, recordnum)?,
+        ),
+    ];
+    for table in ["tc_countdate", "aadv", "import_file"] {
+        let stmt = conn.execute(
+            &format!("delete from {table} where recordnum = :1"),
+            &[&recordnum],
+        )?;
+        let rows = stmt.row_count()?;
+        audit::record(conn, Operation::Delete, table, recordnum, rows)?;
+        purged.push((table, rows));
     }
+    let stmt = conn.execute(
+        "update tc_header set
+        status = null,
+        importdatadate = null,
+        pctheavy = null,
+        aadv = null
+        where recordnum = :1",
+        &[&recordnum],
+    )?;
+    audit::record(
+        conn,
+        Operation::Update,
+        "tc_header",
+        recordnum,
+        stmt.row_count()?,
+    )?;
+    Ok(purged)
 }
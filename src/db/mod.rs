@@ -6,27 +6,126 @@
 //! example, if the count starts at 10:55am, any records for vehicles counted between 10:55 and
 //! 11am will be added to the database, even though it is not a full 15-minute period. Similarly,
 //! when data is aggregated by hour and inserted into the TC_VOLCOUNT table, the first and last
-//! hours may not be a full hour of count data.
+//! hours may not be a full hour of count data. Counts with partial first or last days, or too few
+//! full days, are [flagged][crate::check_data::DayCoverage] by the data checks.
 
+pub mod audit;
 pub mod crud;
 pub mod oracle_impls;
+pub mod record_log;
+pub mod retry;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
 
+use std::collections::HashSet;
 use std::env;
 use std::fmt::Display;
+use std::str::FromStr;
 
-use chrono::NaiveDateTime;
-use log::Level;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use log::{Level, Log};
 use oracle::{
     pool::{Pool, PoolBuilder},
-    Connection, Error as OracleError,
+    sql_type::ToSql,
+    Connection, Error as OracleError, ErrorKind,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{CountError, CountKind, Metadata};
+use crate::{
+    combined_directions::is_combined,
+    denormalize::NonNormalVolCount,
+    location::{Location, Locations},
+    CountError, CountKind, Metadata, RawChannel, RawVehicle, RoadDirection,
+    TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
+};
+use audit::Operation;
+use crud::Crud;
+use retry::RetryPolicy;
 
 /// The maximum number of empty metadata records allowed to be created.
 pub const RECORD_CREATION_LIMIT: u32 = 50;
 
+/// The database connected to unless `DB_CONNECT_STRING` (or `TEST_DB_CONNECT_STRING`) is set.
+pub const DEFAULT_CONNECT_STRING: &str = "dvrpcprod_tp_tls";
+
+/// Which database to connect to: production, or a test/staging schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DbTarget {
+    #[default]
+    Prod,
+    Test,
+}
+
+impl DbTarget {
+    /// The prefix of the environment variables with the target's connection settings.
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            DbTarget::Prod => "",
+            DbTarget::Test => "TEST_",
+        }
+    }
+
+    /// Refuse to write to the production database unless it has been confirmed that it should
+    /// be.
+    pub fn check_write(&self, confirm_prod: bool) -> Result<(), CountError> {
+        if *self == DbTarget::Prod && !confirm_prod {
+            return Err(CountError::ProdWriteNotConfirmed);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DbTarget {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "prod" => Ok(DbTarget::Prod),
+            "test" => Ok(DbTarget::Test),
+            _ => Err(CountError::UnknownDbTarget(s.to_string())),
+        }
+    }
+}
+
+impl Display for DbTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let target = match self {
+            DbTarget::Prod => "prod",
+            DbTarget::Test => "test",
+        };
+        write!(f, "{}", target)
+    }
+}
+
+/// The settings used to connect to a [target][DbTarget] database.
+#[derive(Debug, Clone)]
+pub struct ConnectionSettings {
+    pub username: String,
+    pub password: String,
+    pub connect_string: String,
+}
+
+impl ConnectionSettings {
+    /// Get the settings of a target from environment variables: `DB_USERNAME`, `DB_PASSWORD`, and
+    /// (optionally) `DB_CONNECT_STRING` for production, and the same prefixed with `TEST_` (e.g.
+    /// `TEST_DB_USERNAME`) for the test schema.
+    pub fn from_env(target: DbTarget) -> Result<Self, CountError> {
+        let var = |name: &str| {
+            let name = format!("{}DB_{name}", target.env_prefix());
+            env::var(&name).map_err(|e| {
+                CountError::DbError(format!("Unable to load {name} from .env file: {e}."))
+            })
+        };
+        Ok(Self {
+            username: var("USERNAME")?,
+            password: var("PASSWORD")?,
+            connect_string: var("CONNECT_STRING")
+                .unwrap_or_else(|_| DEFAULT_CONNECT_STRING.to_string()),
+        })
+    }
+}
+
 /// Get database credentials from environment variable.
 pub fn get_creds() -> (String, String) {
     dotenvy::dotenv().expect("Unable to load .env file.");
@@ -39,33 +138,174 @@ pub fn get_creds() -> (String, String) {
 
 /// Create a connection pool.
 pub fn create_pool(username: String, password: String) -> Result<Pool, OracleError> {
-    PoolBuilder::new(username, password, "dvrpcprod_tp_tls")
+    PoolBuilder::new(username, password, DEFAULT_CONNECT_STRING)
         .max_connections(5)
         .build()
 }
 
+/// Create a connection pool to a [target][DbTarget] database, with its settings.
+pub fn create_target_pool(settings: &ConnectionSettings) -> Result<Pool, OracleError> {
+    PoolBuilder::new(
+        settings.username.clone(),
+        settings.password.clone(),
+        settings.connect_string.clone(),
+    )
+    .max_connections(5)
+    .build()
+}
+
+/// Create a connection pool to a [target][DbTarget] database, [retrying][retry] if unable to
+/// connect.
+pub fn create_pool_with_retry(
+    settings: &ConnectionSettings,
+    policy: &RetryPolicy,
+    log: impl Log,
+) -> Result<Pool, OracleError> {
+    policy.run(log, || create_target_pool(settings))
+}
+
+/// Get a connection from a pool, [retrying][retry] if unable to.
+pub fn get_connection(
+    pool: &Pool,
+    policy: &RetryPolicy,
+    log: impl Log,
+) -> Result<Connection, OracleError> {
+    policy.run(log, || pool.get())
+}
+
+/// Execute statements with a connection, [retrying][retry] them on a new connection from the pool
+/// if the connection is lost.
+///
+/// A lost connection's uncommitted changes are lost with it, so this is only for reads, and for
+/// writes made before the first uncommitted change on the connection.
+pub fn retry_statement<T>(
+    pool: &Pool,
+    conn: &mut Connection,
+    policy: &RetryPolicy,
+    log: impl Log,
+    mut op: impl FnMut(&Connection) -> Result<T, CountError>,
+) -> Result<T, CountError> {
+    let mut reconnect = false;
+    policy.run(log, || {
+        if reconnect {
+            *conn = pool.get()?;
+        }
+        reconnect = true;
+        op(conn)
+    })
+}
+
 /// AADV calculation requires an intermediate table to be updated first.
+///
+/// As the procedure that does so doesn't say how many rows it changed, the count's rows in the
+/// table afterwards are recorded in the [audit log][audit].
 pub fn update_intermediate_aadv(recordnum: u32, conn: &Connection) -> Result<(), CountError> {
     let sql = "begin update_tc_countdate(:1); end;";
     let mut stmt = conn.statement(sql).build()?;
-    Ok(stmt.execute(&[&recordnum])?)
+    stmt.execute(&[&recordnum])?;
+    let rows = conn.query_row_as::<u64>(
+        "select count(*) from tc_countdate where recordnum = :1",
+        &[&recordnum],
+    )?;
+    audit::record(conn, Operation::Update, "tc_countdate", recordnum, rows)?;
+    Ok(())
 }
 
 /// Update setdate - first day of full data, not falling on certain days.
 pub fn update_setdate(recordnum: u32, conn: &Connection) -> Result<(), CountError> {
     let sql = "begin update_setdate(:1); end;";
     let mut stmt = conn.statement(sql).build()?;
-    Ok(stmt.execute(&[&recordnum])?)
+    stmt.execute(&[&recordnum])?;
+    // (The procedure only updates the count's TC_HEADER record.)
+    audit::record(conn, Operation::Update, "tc_header", recordnum, 1)?;
+    Ok(())
+}
+
+/// Set the type of count (if not already set) and the last date counted in [`Metadata`].
+///
+/// The first date of the count is set by [`update_setdate`].
+pub fn update_count_kind_and_last_date(
+    conn: &Connection,
+    recordnum: u32,
+    count_kind: CountKind,
+    last_date: NaiveDate,
+) -> Result<(), CountError> {
+    let stmt = conn.execute(
+        "update tc_header set
+        type = coalesce(type, :1),
+        datelastcounted = :2
+        where recordnum = :3",
+        &[&count_kind, &last_date, &recordnum],
+    )?;
+    audit::record(
+        conn,
+        Operation::Update,
+        "tc_header",
+        recordnum,
+        stmt.row_count()?,
+    )?;
+    Ok(())
+}
+
+/// Set the percentage of a count's vehicles that are heavy vehicles in [`Metadata`].
+///
+/// See [`heavy_vehicles`][crate::heavy_vehicles].
+pub fn update_heavy_vehicle_percent(
+    conn: &Connection,
+    recordnum: u32,
+    percent: f32,
+) -> Result<(), CountError> {
+    let stmt = conn.execute(
+        "update tc_header set pctheavy = round(:1, 1) where recordnum = :2",
+        &[&percent, &recordnum],
+    )?;
+    audit::record(
+        conn,
+        Operation::Update,
+        "tc_header",
+        recordnum,
+        stmt.row_count()?,
+    )?;
+    Ok(())
+}
+
+/// Get the axle correction factor of a count, if it has one.
+///
+/// See [`axle_correction`][crate::axle_correction].
+pub fn get_axle_factor(conn: &Connection, recordnum: u32) -> Result<Option<f32>, CountError> {
+    match conn.query_row_as::<f32>(
+        "select factor from tc_axle_factor where recordnum = :1",
+        &[&recordnum],
+    ) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == ErrorKind::NoDataFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Call database function to calculate and insert AADV.
+///
+/// The rows it inserted into the AADV table are recorded in the [audit log][audit].
 pub fn calc_aadv(recordnum: u32, conn: &Connection) -> Result<i32, CountError> {
-    match conn.query_row_as::<i32>(&format!("select calc_aadv({}) from dual", recordnum), &[]) {
-        Ok(v) => Ok(v),
-        Err(_) => Err(CountError::DbError(format!(
-            "Unable to calculate AADV for {recordnum}"
-        ))),
-    }
+    let count_sql = "select count(*) from aadv where recordnum = :1";
+    let before = conn.query_row_as::<u64>(count_sql, &[&recordnum])?;
+    let aadv = match conn.query_row_as::<i32>("select calc_aadv(:1) from dual", &[&recordnum]) {
+        Ok(v) => v,
+        Err(_) => {
+            return Err(CountError::DbError(format!(
+                "Unable to calculate AADV for {recordnum}"
+            )))
+        }
+    };
+    let after = conn.query_row_as::<u64>(count_sql, &[&recordnum])?;
+    audit::record(
+        conn,
+        Operation::Insert,
+        "aadv",
+        recordnum,
+        after.saturating_sub(before),
+    )?;
+    Ok(aadv)
 }
 
 /// A log entry from data imports.
@@ -102,32 +342,149 @@ impl Display for ImportLogEntry {
     }
 }
 
-/// Insert an [`ImportLogEntry`].
+/// Get the recordnum that a file with a particular [hash][crate::extract_from_file::file_hash]
+/// was imported for, if it has been imported.
+pub fn get_imported_file(conn: &Connection, hash: &str) -> Result<Option<u32>, oracle::Error> {
+    match conn.query_row_as::<u32>(
+        "select recordnum from import_file where hash = :1 fetch first 1 rows only",
+        &[&hash],
+    ) {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == ErrorKind::NoDataFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check whether a file - at a particular path, and last modified at a particular time - has
+/// already been fully imported.
+///
+/// Unlike [`get_imported_file`], this doesn't need the file to be read to hash it.
+pub fn is_file_imported(
+    conn: &Connection,
+    filename: &str,
+    mtime: NaiveDateTime,
+) -> Result<bool, oracle::Error> {
+    let count = conn.query_row_as::<u32>(
+        "select count(*) from import_file where filename = :1 and mtime = :2",
+        &[&filename, &mtime],
+    )?;
+    Ok(count > 0)
+}
+
+/// Record that a file with a particular [hash][crate::extract_from_file::file_hash] was fully
+/// imported, along with when it was last modified and the number of rows inserted from it.
+pub fn insert_imported_file(
+    conn: &Connection,
+    recordnum: u32,
+    hash: &str,
+    filename: &str,
+    mtime: Option<NaiveDateTime>,
+    rows_imported: usize,
+) -> Result<(), oracle::Error> {
+    conn.execute(
+        "insert into import_file (recordnum, hash, filename, mtime, rows_imported) \
+        values (:1, :2, :3, :4, :5)",
+        &[
+            &recordnum,
+            &hash,
+            &filename,
+            &mtime,
+            &(rows_imported as u64),
+        ],
+    )?;
+    audit::record(conn, Operation::Insert, "import_file", recordnum, 1)?;
+    conn.commit()
+}
+
+/// Insert an [`ImportLogEntry`], committing it.
+///
+/// The entry is inserted and committed in a transaction of its own, so that logging while a
+/// count's records are being replaced neither commits nor rolls back the changes made so far.
 pub fn insert_import_log_entry(
     conn: &Connection,
     log_record: ImportLogEntry,
 ) -> Result<(), oracle::Error> {
     conn.execute(
-        "insert into import_log (recordnum, message, log_level) values (:1, :2, :3)",
+        "declare
+            pragma autonomous_transaction;
+        begin
+            insert into import_log (recordnum, message, log_level) values (:1, :2, :3);
+            commit;
+        end;",
         &[&log_record.recordnum, &log_record.msg, &log_record.level],
     )?;
-    conn.commit()
+    Ok(())
 }
 
-/// Get all [Import Log Entries](ImportLogEntry).
+/// Filters and pagination for [`get_import_log`].
+///
+/// Any filter that is `None` isn't applied, and all entries are returned if `limit` is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportLogQuery {
+    pub recordnum: Option<u32>,
+    /// Only include entries at this level or a more severe one (e.g. `Warn` includes errors).
+    pub level: Option<Level>,
+    /// The first day to include entries from.
+    pub from: Option<NaiveDate>,
+    /// The last day to include entries from.
+    pub to: Option<NaiveDate>,
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+/// Get [Import Log Entries](ImportLogEntry), most recent first.
+///
+/// The filters are applied directly to the columns (rather than to functions of them), so that
+/// the query can use the indexes on recordnum and datetime.
 pub fn get_import_log(
     conn: &Connection,
-    recordnum: Option<u32>,
+    query: &ImportLogQuery,
 ) -> Result<Vec<ImportLogEntry>, oracle::Error> {
-    let results = match recordnum {
-        Some(v) => conn.query_as::<ImportLogEntry>(
-            "select * from import_log WHERE recordnum = :1 order by datetime desc",
-            &[&v],
-        ),
-        None => {
-            conn.query_as::<ImportLogEntry>("select * from import_log order by datetime desc", &[])
-        }
-    }?;
+    let mut conditions = vec![];
+    let mut params: Vec<&dyn ToSql> = vec![];
+
+    if let Some(recordnum) = &query.recordnum {
+        params.push(recordnum);
+        conditions.push(format!("recordnum = :{}", params.len()));
+    }
+    if let Some(level) = query.level {
+        // The levels are from `Level`, not user input, so can be included directly.
+        let levels = Level::iter()
+            .filter(|v| *v <= level)
+            .map(|v| format!("'{v}'"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        conditions.push(format!("log_level in ({levels})"));
+    }
+    let from = query.from.map(|v| v.and_time(NaiveTime::MIN));
+    if let Some(from) = &from {
+        params.push(from);
+        conditions.push(format!("datetime >= :{}", params.len()));
+    }
+    let to = query
+        .to
+        .and_then(|v| v.succ_opt())
+        .map(|v| v.and_time(NaiveTime::MIN));
+    if let Some(to) = &to {
+        params.push(to);
+        conditions.push(format!("datetime < :{}", params.len()));
+    }
+
+    let mut sql = "select * from import_log".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(&format!(" where {}", conditions.join(" and ")));
+    }
+    sql.push_str(" order by datetime desc");
+    if let Some(offset) = &query.offset {
+        params.push(offset);
+        sql.push_str(&format!(" offset :{} rows", params.len()));
+    }
+    if let Some(limit) = &query.limit {
+        params.push(limit);
+        sql.push_str(&format!(" fetch first :{} rows only", params.len()));
+    }
+
+    let results = conn.query_as::<ImportLogEntry>(&sql, &params)?;
 
     let mut log_records = vec![];
     for row in results {
@@ -151,50 +508,260 @@ pub fn get_metadata(conn: &Connection, recordnum: u32) -> Result<Metadata, Count
     )?)
 }
 
-/// Get paginated [`Metadata`] records.
+/// A count's [`Metadata`], along with its [`Location`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocatedMetadata {
+    #[serde(flatten)]
+    pub metadata: Metadata,
+    pub location: Location,
+}
+
+/// Get the [`Metadata`] of a count along with its location, filled in from `locations`.
+pub fn get_located_metadata(
+    conn: &Connection,
+    recordnum: u32,
+    locations: &Locations,
+) -> Result<LocatedMetadata, CountError> {
+    let metadata = get_metadata(conn, recordnum)?;
+    Ok(LocatedMetadata {
+        location: locations.locate(recordnum, &metadata),
+        metadata,
+    })
+}
+
+/// The field to sort [`Metadata`] records by in [`get_metadata_paginated`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataSort {
+    #[default]
+    Recordnum,
+    /// The last day the count was taken.
+    DateCounted,
+    /// The day the count's data was imported.
+    DateImported,
+    /// The day the record was created.
+    DateCreated,
+}
+
+impl MetadataSort {
+    /// The column of the TC_HEADER table sorted by.
+    fn column(&self) -> &'static str {
+        match self {
+            MetadataSort::Recordnum => "recordnum",
+            MetadataSort::DateCounted => "datelastcounted",
+            MetadataSort::DateImported => "importdatadate",
+            MetadataSort::DateCreated => "createheaderdate",
+        }
+    }
+}
+
+impl FromStr for MetadataSort {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "recordnum" => Ok(MetadataSort::Recordnum),
+            "counted" => Ok(MetadataSort::DateCounted),
+            "imported" => Ok(MetadataSort::DateImported),
+            "created" => Ok(MetadataSort::DateCreated),
+            _ => Err(CountError::UnknownMetadataSort(s.to_string())),
+        }
+    }
+}
+
+impl Display for MetadataSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sort = match self {
+            MetadataSort::Recordnum => "recordnum",
+            MetadataSort::DateCounted => "counted",
+            MetadataSort::DateImported => "imported",
+            MetadataSort::DateCreated => "created",
+        };
+        write!(f, "{}", sort)
+    }
+}
+
+/// Filters, sorting, and pagination for [`get_metadata_paginated`].
+///
+/// Any filter that is `None` isn't applied. Records are sorted in descending order (most recent
+/// first) unless `ascending` is set, and 100 are returned if `limit` isn't set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataQuery {
+    /// The technician who took the count.
+    pub takenby: Option<String>,
+    pub count_kind: Option<CountKind>,
+    /// The municipality (minor civil division) of the count, as its 10-digit code.
+    pub mcd: Option<String>,
+    /// The county of the count, as its 5-digit code (the first five digits of that of its MCD).
+    pub county: Option<String>,
+    /// The first day to include counts last taken on.
+    pub from: Option<NaiveDate>,
+    /// The last day to include counts last taken on.
+    pub to: Option<NaiveDate>,
+    /// The status of the count, e.g. "imported".
+    pub status: Option<String>,
+    pub sort: MetadataSort,
+    pub ascending: bool,
+    pub offset: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+/// A page of [`Metadata`] records, with the total number matching the query it's from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetadataPage {
+    pub total: u32,
+    pub records: Vec<Metadata>,
+}
+
+/// Get paginated [`Metadata`] records, filtered and sorted.
 pub fn get_metadata_paginated(
     conn: &Connection,
-    offset: Option<u32>,
-    limit: Option<u32>,
-) -> Result<Vec<Metadata>, CountError> {
+    query: &MetadataQuery,
+) -> Result<MetadataPage, CountError> {
+    let mut conditions = vec![];
+    let mut params: Vec<&dyn ToSql> = vec![];
+
+    if let Some(takenby) = &query.takenby {
+        params.push(takenby);
+        conditions.push(format!("takenby = :{}", params.len()));
+    }
+    if let Some(count_kind) = &query.count_kind {
+        params.push(count_kind);
+        conditions.push(format!("type = :{}", params.len()));
+    }
+    if let Some(mcd) = &query.mcd {
+        params.push(mcd);
+        conditions.push(format!("mcd = :{}", params.len()));
+    }
+    if let Some(county) = &query.county {
+        params.push(county);
+        conditions.push(format!("mcd like :{} || '%'", params.len()));
+    }
+    if let Some(from) = &query.from {
+        params.push(from);
+        conditions.push(format!("datelastcounted >= :{}", params.len()));
+    }
+    if let Some(to) = &query.to {
+        params.push(to);
+        conditions.push(format!("datelastcounted <= :{}", params.len()));
+    }
+    if let Some(status) = &query.status {
+        params.push(status);
+        conditions.push(format!("status = :{}", params.len()));
+    }
+
+    let mut sql = "from tc_header".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(&format!(" where {}", conditions.join(" and ")));
+    }
+    let total = conn.query_row_as::<u32>(&format!("select count(*) {sql}"), &params)?;
+
+    let direction = if query.ascending { "asc" } else { "desc" };
+    sql = format!(
+        "select * {sql} order by {} {direction}",
+        query.sort.column()
+    );
+    // (Ties are broken by recordnum, so that pages are consistent.)
+    if query.sort != MetadataSort::Recordnum {
+        sql.push_str(&format!(" nulls last, recordnum {direction}"));
+    }
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(100);
+    params.push(&offset);
+    sql.push_str(&format!(" offset :{} rows", params.len()));
+    params.push(&limit);
+    sql.push_str(&format!(" fetch first :{} rows only", params.len()));
+
     let mut records = vec![];
+    for row in conn.query_as::<Metadata>(&sql, &params)? {
+        records.push(row?);
+    }
+    Ok(MetadataPage { total, records })
+}
 
-    let offset = offset.unwrap_or(0);
-    let limit = limit.unwrap_or(100);
-    let results = conn.query_as::<Metadata>(
-        "select * from tc_header 
-            order by recordnum DESC
-            offset :1 rows
-            fetch first :2 rows only",
-        &[&offset, &limit],
-    )?;
+/// Fields to pre-populate new [`Metadata`] records with, so that they don't need to be filled
+/// in afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewRecordFields {
+    pub count_kind: Option<CountKind>,
+    /// The municipality (minor civil division) of the count, as its 10-digit code.
+    pub mcd: Option<String>,
+    /// The technician taking the count.
+    pub takenby: Option<String>,
+}
 
-    for row in results {
-        let row = row?;
-        records.push(row)
+impl NewRecordFields {
+    /// Check that the fields are valid.
+    pub fn validate(&self) -> Result<(), CountError> {
+        if let Some(mcd) = &self.mcd {
+            if mcd.len() != 10 || !mcd.chars().all(|c| c.is_ascii_digit()) {
+                return Err(CountError::InvalidMcd(mcd.clone()));
+            }
+        }
+        if let Some(takenby) = &self.takenby {
+            validate_technician(takenby)?;
+        }
+        Ok(())
+    }
+
+    /// Set those fields of `metadata` that are set here.
+    pub fn apply(&self, metadata: &mut Metadata) -> Result<(), CountError> {
+        self.validate()?;
+        if let Some(count_kind) = &self.count_kind {
+            metadata.count_kind = Some(count_kind.clone());
+        }
+        if let Some(mcd) = &self.mcd {
+            metadata.mcd = Some(mcd.clone());
+        }
+        if let Some(takenby) = &self.takenby {
+            metadata.takenby = Some(takenby.clone());
+        }
+        Ok(())
     }
-    Ok(records)
 }
 
-/// Insert one or more empty [`Metadata`] records (with recordnum and created date only).
-pub fn insert_empty_metadata(conn: &Connection, number: u32) -> Result<Vec<u32>, CountError> {
-    if number == 0 {
-        return Err(CountError::DbError("Cannot create 0 records".to_string()));
+/// Check that a technician (e.g. "KW") is only letters.
+pub fn validate_technician(technician: &str) -> Result<(), CountError> {
+    if technician.is_empty() || !technician.chars().all(|c| c.is_alphabetic()) {
+        return Err(CountError::InvalidField {
+            field: "takenby",
+            value: technician.to_string(),
+        });
     }
-    if number > RECORD_CREATION_LIMIT {
-        return Err(CountError::DbError(format!(
-            "Too many new records requested: cannot created more than {}",
-            RECORD_CREATION_LIMIT
-        )));
+    Ok(())
+}
+
+/// Check that the number of new records requested is between 1 and [`RECORD_CREATION_LIMIT`].
+fn check_record_number(number: u32) -> Result<(), CountError> {
+    if number == 0 || number > RECORD_CREATION_LIMIT {
+        return Err(CountError::InvalidRecordNumber(number));
     }
+    Ok(())
+}
+
+/// Insert one or more empty [`Metadata`] records (with recordnum, created date, and any of
+/// `fields` that are set only).
+pub fn insert_empty_metadata(
+    conn: &Connection,
+    number: u32,
+    fields: &NewRecordFields,
+) -> Result<Vec<u32>, CountError> {
+    check_record_number(number)?;
+    fields.validate()?;
 
     let mut recordnums = vec![];
     for _ in 0..number {
         let stmt = conn.execute(
-            "insert into tc_header (createheaderdate) values (CURRENT_DATE) RETURNING recordnum INTO :recordnum",
-            &[&None::<u32>],
+            "insert into tc_header (createheaderdate, type, mcd, takenby) \
+            values (CURRENT_DATE, :1, :2, :3) RETURNING recordnum INTO :recordnum",
+            &[
+                &fields.count_kind,
+                &fields.mcd,
+                &fields.takenby,
+                &None::<u32>,
+            ],
         )?;
         let recordnum: u32 = stmt.returned_values("recordnum")?[0];
+        audit::record(conn, Operation::Insert, "tc_header", recordnum, 1)?;
         recordnums.push(recordnum);
     }
     conn.commit()?;
@@ -207,15 +774,7 @@ pub fn insert_metadata_from_existing(
     number: u32,
     metadata: Metadata,
 ) -> Result<Vec<u32>, CountError> {
-    if number == 0 {
-        return Err(CountError::DbError("Cannot create 0 records".to_string()));
-    }
-    if number > RECORD_CREATION_LIMIT {
-        return Err(CountError::DbError(format!(
-            "Too many new records requested: cannot created more than {}",
-            RECORD_CREATION_LIMIT
-        )));
-    }
+    check_record_number(number)?;
     let sql = "insert into tc_header (
         amending, ampeak, bikepeddesc, bikepedfacility, bikepedgroup, \
         cntdir, comments, type, counterid, createheaderdate, datelastcounted, \
@@ -270,6 +829,7 @@ pub fn insert_metadata_from_existing(
             &metadata.sr,
             &metadata.sri,
             &metadata.stationid,
+            &metadata.takenby,
             &metadata.tolmt,
             &metadata.trafdir,
             &metadata.x,
@@ -277,12 +837,144 @@ pub fn insert_metadata_from_existing(
             &None::<u32>,
         ])?;
         let recordnum: u32 = stmt.returned_values("recordnum")?[0];
+        audit::record(conn, Operation::Insert, "tc_header", recordnum, 1)?;
         recordnums.push(recordnum);
     }
     conn.commit()?;
     Ok(recordnums)
 }
 
+/// Fields of a [`Metadata`] record to update with [`update_metadata`]; those that aren't set are
+/// left as they are.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadataUpdate {
+    /// The technician who took the count.
+    pub takenby: Option<String>,
+    pub cntdir: Option<RoadDirection>,
+    pub speedlimit: Option<u8>,
+    pub counter_id: Option<String>,
+    /// The status of the count, e.g. "imported".
+    pub status: Option<String>,
+    pub comments: Option<String>,
+}
+
+impl MetadataUpdate {
+    /// Describe the changes this makes to a record, with `status` its current status (which isn't
+    /// part of [`Metadata`]). Fields set to the value they already have aren't included.
+    pub fn changes(&self, metadata: &Metadata, status: Option<&str>) -> Vec<String> {
+        fn change<T: Display + PartialEq + ?Sized>(
+            field: &str,
+            old: Option<&T>,
+            new: Option<&T>,
+        ) -> Option<String> {
+            let new = new?;
+            if old == Some(new) {
+                return None;
+            }
+            let old = old.map_or("(none)".to_string(), |v| format!("'{v}'"));
+            Some(format!("{field} {old} -> '{new}'"))
+        }
+
+        [
+            change("takenby", metadata.takenby.as_ref(), self.takenby.as_ref()),
+            change("cntdir", metadata.cntdir.as_ref(), self.cntdir.as_ref()),
+            change(
+                "speedlimit",
+                metadata.speedlimit.as_ref(),
+                self.speedlimit.as_ref(),
+            ),
+            change(
+                "counterid",
+                metadata.counter_id.as_ref(),
+                self.counter_id.as_ref(),
+            ),
+            change("status", status, self.status.as_deref()),
+            change(
+                "comments",
+                metadata.comments.as_ref(),
+                self.comments.as_ref(),
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Update some of the fields of a [`Metadata`] record, returning the changes made.
+///
+/// The changes are entered into the import log, as a record of who changed what (along with its
+/// time). Nothing is updated or logged if no fields would change.
+pub fn update_metadata(
+    conn: &Connection,
+    recordnum: u32,
+    fields: &MetadataUpdate,
+) -> Result<Vec<String>, CountError> {
+    if let Some(takenby) = &fields.takenby {
+        validate_technician(takenby)?;
+    }
+    let metadata = get_metadata(conn, recordnum)?;
+    let status = conn.query_row_as::<Option<String>>(
+        "select status from tc_header where recordnum = :1",
+        &[&recordnum],
+    )?;
+    let changes = fields.changes(&metadata, status.as_deref());
+    if changes.is_empty() {
+        return Ok(changes);
+    }
+
+    let mut assignments = vec![];
+    let mut params: Vec<&dyn ToSql> = vec![];
+    let columns: [(&str, Option<&dyn ToSql>); 6] = [
+        ("takenby", fields.takenby.as_ref().map(|v| v as &dyn ToSql)),
+        ("cntdir", fields.cntdir.as_ref().map(|v| v as &dyn ToSql)),
+        (
+            "speedlimit",
+            fields.speedlimit.as_ref().map(|v| v as &dyn ToSql),
+        ),
+        (
+            "counterid",
+            fields.counter_id.as_ref().map(|v| v as &dyn ToSql),
+        ),
+        ("status", fields.status.as_ref().map(|v| v as &dyn ToSql)),
+        (
+            "comments",
+            fields.comments.as_ref().map(|v| v as &dyn ToSql),
+        ),
+    ];
+    for (column, value) in columns {
+        if let Some(value) = value {
+            params.push(value);
+            assignments.push(format!("{column} = :{}", params.len()));
+        }
+    }
+    params.push(&recordnum);
+    let sql = format!(
+        "update tc_header set {} where recordnum = :{}",
+        assignments.join(", "),
+        params.len()
+    );
+    let stmt = conn.execute(&sql, &params)?;
+    audit::record(
+        conn,
+        Operation::Update,
+        "tc_header",
+        recordnum,
+        stmt.row_count()?,
+    )?;
+
+    // (This commits the update too.)
+    insert_import_log_entry(
+        conn,
+        ImportLogEntry::new(
+            recordnum,
+            format!("Metadata updated: {}", changes.join("; ")),
+            Level::Info,
+        ),
+    )?;
+    Ok(changes)
+}
+
 /// Get the type of count for a given record number.
 pub fn get_count_kind(conn: &Connection, recordnum: u32) -> Result<Option<CountKind>, CountError> {
     match conn.query_row_as::<Option<CountKind>>(
@@ -294,9 +986,145 @@ pub fn get_count_kind(conn: &Connection, recordnum: u32) -> Result<Option<CountK
     }
 }
 
+/// Get the [class counts][TimeBinnedVehicleClassCount] of a count, in order of time and lane.
+pub fn get_class_count(
+    conn: &Connection,
+    recordnum: u32,
+) -> Result<Vec<TimeBinnedVehicleClassCount>, CountError> {
+    let mut counts = TimeBinnedVehicleClassCount::select(conn, recordnum)?;
+    counts.sort_by_key(|v| (v.date, v.time.time(), v.lane));
+    Ok(counts)
+}
+
+/// Get the [speed range counts][TimeBinnedSpeedRangeCount] of a count, in order of time and lane.
+pub fn get_speed_count(
+    conn: &Connection,
+    recordnum: u32,
+) -> Result<Vec<TimeBinnedSpeedRangeCount>, CountError> {
+    let mut counts = TimeBinnedSpeedRangeCount::select(conn, recordnum)?;
+    counts.sort_by_key(|v| (v.date, v.time.time(), v.lane));
+    Ok(counts)
+}
+
+/// Get the [hourly volume counts][NonNormalVolCount] (the TC_VOLCOUNT table) of a count, in order
+/// of day and lane.
+///
+/// Rows of [both directions combined][crate::combined_directions] are left out of days that also
+/// have rows of each direction, so that nothing is counted twice.
+pub fn get_volume_count(
+    conn: &Connection,
+    recordnum: u32,
+) -> Result<Vec<NonNormalVolCount>, CountError> {
+    let mut counts = NonNormalVolCount::select(conn, recordnum)?;
+    let directional = counts
+        .iter()
+        .filter(|v| !is_combined(v.direction))
+        .map(|v| v.date)
+        .collect::<HashSet<_>>();
+    counts.retain(|v| !is_combined(v.direction) || !directional.contains(&v.date));
+    counts.sort_by_key(|v| (v.date, v.lane));
+    Ok(counts)
+}
+
+/// Get the recordnums of the counts whose data was imported on or after a day, in order, to
+/// check their data again.
+///
+/// Hourly class counts (those whose class counts are all on the hour) are left out, as their data
+/// isn't checked when they're imported either.
+pub fn get_counts_imported_since(
+    conn: &Connection,
+    since: NaiveDate,
+) -> Result<Vec<u32>, CountError> {
+    let sql = "select recordnum from tc_header h \
+        where status = 'imported' and importdatadate >= :1 \
+        and not ( \
+            exists (select 1 from tc_clacount c where c.recordnum = h.recordnum) \
+            and not exists ( \
+                select 1 from tc_clacount c \
+                where c.recordnum = h.recordnum and to_char(c.counttime, 'MI') <> '00' \
+            ) \
+        ) \
+        order by recordnum";
+    let recordnums = conn.query_as::<u32>(sql, &[&since.and_time(NaiveTime::MIN)])?;
+    Ok(recordnums.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Get the individual vehicles of a count stored in the [raw vehicle table][RawVehicle], in
+/// order of time and lane, so that they can be binned again.
+///
+/// Only counts imported with their raw vehicles stored have any.
+pub fn get_raw_vehicles(conn: &Connection, recordnum: u32) -> Result<Vec<RawVehicle>, CountError> {
+    let mut vehicles = RawVehicle::select(conn, recordnum)?;
+    vehicles.sort_by_key(|v| (v.time, v.lane));
+    Ok(vehicles)
+}
+
+/// Get the channels of a count stored in the [raw vehicle channel table][RawChannel], in order of
+/// channel.
+///
+/// Only counts imported with their raw vehicles stored (since channels were stored with them)
+/// have any.
+pub fn get_raw_channels(conn: &Connection, recordnum: u32) -> Result<Vec<RawChannel>, CountError> {
+    let mut channels = RawChannel::select(conn, recordnum)?;
+    channels.sort_by_key(|v| v.channel);
+    Ok(channels)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::LaneDirection;
+
+    #[test]
+    fn new_record_fields_applied_only_if_set() {
+        let mut metadata: Metadata =
+            serde_json::from_str(r#"{"recordnum": 1, "mcd": "4210160000", "takenby": "KW"}"#)
+                .unwrap();
+        let fields = NewRecordFields {
+            count_kind: Some(CountKind::Class),
+            mcd: None,
+            takenby: Some("JS".to_string()),
+        };
+        fields.apply(&mut metadata).unwrap();
+        assert_eq!(metadata.count_kind, Some(CountKind::Class));
+        assert_eq!(metadata.mcd, Some("4210160000".to_string()));
+        assert_eq!(metadata.takenby, Some("JS".to_string()));
+
+        let fields = NewRecordFields {
+            mcd: Some("Abington".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            fields.apply(&mut metadata),
+            Err(CountError::InvalidMcd(_))
+        ));
+
+        let fields = NewRecordFields {
+            takenby: Some("J. S.".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            fields.validate(),
+            Err(CountError::InvalidField {
+                field: "takenby",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn record_number_limited() {
+        assert!(matches!(
+            check_record_number(0),
+            Err(CountError::InvalidRecordNumber(0))
+        ));
+        assert!(check_record_number(1).is_ok());
+        assert!(check_record_number(RECORD_CREATION_LIMIT).is_ok());
+        assert!(matches!(
+            check_record_number(RECORD_CREATION_LIMIT + 1),
+            Err(CountError::InvalidRecordNumber(_))
+        ));
+    }
 
     #[ignore]
     #[test]
@@ -321,4 +1149,159 @@ mod tests {
             "15 min Volume".to_string()
         )
     }
+
+    #[ignore]
+    #[test]
+    fn intermediate_directions_round_trip_through_db() {
+        let (username, password) = get_creds();
+        let pool = create_pool(username, password).unwrap();
+        let conn = pool.get().unwrap();
+
+        for direction in [
+            LaneDirection::Northeast,
+            LaneDirection::Northwest,
+            LaneDirection::Southeast,
+            LaneDirection::Southwest,
+        ] {
+            let (value, read) = conn
+                .query_row_as::<(String, LaneDirection)>(
+                    "select :1, :2 from dual",
+                    &[&direction, &direction],
+                )
+                .unwrap();
+            assert_eq!(value, direction.to_string());
+            assert_eq!(read, direction);
+        }
+    }
+
+    #[ignore]
+    #[test]
+    fn logging_does_not_commit_changes_to_a_count() {
+        let (username, password) = get_creds();
+        let pool = create_pool(username, password).unwrap();
+        let conn = pool.get().unwrap();
+        let log = simplelog::SimpleLogger::new(log::LevelFilter::Off, simplelog::Config::default());
+
+        let class_count = TimeBinnedVehicleClassCount::select(&conn, 166905).unwrap();
+        assert!(!class_count.is_empty());
+
+        // Replace the count's class counts, but fail to insert into the next table, logging the
+        // error (as importing a file does) before rolling back.
+        TimeBinnedVehicleClassCount::delete(&conn, 166905).unwrap();
+        assert!(conn
+            .execute(
+                "insert into tc_specount (recordnum) values (:1)",
+                &[&"not a recordnum"]
+            )
+            .is_err());
+        crate::log_msg(
+            166905,
+            &log,
+            Level::Error,
+            "Error inserting speed range data",
+            &conn,
+        );
+        conn.rollback().unwrap();
+
+        assert_eq!(
+            TimeBinnedVehicleClassCount::select(&conn, 166905)
+                .unwrap()
+                .len(),
+            class_count.len()
+        );
+    }
+
+    #[ignore]
+    #[test]
+    fn import_log_filtered_and_paginated() {
+        let (username, password) = get_creds();
+        let pool = create_pool(username, password).unwrap();
+        let conn = pool.get().unwrap();
+
+        let query = ImportLogQuery {
+            level: Some(Level::Warn),
+            limit: Some(5),
+            ..Default::default()
+        };
+        let entries = get_import_log(&conn, &query).unwrap();
+        assert!(entries.len() <= 5);
+        assert!(entries
+            .iter()
+            .all(|v| v.level == "WARN" || v.level == "ERROR"));
+        assert!(entries.windows(2).all(|v| v[0].datetime >= v[1].datetime));
+
+        // The second page starts where the first one ended.
+        let all = get_import_log(
+            &conn,
+            &ImportLogQuery {
+                limit: Some(10),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let second_page = get_import_log(
+            &conn,
+            &ImportLogQuery {
+                offset: Some(5),
+                limit: Some(5),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            all.iter().skip(5).map(|v| v.datetime).collect::<Vec<_>>(),
+            second_page.iter().map(|v| v.datetime).collect::<Vec<_>>()
+        );
+    }
+
+    #[ignore]
+    #[test]
+    fn metadata_filtered_sorted_and_counted() {
+        let (username, password) = get_creds();
+        let pool = create_pool(username, password).unwrap();
+        let conn = pool.get().unwrap();
+
+        let query = MetadataQuery {
+            count_kind: Some(CountKind::Class),
+            sort: MetadataSort::DateCounted,
+            limit: Some(5),
+            ..Default::default()
+        };
+        let page = get_metadata_paginated(&conn, &query).unwrap();
+        assert!(page.records.len() <= 5);
+        assert!(page.total as usize >= page.records.len());
+        assert!(page
+            .records
+            .iter()
+            .all(|v| v.count_kind == Some(CountKind::Class)));
+        assert!(page.records.windows(2).all(|v| v[0]
+            .datelastcounted
+            .is_none_or(|d| Some(d) >= v[1].datelastcounted)));
+    }
+
+    #[test]
+    fn metadata_update_changes_described() {
+        let metadata: Metadata = serde_json::from_str(
+            r#"{"recordnum": 1, "takenby": "KW", "speedlimit": 35, "counter_id": "40972"}"#,
+        )
+        .unwrap();
+        let update = MetadataUpdate {
+            takenby: Some("JS".to_string()),
+            speedlimit: Some(35),
+            status: Some("imported".to_string()),
+            comments: Some("Counter moved".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            update.changes(&metadata, Some("new")),
+            vec![
+                "takenby 'KW' -> 'JS'",
+                "status 'new' -> 'imported'",
+                "comments (none) -> 'Counter moved'"
+            ]
+        );
+        assert!(MetadataUpdate::default()
+            .changes(&metadata, None)
+            .is_empty());
+    }
 }
@@ -5,7 +5,7 @@ use chrono::NaiveDateTime;
 use log::Level;
 use oracle::{
     sql_type::{FromSql, OracleType, ToSql, ToSqlNull},
-    Connection, Error as OracleError, RowValue, SqlValue,
+    Connection, Error as OracleError, ErrorKind, RowValue, SqlValue,
 };
 
 use crate::{db::ImportLogEntry, CountError, CountKind, LaneDirection, RoadDirection};
@@ -14,8 +14,10 @@ impl FromSql for CountKind {
     fn from_sql(val: &SqlValue<'_>) -> oracle::Result<Self> {
         match CountKind::from_str(&val.to_string()) {
             Ok(v) => Ok(v),
-            Err(CountError::UnknownCountType(_)) => Err(OracleError::NullValue),
-            Err(e) => Err(OracleError::ParseError(Box::new(e))),
+            Err(e @ CountError::UnknownCountType(_)) => {
+                Err(OracleError::new(ErrorKind::NullValue, e.to_string()))
+            }
+            Err(e) => Err(OracleError::with_source(ErrorKind::ParseError, e)),
         }
     }
 }
@@ -37,8 +39,10 @@ impl FromSql for LaneDirection {
     fn from_sql(val: &SqlValue<'_>) -> oracle::Result<Self> {
         match LaneDirection::from_str(&val.to_string()) {
             Ok(v) => Ok(v),
-            Err(CountError::BadDirection(_)) => Err(OracleError::NullValue),
-            Err(e) => Err(OracleError::ParseError(Box::new(e))),
+            Err(e @ CountError::BadDirection(_)) => {
+                Err(OracleError::new(ErrorKind::NullValue, e.to_string()))
+            }
+            Err(e) => Err(OracleError::with_source(ErrorKind::ParseError, e)),
         }
     }
 }
@@ -62,8 +66,10 @@ impl FromSql for RoadDirection {
     fn from_sql(val: &SqlValue<'_>) -> oracle::Result<Self> {
         match RoadDirection::from_str(&val.to_string()) {
             Ok(v) => Ok(v),
-            Err(CountError::BadDirection(_)) => Err(OracleError::NullValue),
-            Err(e) => Err(OracleError::ParseError(Box::new(e))),
+            Err(e @ CountError::BadDirection(_)) => {
+                Err(OracleError::new(ErrorKind::NullValue, e.to_string()))
+            }
+            Err(e) => Err(OracleError::with_source(ErrorKind::ParseError, e)),
         }
     }
 }
@@ -0,0 +1,140 @@
+//! Enter messages logged about a count into the database's import log.
+//!
+//! Much of what goes wrong when extracting and checking data (e.g. rows that can't be parsed) is
+//! logged with the `log` macros, deep in code that doesn't know which count it's working on. A
+//! [`RecordLog`] is told which count is being imported, and keeps the warnings and errors logged
+//! in the meantime so that they can be [entered into the database][RecordLog::write_to_db] along
+//! with the rest of the count's [import log][super::ImportLogEntry].
+//!
+//! Messages logged with [`log_msg`][crate::log_msg] are entered into the database as they are
+//! logged, and so are marked with the [`DB_TARGET`] target and not kept.
+use std::sync::Mutex;
+
+use log::{Level, Log, Metadata, Record};
+use oracle::Connection;
+
+use super::{insert_import_log_entry, ImportLogEntry};
+
+/// The target of log records that have already been entered into the database.
+pub const DB_TARGET: &str = "import_log";
+
+/// A [`Log`] that passes records on to another, keeping the warnings and errors about the current
+/// count to be entered into the database.
+pub struct RecordLog<L> {
+    log: L,
+    recordnum: Mutex<Option<u32>>,
+    entries: Mutex<Vec<ImportLogEntry>>,
+}
+
+impl<L: Log> RecordLog<L> {
+    pub fn new(log: L) -> Self {
+        Self {
+            log,
+            recordnum: Mutex::new(None),
+            entries: Mutex::new(vec![]),
+        }
+    }
+
+    /// Set the count that messages logged from now on are about (or none).
+    pub fn set_recordnum(&self, recordnum: Option<u32>) {
+        if let Ok(mut v) = self.recordnum.lock() {
+            *v = recordnum;
+        }
+    }
+
+    /// Enter the warnings and errors kept since the last time this was called into the database.
+    ///
+    /// Since this is fallible, any failure is logged (to the wrapped log only).
+    pub fn write_to_db(&self, conn: &Connection) {
+        for entry in self.take_entries() {
+            let recordnum = entry.recordnum;
+            if let Err(e) = insert_import_log_entry(conn, entry) {
+                self.log.log(
+                    &Record::builder()
+                        .args(format_args!(
+                            "{recordnum}: Error entering log into database: {e}"
+                        ))
+                        .level(Level::Error)
+                        .build(),
+                );
+            }
+        }
+    }
+
+    fn take_entries(&self) -> Vec<ImportLogEntry> {
+        match self.entries.lock() {
+            Ok(mut v) => std::mem::take(&mut *v),
+            Err(_) => vec![],
+        }
+    }
+}
+
+impl<L: Log> Log for RecordLog<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.log.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= Level::Warn && record.target() != DB_TARGET {
+            let recordnum = self.recordnum.lock().ok().and_then(|v| *v);
+            if let (Some(recordnum), Ok(mut entries)) = (recordnum, self.entries.lock()) {
+                entries.push(ImportLogEntry::new(
+                    recordnum,
+                    record.args().to_string(),
+                    record.level(),
+                ));
+            }
+        }
+        self.log.log(record)
+    }
+
+    fn flush(&self) {
+        self.log.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_message(log: &impl Log, level: Level, target: &str, message: &str) {
+        log.log(
+            &Record::builder()
+                .args(format_args!("{message}"))
+                .level(level)
+                .target(target)
+                .build(),
+        );
+    }
+
+    #[test]
+    fn only_warnings_and_errors_about_a_count_kept() {
+        let record_log = RecordLog::new(simplelog::SimpleLogger::new(
+            log::LevelFilter::Off,
+            simplelog::Config::default(),
+        ));
+
+        log_message(&record_log, Level::Error, "", "before any count");
+        record_log.set_recordnum(Some(166905));
+        log_message(&record_log, Level::Warn, "", "row 7 skipped");
+        log_message(&record_log, Level::Info, "", "just informational");
+        log_message(
+            &record_log,
+            Level::Error,
+            DB_TARGET,
+            "already in the database",
+        );
+        record_log.set_recordnum(None);
+        log_message(&record_log, Level::Error, "", "after the count");
+
+        assert_eq!(
+            record_log.take_entries(),
+            vec![ImportLogEntry::new(
+                166905,
+                "row 7 skipped".to_string(),
+                Level::Warn
+            )]
+        );
+        assert!(record_log.take_entries().is_empty());
+    }
+}
@@ -0,0 +1,287 @@
+//! Retrying connecting to the database, and statements, when the connection fails.
+//!
+//! Our connection to the database is over a VPN, which occasionally drops. Rather than fail
+//! immediately, creating a connection pool and getting a connection from it are retried
+//! according to a [`RetryPolicy`], waiting exponentially longer (with some random jitter)
+//! between each attempt. It is configured with the following environment variables:
+//!   - `DB_CONNECT_RETRIES` - the number of times to retry connecting (default 5)
+//!   - `DB_CONNECT_RETRY_DELAY` - milliseconds to wait before the first retry (default 1000)
+//!   - `DB_CONNECT_RETRY_MAX_DELAY` - maximum milliseconds to wait between retries (default
+//!     60000)
+//!
+//! Reads, and writes made before the first uncommitted change, are retried the same way (on a
+//! new connection) with [`retry_statement`][super::retry_statement]. Other statements aren't:
+//! when the connection is lost partway through importing a file, its uncommitted transaction is
+//! lost with it, so the import program instead reconnects before the next file and (since the
+//! error is [transient][Retryable::is_transient]) tries the file again later, unless some of it
+//! had already been committed.
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use log::{Level, Log, Record};
+use oracle::{Error as OracleError, ErrorKind};
+
+use crate::CountError;
+
+/// Oracle error codes that indicate a lost or failed connection.
+const CONNECTION_ERROR_CODES: [i32; 10] = [
+    3113,  // end-of-file on communication channel
+    3114,  // not connected to Oracle
+    3135,  // connection lost contact
+    12170, // TNS: connect timeout occurred
+    12514, // TNS: listener does not currently know of service
+    12537, // TNS: connection closed
+    12541, // TNS: no listener
+    12543, // TNS: destination host unreachable
+    12547, // TNS: lost contact
+    12571, // TNS: packet writer failure
+];
+
+/// Windows error codes that indicate a file is in use by another process.
+const FILE_LOCKED_ERROR_CODES: [i32; 2] = [
+    32, // sharing violation
+    33, // lock violation
+];
+
+/// Errors that may succeed if the operation that caused them is retried.
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+
+    /// Whether the error may not occur if the file being imported is tried again later: as well as
+    /// retryable errors, those from a file that was briefly locked (e.g. while OneDrive synced it).
+    fn is_transient(&self) -> bool {
+        self.is_retryable()
+    }
+}
+
+impl Retryable for OracleError {
+    fn is_retryable(&self) -> bool {
+        match self.kind() {
+            ErrorKind::OciError => self
+                .oci_code()
+                .is_some_and(|code| CONNECTION_ERROR_CODES.contains(&code)),
+            // Not connected, or connection closed.
+            ErrorKind::DpiError => matches!(self.dpi_code(), Some(1010 | 1080)),
+            _ => false,
+        }
+    }
+}
+
+impl Retryable for CountError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            CountError::OracleError(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    fn is_transient(&self) -> bool {
+        match self {
+            CountError::CannotOpenFile(e) => is_locked(e),
+            _ => self.is_retryable(),
+        }
+    }
+}
+
+/// Whether an IO error is because a file was locked or busy.
+fn is_locked(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::ResourceBusy | io::ErrorKind::TimedOut
+    ) || e
+        .raw_os_error()
+        .is_some_and(|code| cfg!(windows) && FILE_LOCKED_ERROR_CODES.contains(&code))
+}
+
+/// How many times, and how long to wait between, retrying connecting to the database (and
+/// statements that lost their connection).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Get the policy from environment variables, using the default for any not set.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let millis = |var: &str| {
+            env::var(var)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+        };
+        Self {
+            max_retries: env::var("DB_CONNECT_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_retries),
+            initial_delay: millis("DB_CONNECT_RETRY_DELAY").unwrap_or(default.initial_delay),
+            max_delay: millis("DB_CONNECT_RETRY_MAX_DELAY").unwrap_or(default.max_delay),
+        }
+    }
+
+    /// How long to wait before retry number `attempt` (starting at 0).
+    ///
+    /// The delay doubles with each attempt, up to `max_delay`, and is then reduced by a random
+    /// amount of up to half, so that retries from several processes are spread out.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempt);
+        let jitter = (hasher.finish() % 1000) as f64 / 2000.0;
+        delay.mul_f64(1.0 - jitter)
+    }
+
+    /// Run `op`, retrying it while it fails with a [retryable][Retryable] error.
+    ///
+    /// Each retry is logged as a warning to `log`.
+    pub fn run<T, E: Retryable + Display>(
+        &self,
+        log: impl Log,
+        mut op: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut attempt = 0;
+        loop {
+            match op() {
+                Err(e) if attempt < self.max_retries && e.is_retryable() => {
+                    let delay = self.delay(attempt);
+                    attempt += 1;
+                    log.log(
+                        &Record::builder()
+                            .args(format_args!(
+                                "Database connection error: {e}; retrying in {delay:?} \
+                                (retry {attempt} of {})",
+                                self.max_retries
+                            ))
+                            .level(Level::Warn)
+                            .build(),
+                    );
+                    thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileNameProblem;
+    use std::path::PathBuf;
+
+    /// An error that is or isn't retryable.
+    #[derive(Debug)]
+    struct Flaky(bool);
+
+    impl Retryable for Flaky {
+        fn is_retryable(&self) -> bool {
+            self.0
+        }
+    }
+
+    impl Display for Flaky {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "flaky")
+        }
+    }
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+        }
+    }
+
+    fn nop_log() -> Box<simplelog::SimpleLogger> {
+        simplelog::SimpleLogger::new(log::LevelFilter::Off, simplelog::Config::default())
+    }
+
+    #[test]
+    fn delay_grows_exponentially_with_jitter_up_to_max() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+        for (attempt, full) in [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1000), (9, 1000)] {
+            let delay = policy.delay(attempt);
+            assert!(delay <= Duration::from_millis(full));
+            assert!(delay >= Duration::from_millis(full / 2));
+        }
+    }
+
+    #[test]
+    fn run_retries_retryable_errors_until_success() {
+        let mut calls = 0;
+        let result = policy(5).run(nop_log(), || {
+            calls += 1;
+            if calls < 3 {
+                Err(Flaky(true))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn run_stops_at_max_retries_or_unretryable_error() {
+        let mut calls = 0;
+        let result: Result<(), _> = policy(2).run(nop_log(), || {
+            calls += 1;
+            Err(Flaky(true))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result: Result<(), _> = policy(2).run(nop_log(), || {
+            calls += 1;
+            Err(Flaky(false))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn count_errors_other_than_oracle_not_retryable() {
+        let e = CountError::InvalidFileName {
+            problem: FileNameProblem::TooManyParts,
+            path: PathBuf::from("1-2-3-4-5.csv"),
+        };
+        assert!(!e.is_retryable());
+        assert!(!e.is_transient());
+    }
+
+    #[test]
+    fn locked_files_transient_but_not_retryable() {
+        let e = CountError::CannotOpenFile(io::Error::from(io::ErrorKind::WouldBlock));
+        assert!(e.is_transient());
+        assert!(!e.is_retryable());
+
+        let e = CountError::CannotOpenFile(io::Error::from(io::ErrorKind::NotFound));
+        assert!(!e.is_transient());
+    }
+}
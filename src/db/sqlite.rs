@@ -0,0 +1,216 @@
+//! A SQLite [`CountStore`], for use without access to our Oracle database.
+//!
+//! Each kind of count is stored in a table with the same name as its table in our database
+//! ([`Crud::COUNT_TABLE`]), created as needed. Rather than the same fields, these tables have
+//! just the recordnum and the record as JSON, so that records are stored and retrieved exactly
+//! as they are.
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    db::{
+        crud::{in_insert_order, Crud},
+        store::{CountStore, StoredCount},
+    },
+    CountError,
+};
+
+/// A [`CountStore`] in a SQLite database.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Open (or create) a database file.
+    pub fn open(path: &Path) -> Result<Self, CountError> {
+        Ok(Self {
+            conn: Connection::open(path)?,
+        })
+    }
+
+    /// Open a database in memory, which is lost when the store is dropped.
+    pub fn open_in_memory() -> Result<Self, CountError> {
+        Ok(Self {
+            conn: Connection::open_in_memory()?,
+        })
+    }
+
+    /// Create the table for a kind of count, if it doesn't exist.
+    fn create_table<T: Crud>(&self) -> Result<(), CountError> {
+        self.conn.execute(
+            &format!(
+                "create table if not exists {} (recordnum integer not null, record text not null)",
+                T::COUNT_TABLE
+            ),
+            [],
+        )?;
+        Ok(())
+    }
+}
+
+impl CountStore for SqliteStore {
+    fn insert<T: StoredCount>(&self, records: &[T]) -> Result<(), CountError> {
+        self.create_table::<T>()?;
+        let tx = self.conn.unchecked_transaction()?;
+        insert_records(&tx, records)?;
+        Ok(tx.commit()?)
+    }
+
+    fn get<T: StoredCount>(&self, recordnum: u32) -> Result<Vec<T>, CountError> {
+        self.create_table::<T>()?;
+        let mut stmt = self.conn.prepare(&format!(
+            "select record from {} where recordnum = ?1 order by rowid",
+            T::COUNT_TABLE
+        ))?;
+        let rows = stmt.query_map([recordnum], |row| row.get::<_, String>(0))?;
+
+        let mut records = vec![];
+        for row in rows {
+            records.push(serde_json::from_str(&row?)?);
+        }
+        Ok(records)
+    }
+
+    fn delete<T: StoredCount>(&self, recordnum: u32) -> Result<(), CountError> {
+        self.create_table::<T>()?;
+        self.conn.execute(
+            &format!("delete from {} where recordnum = ?1", T::COUNT_TABLE),
+            [recordnum],
+        )?;
+        Ok(())
+    }
+
+    fn replace<T: StoredCount>(&self, recordnum: u32, records: &[T]) -> Result<(), CountError> {
+        self.create_table::<T>()?;
+        // (The transaction is rolled back if dropped without being committed.)
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            &format!("delete from {} where recordnum = ?1", T::COUNT_TABLE),
+            [recordnum],
+        )?;
+        insert_records(&tx, records)?;
+        Ok(tx.commit()?)
+    }
+}
+
+/// Insert records (in the order they'd be inserted into our database), without committing.
+fn insert_records<T: StoredCount>(conn: &Connection, records: &[T]) -> Result<(), CountError> {
+    let mut stmt = conn.prepare(&format!(
+        "insert into {} (recordnum, record) values (?1, ?2)",
+        T::COUNT_TABLE
+    ))?;
+    for record in in_insert_order(records) {
+        stmt.execute(params![record.recordnum(), serde_json::to_string(record)?])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FifteenMinuteVehicle;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn insert_get_and_delete_roundtrip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let records = (0..4)
+            .map(|i| {
+                FifteenMinuteVehicle::new(
+                    101,
+                    date,
+                    date.and_hms_opt(10, i * 15, 0).unwrap(),
+                    i as u16,
+                    None,
+                    Some(1),
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        store.insert(&records).unwrap();
+        store.insert(&records[..1]).unwrap();
+        assert_eq!(store.get::<FifteenMinuteVehicle>(101).unwrap().len(), 5);
+        assert!(store.get::<FifteenMinuteVehicle>(102).unwrap().is_empty());
+
+        store.delete::<FifteenMinuteVehicle>(101).unwrap();
+        assert!(store.get::<FifteenMinuteVehicle>(101).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_replace_leaves_stored_records() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let records = (0..4)
+            .map(|i| {
+                FifteenMinuteVehicle::new(
+                    101,
+                    date,
+                    date.and_hms_opt(10, i * 15, 0).unwrap(),
+                    i as u16,
+                    None,
+                    Some(1),
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        store.insert(&records).unwrap();
+
+        store
+            .conn
+            .execute(
+                "create trigger refuse_insert before insert on tc_15minvolcount \
+                begin select raise(abort, 'refused'); end",
+                [],
+            )
+            .unwrap();
+        assert!(store.replace(101, &records[..1]).is_err());
+        assert_eq!(store.get::<FifteenMinuteVehicle>(101).unwrap().len(), 4);
+
+        store
+            .conn
+            .execute("drop trigger refuse_insert", [])
+            .unwrap();
+        store.replace(101, &records[..1]).unwrap();
+        assert_eq!(store.get::<FifteenMinuteVehicle>(101).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn records_inserted_by_time_and_lane() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        // As in a file with all of one lane's counts before the other's.
+        let records = [(1, 0), (1, 15), (2, 0), (2, 15)]
+            .map(|(lane, minute)| {
+                FifteenMinuteVehicle::new(
+                    101,
+                    date,
+                    date.and_hms_opt(10, minute, 0).unwrap(),
+                    lane as u16,
+                    None,
+                    Some(lane),
+                )
+                .unwrap()
+            })
+            .to_vec();
+
+        store.insert(&records).unwrap();
+        let lanes = store
+            .get::<FifteenMinuteVehicle>(101)
+            .unwrap()
+            .iter()
+            .map(|v| (v.time.format("%H:%M").to_string(), v.lane))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lanes,
+            [
+                ("10:00".to_string(), Some(1)),
+                ("10:00".to_string(), Some(2)),
+                ("10:15".to_string(), Some(1)),
+                ("10:15".to_string(), Some(2)),
+            ]
+        );
+    }
+}
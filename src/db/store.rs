@@ -0,0 +1,118 @@
+//! Storing counts independently of the database they're stored in.
+//!
+//! [`CountStore`] is implemented for our Oracle database by [`OracleStore`] (using the [`Crud`]
+//! implementations of counts), and (with the `sqlite` feature) for SQLite by `SqliteStore`,
+//! which can be used locally and in tests by those without access to our database.
+//! [`store_vehicle_counts`] creates and stores the counts from individual vehicles in any of
+//! them, as the import program does with files of them - except for the hourly volume counts
+//! (TC_VOLCOUNT), which the import program denormalizes from the class counts once they're in
+//! our database (see [`Denormalize`][crate::denormalize::Denormalize]), and so can't be stored
+//! elsewhere.
+use std::num::NonZeroUsize;
+
+use oracle::{Connection, RowValue};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    create_speed_and_class_count, db::crud::Crud, denormalize::create_non_normal_speedavg_count,
+    CountError, FieldMetadata, IndividualVehicle, TimeInterval,
+};
+
+/// A kind of count that can be stored in a [`CountStore`].
+///
+/// This is implemented for all kinds of counts that implement [`Crud`]. (Every one of them is
+/// also an Oracle [`RowValue`], which is only needed by [`OracleStore`], to get them.)
+pub trait StoredCount: Crud + RowValue + Serialize + DeserializeOwned {}
+
+impl<T: Crud + RowValue + Serialize + DeserializeOwned> StoredCount for T {}
+
+/// Insert, get, replace, and delete the records of counts.
+pub trait CountStore {
+    /// Insert records, committing them.
+    fn insert<T: StoredCount>(&self, records: &[T]) -> Result<(), CountError>;
+
+    /// Get all records of a count.
+    fn get<T: StoredCount>(&self, recordnum: u32) -> Result<Vec<T>, CountError>;
+
+    /// Delete all records of a count, committing the deletion.
+    fn delete<T: StoredCount>(&self, recordnum: u32) -> Result<(), CountError>;
+
+    /// Replace all records of a count with `records`, in a single transaction: if they can't
+    /// be inserted, the records already stored aren't deleted.
+    fn replace<T: StoredCount>(&self, recordnum: u32, records: &[T]) -> Result<(), CountError>;
+}
+
+/// A [`CountStore`] in our Oracle database.
+pub struct OracleStore<'a> {
+    conn: &'a Connection,
+    batch_size: NonZeroUsize,
+}
+
+impl<'a> OracleStore<'a> {
+    /// Records are inserted `batch_size` at a time (see [`Crud::insert_batch`]).
+    pub fn new(conn: &'a Connection, batch_size: NonZeroUsize) -> Self {
+        Self { conn, batch_size }
+    }
+}
+
+impl CountStore for OracleStore<'_> {
+    fn insert<T: StoredCount>(&self, records: &[T]) -> Result<(), CountError> {
+        T::insert_batch(self.conn, records, self.batch_size)?;
+        Ok(self.conn.commit()?)
+    }
+
+    fn get<T: StoredCount>(&self, recordnum: u32) -> Result<Vec<T>, CountError> {
+        T::select(self.conn, recordnum)
+    }
+
+    fn delete<T: StoredCount>(&self, recordnum: u32) -> Result<(), CountError> {
+        T::delete(self.conn, recordnum)?;
+        Ok(self.conn.commit()?)
+    }
+
+    fn replace<T: StoredCount>(&self, recordnum: u32, records: &[T]) -> Result<(), CountError> {
+        if let Err(e) = T::delete(self.conn, recordnum)
+            .and_then(|_| T::insert_batch(self.conn, records, self.batch_size))
+        {
+            self.conn.rollback()?;
+            return Err(e.into());
+        }
+        Ok(self.conn.commit()?)
+    }
+}
+
+/// Create the 15-minute class and speed counts and hourly average speeds of a count from its
+/// individual vehicles and store them, replacing any already stored, returning the number of
+/// records stored in each table.
+///
+/// Each table's records are replaced in their own transaction. This doesn't create the hourly
+/// volume counts (TC_VOLCOUNT); see the [module documentation][self].
+pub fn store_vehicle_counts(
+    store: &impl CountStore,
+    metadata: &FieldMetadata,
+    vehicles: &[IndividualVehicle],
+) -> Result<Vec<(&'static str, usize)>, CountError> {
+    let (speed_range_count, vehicle_class_count) = create_speed_and_class_count(
+        TimeInterval::FifteenMin,
+        metadata.clone(),
+        vehicles.to_vec(),
+    );
+    let non_normal_speedavg_count =
+        create_non_normal_speedavg_count(metadata.clone(), vehicles.to_vec());
+
+    Ok(vec![
+        replace(store, metadata.recordnum, &vehicle_class_count)?,
+        replace(store, metadata.recordnum, &speed_range_count)?,
+        replace(store, metadata.recordnum, &non_normal_speedavg_count)?,
+    ])
+}
+
+/// Replace the stored records of a count with `records`, returning the table and number stored.
+fn replace<T: StoredCount>(
+    store: &impl CountStore,
+    recordnum: u32,
+    records: &[T],
+) -> Result<(&'static str, usize), CountError> {
+    store.replace(recordnum, records)?;
+    Ok((T::COUNT_TABLE, records.len()))
+}
@@ -2,12 +2,14 @@
 //!
 //! Denormalization is done in two ways:
 //!  * data from some database tables (see [implementors of Denormalize][Denormalize#implementors])
-//!    is transformed into the shape of the TC_VOLCOUNT table ([NonNormalVolCount]).
+//!    is transformed into the shape of the TC_VOLCOUNT table ([NonNormalVolCount]), as are
+//!    [bicycle counts](create_non_normal_bicycle_vol_count) before they are inserted.
 //!  * raw data, in the form of [IndividualVehicle]s, is
 //!    [processed and transformed](create_non_normal_speedavg_count)
 //!    into the shape of the TC_SPESUM table ([NonNormalAvgSpeedCount]).
 use chrono::NaiveDate;
 use oracle::{Connection, RowValue};
+use serde::{Deserialize, Serialize};
 
 use crate::{intermediate::*, *};
 
@@ -33,96 +35,97 @@ pub trait Denormalize {
             conn,
         )?;
 
-        let mut non_normal_vol_map: HashMap<NonNormalCountKey, NonNormalVolCountValue> =
-            HashMap::new();
+        Ok(non_normal_vol_count(counts))
+    }
+}
 
-        if counts.is_empty() {
-            return Ok(vec![]);
-        }
+/// Transform [`HourlyCount`]s into the shape of the TC_VOLCOUNT table.
+fn non_normal_vol_count(counts: Vec<HourlyCount>) -> Vec<NonNormalVolCount> {
+    let mut non_normal_vol_map: BTreeMap<NonNormalCountKey, NonNormalVolCountValue> =
+        BTreeMap::new();
 
-        for count in counts {
-            let key = NonNormalCountKey {
-                recordnum: count.recordnum,
-                date: count.datetime.date(),
-                direction: Some(count.dir),
-                lane: Some(count.lane),
-            };
+    for count in counts {
+        let key = NonNormalCountKey {
+            recordnum: count.recordnum,
+            date: count.datetime.date(),
+            direction: Some(count.dir),
+            lane: Some(count.lane),
+        };
 
-            // Add new entry if necessary, then insert data.
-            non_normal_vol_map
-                .entry(key)
-                .and_modify(|c| {
-                    c.totalcount = c
-                        .totalcount
-                        .map_or(Some(count.count), |c| Some(c + count.count));
-                    match count.datetime.hour() {
-                        0 => c.am12 = Some(count.count),
-                        1 => c.am1 = Some(count.count),
-                        2 => c.am2 = Some(count.count),
-                        3 => c.am3 = Some(count.count),
-                        4 => c.am4 = Some(count.count),
-                        5 => c.am5 = Some(count.count),
-                        6 => c.am6 = Some(count.count),
-                        7 => c.am7 = Some(count.count),
-                        8 => c.am8 = Some(count.count),
-                        9 => c.am9 = Some(count.count),
-                        10 => c.am10 = Some(count.count),
-                        11 => c.am11 = Some(count.count),
-                        12 => c.pm12 = Some(count.count),
-                        13 => c.pm1 = Some(count.count),
-                        14 => c.pm2 = Some(count.count),
-                        15 => c.pm3 = Some(count.count),
-                        16 => c.pm4 = Some(count.count),
-                        17 => c.pm5 = Some(count.count),
-                        18 => c.pm6 = Some(count.count),
-                        19 => c.pm7 = Some(count.count),
-                        20 => c.pm8 = Some(count.count),
-                        21 => c.pm9 = Some(count.count),
-                        22 => c.pm10 = Some(count.count),
-                        23 => c.pm11 = Some(count.count),
-                        _ => (),
-                    };
-                })
-                .or_insert(NonNormalVolCountValue::first(&count));
-        }
-        // Convert HashMap to Vec of structs.
-        let mut non_normal_vol_count = vec![];
-        for (key, value) in non_normal_vol_map {
-            non_normal_vol_count.push(NonNormalVolCount {
-                recordnum: key.recordnum,
-                date: key.date,
-                direction: key.direction,
-                lane: key.lane,
-                setflag: None,
-                totalcount: value.totalcount,
-                am12: value.am12,
-                am1: value.am1,
-                am2: value.am2,
-                am3: value.am3,
-                am4: value.am4,
-                am5: value.am5,
-                am6: value.am6,
-                am7: value.am7,
-                am8: value.am8,
-                am9: value.am9,
-                am10: value.am10,
-                am11: value.am11,
-                pm12: value.pm12,
-                pm1: value.pm1,
-                pm2: value.pm2,
-                pm3: value.pm3,
-                pm4: value.pm4,
-                pm5: value.pm5,
-                pm6: value.pm6,
-                pm7: value.pm7,
-                pm8: value.pm8,
-                pm9: value.pm9,
-                pm10: value.pm10,
-                pm11: value.pm11,
+        // Add new entry if necessary, then insert data.
+        non_normal_vol_map
+            .entry(key)
+            .and_modify(|c| {
+                c.totalcount = c
+                    .totalcount
+                    .map_or(Some(count.count), |c| Some(c + count.count));
+                match count.datetime.hour() {
+                    0 => c.am12 = Some(count.count),
+                    1 => c.am1 = Some(count.count),
+                    2 => c.am2 = Some(count.count),
+                    3 => c.am3 = Some(count.count),
+                    4 => c.am4 = Some(count.count),
+                    5 => c.am5 = Some(count.count),
+                    6 => c.am6 = Some(count.count),
+                    7 => c.am7 = Some(count.count),
+                    8 => c.am8 = Some(count.count),
+                    9 => c.am9 = Some(count.count),
+                    10 => c.am10 = Some(count.count),
+                    11 => c.am11 = Some(count.count),
+                    12 => c.pm12 = Some(count.count),
+                    13 => c.pm1 = Some(count.count),
+                    14 => c.pm2 = Some(count.count),
+                    15 => c.pm3 = Some(count.count),
+                    16 => c.pm4 = Some(count.count),
+                    17 => c.pm5 = Some(count.count),
+                    18 => c.pm6 = Some(count.count),
+                    19 => c.pm7 = Some(count.count),
+                    20 => c.pm8 = Some(count.count),
+                    21 => c.pm9 = Some(count.count),
+                    22 => c.pm10 = Some(count.count),
+                    23 => c.pm11 = Some(count.count),
+                    _ => (),
+                };
             })
-        }
-        Ok(non_normal_vol_count)
+            .or_insert(NonNormalVolCountValue::first(&count));
     }
+    // Convert BTreeMap to Vec of structs (so ordered by key).
+    let mut non_normal_vol_count = vec![];
+    for (key, value) in non_normal_vol_map {
+        non_normal_vol_count.push(NonNormalVolCount {
+            recordnum: key.recordnum,
+            date: key.date,
+            direction: key.direction,
+            lane: key.lane,
+            setflag: None,
+            totalcount: value.totalcount,
+            am12: value.am12,
+            am1: value.am1,
+            am2: value.am2,
+            am3: value.am3,
+            am4: value.am4,
+            am5: value.am5,
+            am6: value.am6,
+            am7: value.am7,
+            am8: value.am8,
+            am9: value.am9,
+            am10: value.am10,
+            am11: value.am11,
+            pm12: value.pm12,
+            pm1: value.pm1,
+            pm2: value.pm2,
+            pm3: value.pm3,
+            pm4: value.pm4,
+            pm5: value.pm5,
+            pm6: value.pm6,
+            pm7: value.pm7,
+            pm8: value.pm8,
+            pm9: value.pm9,
+            pm10: value.pm10,
+            pm11: value.pm11,
+        })
+    }
+    non_normal_vol_count
 }
 
 impl Denormalize for TimeBinnedVehicleClassCount {
@@ -140,7 +143,7 @@ impl Denormalize for FifteenMinuteVehicle {
 /// Counts aggregated by hour.
 ///
 /// The datetime is truncated to the top of the hour - 13:00, 14:00, etc.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HourlyCount {
     pub recordnum: u32,
     pub datetime: NaiveDateTime,
@@ -153,7 +156,7 @@ pub struct HourlyCount {
 ///
 /// Hourly fields are `Option` because traffic counts aren't done from 12am one day to 12am the
 /// the following day - can start and stop at any time.
-#[derive(Debug, Clone, RowValue)]
+#[derive(Debug, Clone, RowValue, Serialize, Deserialize)]
 pub struct NonNormalVolCount {
     pub recordnum: u32,
     #[row_value(rename = "countdate")]
@@ -190,11 +193,22 @@ pub struct NonNormalVolCount {
     pub pm11: Option<u32>,
 }
 
+impl NonNormalVolCount {
+    /// The hourly volumes of the day, starting at midnight.
+    pub fn hourly(&self) -> [Option<u32>; 24] {
+        [
+            self.am12, self.am1, self.am2, self.am3, self.am4, self.am5, self.am6, self.am7,
+            self.am8, self.am9, self.am10, self.am11, self.pm12, self.pm1, self.pm2, self.pm3,
+            self.pm4, self.pm5, self.pm6, self.pm7, self.pm8, self.pm9, self.pm10, self.pm11,
+        ]
+    }
+}
+
 /// Non-normalized average speed counts.
 ///
 /// Hourly fields are `Option` because traffic counts aren't done from 12am one day to 12am the
 /// the following day - can start and stop at any time.
-#[derive(Debug, Clone, RowValue)]
+#[derive(Debug, Clone, RowValue, Serialize, Deserialize)]
 pub struct NonNormalAvgSpeedCount {
     pub recordnum: u32,
     #[row_value(rename = "countdate")]
@@ -229,15 +243,53 @@ pub struct NonNormalAvgSpeedCount {
     pub pm11: Option<f32>,
 }
 
+/// Create non-normalized volume counts (for the TC_VOLCOUNT table) from [`FifteenMinuteBicycle`]s.
+///
+/// Bicycle counts aren't by direction, so the direction(s) are taken from the metadata. If there
+/// are two, the inbound counts are the first direction, in lane 1, and the outbound counts the
+/// second, in lane 2. Otherwise, the total is the one direction, in lane 1.
+pub fn create_non_normal_bicycle_vol_count(
+    metadata: &FieldMetadata,
+    counts: &[FifteenMinuteBicycle],
+) -> Vec<NonNormalVolCount> {
+    let mut hourly_counts: BTreeMap<(NaiveDateTime, u8), HourlyCount> = BTreeMap::new();
+
+    for count in counts {
+        let datetime =
+            NaiveDateTime::new(count.date, bin_time(count.time.time(), TimeInterval::Hour));
+        let volumes = match metadata.directions.direction2 {
+            Some(direction2) => vec![
+                (metadata.directions.direction1, 1, count.indir.unwrap_or(0)),
+                (direction2, 2, count.outdir.unwrap_or(0)),
+            ],
+            None => vec![(metadata.directions.direction1, 1, count.total)],
+        };
+        for (dir, lane, volume) in volumes {
+            hourly_counts
+                .entry((datetime, lane))
+                .and_modify(|c| c.count += u32::from(volume))
+                .or_insert(HourlyCount {
+                    recordnum: count.recordnum,
+                    datetime,
+                    count: volume.into(),
+                    dir,
+                    lane,
+                });
+        }
+    }
+
+    non_normal_vol_count(hourly_counts.into_values().collect())
+}
+
 /// Create non-normalized average speed counts from [`IndividualVehicle`]s.
 pub fn create_non_normal_speedavg_count(
     metadata: FieldMetadata,
     counts: Vec<IndividualVehicle>,
 ) -> Vec<NonNormalAvgSpeedCount> {
-    let mut non_normal_raw_speed_map: HashMap<NonNormalCountKey, NonNormalRawSpeedValue> =
-        HashMap::new();
-    let mut non_normal_avg_speed_map: HashMap<NonNormalCountKey, NonNormalAvgSpeedValue> =
-        HashMap::default();
+    let mut non_normal_raw_speed_map: BTreeMap<NonNormalCountKey, NonNormalRawSpeedValue> =
+        BTreeMap::new();
+    let mut non_normal_avg_speed_map: BTreeMap<NonNormalCountKey, NonNormalAvgSpeedValue> =
+        BTreeMap::default();
 
     if counts.is_empty() {
         return vec![];
@@ -245,13 +297,14 @@ pub fn create_non_normal_speedavg_count(
 
     // Collect all the speeds per fields in key.
     for count in counts {
-        // Get the direction from the lane of count/metadata of filename.
-        let direction = match count.lane {
-            1 => metadata.directions.direction1,
-            2 => metadata.directions.direction2.unwrap(),
-            3 => metadata.directions.direction3.unwrap(),
-            _ => {
-                error!("Unable to determine lane/direction.");
+        // Get the direction and lane from the channel of the count.
+        let Channel { direction, lane } = match metadata.channels.get(&count.lane) {
+            Some(v) => *v,
+            None => {
+                error!(
+                    "Unable to determine lane/direction of channel {}.",
+                    count.lane
+                );
                 continue;
             }
         };
@@ -260,7 +313,7 @@ pub fn create_non_normal_speedavg_count(
             recordnum: metadata.recordnum,
             date: count.date,
             direction: Some(direction),
-            lane: Some(count.lane),
+            lane: Some(lane),
         };
 
         // Add new entry if necessary, then insert data.
@@ -473,7 +526,7 @@ pub fn create_non_normal_speedavg_count(
         }
     }
 
-    // Convert HashMap to Vec of structs.
+    // Convert BTreeMap to Vec of structs (so ordered by key).
     let mut non_normal_speed_avg_count = vec![];
     for (key, value) in non_normal_avg_speed_map {
         non_normal_speed_avg_count.push(NonNormalAvgSpeedCount {
@@ -511,6 +564,8 @@ pub fn create_non_normal_speedavg_count(
 }
 
 /// Get hourly counts from a database table.
+///
+/// Rows of [both directions combined][crate::combined_directions] are left out.
 pub fn hourly_counts<'a>(
     recordnum: u32,
     table: &'a str,
@@ -522,10 +577,10 @@ pub fn hourly_counts<'a>(
         &format!(
             "select TRUNC(counttime, 'HH24'), countdate, sum({}), {}, countlane 
                 from {} 
-                where recordnum = :1 
+                where recordnum = :1 and {} is not null
                 group by (countdate, trunc(counttime, 'HH24')), {}, countlane 
                 order by countdate",
-            &vol_field, &dir_field, &table, &dir_field
+            &vol_field, &dir_field, &table, &dir_field, &dir_field
         ),
         &[&recordnum],
     ) {
@@ -547,7 +602,7 @@ pub fn hourly_counts<'a>(
             recordnum,
             datetime,
             count,
-            dir: LaneDirection::from_str(&dir).unwrap(),
+            dir: dir.parse()?,
             lane: lane as u8,
         });
     }
@@ -559,6 +614,29 @@ pub fn hourly_counts<'a>(
 mod tests {
     use super::*;
     use crate::db::{create_pool, get_creds};
+    use crate::extract_from_file::Extract;
+
+    #[test]
+    fn create_non_normal_bicycle_vol_count_correct_num_records_and_total_count() {
+        let path = Path::new("test_files/15minutebicycle/167607-ns-4175-na.csv");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counts = FifteenMinuteBicycle::extract(path).unwrap();
+        let mut non_normal_count = create_non_normal_bicycle_vol_count(&metadata, &counts);
+
+        // five days, two directions
+        assert_eq!(non_normal_count.len(), 10);
+
+        let total: u32 = counts.iter().map(|c| u32::from(c.total)).sum();
+        let non_normal_total: u32 = non_normal_count
+            .iter()
+            .map(|c| c.totalcount.unwrap_or(0))
+            .sum();
+        assert_eq!(non_normal_total, total);
+
+        non_normal_count.sort_unstable_by_key(|count| (count.date, count.lane));
+        assert_eq!(non_normal_count[0].direction, Some(LaneDirection::North));
+        assert_eq!(non_normal_count[1].direction, Some(LaneDirection::South));
+    }
 
     #[ignore]
     #[test]
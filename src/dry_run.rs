@@ -0,0 +1,255 @@
+//! Validate and process count files without writing anything to the database.
+//!
+//! A dry run does everything the [import](../import/index.html) program does to a file up to the
+//! point of inserting data - verifying its location, header, and metadata, extracting its data,
+//! and creating counts from it - and then runs the [data checks][crate::check_data] on those
+//! counts. The result is a [`DryRunSummary`] of what would be inserted.
+//!
+//! Denormalized volume counts (TC_VOLCOUNT) of motor vehicles are created from data already in
+//! the database, and so are not included (those of bicycles are).
+//!
+//! The data checks use the [thresholds][crate::check_data::CheckConfig] configured for all counts
+//! and for the season of the count, but not those for its type, which is only in the database.
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    check_data::{
+        check_bicycle_counts, check_fifteen_minute_vehicle_counts, check_speed_range_counts,
+        check_vehicle_class_counts, CheckConfig,
+    },
+    create_binned_bicycle_vol_count, create_speed_and_class_count,
+    db::crud::Crud,
+    denormalize::{
+        create_non_normal_bicycle_vol_count, create_non_normal_speedavg_count,
+        NonNormalAvgSpeedCount, NonNormalVolCount,
+    },
+    extract_from_file::{num_nondata_rows, Extract, InputCount},
+    CountError, Dedup, FieldMetadata, FifteenMinuteBicycle, FifteenMinutePedestrian,
+    FifteenMinuteVehicle, IndividualBicycle, IndividualVehicle, TimeBinnedSpeedRangeCount,
+    TimeBinnedVehicleClassCount, TimeInterval,
+};
+
+/// What importing a file would do.
+#[derive(Debug, Clone)]
+pub struct DryRunSummary {
+    pub path: PathBuf,
+    pub count_type: InputCount,
+    pub metadata: FieldMetadata,
+    /// The number of rows before the data (including the header), which are skipped.
+    pub nondata_rows: usize,
+    /// The number of rows that could not be parsed.
+    pub skipped: usize,
+    /// The number of duplicate records that would be removed.
+    pub duplicates: usize,
+    /// The number of records that would be inserted, per table.
+    pub records: Vec<(&'static str, usize)>,
+    /// Issues found by the data checks.
+    pub warnings: Vec<String>,
+}
+
+impl Display for DryRunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}: {:?} count, recordnum {}",
+            self.path.display(),
+            self.count_type,
+            self.metadata.recordnum
+        )?;
+        writeln!(f, "  data starts after row {}", self.nondata_rows)?;
+        if self.skipped > 0 {
+            writeln!(
+                f,
+                "  {} rows could not be parsed and would be skipped",
+                self.skipped
+            )?;
+        }
+        if self.duplicates > 0 {
+            writeln!(
+                f,
+                "  {} duplicate records would be removed",
+                self.duplicates
+            )?;
+        }
+        for (table, num) in &self.records {
+            writeln!(f, "  {num} records would be inserted into {table}")?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "  warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Validate and process a file as if importing it, without using the database.
+pub fn dry_run(path: &Path) -> Result<DryRunSummary, CountError> {
+    let count_type = InputCount::from_parent_dir(path)?;
+    count_type.check_header(path)?;
+    let metadata = FieldMetadata::from_path(path)?;
+    let config = CheckConfig::from_env()?;
+
+    let mut duplicates = 0;
+    let (skipped, records, warnings) = match count_type {
+        InputCount::IndividualVehicle => {
+            let (mut individual_vehicles, skipped) = IndividualVehicle::extract_with_skipped(path)?;
+            duplicates = IndividualVehicle::remove_duplicates(&mut individual_vehicles);
+            let (speed_range_count, vehicle_class_count) = create_speed_and_class_count(
+                TimeInterval::FifteenMin,
+                metadata.clone(),
+                individual_vehicles.clone(),
+            );
+            let non_normal_speedavg_count =
+                create_non_normal_speedavg_count(metadata.clone(), individual_vehicles);
+            let thresholds =
+                config.thresholds(None, vehicle_class_count.iter().map(|c| c.date).min());
+            (
+                skipped,
+                vec![
+                    (
+                        <TimeBinnedVehicleClassCount as Crud>::COUNT_TABLE,
+                        vehicle_class_count.len(),
+                    ),
+                    (
+                        <TimeBinnedSpeedRangeCount as Crud>::COUNT_TABLE,
+                        speed_range_count.len(),
+                    ),
+                    (
+                        <NonNormalAvgSpeedCount as Crud>::COUNT_TABLE,
+                        non_normal_speedavg_count.len(),
+                    ),
+                ],
+                [
+                    check_vehicle_class_counts(&vehicle_class_count, &thresholds),
+                    check_speed_range_counts(&speed_range_count, metadata.speed_limit, &thresholds),
+                ]
+                .concat(),
+            )
+        }
+        InputCount::IndividualBicycle => {
+            let (individual_bicycles, skipped) = IndividualBicycle::extract_with_skipped(path)?;
+            let bidirectional = metadata.directions.direction2.is_some();
+            let fifteen_min_volcount = create_binned_bicycle_vol_count(
+                TimeInterval::FifteenMin,
+                metadata.clone(),
+                individual_bicycles,
+            );
+            let non_normal_volcount =
+                create_non_normal_bicycle_vol_count(&metadata, &fifteen_min_volcount);
+            (
+                skipped,
+                vec![
+                    (
+                        <FifteenMinuteBicycle as Crud>::COUNT_TABLE,
+                        fifteen_min_volcount.len(),
+                    ),
+                    (
+                        <NonNormalVolCount as Crud>::COUNT_TABLE,
+                        non_normal_volcount.len(),
+                    ),
+                ],
+                check_bicycle_counts(
+                    &fifteen_min_volcount,
+                    bidirectional,
+                    &config.thresholds(None, fifteen_min_volcount.iter().map(|c| c.date).min()),
+                ),
+            )
+        }
+        InputCount::FifteenMinuteVehicle => {
+            let (fifteen_min_volcount, skipped) = FifteenMinuteVehicle::extract_with_skipped(path)?;
+            (
+                skipped,
+                vec![(
+                    <FifteenMinuteVehicle as Crud>::COUNT_TABLE,
+                    fifteen_min_volcount.len(),
+                )],
+                check_fifteen_minute_vehicle_counts(
+                    &fifteen_min_volcount,
+                    &config.thresholds(None, fifteen_min_volcount.iter().map(|c| c.date).min()),
+                ),
+            )
+        }
+        InputCount::FifteenMinuteBicycle => {
+            let (fifteen_min_volcount, skipped) = FifteenMinuteBicycle::extract_with_skipped(path)?;
+            let bidirectional = metadata.directions.direction2.is_some();
+            let non_normal_volcount =
+                create_non_normal_bicycle_vol_count(&metadata, &fifteen_min_volcount);
+            (
+                skipped,
+                vec![
+                    (
+                        <FifteenMinuteBicycle as Crud>::COUNT_TABLE,
+                        fifteen_min_volcount.len(),
+                    ),
+                    (
+                        <NonNormalVolCount as Crud>::COUNT_TABLE,
+                        non_normal_volcount.len(),
+                    ),
+                ],
+                check_bicycle_counts(
+                    &fifteen_min_volcount,
+                    bidirectional,
+                    &config.thresholds(None, fifteen_min_volcount.iter().map(|c| c.date).min()),
+                ),
+            )
+        }
+        InputCount::FifteenMinutePedestrian => {
+            let (fifteen_min_volcount, skipped) =
+                FifteenMinutePedestrian::extract_with_skipped(path)?;
+            (
+                skipped,
+                vec![(
+                    <FifteenMinutePedestrian as Crud>::COUNT_TABLE,
+                    fifteen_min_volcount.len(),
+                )],
+                vec![],
+            )
+        }
+        InputCount::HourlyClass => {
+            // (The data checks of class counts assume 15-minute bins, so aren't run on these.)
+            let (vehicle_class_count, skipped) =
+                TimeBinnedVehicleClassCount::extract_with_skipped(path)?;
+            (
+                skipped,
+                vec![(
+                    <TimeBinnedVehicleClassCount as Crud>::COUNT_TABLE,
+                    vehicle_class_count.len(),
+                )],
+                vec![],
+            )
+        }
+    };
+
+    Ok(DryRunSummary {
+        path: path.to_owned(),
+        count_type,
+        metadata,
+        nondata_rows: num_nondata_rows(path)?,
+        skipped,
+        duplicates,
+        records,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_summarizes_individual_vehicles() {
+        let path = Path::new("test_files/vehicle/101-eee-21-35.csv");
+        let summary = dry_run(path).unwrap();
+        assert_eq!(summary.count_type, InputCount::IndividualVehicle);
+        assert_eq!(summary.skipped, 0);
+        assert_eq!(summary.nondata_rows, 4);
+        assert_eq!(summary.records[0], ("tc_clacount", 15));
+        assert_eq!(summary.records[1], ("tc_specount", 15));
+    }
+
+    #[test]
+    fn dry_run_errs_on_header_mismatch() {
+        let path = Path::new("test_files/vehicle/15min_veh_count.txt");
+        assert!(dry_run(path).is_err());
+    }
+}
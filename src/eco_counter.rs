@@ -0,0 +1,359 @@
+//! Pull 15-minute bicycle and pedestrian counts from the Eco-Counter (Eco-Visio) API.
+//!
+//! Rather than waiting for someone to export a count from Eco-Visio and upload it, the counts of
+//! the sites in a [configuration file][EcoCounterConfig] can be pulled directly from the API and
+//! written to the data directory as files laid out like Eco-Counter's own exports, named
+//! according to the filename specification. They are then imported like any other file, and so
+//! go through exactly the same checks, binning, and inserts.
+//!
+//! The API token is read from the `ECO_COUNTER_TOKEN` env var, and the configuration file from
+//! the one set by the `ECO_COUNTER_CONFIG` env var. The URL of the API can be overridden with the
+//! `ECO_COUNTER_URL` env var. This module is only built with the `eco-counter` feature.
+//!
+//! Sites are configured like:
+//! ```toml
+//! [[sites]]
+//! recordnum = 167607
+//! kind = "bicycle"
+//! site_id = 100012345
+//! # The site's channels for the in and out directions, if it has two directions.
+//! channels = [101012345, 102012345]
+//! directions = "ns"
+//! counter_id = "4175"
+//! start = 2023-09-22
+//! end = 2023-09-26
+//! ```
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use log::warn;
+use serde::Deserialize;
+
+use crate::{toml_date, CountError, Directions};
+
+/// The URL of the API, unless overridden with the `ECO_COUNTER_URL` env var.
+pub const DEFAULT_URL: &str = "https://apieco.eco-counter-tools.com/api/1.0";
+
+/// How long to wait for a response from the API.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The sites to pull counts of, from a TOML file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EcoCounterConfig {
+    #[serde(default)]
+    pub sites: Vec<Site>,
+}
+
+impl EcoCounterConfig {
+    /// Get the configuration from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        for site in &config.sites {
+            site.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// Get the configuration from the file set by the `ECO_COUNTER_CONFIG` env var.
+    pub fn from_env() -> Result<Self, CountError> {
+        let path = env::var("ECO_COUNTER_CONFIG").map_err(|e| {
+            CountError::EcoCounterError(format!("unable to load ECO_COUNTER_CONFIG: {e}"))
+        })?;
+        Self::from_file(Path::new(&path))
+    }
+}
+
+/// Whether a site counts bicycles or pedestrians.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SiteKind {
+    Bicycle,
+    Pedestrian,
+}
+
+impl SiteKind {
+    /// The subdirectory of the data directory that files of this kind of count go in.
+    pub fn dir(&self) -> &'static str {
+        match self {
+            SiteKind::Bicycle => "15minutebicycle",
+            SiteKind::Pedestrian => "15minutepedestrian",
+        }
+    }
+}
+
+/// An Eco-Counter site, and the count to pull from it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Site {
+    pub recordnum: u32,
+    pub kind: SiteKind,
+    /// The id of the site in Eco-Visio.
+    pub site_id: u64,
+    /// The ids of the site's channels for the in and out directions, if it has two directions.
+    #[serde(default)]
+    pub channels: Option<(u64, u64)>,
+    /// The directions of the count, as in the filename specification (e.g. "ns").
+    pub directions: String,
+    pub counter_id: String,
+    /// The first day of the count.
+    #[serde(deserialize_with = "toml_date::deserialize")]
+    pub start: NaiveDate,
+    /// The last day of the count.
+    #[serde(deserialize_with = "toml_date::deserialize")]
+    pub end: NaiveDate,
+}
+
+impl Site {
+    /// Check that the site's fields are consistent with each other.
+    fn validate(&self) -> Result<(), CountError> {
+        let directions: Directions = self.directions.parse()?;
+        if directions.direction2.is_some() != self.channels.is_some() {
+            return Err(CountError::EcoCounterError(format!(
+                "{}: channels must be given for two directions, and only for two directions",
+                self.recordnum
+            )));
+        }
+        if self.end < self.start {
+            return Err(CountError::EcoCounterError(format!(
+                "{}: end is before start",
+                self.recordnum
+            )));
+        }
+        Ok(())
+    }
+
+    /// The name of the file for the site's count, per the filename specification.
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}-{}-{}-na.csv",
+            self.recordnum, self.directions, self.counter_id
+        )
+    }
+}
+
+/// A 15-minute period of a count pulled from the API.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EcoCounterRow {
+    pub time: NaiveDateTime,
+    pub total: u16,
+    pub indir: Option<u16>,
+    pub outdir: Option<u16>,
+}
+
+/// A period of a site or channel, as returned by the API.
+#[derive(Debug, Deserialize)]
+struct ApiCount {
+    /// The start of the period, in the site's time zone, e.g. "2023-09-22T00:00:00+0000".
+    date: String,
+    /// The count, if there is one for the period.
+    counts: Option<u16>,
+}
+
+/// A client of the Eco-Counter API.
+pub struct Client {
+    agent: ureq::Agent,
+    url: String,
+    token: String,
+}
+
+impl Client {
+    pub fn new(url: String, token: String) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            url,
+            token,
+        }
+    }
+
+    /// Create a client with the token (and, optionally, URL) in the environment.
+    pub fn from_env() -> Result<Self, CountError> {
+        let token = env::var("ECO_COUNTER_TOKEN").map_err(|e| {
+            CountError::EcoCounterError(format!("unable to load ECO_COUNTER_TOKEN: {e}"))
+        })?;
+        let url = env::var("ECO_COUNTER_URL").unwrap_or_else(|_| DEFAULT_URL.to_string());
+        Ok(Self::new(url, token))
+    }
+
+    /// Get the 15-minute counts of a site or channel from the first day to the last.
+    fn counts(
+        &self,
+        id: u64,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<(NaiveDateTime, Option<u16>)>, CountError> {
+        let begin = start.and_time(NaiveTime::MIN);
+        let end = end.and_time(NaiveTime::MIN) + TimeDelta::days(1);
+        let counts: Vec<ApiCount> = self
+            .agent
+            .get(&format!("{}/data/site/{id}", self.url))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .query("begin", &begin.format("%Y-%m-%dT%H:%M:%S").to_string())
+            .query("end", &end.format("%Y-%m-%dT%H:%M:%S").to_string())
+            .query("step", "15m")
+            .call()
+            .map_err(|e| CountError::EcoCounterError(format!("{id}: {e}")))?
+            .into_json()?;
+
+        counts
+            .into_iter()
+            .map(|v| {
+                let time = DateTime::parse_from_str(&v.date, "%Y-%m-%dT%H:%M:%S%z")
+                    .map_err(|_| {
+                        CountError::EcoCounterError(format!("{id}: invalid date '{}'", v.date))
+                    })?
+                    .naive_local();
+                Ok((time, v.counts))
+            })
+            .collect()
+    }
+
+    /// Pull a site's count from the API.
+    ///
+    /// Periods without a count (e.g. because the counter was down) are skipped.
+    pub fn pull(&self, site: &Site) -> Result<Vec<EcoCounterRow>, CountError> {
+        let mut periods: BTreeMap<NaiveDateTime, [Option<u16>; 3]> = BTreeMap::new();
+        for (time, total) in self.counts(site.site_id, site.start, site.end)? {
+            periods.entry(time).or_default()[0] = total;
+        }
+        if let Some((indir, outdir)) = site.channels {
+            for (i, channel) in [indir, outdir].into_iter().enumerate() {
+                for (time, count) in self.counts(channel, site.start, site.end)? {
+                    periods.entry(time).or_default()[i + 1] = count;
+                }
+            }
+        }
+
+        let mut rows = vec![];
+        for (time, [total, indir, outdir]) in periods {
+            match (total, site.channels) {
+                (Some(total), None) => rows.push(EcoCounterRow {
+                    time,
+                    total,
+                    indir: None,
+                    outdir: None,
+                }),
+                (Some(total), Some(_)) if indir.is_some() && outdir.is_some() => {
+                    rows.push(EcoCounterRow {
+                        time,
+                        total,
+                        indir,
+                        outdir,
+                    })
+                }
+                _ => warn!("{}: no count for {time}; skipped", site.recordnum),
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// Lay out a site's count like an Eco-Counter export.
+pub fn to_csv(site: &Site, rows: &[EcoCounterRow]) -> String {
+    let id = &site.counter_id;
+    let mut csv = format!(
+        "Period,{} - {},\n,\n",
+        site.start.format("%B %d, %Y"),
+        site.end.format("%B %d, %Y")
+    );
+    match site.channels {
+        Some(_) => csv.push_str(&format!("Time,{id},{id} IN,{id} OUT,\n")),
+        None => csv.push_str(&format!("Time,{id},\n")),
+    }
+    for row in rows {
+        let _ = write!(
+            csv,
+            "{},{},",
+            row.time.format("%Y-%m-%d %H:%M:%S"),
+            row.total
+        );
+        if let (Some(indir), Some(outdir)) = (row.indir, row.outdir) {
+            let _ = write!(csv, "{indir},{outdir},");
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Write a site's count to the appropriate subdirectory of the data directory, to be imported,
+/// returning its path.
+pub fn write_file(
+    site: &Site,
+    rows: &[EcoCounterRow],
+    data_dir: &Path,
+) -> Result<PathBuf, CountError> {
+    let dir = data_dir.join(site.kind.dir());
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(site.file_name());
+    fs::write(&path, to_csv(site, rows))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        extract_from_file::{check_times, Extract},
+        FifteenMinuteBicycle,
+    };
+
+    fn site() -> Site {
+        toml::from_str::<EcoCounterConfig>(
+            r#"
+            [[sites]]
+            recordnum = 167607
+            kind = "bicycle"
+            site_id = 100012345
+            channels = [101012345, 102012345]
+            directions = "ns"
+            counter_id = "4175"
+            start = 2023-09-22
+            end = 2023-09-22
+            "#,
+        )
+        .unwrap()
+        .sites
+        .remove(0)
+    }
+
+    #[test]
+    fn config_validated() {
+        site().validate().unwrap();
+        let mut one_direction = site();
+        one_direction.directions = "n".to_string();
+        assert!(matches!(
+            one_direction.validate(),
+            Err(CountError::EcoCounterError(_))
+        ));
+    }
+
+    #[test]
+    fn written_file_can_be_extracted() {
+        let site = site();
+        let rows = (0..96)
+            .map(|i| EcoCounterRow {
+                time: site.start.and_time(NaiveTime::MIN) + TimeDelta::minutes(15 * i),
+                total: 3,
+                indir: Some(1),
+                outdir: Some(2),
+            })
+            .collect::<Vec<_>>();
+        let data_dir = std::env::temp_dir().join("traffic_counts_eco_counter");
+
+        let path = write_file(&site, &rows, &data_dir).unwrap();
+        assert!(path.ends_with("15minutebicycle/167607-ns-4175-na.csv"));
+        let counts = FifteenMinuteBicycle::extract(&path).unwrap();
+        assert_eq!(counts.len(), 96);
+        assert_eq!(counts[95].total, 3);
+        assert_eq!(counts[95].outdir, Some(2));
+        let now = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        check_times(&path, counts.iter().map(|v| v.time), now, false).unwrap();
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+//! Email notifications about runs of the import program.
+//!
+//! So that a run that goes wrong doesn't go unnoticed until someone looks for missing data, a
+//! [`Notifier`] emails a [summary][ImportSummary] of each run that processed any files - which
+//! were imported, which weren't and why, and the warnings raised - to a list of addresses, and
+//! sends an alert as soon as the program stops because of an error.
+//!
+//! It is configured with env vars: `SMTP_HOST` (notifications are only sent if this is set),
+//! `SMTP_PORT` (by default, 587, with STARTTLS), `SMTP_USERNAME` and `SMTP_PASSWORD`, if the
+//! server requires them, `NOTIFY_FROM` (the address to send from), and `NOTIFY_TO` (a
+//! comma-separated list of addresses to send to). This module is only built with the `email`
+//! feature.
+use std::env;
+use std::fmt::Write as _;
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+
+use crate::{import_summary::ImportSummary, CountError};
+
+/// Sends emails about runs of the import program.
+pub struct Notifier {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl Notifier {
+    /// Create a notifier with the settings in the environment, or `None` if `SMTP_HOST` isn't set.
+    pub fn from_env() -> Result<Option<Self>, CountError> {
+        let Ok(host) = env::var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let var = |name: &str| {
+            env::var(name).map_err(|_| CountError::EmailError(format!("{name} is not set")))
+        };
+        let address = |v: &str| {
+            v.trim()
+                .parse::<Mailbox>()
+                .map_err(|e| CountError::EmailError(format!("invalid address '{v}': {e}")))
+        };
+        let from = address(&var("NOTIFY_FROM")?)?;
+        let to = var("NOTIFY_TO")?
+            .split(',')
+            .map(address)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut transport = SmtpTransport::starttls_relay(&host)
+            .map_err(|e| CountError::EmailError(e.to_string()))?;
+        if let Ok(port) = env::var("SMTP_PORT") {
+            let port = port
+                .parse()
+                .map_err(|_| CountError::EmailError(format!("invalid SMTP_PORT '{port}'")))?;
+            transport = transport.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD"))
+        {
+            transport = transport.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Some(Self {
+            transport: transport.build(),
+            from,
+            to,
+        }))
+    }
+
+    /// Send an email to everyone on the list.
+    pub fn send(&self, subject: &str, body: String) -> Result<(), CountError> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message
+            .body(body)
+            .map_err(|e| CountError::EmailError(e.to_string()))?;
+        self.transport
+            .send(&message)
+            .map_err(|e| CountError::EmailError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Send the summary of a run.
+    pub fn send_summary(&self, summary: &ImportSummary) -> Result<(), CountError> {
+        let (subject, body) = summary_email(summary);
+        self.send(&subject, body)
+    }
+
+    /// Send an alert that the program has stopped because of an error.
+    pub fn send_alert(&self, message: &str) -> Result<(), CountError> {
+        self.send(
+            "Traffic count import stopped",
+            format!("The import program stopped because of an error:\n\n{message}\n"),
+        )
+    }
+}
+
+/// The subject and body of the email summarizing a run.
+///
+/// The subject says how many files were and weren't imported, so that a failure is noticed
+/// without opening it; the body adds the reasons and every warning, by file.
+pub fn summary_email(summary: &ImportSummary) -> (String, String) {
+    let imported = summary.files.iter().filter(|file| file.imported).count();
+    let subject = format!(
+        "Traffic count import: {imported} imported, {} not imported",
+        summary.files.len() - imported
+    );
+
+    let mut body = format!("{summary}\n");
+    for file in summary
+        .files
+        .iter()
+        .filter(|file| !file.warnings.is_empty())
+    {
+        let _ = write!(body, "\nWarnings for {}", file.path.display());
+        if let Some(recordnum) = file.recordnum {
+            let _ = write!(body, " ({recordnum})");
+        }
+        body.push_str(":\n");
+        for warning in &file.warnings {
+            let _ = writeln!(body, "  {warning}");
+        }
+    }
+    (subject, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use log::Level;
+
+    #[test]
+    fn summary_email_has_failures_in_subject_and_warnings_in_body() {
+        let mut summary = ImportSummary::new();
+        summary.start_file(Path::new("vehicle/166905-ew-40972-35.txt"), vec![]);
+        summary.set_recordnum(166905);
+        summary.imported();
+        summary.start_file(
+            Path::new("vehicle/166906-ew-40972-35.txt"),
+            vec![(Level::Warn, "3 rows could not be parsed".to_string())],
+        );
+        summary.finish_file(vec![(
+            Level::Error,
+            "recordnum not found in TC_HEADER table".to_string(),
+        )]);
+
+        let (subject, body) = summary_email(&summary);
+        assert_eq!(subject, "Traffic count import: 1 imported, 1 not imported");
+        assert!(body.contains(
+            "vehicle/166906-ew-40972-35.txt skipped: recordnum not found in TC_HEADER table"
+        ));
+        assert!(body.contains(
+            "Warnings for vehicle/166905-ew-40972-35.txt (166905):\n  3 rows could not be parsed"
+        ));
+    }
+}
@@ -0,0 +1,399 @@
+//! Export processed count data to files.
+//!
+//! This is for those who want the processed data but don't have access to our database. Records
+//! are written to one file per recordnum and kind of count, named `{recordnum}-{name}.{ext}`,
+//! in [CSV or JSON format][ExportFormat], or (with the `parquet` feature) as
+//! [Apache Arrow or Parquet][crate::columnar] files.
+//!
+//! [`export_vehicle_counts`] exports all the counts created from raw vehicle records - 15-minute
+//! and hourly class and speed counts, 15-minute [headways][crate::headway], and hourly
+//! [speed compliance][crate::speed_compliance] (if the speed limit is known). The class counts
+//! can be exported under another [classification scheme][ClassScheme], in which case each of its
+//! classes is a column of the CSV. The [individual vehicles][export_individual_vehicles] they're
+//! created from can also be exported.
+//!
+//! The [location][crate::location::Location] of a count, if known, is exported with them, to
+//! `{recordnum}-location.{ext}` (a single record), so the counts can be put on a map.
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    class_scheme::{ClassScheme, ClassSchemeCount},
+    create_speed_and_class_count,
+    headway::create_headway_counts,
+    location::Location,
+    speed_compliance::create_speed_compliance,
+    CountError, FieldMetadata, IndividualVehicle, TimeInterval,
+};
+
+/// The file formats data can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    /// Apache Arrow IPC files.
+    #[cfg(feature = "parquet")]
+    Arrow,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl ExportFormat {
+    /// The file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Arrow => "arrow",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            #[cfg(feature = "parquet")]
+            "arrow" => Ok(ExportFormat::Arrow),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(CountError::UnknownExportFormat(s.to_string())),
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Write `records` to a file in `dir`, returning the path of the file.
+///
+/// `name` identifies the kind of count, e.g. "15min-class". Any existing file with the same path
+/// is overwritten.
+pub fn export<T: Serialize + DeserializeOwned>(
+    records: &[T],
+    dir: &Path,
+    recordnum: u32,
+    name: &str,
+    format: ExportFormat,
+) -> Result<PathBuf, CountError> {
+    let path = dir.join(format!("{recordnum}-{name}.{}", format.extension()));
+
+    match format {
+        ExportFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(BufWriter::new(File::create(&path)?));
+            for record in records {
+                wtr.serialize(record)?;
+            }
+            wtr.flush()?;
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), records)?
+        }
+        #[cfg(feature = "parquet")]
+        ExportFormat::Arrow => crate::columnar::write_arrow(records, &path)?,
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => crate::columnar::write_parquet(records, &path)?,
+    }
+
+    Ok(path)
+}
+
+/// Write class counts aggregated under a [`ClassScheme`] to a file in `dir`, returning the path
+/// of the file.
+///
+/// As with [`export`], but in CSV each of the scheme's classes is its own column.
+pub fn export_class_scheme_counts(
+    counts: &[ClassSchemeCount],
+    scheme: &ClassScheme,
+    dir: &Path,
+    recordnum: u32,
+    name: &str,
+    format: ExportFormat,
+) -> Result<PathBuf, CountError> {
+    if format != ExportFormat::Csv {
+        return export(counts, dir, recordnum, name, format);
+    }
+
+    let path = dir.join(format!("{recordnum}-{name}.{}", format.extension()));
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(File::create(&path)?));
+    let mut header = vec!["date", "time", "lane", "recordnum", "direction"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    header.extend(scheme.classes().into_iter().map(|v| v.name));
+    header.push("total".to_string());
+    wtr.write_record(&header)?;
+    for count in counts {
+        let mut record = vec![
+            count.date.to_string(),
+            count.time.to_string(),
+            count.lane.map(|v| v.to_string()).unwrap_or_default(),
+            count.recordnum.to_string(),
+            count.direction.map(|v| v.to_string()).unwrap_or_default(),
+        ];
+        record.extend(count.classes.iter().map(|v| v.to_string()));
+        record.push(count.total.to_string());
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+
+    Ok(path)
+}
+
+/// Export [`IndividualVehicle`]s to a file in `dir`, named `{recordnum}-vehicles.{ext}`,
+/// returning the path of the file.
+pub fn export_individual_vehicles(
+    metadata: &FieldMetadata,
+    individual_vehicles: &[IndividualVehicle],
+    dir: &Path,
+    format: ExportFormat,
+) -> Result<PathBuf, CountError> {
+    export(
+        individual_vehicles,
+        dir,
+        metadata.recordnum,
+        "vehicles",
+        format,
+    )
+}
+
+/// Export the [`Location`] of a count to a file in `dir`, named `{recordnum}-location.{ext}`,
+/// returning the path of the file.
+pub fn export_location(
+    location: &Location,
+    dir: &Path,
+    format: ExportFormat,
+) -> Result<PathBuf, CountError> {
+    export(
+        std::slice::from_ref(location),
+        dir,
+        location.recordnum,
+        "location",
+        format,
+    )
+}
+
+/// Create 15-minute and hourly class and speed counts, 15-minute headways, and speed compliance
+/// from [`IndividualVehicle`]s and export them to files in `dir`, returning the paths of the files.
+///
+/// Speed compliance is only exported if the speed limit is known, and the location of the count
+/// only if `location` is given.
+///
+/// Class counts are exported under `scheme`, rather than by FHWA class, unless it is
+/// [`ClassScheme::Fhwa`].
+pub fn export_vehicle_counts(
+    metadata: &FieldMetadata,
+    individual_vehicles: &[IndividualVehicle],
+    dir: &Path,
+    format: ExportFormat,
+    scheme: &ClassScheme,
+    location: Option<&Location>,
+) -> Result<Vec<PathBuf>, CountError> {
+    let mut paths = vec![];
+    for (interval, name) in [
+        (TimeInterval::FifteenMin, "15min"),
+        (TimeInterval::Hour, "hourly"),
+    ] {
+        let (speed_range_count, vehicle_class_count) =
+            create_speed_and_class_count(interval, metadata.clone(), individual_vehicles.to_vec());
+        if *scheme == ClassScheme::Fhwa {
+            paths.push(export(
+                &vehicle_class_count,
+                dir,
+                metadata.recordnum,
+                &format!("{name}-class"),
+                format,
+            )?);
+        } else {
+            let counts = vehicle_class_count
+                .iter()
+                .map(|v| ClassSchemeCount::from_count(v, scheme))
+                .collect::<Vec<_>>();
+            paths.push(export_class_scheme_counts(
+                &counts,
+                scheme,
+                dir,
+                metadata.recordnum,
+                &format!("{name}-class"),
+                format,
+            )?);
+        }
+        paths.push(export(
+            &speed_range_count,
+            dir,
+            metadata.recordnum,
+            &format!("{name}-speed"),
+            format,
+        )?);
+    }
+    paths.push(export(
+        &create_headway_counts(TimeInterval::FifteenMin, metadata, individual_vehicles),
+        dir,
+        metadata.recordnum,
+        "15min-headway",
+        format,
+    )?);
+    if let Some(report) = create_speed_compliance(metadata, individual_vehicles) {
+        paths.push(export(
+            &report.rows(),
+            dir,
+            metadata.recordnum,
+            "speed-compliance",
+            format,
+        )?);
+    }
+    if let Some(location) = location {
+        paths.push(export_location(location, dir, format)?);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_from_file::Extract, *};
+
+    #[test]
+    fn export_format_from_str_is_case_insensitive() {
+        assert_eq!(ExportFormat::from_str("CSV").unwrap(), ExportFormat::Csv);
+        assert_eq!(ExportFormat::from_str("json").unwrap(), ExportFormat::Json);
+        assert!(matches!(
+            ExportFormat::from_str("xml"),
+            Err(CountError::UnknownExportFormat(_))
+        ));
+    }
+
+    #[test]
+    fn export_writes_csv_and_json() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let (_, class_counts) =
+            create_speed_and_class_count(TimeInterval::Hour, metadata, counted_vehicles);
+        let dir = std::env::temp_dir();
+
+        let csv_path = export(
+            &class_counts,
+            &dir,
+            166905,
+            "hourly-class",
+            ExportFormat::Csv,
+        )
+        .unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert_eq!(csv.lines().count(), class_counts.len() + 1);
+        assert!(csv.starts_with("date,time,lane,recordnum,direction,c1"));
+
+        let json_path = export(
+            &class_counts,
+            &dir,
+            166905,
+            "hourly-class",
+            ExportFormat::Json,
+        )
+        .unwrap();
+        let json = std::fs::read_to_string(&json_path).unwrap();
+        let exported: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(exported.len(), class_counts.len());
+
+        std::fs::remove_file(csv_path).unwrap();
+        std::fs::remove_file(json_path).unwrap();
+    }
+
+    #[test]
+    fn export_vehicle_counts_writes_six_files() {
+        let path = Path::new("test_files/vehicle/101-eee-21-35.csv");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let dir = std::env::temp_dir();
+
+        let paths = export_vehicle_counts(
+            &metadata,
+            &counted_vehicles,
+            &dir,
+            ExportFormat::Json,
+            &ClassScheme::Fhwa,
+            None,
+        )
+        .unwrap();
+        assert_eq!(paths.len(), 6);
+        assert!(paths[0].ends_with("101-15min-class.json"));
+        assert!(paths[3].ends_with("101-hourly-speed.json"));
+        assert!(paths[4].ends_with("101-15min-headway.json"));
+        assert!(paths[5].ends_with("101-speed-compliance.json"));
+
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn location_exported_with_counts() {
+        let path = Path::new("test_files/vehicle/101-eee-21-35.csv");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let dir = std::env::temp_dir().join("traffic_counts_location_export");
+        std::fs::create_dir_all(&dir).unwrap();
+        let location = Location {
+            recordnum: 101,
+            road: Some("Market St".to_string()),
+            ..Default::default()
+        };
+
+        let paths = export_vehicle_counts(
+            &metadata,
+            &counted_vehicles,
+            &dir,
+            ExportFormat::Csv,
+            &ClassScheme::Fhwa,
+            Some(&location),
+        )
+        .unwrap();
+        assert_eq!(paths.len(), 7);
+        assert!(paths[6].ends_with("101-location.csv"));
+        let csv = std::fs::read_to_string(&paths[6]).unwrap();
+        assert_eq!(csv.lines().nth(1).unwrap(), "101,Market St,,,,,,");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn class_counts_exported_under_scheme_with_column_per_class() {
+        let path = Path::new("test_files/vehicle/101-eee-21-35.csv");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let dir = std::env::temp_dir().join("traffic_counts_class_scheme_export");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let paths = export_vehicle_counts(
+            &metadata,
+            &counted_vehicles,
+            &dir,
+            ExportFormat::Csv,
+            &ClassScheme::SixBin,
+            None,
+        )
+        .unwrap();
+        let csv = std::fs::read_to_string(&paths[0]).unwrap();
+        assert!(csv.starts_with(
+            "date,time,lane,recordnum,direction,bikes,cars,light_trucks,buses,single_unit,\
+             combination,total"
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
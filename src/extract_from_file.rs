@@ -1,32 +1,77 @@
 //! Extract count data from files.
 //!
 //! See the [Extract trait implementors](Extract#implementors) for kinds of counts.
+//!
+//! Dates and times are usually like "3/7/2024" and "1:05:00 PM", but some exports use ISO 8601
+//! dates (2024-03-07), 24-hour times (13:05:00), or ISO 8601 datetimes (2024-03-07T13:05:00), so
+//! each of those is tried in turn.
+//!
+//! Files may be comma-, tab-, or semicolon-delimited, and encoded in UTF-8 (with or without a
+//! byte order mark) or Windows-1252; both are detected from the file itself.
 use std::fs::{self, File};
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use csv::{Reader, ReaderBuilder};
-use log::error;
+use chrono::{DateTime, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use csv::{ByteRecord, Reader, ReaderBuilder, StringRecord};
+use log::{error, warn};
+use sha2::{Digest, Sha256};
 
 use crate::{
-    CountError, FieldMetadata, FifteenMinuteBicycle, FifteenMinutePedestrian, FifteenMinuteVehicle,
-    IndividualBicycle, IndividualVehicle,
+    Channel, CountError, FieldMetadata, FifteenMinuteBicycle, FifteenMinutePedestrian,
+    FifteenMinuteVehicle, IndividualBicycle, IndividualVehicle, TimeBinnedVehicleClassCount,
+    TimeProblem,
 };
 
 // headers stripped of double quotes and spaces
-const FIFTEEN_MINUTE_VEHICLE_HEADER: &str = "Number,Date,Time,Channel1";
 const FIFTEEN_MINUTE_BIKE_OR_PED_HEADER: &str = "Time,";
-const IND_VEH_OR_IND_BIKE: &str = "Veh.No.,Date,Time,Channel,Class,Speed";
+// columns (in any order, and possibly among others) that identify the rest of the headers
+const FIFTEEN_MINUTE_VEHICLE_COLUMNS: [&str; 4] = ["Number", "Date", "Time", "Channel1"];
+const PEEK_CLASS_COLUMNS: [&str; 5] = ["Date", "Time", "Lane", "Class1", "Class13"];
+const METROCOUNT_COLUMNS: [&str; 5] = ["Date", "Time", "Lane", "Speed", "Cl"];
+const IND_VEH_OR_IND_BIKE_COLUMNS: [&str; 6] =
+    ["Veh.No.", "Date", "Time", "Channel", "Class", "Speed"];
+/// The last row of a file that its header may be in, however many rows of notes, metadata, etc.
+/// there are before it.
+pub const MAX_HEADER_ROW: usize = 50;
+/// The delimiters fields in files may be separated by, with the most usual first.
+const DELIMITERS: [u8; 3] = [b',', b'\t', b';'];
+
+/// The number of months before now that a count's records may be from.
+pub const MAX_COUNT_AGE_MONTHS: u32 = 18;
+/// How far (in seconds) a record's time may go back from that of the one before it.
+///
+/// Records of individual vehicles/bicycles in different lanes are interleaved a few seconds out
+/// of order, whereas a reset clock jumps back much further.
+const MAX_TIME_REGRESSION: i64 = 60;
+/// How far (in hours) a count's first or last record may be from the start or end its file
+/// claims; a count on a quiet road may go a while without any vehicles.
+const MAX_SPAN_DIFFERENCE: i64 = 24;
+
+/// The formats of dates in files, in the order they're tried: StarNext/JAMAR's usual one, then ISO
+/// 8601 and similar.
+const DATE_FORMATS: [&str; 3] = ["%-m/%-d/%Y", "%Y-%m-%d", "%Y/%m/%d"];
+/// The formats of times in files, in the order they're tried: 12-hour, then 24-hour (including
+/// the fractions of seconds of MetroCount's).
+const TIME_FORMATS: [&str; 5] = [
+    "%-I:%M:%S %p",
+    "%-I:%M %p",
+    "%H:%M:%S",
+    "%H:%M:%S%.f",
+    "%H:%M",
+];
 
 /// The kinds of counts this module can handle as inputs.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InputCount {
-    /// Pre-binned, 15-minute volume counts from Eco-Counter
-    /// See [`FifteenMinutePedestrian`], the corresponding type.
-    FifteenMinuteBicycle,
-    /// Pre-binned, 15-minute volume counts from Eco-Counter
+    /// Pre-binned, 15-minute bicycle volume counts from Eco-Counter.
     ///
     /// See [`FifteenMinuteBicycle`], the corresponding type.
+    FifteenMinuteBicycle,
+    /// Pre-binned, 15-minute pedestrian volume counts from Eco-Counter.
+    ///
+    /// See [`FifteenMinutePedestrian`], the corresponding type.
     FifteenMinutePedestrian,
     /// Pre-binned, 15-minute volume counts from StarNext/JAMAR.
     ///
@@ -38,8 +83,13 @@ pub enum InputCount {
     IndividualVehicle,
     /// Individual bicycles from StarNext/JAMAR prior to any binning.
     ///
-    /// See ['IndividualBicycle'], the corresponding type.
+    /// See [`IndividualBicycle`], the corresponding type.
     IndividualBicycle,
+    /// Pre-binned, hourly class counts from legacy PEEK ADR counters.
+    ///
+    /// These are in the same form as the counts [created][crate::create_speed_and_class_count]
+    /// from individual vehicles; see [`TimeBinnedVehicleClassCount`], the corresponding type.
+    HourlyClass,
 }
 
 impl InputCount {
@@ -50,7 +100,7 @@ impl InputCount {
             .parent()
             .ok_or(CountError::BadPath(path.to_owned()))?
             .components()
-            .last()
+            .next_back()
             .ok_or(CountError::BadPath(path.to_owned()))?
             .as_os_str()
             .to_str()
@@ -62,112 +112,292 @@ impl InputCount {
             "15minutevehicle" => Ok(InputCount::FifteenMinuteVehicle),
             "vehicle" => Ok(InputCount::IndividualVehicle),
             "bicycle" => Ok(InputCount::IndividualBicycle),
+            "hourlyclass" => Ok(InputCount::HourlyClass),
             _ => Err(CountError::BadLocation(parent.to_string())),
         }
     }
+
+    /// Infer the `InputCount` of a file from its header alone, with that of the directory it's in
+    /// (if any) as a hint.
+    ///
+    /// The hint is used if it is consistent with the header, and otherwise ignored. Some headers
+    /// are shared by more than one kind of count (see [`check_header`][Self::check_header]):
+    /// without a consistent hint, those of StarNext/JAMAR individual vehicles/bicycles are taken
+    /// to be vehicles, but Eco-Counter bicycle and pedestrian counts can't be told apart.
+    pub fn from_contents(path: &Path, hint: Option<InputCount>) -> Result<Self, CountError> {
+        if let Some(hint) = hint {
+            if hint.check_header(path).is_ok() {
+                return Ok(hint);
+            }
+        }
+        match find_header(path)?.kind {
+            Header::FifteenMinuteBikeOrPed => Err(CountError::AmbiguousCountType(path.to_owned())),
+            Header::FifteenMinuteVehicle => Ok(InputCount::FifteenMinuteVehicle),
+            Header::IndVehOrIndBike | Header::MetroCount => Ok(InputCount::IndividualVehicle),
+            Header::PeekClass => Ok(InputCount::HourlyClass),
+        }
+    }
+
+    /// Check that the header of a file is consistent with this `InputCount`.
+    ///
+    /// Headers can't distinguish between every kind of count - bicycle and pedestrian counts
+    /// from Eco-Counter share the same header, as do individual vehicles and bicycles from
+    /// StarNext/JAMAR - so this only verifies that the header is from the same kind of export.
+    /// Individual vehicles may also be from MetroCount.
+    pub fn check_header(&self, path: &Path) -> Result<(), CountError> {
+        let header = find_header(path)?.kind;
+        let matches = match self {
+            InputCount::FifteenMinuteBicycle | InputCount::FifteenMinutePedestrian => {
+                header == Header::FifteenMinuteBikeOrPed
+            }
+            InputCount::FifteenMinuteVehicle => header == Header::FifteenMinuteVehicle,
+            InputCount::IndividualVehicle => {
+                header == Header::IndVehOrIndBike || header == Header::MetroCount
+            }
+            InputCount::IndividualBicycle => header == Header::IndVehOrIndBike,
+            InputCount::HourlyClass => header == Header::PeekClass,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(CountError::LocationHeaderMisMatch(path.to_owned()))
+        }
+    }
+}
+
+/// The kinds of headers in files, which correspond to one or more [`InputCount`]s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Header {
+    FifteenMinuteBikeOrPed,
+    FifteenMinuteVehicle,
+    IndVehOrIndBike,
+    /// Individual vehicles from MetroCount.
+    MetroCount,
+    /// Hourly class counts from PEEK ADR.
+    PeekClass,
+}
+
+/// The header row of a file, and what it tells about the rest of the file.
+#[derive(Debug, Clone, PartialEq)]
+struct HeaderRow {
+    /// The number of rows up to and including the header.
+    num_rows: usize,
+    kind: Header,
+    delimiter: u8,
+    columns: Columns,
+}
+
+/// The names of the columns of a file, from its header, stripped of double quotes and leading
+/// and trailing spaces. Names are matched ignoring case and spaces (so "Veh. No." is "Veh.No.").
+///
+/// Fields are found by the name of their column rather than by position, since StarNext's
+/// export options can add columns (e.g. "Gap") or change their order.
+#[derive(Debug, Clone, PartialEq)]
+struct Columns(Vec<String>);
+
+impl Columns {
+    /// Get the columns from a header row with comma-separated fields.
+    fn new(header: &str) -> Self {
+        Self(header.split(',').map(|v| v.trim().to_string()).collect())
+    }
+
+    /// Get the index of the column with a name (ignoring case and spaces), if there is one.
+    fn index(&self, name: &str) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|v| v.replace(' ', "").eq_ignore_ascii_case(name))
+    }
+
+    /// Get the index of the column with a name (ignoring case and spaces), which the file must
+    /// have.
+    fn require(&self, name: &'static str) -> Result<usize, CountError> {
+        self.index(name).ok_or(CountError::MissingColumn(name))
+    }
+
+    fn contains_all(&self, names: &[&str]) -> bool {
+        names.iter().all(|v| self.index(v).is_some())
+    }
+}
+
+/// The indexes of the columns of a StarNext/JAMAR 15-minute volume count.
+#[derive(Debug, Clone, Copy)]
+struct FifteenMinuteVehicleColumns {
+    date: usize,
+    time: usize,
+    /// The column of each of the (up to four) channels of the counter.
+    channels: [Option<usize>; 4],
+}
+
+impl FifteenMinuteVehicleColumns {
+    fn new(columns: &Columns) -> Result<Self, CountError> {
+        Ok(Self {
+            date: columns.require("Date")?,
+            time: columns.require("Time")?,
+            channels: [1, 2, 3, 4].map(|v| columns.index(&format!("Channel{v}"))),
+        })
+    }
+}
+
+/// The indexes of the columns of an individual vehicle/bicycle count, including those only some
+/// exports have.
+///
+/// MetroCount's vehicle listings have the same fields as StarNext/JAMAR's, under other names:
+/// "Lane" for the channel, "Cl" for the class (which must be in the FHWA scheme), "Axle", and
+/// "Hdwy".
+#[derive(Debug, Clone, Copy)]
+struct IndividualColumns {
+    date: usize,
+    time: usize,
+    channel: usize,
+    class: usize,
+    speed: usize,
+    axles: Option<usize>,
+    gap: Option<usize>,
+    headway: Option<usize>,
+    length: Option<usize>,
+}
+
+impl IndividualColumns {
+    fn new(header: &HeaderRow) -> Result<Self, CountError> {
+        let columns = &header.columns;
+        if header.kind == Header::MetroCount {
+            return Ok(Self {
+                date: columns.require("Date")?,
+                time: columns.require("Time")?,
+                channel: columns.require("Lane")?,
+                class: columns.require("Cl")?,
+                speed: columns.require("Speed")?,
+                axles: columns.index("Axle"),
+                gap: columns.index("Gap"),
+                headway: columns.index("Hdwy"),
+                length: None,
+            });
+        }
+        Ok(Self {
+            date: columns.require("Date")?,
+            time: columns.require("Time")?,
+            channel: columns.require("Channel")?,
+            class: columns.require("Class")?,
+            speed: columns.require("Speed")?,
+            axles: columns.index("Axles"),
+            gap: columns.index("Gap"),
+            headway: columns.index("Headway"),
+            length: columns.index("Length"),
+        })
+    }
+}
+
+/// The indexes of the columns of a PEEK ADR class count.
+///
+/// The "Lane" column is the channel of the counter, and there's a column for the number of
+/// vehicles of each class, "Class 1" through "Class 13", and optionally "Unclassified".
+#[derive(Debug, Clone, Copy)]
+struct PeekClassColumns {
+    date: usize,
+    time: usize,
+    channel: usize,
+    classes: [usize; 13],
+    unclassified: Option<usize>,
+}
+
+impl PeekClassColumns {
+    fn new(columns: &Columns) -> Result<Self, CountError> {
+        let mut classes = [0; 13];
+        for (i, index) in classes.iter_mut().enumerate() {
+            *index = columns
+                .index(&format!("Class{}", i + 1))
+                .ok_or(CountError::MissingColumn("class"))?;
+        }
+        Ok(Self {
+            date: columns.require("Date")?,
+            time: columns.require("Time")?,
+            channel: columns.require("Lane")?,
+            classes,
+            unclassified: columns.index("Unclassified"),
+        })
+    }
+}
+
+/// The indexes of the columns of an Eco-Counter count.
+///
+/// Other than "Time", columns are named after the counter (e.g. "13-4175", "13-4175 IN", and
+/// "13-4175 OUT"), so the total is the first column without a separate "IN" or "OUT" at the end
+/// of its name. (The name of the counter itself may end in "in", e.g. "Cabin".)
+#[derive(Debug, Clone, Copy)]
+struct EcoCounterColumns {
+    time: usize,
+    total: usize,
+    indir: Option<usize>,
+    outdir: Option<usize>,
+}
+
+impl EcoCounterColumns {
+    fn new(columns: &Columns) -> Result<Self, CountError> {
+        let time = columns.require("Time")?;
+        let ends_with =
+            |name: &str, direction: &str| name.to_uppercase().ends_with(&format!(" {direction}"));
+        let position = |f: &dyn Fn(&str) -> bool| {
+            columns
+                .0
+                .iter()
+                .enumerate()
+                .position(|(i, name)| i != time && !name.is_empty() && f(name))
+        };
+        Ok(Self {
+            time,
+            total: position(&|v| !ends_with(v, "IN") && !ends_with(v, "OUT"))
+                .ok_or(CountError::MissingColumn("total"))?,
+            indir: position(&|v| ends_with(v, "IN")),
+            outdir: position(&|v| ends_with(v, "OUT")),
+        })
+    }
 }
 
 /// A trait for extracting count data from a file.
 pub trait Extract {
     type Item;
-    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError>;
+
+    /// Extract records from a file, along with the number of rows that were skipped because
+    /// they could not be parsed.
+    ///
+    /// Each skipped row is logged with its row number, so that one bad row doesn't prevent the
+    /// rest of the file from being extracted.
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError>;
+
+    /// Extract records from a file.
+    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError> {
+        Ok(Self::extract_with_skipped(path)?.0)
+    }
 }
 
 /// Extract FifteenMinuteVehicle records from a file.
 impl Extract for FifteenMinuteVehicle {
     type Item = FifteenMinuteVehicle;
 
-    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError> {
-        let data_file = File::open(path)?;
-        let mut rdr = create_reader(&data_file);
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError> {
         let metadata = FieldMetadata::from_path(path)?;
 
         // Iterate through data rows.
         let mut counts = vec![];
-        for row in rdr.records().skip(num_nondata_rows(path)?) {
-            // Parse date.
-            let date_format = "%-m/%-d/%Y";
-            let date_col = &row.as_ref().unwrap()[1];
-            let count_date = NaiveDate::parse_from_str(date_col, date_format).unwrap();
-
-            // Parse time.
-            let time_format = "%-I:%M %P";
-            let time_col = &row.as_ref().unwrap()[2];
-            let count_time = NaiveTime::parse_from_str(time_col, time_format).unwrap();
-
-            let datetime = NaiveDateTime::new(count_date, count_time);
-
-            // There will always be at least one count per row.
-            // Extract the first (and perhaps only) direction.
-            match row.as_ref().unwrap().get(3) {
-                Some(count) => match count.parse() {
-                    Ok(count) => match FifteenMinuteVehicle::new(
-                        metadata.recordnum,
-                        count_date,
-                        datetime,
-                        count,
-                        Some(metadata.directions.direction1),
-                        Some(1),
-                    ) {
-                        Ok(v) => counts.push(v),
-                        Err(e) => {
-                            error!("{e}");
-                            continue;
-                        }
-                    },
-                    Err(e) => return Err(CountError::ParseError(e)),
-                },
-                None => return Err(CountError::DirectionLenMisMatch(path.to_owned())),
-            }
-
-            // There may also be a second count within the row.
-            if let Some(direction) = metadata.directions.direction2 {
-                match row.as_ref().unwrap().get(4) {
-                    Some(count) => match count.parse() {
-                        Ok(count) => match FifteenMinuteVehicle::new(
-                            metadata.recordnum,
-                            count_date,
-                            datetime,
-                            count,
-                            Some(direction),
-                            Some(2),
-                        ) {
-                            Ok(v) => counts.push(v),
-                            Err(e) => {
-                                error!("{e}");
-                                continue;
-                            }
-                        },
-                        Err(e) => return Err(CountError::ParseError(e)),
-                    },
-                    None => return Err(CountError::DirectionLenMisMatch(path.to_owned())),
-                }
-            }
-            // There may also be a third count within the row.
-            if let Some(direction) = metadata.directions.direction3 {
-                match row.as_ref().unwrap().get(5) {
-                    Some(count) => match count.parse() {
-                        Ok(count) => match FifteenMinuteVehicle::new(
-                            metadata.recordnum,
-                            count_date,
-                            datetime,
-                            count,
-                            Some(direction),
-                            Some(3),
-                        ) {
-                            Ok(v) => counts.push(v),
-                            Err(e) => {
-                                error!("{e}");
-                                continue;
-                            }
-                        },
-                        Err(e) => return Err(CountError::ParseError(e)),
-                    },
-                    None => return Err(CountError::DirectionLenMisMatch(path.to_owned())),
+        let mut skipped = 0;
+        let (header, rows) = data_rows(path)?;
+        let columns = FifteenMinuteVehicleColumns::new(&header.columns)?;
+        for row in rows {
+            let row_num = row_num(&row);
+            let row = row.map_err(CountError::from);
+            match row
+                .and_then(|row| parse_fifteen_minute_vehicle_row(&row, &columns, &metadata, path))
+            {
+                Ok(v) => counts.extend(v),
+                // Not a problem with the row, but with the file as a whole.
+                Err(e @ CountError::DirectionLenMisMatch(_)) => return Err(e),
+                Err(e) => {
+                    log_skipped_row(path, row_num, &e);
+                    skipped += 1;
                 }
             }
         }
-        Ok(counts)
+        Ok((counts, skipped))
     }
 }
 
@@ -175,42 +405,64 @@ impl Extract for FifteenMinuteVehicle {
 impl Extract for IndividualVehicle {
     type Item = IndividualVehicle;
 
-    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError> {
-        let data_file = File::open(path)?;
-        let mut rdr = create_reader(&data_file);
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError> {
+        let mut rows = IndividualVehicleIter::new(path)?;
 
         // Iterate through data rows.
         let mut counts = vec![];
-        for row in rdr.records().skip(num_nondata_rows(path)?) {
-            // Parse date.
-            let date_format = "%-m/%-d/%Y";
-            let date_col = &row.as_ref().unwrap()[1];
-            let count_date = NaiveDate::parse_from_str(date_col, date_format).unwrap();
-
-            // Parse time.
-            let time_format = "%-I:%M:%S %P";
-            let time_col = &row.as_ref().unwrap()[2];
-            let count_time = NaiveTime::parse_from_str(time_col, time_format).unwrap();
-
-            let datetime = NaiveDateTime::new(count_date, count_time);
-
-            let count = match IndividualVehicle::new(
-                count_date,
-                datetime,
-                row.as_ref().unwrap()[3].parse().unwrap(),
-                row.as_ref().unwrap()[4].parse().unwrap(),
-                row.as_ref().unwrap()[5].parse().unwrap(),
-            ) {
-                Ok(v) => v,
+        let mut skipped = 0;
+        while let Some(row) = rows.next() {
+            match row {
+                Ok(v) => counts.push(v),
                 Err(e) => {
-                    error!("{e}");
-                    continue;
+                    log_skipped_row(path, rows.row_num(), &e);
+                    skipped += 1;
                 }
-            };
-
-            counts.push(count);
+            }
         }
-        Ok(counts)
+        Ok((counts, skipped))
+    }
+}
+
+/// An iterator over the [`IndividualVehicle`]s in a file, reading one row at a time.
+///
+/// Unlike [`IndividualVehicle::extract`], this doesn't load the whole file into memory, so a
+/// count can be [binned][crate::create_speed_and_class_count] with constant memory however long
+/// it is. Rows that can't be parsed are returned as errors, and can be skipped (as `extract`
+/// does) by the caller.
+pub struct IndividualVehicleIter {
+    path: PathBuf,
+    records: DataRows,
+    columns: IndividualColumns,
+    row_num: u64,
+}
+
+impl IndividualVehicleIter {
+    pub fn new(path: &Path) -> Result<Self, CountError> {
+        let (header, records) = data_rows(path)?;
+        Ok(Self {
+            path: path.to_owned(),
+            records,
+            columns: IndividualColumns::new(&header)?,
+            row_num: 0,
+        })
+    }
+
+    /// The row number (i.e. line in the file) of the row last returned.
+    pub fn row_num(&self) -> u64 {
+        self.row_num
+    }
+}
+
+impl Iterator for IndividualVehicleIter {
+    type Item = Result<IndividualVehicle, CountError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.records.next()?;
+        self.row_num = row_num(&row);
+        Some(row.map_err(CountError::from).and_then(|row| {
+            parse_individual_vehicle_row(&row, &self.columns, &self.path, self.row_num)
+        }))
     }
 }
 
@@ -218,44 +470,25 @@ impl Extract for IndividualVehicle {
 impl Extract for IndividualBicycle {
     type Item = IndividualBicycle;
 
-    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError> {
-        let data_file = File::open(path)?;
-        let mut rdr = create_reader(&data_file);
-
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError> {
         // Iterate through data rows.
         let mut counts = vec![];
-        for row in rdr.records().skip(num_nondata_rows(path)?) {
-            // Bicycles are given class 14. Skip if not 14.
-            if row.as_ref().unwrap()[4].parse::<u16>().unwrap() != 14 {
-                continue;
-            }
-            // Parse date.
-            let date_format = "%-m/%-d/%Y";
-            let date_col = &row.as_ref().unwrap()[1];
-            let count_date = NaiveDate::parse_from_str(date_col, date_format).unwrap();
-
-            // Parse time.
-            let time_format = "%-I:%M:%S %P";
-            let time_col = &row.as_ref().unwrap()[2];
-            let count_time = NaiveTime::parse_from_str(time_col, time_format).unwrap();
-
-            let datetime = NaiveDateTime::new(count_date, count_time);
-
-            let count = match IndividualBicycle::new(
-                count_date,
-                datetime,
-                row.as_ref().unwrap()[3].parse().unwrap(),
-            ) {
-                Ok(v) => v,
+        let mut skipped = 0;
+        let (header, rows) = data_rows(path)?;
+        let columns = IndividualColumns::new(&header)?;
+        for row in rows {
+            let row_num = row_num(&row);
+            let row = row.map_err(CountError::from);
+            match row.and_then(|row| parse_individual_bicycle_row(&row, &columns)) {
+                Ok(Some(v)) => counts.push(v),
+                Ok(None) => continue,
                 Err(e) => {
-                    error!("{e}");
-                    continue;
+                    log_skipped_row(path, row_num, &e);
+                    skipped += 1;
                 }
-            };
-
-            counts.push(count);
+            }
         }
-        Ok(counts)
+        Ok((counts, skipped))
     }
 }
 
@@ -263,58 +496,37 @@ impl Extract for IndividualBicycle {
 impl Extract for FifteenMinuteBicycle {
     type Item = FifteenMinuteBicycle;
 
-    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError> {
-        let data_file = File::open(path)?;
-        let mut rdr = create_reader(&data_file);
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError> {
         let metadata = FieldMetadata::from_path(path)?;
 
         // Iterate through data rows.
         let mut counts = vec![];
-        for row in rdr.records().skip(num_nondata_rows(path)?) {
-            // Parse datetime.
-            let datetime_format = "%Y-%m-%d %H:%M:%S";
-            let datetime_col = &row.as_ref().unwrap()[0];
-            let count_dt = NaiveDateTime::parse_from_str(datetime_col, datetime_format).unwrap();
-
-            // Determine which fields to collect depending on direction(s) of count.
-            match metadata.directions.direction2 {
-                // If there's only one direction for this count, we only need the total.
-                None => {
-                    match FifteenMinuteBicycle::new(
+        let mut skipped = 0;
+        let (header, rows) = data_rows(path)?;
+        let columns = EcoCounterColumns::new(&header.columns)?;
+        for row in rows {
+            let row_num = row_num(&row);
+            let row = row.map_err(CountError::from);
+            match row
+                .and_then(|row| parse_eco_counter_row(&row, &columns, &metadata))
+                .and_then(|(datetime, total, indir, outdir)| {
+                    FifteenMinuteBicycle::new(
                         metadata.recordnum,
-                        count_dt.date(),
-                        count_dt,
-                        row.as_ref().unwrap()[1].parse().unwrap(),
-                        None,
-                        None,
-                    ) {
-                        Ok(v) => counts.push(v),
-                        Err(e) => {
-                            error!("{e}");
-                            continue;
-                        }
-                    };
-                }
-                // If there are two directions, we need total, indir, and outdir.
-                Some(_) => {
-                    match FifteenMinuteBicycle::new(
-                        metadata.recordnum,
-                        count_dt.date(),
-                        count_dt,
-                        row.as_ref().unwrap()[1].parse().unwrap(),
-                        Some(row.as_ref().unwrap()[2].parse().unwrap()),
-                        Some(row.as_ref().unwrap()[3].parse().unwrap()),
-                    ) {
-                        Ok(v) => counts.push(v),
-                        Err(e) => {
-                            error!("{e}");
-                            continue;
-                        }
-                    };
+                        datetime.date(),
+                        datetime,
+                        total,
+                        indir,
+                        outdir,
+                    )
+                }) {
+                Ok(v) => counts.push(v),
+                Err(e) => {
+                    log_skipped_row(path, row_num, &e);
+                    skipped += 1;
                 }
             }
         }
-        Ok(counts)
+        Ok((counts, skipped))
     }
 }
 
@@ -322,89 +534,638 @@ impl Extract for FifteenMinuteBicycle {
 impl Extract for FifteenMinutePedestrian {
     type Item = FifteenMinutePedestrian;
 
-    fn extract(path: &Path) -> Result<Vec<Self::Item>, CountError> {
-        let data_file = File::open(path)?;
-        let mut rdr = create_reader(&data_file);
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError> {
         let metadata = FieldMetadata::from_path(path)?;
 
         // Iterate through data rows.
         let mut counts = vec![];
-        for row in rdr.records().skip(num_nondata_rows(path)?) {
-            // Parse datetime.
-            let datetime_format = "%Y-%m-%d %H:%M:%S";
-            let datetime_col = &row.as_ref().unwrap()[0];
-            let count_dt = NaiveDateTime::parse_from_str(datetime_col, datetime_format).unwrap();
-
-            // Determine which fields to collect depending on direction(s) of count.
-            match metadata.directions.direction2 {
-                // If there's only one direction for this count, we only need the total.
-                None => {
-                    match FifteenMinutePedestrian::new(
+        let mut skipped = 0;
+        let (header, rows) = data_rows(path)?;
+        let columns = EcoCounterColumns::new(&header.columns)?;
+        for row in rows {
+            let row_num = row_num(&row);
+            let row = row.map_err(CountError::from);
+            match row
+                .and_then(|row| parse_eco_counter_row(&row, &columns, &metadata))
+                .and_then(|(datetime, total, indir, outdir)| {
+                    FifteenMinutePedestrian::new(
                         metadata.recordnum,
-                        count_dt.date(),
-                        count_dt,
-                        row.as_ref().unwrap()[1].parse().unwrap(),
-                        None,
-                        None,
-                    ) {
-                        Ok(v) => counts.push(v),
-                        Err(e) => {
-                            error!("{e}");
-                            continue;
-                        }
-                    };
+                        datetime.date(),
+                        datetime,
+                        total,
+                        indir,
+                        outdir,
+                    )
+                }) {
+                Ok(v) => counts.push(v),
+                Err(e) => {
+                    log_skipped_row(path, row_num, &e);
+                    skipped += 1;
                 }
-                // If there are two directions, we need total, indir, and outdir.
-                Some(_) => {
-                    match FifteenMinutePedestrian::new(
-                        metadata.recordnum,
-                        count_dt.date(),
-                        count_dt,
-                        row.as_ref().unwrap()[1].parse().unwrap(),
-                        Some(row.as_ref().unwrap()[2].parse().unwrap()),
-                        Some(row.as_ref().unwrap()[3].parse().unwrap()),
-                    ) {
-                        Ok(v) => counts.push(v),
-                        Err(e) => {
-                            error!("{e}");
-                            continue;
-                        }
-                    };
+            }
+        }
+        Ok((counts, skipped))
+    }
+}
+
+/// Extract TimeBinnedVehicleClassCount records from a (PEEK ADR hourly class) file.
+///
+/// PEEK ADR files have a block of the counter's own metadata (site, start, etc.) before the
+/// header, which is ignored in favor of the filename's, like the rows before the header of any
+/// other file.
+impl Extract for TimeBinnedVehicleClassCount {
+    type Item = TimeBinnedVehicleClassCount;
+
+    fn extract_with_skipped(path: &Path) -> Result<(Vec<Self::Item>, usize), CountError> {
+        let metadata = FieldMetadata::from_path(path)?;
+
+        // Iterate through data rows.
+        let mut counts = vec![];
+        let mut skipped = 0;
+        let (header, rows) = data_rows(path)?;
+        let columns = PeekClassColumns::new(&header.columns)?;
+        for row in rows {
+            let row_num = row_num(&row);
+            let row = row.map_err(CountError::from);
+            match row.and_then(|row| parse_peek_class_row(&row, &columns, &metadata)) {
+                Ok(v) => counts.push(v),
+                Err(e) => {
+                    log_skipped_row(path, row_num, &e);
+                    skipped += 1;
                 }
             }
         }
-        Ok(counts)
+        Ok((counts, skipped))
     }
 }
 
-/// Create CSV reader from file.
-pub fn create_reader(file: &File) -> Reader<&File> {
+/// The span of time a file claims a count covers, in the rows before its header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClaimedSpan {
+    pub start: NaiveDateTime,
+    /// The (exclusive) end of the count, which only Eco-Counter files include.
+    pub end: Option<NaiveDateTime>,
+}
+
+impl ClaimedSpan {
+    /// Get the span a file claims to cover, if it does.
+    ///
+    /// StarNext/JAMAR files have the start of the count, either in a "Date/Time:" row (for
+    /// individual vehicles/bicycles) or as the date and time in the first two rows (for 15-minute
+    /// counts); Eco-Counter files have a "Period" row with the first and last days of the count.
+    pub fn from_file(path: &Path) -> Result<Option<Self>, CountError> {
+        let HeaderRow {
+            num_rows,
+            delimiter,
+            ..
+        } = find_header(path)?;
+        let contents = read_text(path)?;
+        let preamble = contents
+            .lines()
+            .take(num_rows - 1)
+            .map(|line| {
+                line.replace(delimiter as char, ",")
+                    .replace('"', "")
+                    .trim()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+
+        for line in &preamble {
+            if let Some(start) = line.strip_prefix("Date/Time:,") {
+                let start = parse_datetime_str(start.trim())
+                    .ok_or_else(|| bad_field("start date/time", start))?;
+                return Ok(Some(ClaimedSpan { start, end: None }));
+            }
+            if let Some(period) = line.strip_prefix("Period,") {
+                let parse = |date: &str| {
+                    NaiveDate::parse_from_str(date.trim(), "%B %d, %Y")
+                        .map_err(|_| bad_field("period", period))
+                };
+                let (first, last) = period
+                    .trim_end_matches(',')
+                    .split_once(" - ")
+                    .ok_or_else(|| bad_field("period", period))?;
+                return Ok(Some(ClaimedSpan {
+                    start: parse(first)?.and_time(NaiveTime::MIN),
+                    end: Some(parse(last)?.and_time(NaiveTime::MIN) + TimeDelta::days(1)),
+                }));
+            }
+        }
+
+        if let [date, time, ..] = preamble.as_slice() {
+            if let (Some(date), Some(time)) = (parse_date_str(date), parse_time_str(time)) {
+                return Ok(Some(ClaimedSpan {
+                    start: NaiveDateTime::new(date, time),
+                    end: None,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Check that the times of the records extracted from a file are plausible.
+///
+/// The times (in the order they are in the file) must not go backwards (beyond the small
+/// amount that interleaved lanes do), be in the future (relative to `now`) or more than
+/// [`MAX_COUNT_AGE_MONTHS`] old (unless `allow_old`, e.g. for a backlog of old counts), or be
+/// inconsistent with the span the file's header claims the count covers. This catches problems
+/// like counters' clocks being reset before the data is inserted.
+pub fn check_times(
+    path: &Path,
+    times: impl IntoIterator<Item = NaiveDateTime>,
+    now: NaiveDateTime,
+    allow_old: bool,
+) -> Result<(), CountError> {
+    let implausible = |problem| CountError::ImplausibleTimes {
+        problem,
+        path: path.to_owned(),
+    };
+    let oldest = now
+        .checked_sub_months(Months::new(MAX_COUNT_AGE_MONTHS))
+        .unwrap_or(NaiveDateTime::MIN);
+    let span = ClaimedSpan::from_file(path)?;
+
+    let mut first = None;
+    let mut last: Option<NaiveDateTime> = None;
+    for time in times {
+        if let Some(previous) = last {
+            if previous - time > TimeDelta::seconds(MAX_TIME_REGRESSION) {
+                return Err(implausible(TimeProblem::OutOfOrder { previous, time }));
+            }
+        }
+        if time > now {
+            return Err(implausible(TimeProblem::InFuture(time)));
+        }
+        if time < oldest && !allow_old {
+            return Err(implausible(TimeProblem::TooOld(time)));
+        }
+        if let Some(span) = span {
+            if time < span.start || span.end.is_some_and(|end| time >= end) {
+                return Err(implausible(TimeProblem::OutsideClaimedSpan(time)));
+            }
+        }
+        first = first.or(Some(time));
+        last = Some(last.map_or(time, |last| last.max(time)));
+    }
+
+    if let (Some(span), Some(first), Some(last)) = (span, first, last) {
+        let max_difference = TimeDelta::hours(MAX_SPAN_DIFFERENCE);
+        if first - span.start > max_difference {
+            return Err(implausible(TimeProblem::LateStart {
+                claimed: span.start,
+                first,
+            }));
+        }
+        if let Some(end) = span.end {
+            if end - last > max_difference {
+                return Err(implausible(TimeProblem::EarlyEnd { claimed: end, last }));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a row of a StarNext/JAMAR 15-minute volume count into one count per direction.
+fn parse_fifteen_minute_vehicle_row(
+    row: &StringRecord,
+    columns: &FifteenMinuteVehicleColumns,
+    metadata: &FieldMetadata,
+    path: &Path,
+) -> Result<Vec<FifteenMinuteVehicle>, CountError> {
+    let count_date = parse_date(row, columns.date)?;
+    let count_time = parse_time(row, columns.time)?;
+    let datetime = NaiveDateTime::new(count_date, count_time);
+
+    // There will always be at least one count per row, and there may also be a second and
+    // third, each in the column of its channel.
+    let mut counts = vec![];
+    for (channel, Channel { direction, lane }) in &metadata.channels {
+        let column = (*channel as usize)
+            .checked_sub(1)
+            .and_then(|i| columns.channels.get(i).copied().flatten());
+        let index = match column {
+            Some(v) if row.get(v).is_some() => v,
+            _ => return Err(CountError::DirectionLenMisMatch(path.to_owned())),
+        };
+        counts.push(FifteenMinuteVehicle::new(
+            metadata.recordnum,
+            count_date,
+            datetime,
+            parse_field(row, index, "count")?,
+            Some(*direction),
+            Some(*lane),
+        )?);
+    }
+    Ok(counts)
+}
+
+/// Parse a row of a StarNext/JAMAR individual vehicle count.
+///
+/// Its axles, gap, headway, and length, which no counts are created from, are left out if they
+/// can't be parsed (with a warning), rather than the vehicle being skipped.
+fn parse_individual_vehicle_row(
+    row: &StringRecord,
+    columns: &IndividualColumns,
+    path: &Path,
+    row_num: u64,
+) -> Result<IndividualVehicle, CountError> {
+    let count_date = parse_date(row, columns.date)?;
+    let count_time = parse_time(row, columns.time)?;
+
+    let mut vehicle = IndividualVehicle::new(
+        count_date,
+        NaiveDateTime::new(count_date, count_time),
+        parse_field(row, columns.channel, "channel")?,
+        parse_field(row, columns.class, "class")?,
+        parse_field(row, columns.speed, "speed")?,
+    )?;
+    vehicle.axles = parse_supplementary_field(row, columns.axles, "axles", path, row_num);
+    vehicle.gap = parse_supplementary_field(row, columns.gap, "gap", path, row_num);
+    vehicle.headway = parse_supplementary_field(row, columns.headway, "headway", path, row_num);
+    vehicle.length = parse_supplementary_field(row, columns.length, "length", path, row_num);
+    Ok(vehicle)
+}
+
+/// Parse a row of a StarNext/JAMAR individual bicycle count.
+///
+/// Bicycles are given class 14; rows with other classes are `None`.
+fn parse_individual_bicycle_row(
+    row: &StringRecord,
+    columns: &IndividualColumns,
+) -> Result<Option<IndividualBicycle>, CountError> {
+    if parse_field::<u16>(row, columns.class, "class")? != 14 {
+        return Ok(None);
+    }
+    let count_date = parse_date(row, columns.date)?;
+    let count_time = parse_time(row, columns.time)?;
+
+    Ok(Some(IndividualBicycle::new(
+        count_date,
+        NaiveDateTime::new(count_date, count_time),
+        parse_field(row, columns.channel, "channel")?,
+    )?))
+}
+
+/// Parse a row of a PEEK ADR hourly class count.
+fn parse_peek_class_row(
+    row: &StringRecord,
+    columns: &PeekClassColumns,
+    metadata: &FieldMetadata,
+) -> Result<TimeBinnedVehicleClassCount, CountError> {
+    let count_date = parse_date(row, columns.date)?;
+    let count_time = parse_time(row, columns.time)?;
+    let channel_num: u8 = parse_field(row, columns.channel, "lane")?;
+    let channel = metadata
+        .channels
+        .get(&channel_num)
+        .ok_or_else(|| bad_field("lane", &channel_num.to_string()))?;
+
+    let mut classes = [0; 13];
+    for (class, index) in classes.iter_mut().zip(columns.classes) {
+        *class = parse_field(row, index, "class")?;
+    }
+    let unclassified =
+        parse_optional_field(row, columns.unclassified, "unclassified")?.unwrap_or(0);
+    let [c1, c2, c3, c4, c5, c6, c7, c8, c9, c10, c11, c12, c13] = classes;
+
+    Ok(TimeBinnedVehicleClassCount {
+        date: count_date,
+        time: NaiveDateTime::new(count_date, count_time),
+        lane: Some(channel.lane),
+        recordnum: metadata.recordnum,
+        direction: Some(channel.direction),
+        c1,
+        // Unclassified vehicles get included with class 2 and also counted on their own.
+        c2: c2 + unclassified,
+        c3,
+        c4,
+        c5,
+        c6,
+        c7,
+        c8,
+        c9,
+        c10,
+        c11,
+        c12,
+        c13,
+        c15: Some(unclassified),
+        total: classes.iter().sum::<u32>() + unclassified,
+    })
+}
+
+/// Parse a row of an Eco-Counter count into its datetime, total, and - if the count has two
+/// directions - in and out counts.
+///
+/// If there's only one direction for the count, only the total is needed.
+fn parse_eco_counter_row(
+    row: &StringRecord,
+    columns: &EcoCounterColumns,
+    metadata: &FieldMetadata,
+) -> Result<(NaiveDateTime, u16, Option<u16>, Option<u16>), CountError> {
+    let value = get_field(row, columns.time, "datetime")?;
+    let datetime = parse_datetime_str(value).ok_or_else(|| bad_field("datetime", value))?;
+    let total = parse_field(row, columns.total, "total")?;
+
+    match metadata.directions.direction2 {
+        None => Ok((datetime, total, None, None)),
+        Some(_) => Ok((
+            datetime,
+            total,
+            Some(parse_field(
+                row,
+                columns.indir.ok_or(CountError::MissingColumn("in"))?,
+                "in",
+            )?),
+            Some(parse_field(
+                row,
+                columns.outdir.ok_or(CountError::MissingColumn("out"))?,
+                "out",
+            )?),
+        )),
+    }
+}
+
+/// Get a field of a row.
+fn get_field<'a>(
+    row: &'a StringRecord,
+    index: usize,
+    field: &'static str,
+) -> Result<&'a str, CountError> {
+    row.get(index).ok_or(CountError::MissingField(field))
+}
+
+/// Parse a field of a row into a number (or anything else that implements `FromStr`).
+fn parse_field<T: FromStr>(
+    row: &StringRecord,
+    index: usize,
+    field: &'static str,
+) -> Result<T, CountError> {
+    let value = get_field(row, index, field)?;
+    value.parse().map_err(|_| bad_field(field, value))
+}
+
+/// Parse a field of a row that may not be in the file (`index` is `None`) or may be empty.
+fn parse_optional_field<T: FromStr>(
+    row: &StringRecord,
+    index: Option<usize>,
+    field: &'static str,
+) -> Result<Option<T>, CountError> {
+    match index.and_then(|i| row.get(i)) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(|_| bad_field(field, value)),
+    }
+}
+
+/// Parse an optional field that no counts are created from, warning that it's left out (as
+/// `None`) if it can't be parsed.
+fn parse_supplementary_field<T: FromStr>(
+    row: &StringRecord,
+    index: Option<usize>,
+    field: &'static str,
+    path: &Path,
+    row_num: u64,
+) -> Option<T> {
+    parse_optional_field(row, index, field).unwrap_or_else(|e| {
+        warn!("{path:?}, row {row_num}: {e}; left out");
+        None
+    })
+}
+
+/// Parse the date field of a row.
+fn parse_date(row: &StringRecord, index: usize) -> Result<NaiveDate, CountError> {
+    let value = get_field(row, index, "date")?;
+    parse_date_str(value).ok_or_else(|| bad_field("date", value))
+}
+
+/// Parse the time field of a row.
+fn parse_time(row: &StringRecord, index: usize) -> Result<NaiveTime, CountError> {
+    let value = get_field(row, index, "time")?;
+    parse_time_str(value).ok_or_else(|| bad_field("time", value))
+}
+
+/// Parse a date in any of the [formats][DATE_FORMATS] used in files.
+fn parse_date_str(value: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value.trim(), format).ok())
+}
+
+/// Parse a time in any of the [formats][TIME_FORMATS] used in files.
+fn parse_time_str(value: &str) -> Option<NaiveTime> {
+    TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value.trim(), format).ok())
+}
+
+/// Parse a datetime: a date and time in any of the formats used in files, separated by a space
+/// or "T" (as in ISO 8601). A UTC offset (e.g. "2024-03-07T13:00:00-05:00") is ignored, leaving
+/// the local time.
+fn parse_datetime_str(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if let Ok(v) = DateTime::parse_from_rfc3339(value) {
+        return Some(v.naive_local());
+    }
+    let (date, time) = value.split_once(' ').or_else(|| value.split_once('T'))?;
+    Some(NaiveDateTime::new(
+        parse_date_str(date)?,
+        parse_time_str(time)?,
+    ))
+}
+
+fn bad_field(field: &'static str, value: &str) -> CountError {
+    CountError::BadField {
+        field,
+        value: value.to_string(),
+    }
+}
+
+/// Get the row number (i.e. line in the file) of a row.
+fn row_num(row: &Result<StringRecord, csv::Error>) -> u64 {
+    let position = match row {
+        Ok(v) => v.position(),
+        Err(e) => e.position(),
+    };
+    position.map_or(0, |p| p.line())
+}
+
+/// Log a row that could not be parsed and so is being skipped.
+fn log_skipped_row(path: &Path, row_num: u64, e: &CountError) {
+    error!("{path:?}, row {row_num} skipped: {e}");
+}
+
+/// Get the SHA-256 hash of the contents of a file, as a hex string.
+///
+/// Used to identify files that have already been imported, regardless of their name.
+pub fn file_hash(path: &Path) -> Result<String, CountError> {
+    let digest = Sha256::digest(fs::read(path)?);
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Create CSV reader from file, with fields separated by `delimiter` (e.g. `b','`).
+pub fn create_reader<R: Read>(file: R, delimiter: u8) -> Reader<R> {
     ReaderBuilder::new()
         .has_headers(false)
+        .delimiter(delimiter)
         .trim(csv::Trim::All)
         .flexible(true)
         .from_reader(file)
 }
 
+/// The data rows of a file, i.e. those after its header.
+type DataRows = Box<dyn Iterator<Item = Result<StringRecord, csv::Error>>>;
+
+/// Read the data rows of a file, one at a time, with the file's delimiter and encoding, along
+/// with its header.
+///
+/// Rows are skipped by the line they're on rather than counted off, as the CSV reader passes
+/// over blank lines (e.g. between a counter's metadata and the header) without a record. The
+/// reader's own line numbers lag behind after blank lines and CRLF line endings, so each row is
+/// given the line of the file it's actually on (see [`Lines`]).
+fn data_rows(path: &Path) -> Result<(HeaderRow, DataRows), CountError> {
+    let header = find_header(path)?;
+    let num_rows = header.num_rows as u64;
+    let lines = Lines::new(fs::read(path)?);
+    let rows = create_reader(File::open(path)?, header.delimiter)
+        .into_byte_records()
+        .map(move |row| row.map(|row| lines.set_line(decode_record(row))))
+        .skip_while(move |row| row_num(row) <= num_rows);
+    Ok((header, Box::new(rows)))
+}
+
+/// The lines of a file, for finding the line a row of it is on.
+///
+/// The position the CSV reader gives a row starts at whatever line terminators (and blank lines)
+/// came before it, so the row itself starts after those.
+struct Lines {
+    contents: Vec<u8>,
+    /// The byte offset of each "\n", which ends a line (as with [`str::lines`]).
+    ends: Vec<usize>,
+}
+
+impl Lines {
+    fn new(contents: Vec<u8>) -> Self {
+        let ends = contents
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte == b'\n')
+            .map(|(i, _)| i)
+            .collect();
+        Self { contents, ends }
+    }
+
+    /// Set the (1-based) line of a row to the one it starts on.
+    fn set_line(&self, mut row: StringRecord) -> StringRecord {
+        if let Some(position) = row.position() {
+            let byte = position.byte() as usize;
+            let start = self
+                .contents
+                .get(byte..)
+                .and_then(|rest| rest.iter().position(|v| !matches!(v, b'\r' | b'\n')))
+                .map_or(self.contents.len(), |i| byte + i);
+            let mut position = position.clone();
+            position.set_line(self.ends.partition_point(|end| *end < start) as u64 + 1);
+            row.set_position(Some(position));
+        }
+        row
+    }
+}
+
+/// Convert a row to text, as UTF-8 if it is valid UTF-8 and otherwise as Windows-1252.
+fn decode_record(row: ByteRecord) -> StringRecord {
+    match StringRecord::from_byte_record(row) {
+        Ok(v) => v,
+        Err(e) => {
+            let row = e.into_byte_record();
+            let mut decoded = row.iter().map(decode).collect::<StringRecord>();
+            decoded.set_position(row.position().cloned());
+            decoded
+        }
+    }
+}
+
+/// Read the contents of a file as text, without any UTF-8 byte order mark.
+fn read_text(path: &Path) -> Result<String, CountError> {
+    let contents = fs::read(path)?;
+    let contents = contents.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&contents);
+    Ok(decode(contents))
+}
+
+/// Decode text as UTF-8, or, if it isn't valid UTF-8, as Windows-1252 (which Windows programs
+/// often save text in).
+fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(v) => v.to_string(),
+        Err(_) => bytes.iter().map(|byte| windows_1252_char(*byte)).collect(),
+    }
+}
+
+/// Get the character of a byte in Windows-1252, which differs from Latin-1 (and so Unicode)
+/// only in 0x80-0x9F.
+fn windows_1252_char(byte: u8) -> char {
+    const CHARS_80_9F: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9f => CHARS_80_9F[byte as usize - 0x80],
+        _ => byte as char,
+    }
+}
+
 /// Get the number of nondata rows in a file based on header.
 ///
-/// This is a rather naive solution - it simply checks that the exact string (stripped of
-/// double quotes and spaces) of one of the potential headers (and thus `InputCount`) is in the
-/// file. To make it somewhat performant, it limits the search to the first 50 lines, which
-/// is an egregiously large number to ensure that we will never miss the header and prevents the
-/// search going through tens of thousands of lines, which is the typical number in files.
+/// This is a rather naive solution - it simply checks that the columns (stripped of double
+/// quotes and spaces) of one of the potential headers (and thus `InputCount`) are in a row of the
+/// file, wherever it is - however many rows of notes StarNext or a counter puts before it. To
+/// make it somewhat performant, it limits the search to the first [`MAX_HEADER_ROW`] lines,
+/// which is an egregiously large number to ensure that we will never miss the header and prevents
+/// the search going through tens of thousands of lines, which is the typical number in files.
+///
+/// Rows are lines of the file, so blank lines before the header are counted too.
 pub fn num_nondata_rows(path: &Path) -> Result<usize, CountError> {
-    let mut num_rows = 0;
-    let contents = fs::read_to_string(path)?;
-    for line in contents.lines().take(50) {
-        num_rows += 1;
-        let line = line.replace(['"', ' '], "");
-        if line.starts_with(FIFTEEN_MINUTE_BIKE_OR_PED_HEADER)
-            || line.contains(FIFTEEN_MINUTE_VEHICLE_HEADER)
-            || line.contains(IND_VEH_OR_IND_BIKE)
+    Ok(find_header(path)?.num_rows)
+}
+
+/// Find the header row in a file.
+///
+/// See [`num_nondata_rows`] for how this is done. Fields may be separated by commas, tabs (as
+/// in StarNext's "save as text"), or semicolons; whichever there are the most of in a row is
+/// taken to be its delimiter.
+fn find_header(path: &Path) -> Result<HeaderRow, CountError> {
+    let contents = read_text(path)?;
+    for (i, line) in contents.lines().take(MAX_HEADER_ROW).enumerate() {
+        let delimiter = DELIMITERS
+            .into_iter()
+            .rev()
+            .max_by_key(|v| line.matches(*v as char).count())
+            .unwrap_or(b',');
+        let line = line.replace(delimiter as char, ",").replace('"', "");
+        let columns = Columns::new(&line);
+        let kind = if line
+            .replace(' ', "")
+            .starts_with(FIFTEEN_MINUTE_BIKE_OR_PED_HEADER)
         {
-            return Ok(num_rows);
-        }
+            Header::FifteenMinuteBikeOrPed
+        } else if columns.contains_all(&FIFTEEN_MINUTE_VEHICLE_COLUMNS) {
+            Header::FifteenMinuteVehicle
+        } else if columns.contains_all(&IND_VEH_OR_IND_BIKE_COLUMNS) {
+            Header::IndVehOrIndBike
+        } else if columns.contains_all(&METROCOUNT_COLUMNS) {
+            Header::MetroCount
+        } else if columns.contains_all(&PEEK_CLASS_COLUMNS) {
+            Header::PeekClass
+        } else {
+            continue;
+        };
+        return Ok(HeaderRow {
+            num_rows: i + 1,
+            kind,
+            delimiter,
+            columns,
+        });
     }
     Err(CountError::BadHeader(path.to_owned()))
 }
@@ -412,7 +1173,30 @@ pub fn num_nondata_rows(path: &Path) -> Result<usize, CountError> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::LaneDirection;
+    use crate::{Dedup, LaneDirection, VehicleClass};
+
+    #[test]
+    fn alternate_date_and_time_formats_parsed() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+        for value in ["3/7/2024", "2024-03-07", "2024/03/07"] {
+            assert_eq!(parse_date_str(value), Some(date), "{value}");
+        }
+        let time = NaiveTime::from_hms_opt(13, 5, 0).unwrap();
+        for value in ["1:05:00 PM", "1:05 pm", "13:05:00", "13:05"] {
+            assert_eq!(parse_time_str(value), Some(time), "{value}");
+        }
+        let datetime = NaiveDateTime::new(date, time);
+        for value in [
+            "3/7/2024 1:05:00 PM",
+            "2024-03-07 13:05:00",
+            "2024-03-07T13:05:00",
+            "2024-03-07T13:05:00-05:00",
+        ] {
+            assert_eq!(parse_datetime_str(value), Some(datetime), "{value}");
+        }
+        assert_eq!(parse_date_str("7 March 2024"), None);
+        assert_eq!(parse_time_str("25:00"), None);
+    }
 
     #[test]
     fn extract_ind_vehicle_gets_correct_number_of_counts() {
@@ -421,6 +1205,18 @@ mod tests {
         assert_eq!(counted_vehicles.len(), 8706);
     }
 
+    #[test]
+    fn ind_vehicle_iter_streams_same_counts_as_extract() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let streamed = IndividualVehicleIter::new(path)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let extracted = IndividualVehicle::extract(path).unwrap();
+        assert_eq!(streamed.len(), extracted.len());
+        assert_eq!(streamed[100].time, extracted[100].time);
+    }
+
     #[test]
     fn extract_ind_vehicle_gets_correct_number_of_counts_by_lane() {
         let path = Path::new("test_files/vehicle/101-eee-21-35.csv");
@@ -445,6 +1241,14 @@ mod tests {
         assert_eq!(lane3.len(), 27);
     }
 
+    #[test]
+    fn extract_ind_vehicle_skips_rows_that_cannot_be_parsed() {
+        let path = Path::new("test_files/vehicle/104-ew-21-35.csv");
+        let (counted_vehicles, skipped) = IndividualVehicle::extract_with_skipped(path).unwrap();
+        assert_eq!(counted_vehicles.len(), 3);
+        assert_eq!(skipped, 2);
+    }
+
     #[test]
     fn extract_fifteen_min_vehicle_gets_correct_number_of_counts_168193() {
         let path = Path::new("test_files/15minutevehicle/168193-ew-39352-na.txt");
@@ -562,6 +1366,40 @@ mod tests {
         assert!(matches!(count_type, Err(CountError::BadLocation(_))))
     }
 
+    #[test]
+    fn check_header_ok_when_location_and_header_match() {
+        for path in [
+            "test_files/vehicle/166905-ew-40972-35.txt",
+            "test_files/bicycle/178955-s-1613-25.csv",
+            "test_files/15minutevehicle/168193-ew-39352-na.txt",
+            "test_files/15minutebicycle/167607-ns-4175-na.csv",
+            "test_files/15minutepedestrian/167297-ns-4874-na.csv",
+        ] {
+            let path = Path::new(path);
+            let count_type = InputCount::from_parent_dir(path).unwrap();
+            assert!(count_type.check_header(path).is_ok());
+        }
+    }
+
+    #[test]
+    fn check_header_errs_when_location_and_header_mismatch() {
+        for path in [
+            "test_files/vehicle/15min_veh_count.txt",
+            "test_files/15minutevehicle/ind_veh_count.txt",
+            "test_files/15minutevehicle/15min_bicycle_count.txt",
+            "test_files/15minutevehicle/15min_pedestrian_count.csv",
+            "test_files/15minutebicycle/15min_veh_count.txt",
+            "test_files/15minutepedestrian/15min_veh_count.txt",
+        ] {
+            let path = Path::new(path);
+            let count_type = InputCount::from_parent_dir(path).unwrap();
+            assert!(matches!(
+                count_type.check_header(path),
+                Err(CountError::LocationHeaderMisMatch(_))
+            ));
+        }
+    }
+
     #[test]
     fn num_nondata_rows_correct_15min_veh_sample() {
         let path = Path::new("test_files/15minutevehicle/168193-ew-39352-na.txt");
@@ -574,6 +1412,35 @@ mod tests {
         assert_eq!(num_nondata_rows(path).unwrap(), 4);
     }
 
+    #[test]
+    fn eco_counter_named_ending_in_in_not_taken_for_direction() {
+        let original = Path::new("test_files/15minutebicycle/167607-ns-4175-na.csv");
+        let dir = std::env::temp_dir().join("traffic_counts_eco_counter/15minutebicycle");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("167607-ns-4175-na.csv");
+        let contents = fs::read_to_string(original)
+            .unwrap()
+            .replace("13-4175", "13-4175 Cabin");
+        fs::write(&path, contents).unwrap();
+
+        let columns = find_header(&path).unwrap().columns;
+        let columns = EcoCounterColumns::new(&columns).unwrap();
+        assert_eq!(
+            (columns.total, columns.indir, columns.outdir),
+            (1, Some(2), Some(3))
+        );
+        let counts = |path: &Path| {
+            FifteenMinuteBicycle::extract(path)
+                .unwrap()
+                .into_iter()
+                .map(|v| (v.time, v.total, v.indir, v.outdir))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(&path), counts(original));
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
     #[test]
     fn count_type_and_num_nondata_rows_correct_15min_bicycle_sample() {
         let path = Path::new("test_files/15minutebicycle/167607-ns-4175-na.csv");
@@ -601,4 +1468,282 @@ mod tests {
         let num_rows = num_nondata_rows(path).unwrap();
         assert_eq!(num_rows, 4);
     }
+
+    #[test]
+    fn tab_delimited_windows_1252_file_with_bom_extracted() {
+        let original = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let dir = std::env::temp_dir().join("traffic_counts_encoding/vehicle");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("166905-ew-40972-35.txt");
+        let mut contents = b"\xEF\xBB\xBFExported by Jamar \x96 STARneXt\n".to_vec();
+        contents.extend(
+            fs::read_to_string(original)
+                .unwrap()
+                .replace(", ", "\t")
+                .bytes(),
+        );
+        fs::write(&path, contents).unwrap();
+
+        assert_eq!(num_nondata_rows(&path).unwrap(), 5);
+        InputCount::IndividualVehicle.check_header(&path).unwrap();
+        let vehicles = IndividualVehicle::extract(&path).unwrap();
+        assert_eq!(
+            vehicles.len(),
+            IndividualVehicle::extract(original).unwrap().len()
+        );
+        assert_eq!(decode(b"\x93quoted\x94"), "“quoted”");
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn blank_lines_before_header_skipped() {
+        let original = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let dir = std::env::temp_dir().join("traffic_counts_blank_lines/vehicle");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("166905-ew-40972-35.txt");
+        let contents = fs::read_to_string(original).unwrap();
+        fs::write(
+            &path,
+            format!("\n{}", contents.replacen("Veh. No.", "\nVeh. No.", 1)),
+        )
+        .unwrap();
+
+        assert_eq!(num_nondata_rows(&path).unwrap(), 6);
+        let vehicles = IndividualVehicle::extract(&path).unwrap();
+        let expected = IndividualVehicle::extract(original).unwrap();
+        assert_eq!(vehicles.len(), expected.len());
+        assert_eq!(vehicles[0].time, expected[0].time);
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn extra_and_reordered_columns_mapped_by_name() {
+        let original = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let dir = std::env::temp_dir().join("traffic_counts_columns/vehicle");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("166905-ew-40972-35.txt");
+        // Add a "Gap" column and put "Speed" before "Class".
+        let contents = fs::read_to_string(original)
+            .unwrap()
+            .lines()
+            .map(
+                |line| match line.split(", ").collect::<Vec<_>>().as_slice() {
+                    [num, date, time, channel, class, speed] => {
+                        let gap = if *num == "Veh. No." { "Gap" } else { "1.5" };
+                        format!("{num}, {date}, {time}, {gap}, {channel}, {speed}, {class}\n")
+                    }
+                    _ => format!("{line}\n"),
+                },
+            )
+            .collect::<String>();
+        fs::write(&path, contents).unwrap();
+
+        InputCount::IndividualVehicle.check_header(&path).unwrap();
+        let fields = |path: &Path| {
+            IndividualVehicle::extract(path)
+                .unwrap()
+                .into_iter()
+                .map(|v| (v.time, v.lane, v.class, v.speed))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fields(&path), fields(original));
+
+        // The gap is included, when the file has it.
+        assert_eq!(IndividualVehicle::extract(&path).unwrap()[0].gap, Some(1.5));
+        assert_eq!(IndividualVehicle::extract(original).unwrap()[0].gap, None);
+
+        // A gap that can't be parsed is left out, rather than the vehicle.
+        let contents = fs::read_to_string(&path)
+            .unwrap()
+            .replace(", 1.5,", ", n/a,");
+        fs::write(&path, contents).unwrap();
+        assert_eq!(fields(&path), fields(original));
+        assert_eq!(IndividualVehicle::extract(&path).unwrap()[0].gap, None);
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn extract_metrocount_vehicles() {
+        let path = Path::new("test_files/vehicle/166908-ew-40972-35.csv");
+        InputCount::IndividualVehicle.check_header(path).unwrap();
+        assert!(matches!(
+            InputCount::IndividualBicycle.check_header(path),
+            Err(CountError::LocationHeaderMisMatch(_))
+        ));
+        let vehicles = IndividualVehicle::extract(path).unwrap();
+        assert_eq!(vehicles.len(), 6);
+        assert_eq!(vehicles.iter().filter(|v| v.lane == 2).count(), 2);
+        assert_eq!(
+            vehicles[0].time.time(),
+            NaiveTime::from_hms_milli_opt(10, 59, 45, 120).unwrap()
+        );
+        assert_eq!(vehicles[0].class, VehicleClass::PassengerCars);
+        assert_eq!(vehicles[0].speed, 34.3);
+        assert_eq!(vehicles[3].axles, Some(5));
+        assert_eq!(vehicles[1].headway, Some(3.2));
+    }
+
+    #[test]
+    fn extract_peek_hourly_class_counts() {
+        let path = Path::new("test_files/hourlyclass/166910-ew-40972-35.csv");
+        InputCount::HourlyClass.check_header(path).unwrap();
+        let counts = TimeBinnedVehicleClassCount::extract(path).unwrap();
+        // (The blank line between the counter's metadata and the header isn't a row of data.)
+        assert_eq!(counts.len(), 4);
+        assert_eq!(
+            counts[0].time,
+            NaiveDate::from_ymd_opt(2023, 11, 6)
+                .unwrap()
+                .and_hms_opt(11, 0, 0)
+                .unwrap()
+        );
+        assert_eq!(counts[0].direction, Some(LaneDirection::East));
+        assert_eq!(counts[1].direction, Some(LaneDirection::West));
+        assert_eq!(counts[0].c1, 1);
+        // Unclassified vehicles are included with class 2, as well as counted on their own.
+        assert_eq!(counts[0].c2, 114);
+        assert_eq!(counts[0].c9, 6);
+        assert_eq!(counts[0].c15, Some(2));
+        assert_eq!(counts[0].total, 150);
+        assert_eq!(
+            counts[2].time,
+            NaiveDate::from_ymd_opt(2023, 11, 6)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn count_type_inferred_from_contents() {
+        let path = Path::new("test_files/15minutevehicle/ind_veh_count.txt");
+        assert_eq!(
+            InputCount::from_contents(path, InputCount::from_parent_dir(path).ok()).unwrap(),
+            InputCount::IndividualVehicle
+        );
+        let path = Path::new("test_files/bicycle/178955-s-1613-25.csv");
+        assert_eq!(
+            InputCount::from_contents(path, InputCount::from_parent_dir(path).ok()).unwrap(),
+            InputCount::IndividualBicycle
+        );
+        assert_eq!(
+            InputCount::from_contents(path, None).unwrap(),
+            InputCount::IndividualVehicle
+        );
+        let path = Path::new("test_files/15minutevehicle/15min_bicycle_count.txt");
+        assert!(matches!(
+            InputCount::from_contents(path, InputCount::from_parent_dir(path).ok()),
+            Err(CountError::AmbiguousCountType(_))
+        ));
+        let path = Path::new("test_files/15minutebicycle/15min_veh_count.txt");
+        assert_eq!(
+            InputCount::from_contents(path, InputCount::from_parent_dir(path).ok()).unwrap(),
+            InputCount::FifteenMinuteVehicle
+        );
+    }
+
+    #[test]
+    fn file_hash_same_for_same_contents_only() {
+        let path1 = Path::new("test_files/vehicle/101-eee-21-35.csv");
+        let path2 = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let hash = file_hash(path1).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, file_hash(path1).unwrap());
+        assert_ne!(hash, file_hash(path2).unwrap());
+    }
+
+    #[test]
+    fn remove_duplicates_removes_only_duplicates() {
+        let path = Path::new("test_files/vehicle/101-eee-21-35.csv");
+        let mut counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        assert_eq!(
+            IndividualVehicle::remove_duplicates(&mut counted_vehicles),
+            0
+        );
+
+        let mut duplicated = counted_vehicles.clone();
+        duplicated.extend_from_slice(&counted_vehicles[..10]);
+        assert_eq!(IndividualVehicle::remove_duplicates(&mut duplicated), 10);
+        assert_eq!(duplicated.len(), counted_vehicles.len());
+    }
+
+    #[test]
+    fn claimed_span_from_file_correct() {
+        let dt = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        for (path, start, end) in [
+            (
+                "test_files/vehicle/166905-ew-40972-35.txt",
+                dt("2023-11-06 10:58"),
+                None,
+            ),
+            (
+                "test_files/15minutevehicle/168193-ew-39352-na.txt",
+                dt("2024-01-03 11:30"),
+                None,
+            ),
+            (
+                "test_files/15minutebicycle/167607-ns-4175-na.csv",
+                dt("2023-09-22 00:00"),
+                Some(dt("2023-09-27 00:00")),
+            ),
+        ] {
+            let span = ClaimedSpan::from_file(Path::new(path)).unwrap();
+            assert_eq!(span, Some(ClaimedSpan { start, end }));
+        }
+    }
+
+    #[test]
+    fn check_times_ok_for_plausible_times() {
+        let now = NaiveDateTime::parse_from_str("2024-01-10 00:00", "%Y-%m-%d %H:%M").unwrap();
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        assert!(check_times(path, counted_vehicles.iter().map(|v| v.time), now, false).is_ok());
+
+        let path = Path::new("test_files/15minutebicycle/167607-ns-4175-na.csv");
+        let counts = FifteenMinuteBicycle::extract(path).unwrap();
+        assert!(check_times(path, counts.iter().map(|v| v.time), now, false).is_ok());
+    }
+
+    #[test]
+    fn check_times_errs_for_implausible_times() {
+        let now = NaiveDateTime::parse_from_str("2024-01-10 00:00", "%Y-%m-%d %H:%M").unwrap();
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let times = IndividualVehicle::extract(path)
+            .unwrap()
+            .iter()
+            .map(|v| v.time)
+            .collect::<Vec<_>>();
+        let check =
+            |times: &[NaiveDateTime], now| match check_times(path, times.to_vec(), now, false) {
+                Err(CountError::ImplausibleTimes { problem, .. }) => Some(problem),
+                _ => None,
+            };
+
+        // The counter's clock is reset partway through.
+        let mut reset = times.clone();
+        reset[100] -= TimeDelta::hours(2);
+        assert!(matches!(
+            check(&reset, now),
+            Some(TimeProblem::OutOfOrder { .. })
+        ));
+
+        // It's checked too long after, or somehow before, it was counted.
+        let later = now + TimeDelta::days(600);
+        assert!(matches!(check(&times, later), Some(TimeProblem::TooOld(_))));
+        assert!(check_times(path, times.clone(), later, true).is_ok());
+        let earlier = now - TimeDelta::days(65);
+        assert!(matches!(
+            check(&times, earlier),
+            Some(TimeProblem::InFuture(_))
+        ));
+
+        // The count starts long after its header says it does.
+        assert!(matches!(
+            check(&times[times.len() - 10..], now),
+            Some(TimeProblem::LateStart { .. })
+        ));
+    }
 }
@@ -0,0 +1,162 @@
+//! Headways between individual vehicles.
+//!
+//! A headway is the time between one vehicle and the next in the same lane. Signal warrant
+//! studies need to know how often there are gaps in traffic long enough to cross or enter it, and
+//! records of [individual vehicles][IndividualVehicle] contain everything needed to find them.
+//! Vehicle lengths aren't recorded, so headways (front to front) stand in for gaps (back to front).
+//!
+//! Headways are binned by the time of the second vehicle of each pair, so a bin's first vehicle
+//! in a lane may have a headway measured from a vehicle in the bin before it. Counters only record
+//! times to the second, and so headways are in whole seconds.
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::{bin_time, Channel, FieldMetadata, IndividualVehicle, LaneDirection, TimeInterval};
+
+/// Headways shorter than this many seconds are counted, since drivers don't accept gaps that short.
+pub const SHORT_HEADWAY: i64 = 2;
+
+/// The distribution of headways in one lane, binned into [time intervals][TimeInterval].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeBinnedHeadways {
+    pub date: NaiveDate,
+    pub time: NaiveDateTime,
+    pub lane: u8,
+    pub recordnum: u32,
+    pub direction: LaneDirection,
+    /// The number of headways.
+    pub headways: u32,
+    /// The mean headway, in seconds.
+    pub mean: Option<f32>,
+    /// The median headway, in seconds.
+    pub median: Option<f32>,
+    /// The percentage of headways shorter than [`SHORT_HEADWAY`].
+    pub percent_short: Option<f32>,
+}
+
+impl TimeBinnedHeadways {
+    fn new(
+        recordnum: u32,
+        time: NaiveDateTime,
+        Channel { direction, lane }: Channel,
+        mut headways: Vec<i64>,
+    ) -> Self {
+        headways.sort();
+        let len = headways.len();
+        let (mean, median, percent_short) = if len == 0 {
+            (None, None, None)
+        } else {
+            let median = if len.is_multiple_of(2) {
+                (headways[len / 2 - 1] + headways[len / 2]) as f32 / 2.0
+            } else {
+                headways[len / 2] as f32
+            };
+            let short = headways.iter().filter(|v| **v < SHORT_HEADWAY).count();
+            (
+                Some(headways.iter().sum::<i64>() as f32 / len as f32),
+                Some(median),
+                Some(short as f32 / len as f32 * 100.0),
+            )
+        };
+        Self {
+            date: time.date(),
+            time,
+            lane,
+            recordnum,
+            direction,
+            headways: len as u32,
+            mean,
+            median,
+            percent_short,
+        }
+    }
+}
+
+/// Create time-binned headway distributions for each lane from [`IndividualVehicle`]s.
+///
+/// Only periods with at least one vehicle are included, in order by time and then channel.
+pub fn create_headway_counts(
+    interval: TimeInterval,
+    metadata: &FieldMetadata,
+    vehicles: &[IndividualVehicle],
+) -> Vec<TimeBinnedHeadways> {
+    // The times of the vehicles in each channel.
+    let mut times: BTreeMap<u8, Vec<NaiveDateTime>> = BTreeMap::new();
+    for vehicle in vehicles {
+        times.entry(vehicle.lane).or_default().push(vehicle.time);
+    }
+
+    let mut bins: BTreeMap<(NaiveDateTime, u8), (Channel, Vec<i64>)> = BTreeMap::new();
+    for (channel_num, mut times) in times {
+        let Some(channel) = metadata.channels.get(&channel_num).copied() else {
+            error!("Unable to determine lane/direction of channel {channel_num}.");
+            continue;
+        };
+        times.sort();
+        for (i, time) in times.iter().enumerate() {
+            let bin = NaiveDateTime::new(time.date(), bin_time(time.time(), interval));
+            let (_, headways) = bins
+                .entry((bin, channel_num))
+                .or_insert_with(|| (channel, vec![]));
+            if i > 0 {
+                headways.push((*time - times[i - 1]).num_seconds());
+            }
+        }
+    }
+
+    bins.into_iter()
+        .map(|((time, _), (channel, headways))| {
+            TimeBinnedHeadways::new(metadata.recordnum, time, channel, headways)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract_from_file::Extract;
+    use std::path::Path;
+
+    #[test]
+    fn headways_per_lane_correct() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let vehicles = IndividualVehicle::extract(path).unwrap();
+        let headways = create_headway_counts(TimeInterval::FifteenMin, &metadata, &vehicles);
+
+        // Every vehicle but the first in each lane has a headway.
+        assert_eq!(
+            headways.iter().map(|v| v.headways).sum::<u32>() as usize,
+            vehicles.len() - metadata.channels.len()
+        );
+
+        // 10:45-11:00 has two vehicles in channel 1 (10:59:45 and 10:59:52) and two in channel 2
+        // (10:59:47 and 10:59:50).
+        assert_eq!(headways[0].headways, 1);
+        assert_eq!(headways[0].mean, Some(7.0));
+        assert_eq!(headways[1].median, Some(3.0));
+        assert_eq!(headways[1].percent_short, Some(0.0));
+    }
+
+    #[test]
+    fn median_and_short_headways_correct() {
+        let time = NaiveDate::from_ymd_opt(2024, 4, 8)
+            .unwrap()
+            .and_hms_opt(7, 0, 0)
+            .unwrap();
+        let channel = Channel {
+            direction: LaneDirection::East,
+            lane: 1,
+        };
+        let headways = TimeBinnedHeadways::new(123, time, channel, vec![8, 1, 3, 0]);
+        assert_eq!(headways.mean, Some(3.0));
+        assert_eq!(headways.median, Some(2.0));
+        assert_eq!(headways.percent_short, Some(50.0));
+
+        let none = TimeBinnedHeadways::new(123, time, channel, vec![]);
+        assert_eq!(none.mean, None);
+    }
+}
@@ -0,0 +1,177 @@
+//! The share of a count's vehicles that are heavy vehicles.
+//!
+//! Heavy vehicles are buses and trucks: [classes][crate::VehicleClass] 4 through 13. Their
+//! percentage of all vehicles (including unclassified ones) is summarized for each hour and day
+//! of a count, and for the count as a whole, which is what goes into the count's TC_HEADER record.
+//! All lanes and directions are combined.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::{TimeBinnedVehicleClassCount, VehicleClass};
+
+/// The number of heavy vehicles and of all vehicles in some period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeavyVehicleVolume {
+    pub heavy: u32,
+    pub total: u32,
+}
+
+impl HeavyVehicleVolume {
+    /// Get the number of heavy vehicles (classes 4-13) in a class count.
+    pub fn from_count(count: &TimeBinnedVehicleClassCount) -> Self {
+        let heavy = VehicleClass::iter()
+            .filter(|v| (4..=13).contains(&v.as_num()))
+            .map(|v| count.class(v))
+            .sum();
+        Self {
+            heavy,
+            total: count.total,
+        }
+    }
+
+    fn add(&mut self, other: Self) {
+        self.heavy += other.heavy;
+        self.total += other.total;
+    }
+
+    /// The percentage of vehicles that are heavy vehicles, if there were any vehicles.
+    pub fn percent(&self) -> Option<f32> {
+        if self.total == 0 {
+            None
+        } else {
+            Some(self.heavy as f32 / self.total as f32 * 100.0)
+        }
+    }
+}
+
+/// Heavy vehicle volumes of a count, by hour, by day, and overall.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeavyVehicleSummary {
+    pub recordnum: u32,
+    /// The start of each hour, and its volumes.
+    pub hourly: BTreeMap<NaiveDateTime, HeavyVehicleVolume>,
+    pub daily: BTreeMap<NaiveDate, HeavyVehicleVolume>,
+    pub overall: HeavyVehicleVolume,
+}
+
+impl Display for HeavyVehicleSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} heavy vehicles: {} of {}",
+            self.recordnum, self.overall.heavy, self.overall.total
+        )?;
+        if let Some(percent) = self.overall.percent() {
+            write!(f, " ({percent:.1}%)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Summarize the heavy vehicles of a count from its [`TimeBinnedVehicleClassCount`]s.
+///
+/// The counts may be binned into any interval of an hour or less. Returns `None` if there are
+/// no counts.
+pub fn create_heavy_vehicle_summary(
+    counts: &[TimeBinnedVehicleClassCount],
+) -> Option<HeavyVehicleSummary> {
+    let recordnum = counts.first()?.recordnum;
+    let mut hourly: BTreeMap<NaiveDateTime, HeavyVehicleVolume> = BTreeMap::new();
+    let mut daily: BTreeMap<NaiveDate, HeavyVehicleVolume> = BTreeMap::new();
+    let mut overall = HeavyVehicleVolume::default();
+
+    for count in counts {
+        let volume = HeavyVehicleVolume::from_count(count);
+        let hour = count
+            .date
+            .and_hms_opt(count.time.hour(), 0, 0)
+            .expect("hour of a valid time is valid");
+        hourly.entry(hour).or_default().add(volume);
+        daily.entry(count.date).or_default().add(volume);
+        overall.add(volume);
+    }
+
+    Some(HeavyVehicleSummary {
+        recordnum,
+        hourly,
+        daily,
+        overall,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LaneDirection;
+
+    fn count(
+        time: &str,
+        lane: u8,
+        cars: u32,
+        buses: u32,
+        trucks: u32,
+    ) -> TimeBinnedVehicleClassCount {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        TimeBinnedVehicleClassCount {
+            date: time.date(),
+            time,
+            lane: Some(lane),
+            recordnum: 123,
+            direction: Some(LaneDirection::East),
+            c1: 0,
+            c2: cars,
+            c3: 0,
+            c4: buses,
+            c5: 0,
+            c6: 0,
+            c7: 0,
+            c8: 0,
+            c9: trucks,
+            c10: 0,
+            c11: 0,
+            c12: 0,
+            c13: 0,
+            c15: Some(0),
+            total: cars + buses + trucks,
+        }
+    }
+
+    #[test]
+    fn heavy_vehicles_summarized_by_hour_day_and_count() {
+        let counts = vec![
+            count("2024-04-08 07:00", 1, 8, 1, 1),
+            count("2024-04-08 07:15", 2, 10, 0, 0),
+            count("2024-04-08 08:00", 1, 0, 0, 0),
+            count("2024-04-09 07:00", 1, 15, 0, 5),
+        ];
+        let summary = create_heavy_vehicle_summary(&counts).unwrap();
+
+        let seven = NaiveDateTime::parse_from_str("2024-04-08 07:00", "%Y-%m-%d %H:%M").unwrap();
+        let eight = NaiveDateTime::parse_from_str("2024-04-08 08:00", "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(summary.hourly.len(), 3);
+        assert_eq!(
+            summary.hourly[&seven],
+            HeavyVehicleVolume {
+                heavy: 2,
+                total: 20
+            }
+        );
+        assert_eq!(summary.hourly[&seven].percent(), Some(10.0));
+        assert_eq!(summary.hourly[&eight].percent(), None);
+
+        assert_eq!(summary.daily.len(), 2);
+        assert_eq!(summary.daily[&seven.date()].percent(), Some(10.0));
+        assert_eq!(summary.overall.heavy, 7);
+        assert_eq!(summary.overall.total, 40);
+        assert_eq!(summary.overall.percent(), Some(17.5));
+        assert_eq!(summary.to_string(), "123 heavy vehicles: 7 of 40 (17.5%)");
+    }
+
+    #[test]
+    fn no_summary_without_counts() {
+        assert!(create_heavy_vehicle_summary(&[]).is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! The order files are imported in.
+//!
+//! By default, files are imported in the order they're found in the data directory, which is
+//! whatever order the filesystem lists them in. When many are waiting, they can instead be
+//! [ordered][order_paths] by the [date their counts start][ImportOrder::CountDate] (oldest
+//! first) or by [recordnum][ImportOrder::Recordnum], and the files in prioritized subdirectories
+//! (e.g. of the kinds of counts needed soonest) can be imported before all others.
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+
+use crate::{extract_from_file::ClaimedSpan, CountError, FieldMetadata};
+
+/// The order to import files in (after those in prioritized directories).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportOrder {
+    /// The order the filesystem lists them in.
+    #[default]
+    Filesystem,
+    /// The oldest count first, by the start of the count given in the file's header. Files
+    /// without one are last.
+    CountDate,
+    /// The lowest recordnum first, from the filename. Files not named to the filename
+    /// specification are last.
+    Recordnum,
+}
+
+impl FromStr for ImportOrder {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "filesystem" => Ok(ImportOrder::Filesystem),
+            "count-date" => Ok(ImportOrder::CountDate),
+            "recordnum" => Ok(ImportOrder::Recordnum),
+            _ => Err(CountError::UnknownImportOrder(s.to_string())),
+        }
+    }
+}
+
+impl Display for ImportOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let order = match self {
+            ImportOrder::Filesystem => "filesystem",
+            ImportOrder::CountDate => "count-date",
+            ImportOrder::Recordnum => "recordnum",
+        };
+        write!(f, "{}", order)
+    }
+}
+
+/// Put paths in the order to import them: those within each of `priority_dirs` first (in the
+/// order the directories are given), and then all others, each group in `order`.
+///
+/// Otherwise equal paths keep the order they were in.
+pub fn order_paths(paths: &mut [PathBuf], order: ImportOrder, priority_dirs: &[PathBuf]) {
+    paths.sort_by_cached_key(|path| {
+        let priority = priority_dirs
+            .iter()
+            .position(|dir| path.starts_with(dir))
+            .unwrap_or(priority_dirs.len());
+        let (start, recordnum) = match order {
+            ImportOrder::Filesystem => (None, None),
+            ImportOrder::CountDate => (count_start(path), None),
+            ImportOrder::Recordnum => (
+                None,
+                FieldMetadata::from_filename(path).ok().map(|v| v.recordnum),
+            ),
+        };
+        // (Those without a start or recordnum go after those with one.)
+        (
+            priority,
+            start.is_none(),
+            start,
+            recordnum.is_none(),
+            recordnum,
+        )
+    });
+}
+
+/// The start of the count in a file, as given in its header.
+fn count_start(path: &Path) -> Option<NaiveDateTime> {
+    ClaimedSpan::from_file(path).ok().flatten().map(|v| v.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_ordered_after_prioritized_dirs() {
+        let paths = [
+            "test_files/vehicle/166905-ew-40972-35.txt",
+            "test_files/vehicle/165367-ee-38397-45.txt",
+            "test_files/15minutevehicle/168193-ew-39352-na.txt",
+            "test_files/vehicle/101-eee-21-35.csv",
+        ]
+        .map(PathBuf::from);
+        let expected = |order: [usize; 4]| order.map(|i| paths[i].clone());
+
+        let mut ordered = paths.clone();
+        order_paths(&mut ordered, ImportOrder::Filesystem, &[]);
+        assert_eq!(ordered, paths);
+
+        order_paths(&mut ordered, ImportOrder::Recordnum, &[]);
+        assert_eq!(ordered, expected([3, 1, 0, 2]));
+
+        // 166905 and 101 start at the same time, so stay in the same order.
+        let mut ordered = paths.clone();
+        order_paths(&mut ordered, ImportOrder::CountDate, &[]);
+        assert_eq!(ordered, expected([0, 3, 1, 2]));
+
+        order_paths(
+            &mut ordered,
+            ImportOrder::Recordnum,
+            &[PathBuf::from("test_files/15minutevehicle")],
+        );
+        assert_eq!(ordered, expected([2, 3, 1, 0]));
+
+        assert_eq!(
+            ImportOrder::from_str("Count-Date").unwrap(),
+            ImportOrder::CountDate
+        );
+        assert!(ImportOrder::from_str("size").is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! A summary of a run of the import program.
+//!
+//! Each time the import program goes through the files in the data directory, it keeps an
+//! [`ImportSummary`] of what happened to each of them - whether it was imported or skipped (and
+//! why), how many records were inserted into which tables, and what warnings were raised - so
+//! that it can be reviewed in one place rather than pieced together from the log.
+//!
+//! The reasons files were skipped and the warnings raised are collected by [`SummaryLog`], which
+//! wraps the log that messages about importing them are written to.
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{Local, NaiveDateTime};
+use log::{Level, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+use crate::{export::ExportFormat, CountError};
+
+/// What happened during one run of the import program.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub start: NaiveDateTime,
+    pub files: Vec<FileSummary>,
+}
+
+/// What happened to one file during a run of the import program.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSummary {
+    pub path: PathBuf,
+    /// The [hash][crate::extract_from_file::file_hash] of the file's contents.
+    pub hash: Option<String>,
+    pub recordnum: Option<u32>,
+    /// The number of rows extracted from the file, and the number that couldn't be parsed.
+    pub extracted: Option<(usize, usize)>,
+    pub imported: bool,
+    /// Why the file wasn't imported, if it wasn't.
+    pub reason: Option<String>,
+    /// The number of records inserted, per table.
+    pub inserted: Vec<(&'static str, usize)>,
+    pub warnings: Vec<String>,
+}
+
+/// A [`FileSummary`] flattened into one row, for CSV.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileSummaryRow {
+    path: String,
+    recordnum: Option<u32>,
+    imported: bool,
+    reason: Option<String>,
+    inserted: usize,
+    warnings: String,
+}
+
+impl ImportSummary {
+    pub fn new() -> Self {
+        Self {
+            start: Local::now().naive_local(),
+            files: vec![],
+        }
+    }
+
+    /// Start summarizing a file, completing the summary of the previous one with the
+    /// `messages` logged while importing it.
+    pub fn start_file(&mut self, path: &Path, messages: Vec<(Level, String)>) {
+        self.finish_file(messages);
+        self.files.push(FileSummary {
+            path: path.to_owned(),
+            hash: None,
+            recordnum: None,
+            extracted: None,
+            imported: false,
+            reason: None,
+            inserted: vec![],
+            warnings: vec![],
+        });
+    }
+
+    /// Complete the summary of the current file with the `messages` logged while importing it.
+    ///
+    /// If the file wasn't imported, the first error is the reason why; all other messages are
+    /// warnings.
+    pub fn finish_file(&mut self, messages: Vec<(Level, String)>) {
+        let Some(file) = self.files.last_mut() else {
+            return;
+        };
+        for (level, msg) in messages {
+            if !file.imported && file.reason.is_none() && level == Level::Error {
+                file.reason = Some(msg);
+            } else {
+                file.warnings.push(msg);
+            }
+        }
+        if !file.imported && file.reason.is_none() {
+            file.reason = Some("unknown".to_string());
+        }
+    }
+
+    /// Set the recordnum of the current file.
+    pub fn set_recordnum(&mut self, recordnum: u32) {
+        if let Some(file) = self.files.last_mut() {
+            file.recordnum = Some(recordnum);
+        }
+    }
+
+    /// Set the hash of the current file.
+    pub fn set_hash(&mut self, hash: &str) {
+        if let Some(file) = self.files.last_mut() {
+            file.hash = Some(hash.to_string());
+        }
+    }
+
+    /// Set the number of rows extracted from the current file, and the number skipped because
+    /// they couldn't be parsed.
+    pub fn extracted(&mut self, rows: usize, skipped: usize) {
+        if let Some(file) = self.files.last_mut() {
+            file.extracted = Some((rows, skipped));
+        }
+    }
+
+    /// Add the number of records from the current file inserted into `table`.
+    pub fn inserted(&mut self, table: &'static str, num: usize) {
+        if let Some(file) = self.files.last_mut() {
+            file.inserted.push((table, num));
+        }
+    }
+
+    /// The total number of records from the current file inserted into all tables.
+    pub fn current_inserted(&self) -> usize {
+        self.files
+            .last()
+            .map_or(0, |file| file.inserted.iter().map(|(_, num)| num).sum())
+    }
+
+    /// Mark the current file as imported.
+    pub fn imported(&mut self) {
+        if let Some(file) = self.files.last_mut() {
+            file.imported = true;
+        }
+    }
+
+    /// Write the summary to a file in `dir`, returning the path of the file.
+    ///
+    /// In CSV (and Arrow and Parquet) format, there is one row per file, with the total number of
+    /// records inserted and warnings joined together.
+    pub fn write(&self, dir: &Path, format: ExportFormat) -> Result<PathBuf, CountError> {
+        let path = dir.join(format!(
+            "import-summary-{}.{}",
+            self.start.format("%Y%m%d%H%M%S"),
+            format.extension()
+        ));
+        let rows = self.files.iter().map(|file| FileSummaryRow {
+            path: file.path.display().to_string(),
+            recordnum: file.recordnum,
+            imported: file.imported,
+            reason: file.reason.clone(),
+            inserted: file.inserted.iter().map(|(_, num)| num).sum(),
+            warnings: file.warnings.join("; "),
+        });
+
+        match format {
+            ExportFormat::Csv => {
+                let mut wtr = csv::Writer::from_writer(BufWriter::new(File::create(&path)?));
+                for row in rows {
+                    wtr.serialize(row)?;
+                }
+                wtr.flush()?;
+            }
+            ExportFormat::Json => {
+                serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), self)?
+            }
+            #[cfg(feature = "parquet")]
+            ExportFormat::Arrow => crate::columnar::write_arrow(&rows.collect::<Vec<_>>(), &path)?,
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => {
+                crate::columnar::write_parquet(&rows.collect::<Vec<_>>(), &path)?
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+impl Default for ImportSummary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for ImportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let imported = self.files.iter().filter(|file| file.imported).count();
+        let inserted: usize = self
+            .files
+            .iter()
+            .flat_map(|file| &file.inserted)
+            .map(|(_, num)| num)
+            .sum();
+        let warnings: usize = self.files.iter().map(|file| file.warnings.len()).sum();
+        write!(
+            f,
+            "Import summary: {} files processed, {imported} imported, {} skipped; \
+            {inserted} records inserted; {warnings} warnings",
+            self.files.len(),
+            self.files.len() - imported
+        )?;
+        for file in &self.files {
+            if let Some(reason) = &file.reason {
+                write!(f, "\n  {} skipped: {reason}", file.path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`Log`] that passes records on to another, keeping the warnings and errors to be
+/// [summarized][ImportSummary].
+pub struct SummaryLog<L> {
+    log: L,
+    messages: Mutex<Vec<(Level, String)>>,
+}
+
+impl<L: Log> SummaryLog<L> {
+    pub fn new(log: L) -> Self {
+        Self {
+            log,
+            messages: Mutex::new(vec![]),
+        }
+    }
+
+    /// The log that records are passed on to.
+    pub fn inner(&self) -> &L {
+        &self.log
+    }
+
+    /// Take the warnings and errors logged since the last time this was called.
+    pub fn take_messages(&self) -> Vec<(Level, String)> {
+        match self.messages.lock() {
+            Ok(mut v) => std::mem::take(&mut *v),
+            Err(_) => vec![],
+        }
+    }
+}
+
+impl<L: Log> Log for SummaryLog<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.log.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= Level::Warn {
+            if let Ok(mut v) = self.messages.lock() {
+                v.push((record.level(), record.args().to_string()));
+            }
+        }
+        self.log.log(record)
+    }
+
+    fn flush(&self) {
+        self.log.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_records_reason_for_skipped_file_and_warnings() {
+        let mut summary = ImportSummary::new();
+        summary.start_file(Path::new("vehicle/1-e-1-35.csv"), vec![]);
+        summary.start_file(
+            Path::new("vehicle/2-e-1-35.csv"),
+            vec![
+                (Level::Warn, "2 rows could not be parsed".to_string()),
+                (Level::Error, "1: Not processed: bad header".to_string()),
+            ],
+        );
+        summary.set_recordnum(2);
+        summary.inserted("tc_clacount", 10);
+        summary.imported();
+        summary.finish_file(vec![(Level::Warn, "2: warning".to_string())]);
+
+        assert_eq!(
+            summary.files[0].reason,
+            Some("1: Not processed: bad header".to_string())
+        );
+        assert_eq!(summary.files[0].warnings.len(), 1);
+        assert!(summary.files[1].imported);
+        assert_eq!(summary.files[1].recordnum, Some(2));
+        assert_eq!(summary.files[1].reason, None);
+        assert_eq!(summary.files[1].warnings, vec!["2: warning".to_string()]);
+        assert!(summary
+            .to_string()
+            .starts_with("Import summary: 2 files processed, 1 imported, 1 skipped; 10 records"));
+    }
+
+    #[test]
+    fn summary_log_keeps_only_warnings_and_errors() {
+        let log = SummaryLog::new(simplelog::SimpleLogger::new(
+            log::LevelFilter::Off,
+            simplelog::Config::default(),
+        ));
+        for level in [Level::Info, Level::Warn, Level::Error] {
+            log.log(
+                &Record::builder()
+                    .args(format_args!("{level}"))
+                    .level(level)
+                    .build(),
+            );
+        }
+        assert_eq!(log.take_messages().len(), 2);
+        assert!(log.take_messages().is_empty());
+    }
+}
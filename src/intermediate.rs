@@ -1,10 +1,11 @@
 //! Intermediate data types.
 //!
-//! As data records are iterated over, HashMaps are used to associate keys with values. However,
+//! As data records are iterated over, BTreeMaps are used to associate keys with values. However,
 //! in the end Vecs are a better representation of the data (a row in a table). The following
-//! "formulas" show how each is used.
+//! "formulas" show how each is used. (BTreeMaps rather than HashMaps, so that the Vecs - and so
+//! the records inserted and exported - are always in the same order: that of the keys.)
 //!
-//! HashMap Key + HashMap Value = Vec
+//! BTreeMap Key + BTreeMap Value = Vec
 //!
 //! [`BinnedCountKey`] + [`VehicleClassCount`] = [`crate::TimeBinnedVehicleClassCount`].
 //!
@@ -14,11 +15,12 @@
 //!
 //! [`NonNormalCountKey`] + [`NonNormalVolCountValue`] = [`crate::denormalize::NonNormalVolCount`].
 use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
 
 use crate::{denormalize::HourlyCount, LaneDirection, VehicleClass, Weather};
 
 /// The key for records of the TC_SPECOUNT and TC_CLACOUNT tables.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct BinnedCountKey {
     pub date: NaiveDate,
     pub time: NaiveDateTime,
@@ -32,7 +34,7 @@ pub struct BinnedCountKey {
 /// Note: unclassified vehicles are counted in `c15` field, but also are included in the `c2`
 /// (Passenger Cars). Thus, a simple sum of fields `c1` through `c15` would double-count
 /// unclassified vehicles.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct VehicleClassCount {
     pub recordnum: u32,
     pub direction: LaneDirection,
@@ -111,7 +113,7 @@ impl VehicleClassCount {
 /// The rest of the fields for the TC_SPECOUNT table.
 ///
 /// This is generally - but not always - for 15-minute intervals.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SpeedRangeCount {
     pub recordnum: u32,
     pub direction: LaneDirection,
@@ -164,52 +166,75 @@ impl SpeedRangeCount {
     }
     /// Insert individual speed into count.
     pub fn insert(&mut self, speed: f32) {
-        // The end of the ranges are inclusive to the number's .0 decimal;
-        // that is:
-        // 0-15: 0.0 to 15.0
-        // >15-20: 15.1 to 20.0, etc.
-
-        // Unfortunately, using floats as tests in pattern matching will be an error in a future
-        // Rust release, so need to do if/else rather than match.
-        // <https://github.com/rust-lang/rust/issues/41620>
-        if speed.is_sign_negative() {
-            // This shouldn't be necessary, but I saw a -0.0 in one of the files.
-            self.s1 += 1
-        } else if (0.0..=15.0).contains(&speed) {
-            self.s1 += 1;
-        } else if (15.1..=20.0).contains(&speed) {
-            self.s2 += 1;
-        } else if (20.1..=25.0).contains(&speed) {
-            self.s3 += 1;
-        } else if (25.1..=30.0).contains(&speed) {
-            self.s4 += 1;
-        } else if (30.1..=35.0).contains(&speed) {
-            self.s5 += 1;
-        } else if (35.1..=40.0).contains(&speed) {
-            self.s6 += 1;
-        } else if (40.1..=45.0).contains(&speed) {
-            self.s7 += 1;
-        } else if (45.1..=50.0).contains(&speed) {
-            self.s8 += 1;
-        } else if (50.1..=55.0).contains(&speed) {
-            self.s9 += 1;
-        } else if (55.1..=60.0).contains(&speed) {
-            self.s10 += 1;
-        } else if (60.1..=65.0).contains(&speed) {
-            self.s11 += 1;
-        } else if (65.1..=70.0).contains(&speed) {
-            self.s12 += 1;
-        } else if (70.1..=75.0).contains(&speed) {
-            self.s13 += 1;
-        } else if (75.1..).contains(&speed) {
-            self.s14 += 1;
+        match speed_range(speed) {
+            Some(1) => self.s1 += 1,
+            Some(2) => self.s2 += 1,
+            Some(3) => self.s3 += 1,
+            Some(4) => self.s4 += 1,
+            Some(5) => self.s5 += 1,
+            Some(6) => self.s6 += 1,
+            Some(7) => self.s7 += 1,
+            Some(8) => self.s8 += 1,
+            Some(9) => self.s9 += 1,
+            Some(10) => self.s10 += 1,
+            Some(11) => self.s11 += 1,
+            Some(12) => self.s12 += 1,
+            Some(13) => self.s13 += 1,
+            Some(14) => self.s14 += 1,
+            _ => (),
         }
         self.total += 1;
     }
 }
 
+/// Get the speed range (1-14, as in `s1`-`s14` of [`SpeedRangeCount`]) of an individual speed.
+///
+/// Speeds between the ranges (e.g. 15.05) aren't in any.
+pub fn speed_range(speed: f32) -> Option<usize> {
+    // The end of the ranges are inclusive to the number's .0 decimal;
+    // that is:
+    // 0-15: 0.0 to 15.0
+    // >15-20: 15.1 to 20.0, etc.
+
+    // Unfortunately, using floats as tests in pattern matching will be an error in a future
+    // Rust release, so need to do if/else rather than match.
+    // <https://github.com/rust-lang/rust/issues/41620>
+    // (Checking the sign shouldn't be necessary, but I saw a -0.0 in one of the files.)
+    if speed.is_sign_negative() || (0.0..=15.0).contains(&speed) {
+        Some(1)
+    } else if (15.1..=20.0).contains(&speed) {
+        Some(2)
+    } else if (20.1..=25.0).contains(&speed) {
+        Some(3)
+    } else if (25.1..=30.0).contains(&speed) {
+        Some(4)
+    } else if (30.1..=35.0).contains(&speed) {
+        Some(5)
+    } else if (35.1..=40.0).contains(&speed) {
+        Some(6)
+    } else if (40.1..=45.0).contains(&speed) {
+        Some(7)
+    } else if (45.1..=50.0).contains(&speed) {
+        Some(8)
+    } else if (50.1..=55.0).contains(&speed) {
+        Some(9)
+    } else if (55.1..=60.0).contains(&speed) {
+        Some(10)
+    } else if (60.1..=65.0).contains(&speed) {
+        Some(11)
+    } else if (65.1..=70.0).contains(&speed) {
+        Some(12)
+    } else if (70.1..=75.0).contains(&speed) {
+        Some(13)
+    } else if (75.1..).contains(&speed) {
+        Some(14)
+    } else {
+        None
+    }
+}
+
 /// The key for records of the TC_VOLCOUNT and TC_SPESUM tables.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub struct NonNormalCountKey {
     pub recordnum: u32,
     pub date: NaiveDate,
@@ -259,7 +284,7 @@ pub struct NonNormalVolCountValue {
 impl NonNormalVolCountValue {
     /// Create a NonNormalVolCountValue with `None` for everything except
     /// the total and the first hour/count.
-    /// (For the first time a new key is created in a HashMap.)
+    /// (For the first time a new key is created in a map.)
     pub fn first(count: &HourlyCount) -> Self {
         let mut value = Self {
             ..Default::default()
@@ -335,7 +360,7 @@ pub struct NonNormalAvgSpeedValue {
 impl NonNormalAvgSpeedValue {
     // Create new NonNormalAvgSpeedValue, including the first hourly average we calculate.
     // Subsequent hourly averages are added by modifying this instance (via the key in
-    // the HashMap this is the value for).
+    // the map this is the value for).
     pub fn first(hour_as_str: &str, average_speed: f32) -> Self {
         let mut value = Self {
             ..Default::default()
@@ -402,7 +427,7 @@ pub struct NonNormalRawSpeedValue {
 }
 impl NonNormalRawSpeedValue {
     /// Create a `NonNormalAvgSpeedValue` with empty Vecs.
-    /// (For the first time a new key is created in a HashMap.)
+    /// (For the first time a new key is created in a map.)
     pub fn first(hour: u32, speed: f32) -> Self {
         let mut value = Self {
             ..Default::default()
@@ -1,8 +1,31 @@
 //! This library contains data structures related to DVRPC's traffic counts
 //! and enables performing various kinds of operations on them, like
+//! [fetching][source] files to import from where they're uploaded,
+//! [unpacking][unpack] archives of files,
 //! [extracting][extract_from_file] data from files,
-//! [CRUD db operations][db::crud],
-//! and [denormalizing][denormalize] count data.
+//! [building][metadata_builder] the metadata of counts not named to the filename specification,
+//! checking counts' counters against the [inventory][counter_inventory] of them,
+//! [CRUD db operations][db::crud] (in our Oracle database or [elsewhere][db::store]),
+//! [denormalizing][denormalize] count data,
+//! [binning][rebin] counts again from their stored raw vehicles,
+//! [estimating AADT][aadt] with seasonal and day-of-week factors,
+//! [correcting][axle_correction] counts of axles to counts of vehicles,
+//! collapsing vehicle classes into other [classification schemes][class_scheme],
+//! totaling [both directions combined][combined_directions],
+//! [locating][location] counts by road, municipality, county, and coordinates,
+//! finding [peak hours][peak_hour], [headways][headway] between vehicles, the share of
+//! [heavy vehicles][heavy_vehicles], and [compliance][speed_compliance] with the speed limit,
+//! profiling [volume by time of day][volume_profile] and [day of the week][day_of_week],
+//! [exporting][export] processed data to files (including in [FHWA TMG formats][tmg], as Excel
+//! [workbooks][workbook], and as Arrow and Parquet files with the `parquet` feature),
+//! creating summary [reports][report] of counts,
+//! doing a [dry run][dry_run] of an import, [ordering][import_order] and [filtering][path_filter]
+//! the files to import,
+//! pulling counts from the Eco-Counter API (with the `eco-counter` feature),
+//! [summarizing][import_summary] an import (and keeping a [manifest] of its inputs and outputs)
+//! and emailing the summary (with the `email` feature),
+//! keeping [metrics][metrics] of the import program's health and serving its [status], and
+//! running it as a Windows service.
 //!
 //! The [import](../import/index.html) program implements extracting data from files
 //! and inserting it into our database. See its documentation for further details, including
@@ -10,9 +33,11 @@
 //!
 //! See <https://www.dvrpc.org/traffic/> for additional information about traffic counting.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::Display;
-use std::io;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufReader};
 use std::num::ParseIntError;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -21,14 +46,51 @@ use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
 use db::ImportLogEntry;
 use log::{error, Level, Log, Record};
 use oracle::{Connection, RowValue};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod aadt;
+pub mod axle_correction;
 pub mod check_data;
+pub mod class_scheme;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod combined_directions;
+pub mod counter_inventory;
+pub mod day_of_week;
 pub mod db;
 pub mod denormalize;
+pub mod dry_run;
+#[cfg(feature = "eco-counter")]
+pub mod eco_counter;
+#[cfg(feature = "email")]
+pub mod email;
+pub mod export;
 pub mod extract_from_file;
+pub mod headway;
+pub mod heavy_vehicles;
+pub mod import_order;
+pub mod import_summary;
 pub mod intermediate;
+pub mod location;
+pub mod log_file;
+pub mod manifest;
+pub mod metadata_builder;
+pub mod metrics;
+pub mod path_filter;
+pub mod peak_hour;
+pub mod rebin;
+pub mod report;
+#[cfg(windows)]
+pub mod service;
+pub mod source;
+pub mod speed_compliance;
+pub mod status;
+pub mod tmg;
+pub mod toml_date;
+pub mod unpack;
+pub mod volume_profile;
+pub mod workbook;
 use intermediate::*;
 
 /// A trait for getting a [`NaiveDate`](https://docs.rs/chrono/latest/chrono/struct.NaiveDate.html)
@@ -37,12 +99,32 @@ pub trait GetDate {
     fn get_date(&self) -> NaiveDate;
 }
 
+/// A trait for finding and removing records that are duplicates of one another, e.g. from rows
+/// repeated in a file.
+pub trait Dedup: Sized {
+    /// The fields that, if the same, mean two records are duplicates.
+    type Key: Eq + Hash;
+
+    fn dedup_key(&self) -> Self::Key;
+
+    /// Remove duplicates, keeping the first of each, and return the number removed.
+    fn remove_duplicates(records: &mut Vec<Self>) -> usize {
+        let num_records = records.len();
+        let mut seen = HashSet::new();
+        records.retain(|record| seen.insert(record.dedup_key()));
+        num_records - records.len()
+    }
+}
+
 /// Various errors that can occur.
+///
+/// All variants own their data (e.g. [`PathBuf`] rather than `&Path`), so errors can be collected,
+/// sent across threads, and stored after the file they relate to is no longer in scope.
 #[derive(Debug, Error)]
 pub enum CountError {
     #[error("unknown count type '{0}'")]
     UnknownCountType(String),
-    #[error("problem with file or directory path")]
+    #[error("problem with file or directory path '{0}'")]
     BadPath(PathBuf),
     #[error("unable to open file '{0}'")]
     CannotOpenFile(#[from] io::Error),
@@ -51,18 +133,38 @@ pub enum CountError {
         problem: FileNameProblem,
         path: PathBuf,
     },
+    #[error("the times of the records in {path:?} are implausible: {problem:?}")]
+    ImplausibleTimes { problem: TimeProblem, path: PathBuf },
     #[error("no matching count type for directory '{0}'")]
     BadLocation(String),
     #[error("no matching count type for header in '{0}'")]
     BadHeader(PathBuf),
     #[error("no such direction '{0}'")]
     BadDirection(String),
+    #[error("invalid combination of directions ({0})")]
+    InvalidDirections(String),
     #[error("mismatch in count types between file location ('{0}') and header of that file")]
     LocationHeaderMisMatch(PathBuf),
+    #[error("unable to tell which kind of count is in '{0}' from its header")]
+    AmbiguousCountType(PathBuf),
     #[error("mismatch in number of directions between filename ('{0}') and data in that file")]
     DirectionLenMisMatch(PathBuf),
+    #[error("records on channel(s) {0}, which no direction is mapped to")]
+    UnmappedChannels(String),
     #[error("cannot parse value as number")]
     ParseError(#[from] ParseIntError),
+    #[error("missing {0} field")]
+    MissingField(&'static str),
+    #[error("missing {0} column in header")]
+    MissingColumn(&'static str),
+    #[error("unable to parse {field} field ('{value}')")]
+    BadField { field: &'static str, value: String },
+    #[error("invalid {field} ('{value}')")]
+    InvalidField { field: &'static str, value: String },
+    #[error("counter '{0}' is not in the inventory of counters")]
+    UnknownCounter(String),
+    #[error("channels not mapped from directions can't be in a filename; use a sidecar file")]
+    ChannelsNotInFilename,
     #[error("no such vehicle class '{0}'")]
     BadVehicleClass(u8),
     #[error("unable to determine interval from count")]
@@ -71,16 +173,85 @@ pub enum CountError {
     HeadertoStringRecordError(#[from] csv::Error),
     #[error("invalid MCD ({0})")]
     InvalidMcd(String),
+    #[error("missing {0} in the count's metadata")]
+    MissingMetadata(&'static str),
+    #[error("station ID '{0}' is longer than 6 characters")]
+    InvalidStationId(String),
+    #[error("another import is already running (it has locked '{0}')")]
+    AlreadyRunning(PathBuf),
     #[error("inconsistent data in database")]
     InconsistentData,
+    #[error("no records from {0} on to append")]
+    NothingToAppend(NaiveDate),
+    #[error("no raw vehicles of count {0} are stored (it wasn't imported with IMPORT_STORE_RAW)")]
+    NoRawVehicles(u32),
     // Errors from database specifically handled/custom error messages.
     #[error("{0}")]
     DbError(String),
     // Errors from database passed through transparently without specific handling.
     #[error("database error '{0}'")]
     OracleError(#[from] oracle::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite database error '{0}'")]
+    SqliteError(#[from] rusqlite::Error),
     #[error("{0}")]
     DataCheckError(String),
+    #[error("unknown export format '{0}'")]
+    UnknownExportFormat(String),
+    #[error("unable to write Arrow/Parquet file: {0}")]
+    ColumnarError(String),
+    #[error("unknown TMG record type '{0}'")]
+    UnknownTmgRecordType(String),
+    #[error(
+        "cannot create {0} records (between 1 and {max} can be created at once)",
+        max = db::RECORD_CREATION_LIMIT
+    )]
+    InvalidRecordNumber(u32),
+    #[error("unknown database target '{0}'")]
+    UnknownDbTarget(String),
+    #[error(
+        "not writing to the production database without --confirm-prod (or DB_CONFIRM_PROD); \
+        use --target test for the test schema"
+    )]
+    ProdWriteNotConfirmed,
+    #[error("unknown operation '{0}'")]
+    UnknownOperation(String),
+    #[error("unknown sort field '{0}'")]
+    UnknownMetadataSort(String),
+    #[error("unknown header check '{0}'")]
+    UnknownHeaderCheck(String),
+    #[error("unknown option for partial periods '{0}'")]
+    UnknownPartialPeriods(String),
+    #[error("unknown import order '{0}'")]
+    UnknownImportOrder(String),
+    #[error("unable to estimate AADT: {0}")]
+    AdjustmentFactorError(String),
+    #[error("invalid axle correction factor: {0}")]
+    BadAxleFactor(String),
+    #[error("unknown class scheme '{0}'")]
+    UnknownClassScheme(String),
+    #[error("unknown log level '{0}'")]
+    UnknownLogLevel(String),
+    #[error("Eco-Counter API error: {0}")]
+    EcoCounterError(String),
+    #[error("unable to send email: {0}")]
+    EmailError(String),
+    #[error("unable to create report: {0}")]
+    ReportError(String),
+    #[error("Windows service error: {0}")]
+    ServiceError(String),
+    #[error("source error: {0}")]
+    SourceError(String),
+    #[error("unable to (de)serialize JSON data: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("unable to parse TOML config: {0}")]
+    TomlError(#[from] toml::de::Error),
+    #[error("unable to unpack archive: {0}")]
+    ArchiveError(#[from] zip::result::ZipError),
+    #[error("unable to write Excel workbook: {0}")]
+    XlsxError(#[from] rust_xlsxwriter::XlsxError),
+    #[error("invalid glob pattern: {0}")]
+    GlobError(#[from] globset::Error),
 }
 
 /// Identifying the problem when there's an error with a filename.
@@ -90,15 +261,42 @@ pub enum FileNameProblem {
     TooFewParts,
     InvalidRecordNum,
     InvalidDirections,
+    InvalidCounterId,
     InvalidSpeedLimit,
 }
 
+/// Identifying the problem when the times of the records in a file are implausible.
+///
+/// See [`check_times`](extract_from_file::check_times).
+#[derive(Debug)]
+pub enum TimeProblem {
+    /// A record's time is earlier than the one before it, as when a counter's clock is reset.
+    OutOfOrder {
+        previous: NaiveDateTime,
+        time: NaiveDateTime,
+    },
+    InFuture(NaiveDateTime),
+    TooOld(NaiveDateTime),
+    /// A record's time is outside the span the file's header says the count covers.
+    OutsideClaimedSpan(NaiveDateTime),
+    /// The count starts well after the time the file's header says it does.
+    LateStart {
+        claimed: NaiveDateTime,
+        first: NaiveDateTime,
+    },
+    /// The count ends well before the time the file's header says it does.
+    EarlyEnd {
+        claimed: NaiveDateTime,
+        last: NaiveDateTime,
+    },
+}
+
 /// All of the kinds of counts.
 ///
 /// These are all the types that are in both tc_header and tc_counttype tables.
 /// tc_countype doesn't include Video, that's only in tc_header.
 /// tc_header doesn't include EightDay or Loop, they're only in tc_counttype.
-#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum CountKind {
     Bicycle1,
     Bicycle2,
@@ -181,13 +379,27 @@ impl Display for CountKind {
 ///   - [TimeBinnedVehicleClassCount] by [create_speed_and_class_count]
 ///   - [TimeBinnedSpeedRangeCount] also by [create_speed_and_class_count]  
 ///   - [NonNormalAvgSpeedCount](denormalize::NonNormalAvgSpeedCount) by [denormalize::create_non_normal_speedavg_count]
-#[derive(Debug, Clone)]
+///
+/// Depending on its export options, a file may also include the number of axles, gap, headway,
+/// and length of each vehicle; these are `None` if it doesn't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndividualVehicle {
     pub date: NaiveDate,
     pub time: NaiveDateTime,
     pub lane: u8,
     pub class: VehicleClass,
     pub speed: f32,
+    #[serde(default)]
+    pub axles: Option<u8>,
+    /// The time, in seconds, between the back of the vehicle before and the front of this one.
+    #[serde(default)]
+    pub gap: Option<f32>,
+    /// The time, in seconds, between the front of the vehicle before and the front of this one.
+    #[serde(default)]
+    pub headway: Option<f32>,
+    /// The length of the vehicle, in feet.
+    #[serde(default)]
+    pub length: Option<f32>,
 }
 
 impl GetDate for IndividualVehicle {
@@ -211,14 +423,130 @@ impl IndividualVehicle {
             lane,
             class,
             speed,
+            axles: None,
+            gap: None,
+            headway: None,
+            length: None,
         })
     }
 }
 
+impl Dedup for IndividualVehicle {
+    /// Same time (to the second), lane, class, and speed.
+    type Key = (NaiveDateTime, u8, u8, u32);
+
+    fn dedup_key(&self) -> Self::Key {
+        (
+            self.time,
+            self.lane,
+            self.class.as_num(),
+            self.speed.to_bits(),
+        )
+    }
+}
+
+/// An [`IndividualVehicle`] as stored in the raw vehicle table (TC_RAWVEHICLE), so that a count
+/// can be binned again (into other intervals or speed ranges) without its original file.
+///
+/// Only its time, channel, class (by number), and speed are stored, along with the direction and
+/// lane its channel was mapped to when it was imported, so that it's binned again the same way.
+#[derive(Debug, Clone, RowValue, PartialEq, Serialize, Deserialize)]
+pub struct RawVehicle {
+    pub recordnum: u32,
+    #[row_value(rename = "countdate")]
+    pub date: NaiveDate,
+    #[row_value(rename = "counttime")]
+    pub time: NaiveDateTime,
+    pub channel: u8,
+    #[row_value(rename = "ctdir")]
+    pub direction: LaneDirection,
+    #[row_value(rename = "countlane")]
+    pub lane: u8,
+    pub class: u8,
+    pub speed: f32,
+}
+
+impl RawVehicle {
+    /// Create a raw vehicle from a vehicle counted on a channel mapped to `channel`.
+    pub fn new(recordnum: u32, vehicle: &IndividualVehicle, channel: Channel) -> Self {
+        Self {
+            recordnum,
+            date: vehicle.date,
+            time: vehicle.time,
+            channel: vehicle.lane,
+            direction: channel.direction,
+            lane: channel.lane,
+            class: vehicle.class.as_num(),
+            speed: vehicle.speed,
+        }
+    }
+
+    /// The direction and lane the vehicle's channel was mapped to.
+    pub fn mapped_channel(&self) -> Channel {
+        Channel {
+            direction: self.direction,
+            lane: self.lane,
+        }
+    }
+}
+
+impl TryFrom<RawVehicle> for IndividualVehicle {
+    type Error = CountError;
+
+    fn try_from(value: RawVehicle) -> Result<Self, Self::Error> {
+        IndividualVehicle::new(
+            value.date,
+            value.time,
+            value.channel,
+            value.class,
+            value.speed,
+        )
+    }
+}
+
+/// The direction and lane a channel of a count was mapped to when it was imported, as stored in
+/// the raw vehicle channel table (TC_RAWCHANNEL) along with its [raw vehicles](RawVehicle).
+///
+/// Every mapped channel is stored, including any that recorded nothing, so that the count is
+/// binned again with the same (zero-filled) lanes.
+#[derive(Debug, Clone, RowValue, PartialEq, Serialize, Deserialize)]
+pub struct RawChannel {
+    pub recordnum: u32,
+    pub channel: u8,
+    #[row_value(rename = "ctdir")]
+    pub direction: LaneDirection,
+    #[row_value(rename = "countlane")]
+    pub lane: u8,
+}
+
+impl RawChannel {
+    /// Create a raw vehicle channel for each of the channels of a count.
+    pub fn from_metadata(metadata: &FieldMetadata) -> Vec<Self> {
+        metadata
+            .channels
+            .iter()
+            .map(|(channel, mapped)| Self {
+                recordnum: metadata.recordnum,
+                channel: *channel,
+                direction: mapped.direction,
+                lane: mapped.lane,
+            })
+            .collect()
+    }
+
+    /// The direction and lane the channel was mapped to.
+    pub fn mapped_channel(&self) -> Channel {
+        Channel {
+            direction: self.direction,
+            lane: self.lane,
+        }
+    }
+}
+
 /// An individual bicycle that has been counted, with no binning applied to it.
 ///
 /// One kind of count can be derived from this type of data: [FifteenMinuteBicycle].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndividualBicycle {
     pub date: NaiveDate,
     pub time: NaiveDateTime,
@@ -241,7 +569,7 @@ impl IndividualBicycle {
 ///
 /// These are either pre-binned (data already grouped like this)
 /// or created from records of [`IndividualBicycle`]s.
-#[derive(Debug, Clone, RowValue, PartialEq)]
+#[derive(Debug, Clone, RowValue, PartialEq, Serialize, Deserialize)]
 pub struct FifteenMinuteBicycle {
     #[row_value(rename = "dvrpcnum")]
     pub recordnum: u32,
@@ -283,7 +611,10 @@ impl FifteenMinuteBicycle {
 }
 
 /// Pre-binned, 15-minute pedestrian volume counts.
-#[derive(Debug, Clone, RowValue)]
+///
+/// These come from Eco-Counter exports, which are laid out like [`FifteenMinuteBicycle`]
+/// exports, but may include a different number of rows before the header.
+#[derive(Debug, Clone, RowValue, PartialEq, Serialize, Deserialize)]
 pub struct FifteenMinutePedestrian {
     #[row_value(rename = "dvrpcnum")]
     pub recordnum: u32,
@@ -325,7 +656,7 @@ impl FifteenMinutePedestrian {
 }
 
 /// Pre-binned, 15-minute motor vehicle volume counts.
-#[derive(Debug, Clone, RowValue)]
+#[derive(Debug, Clone, RowValue, Serialize, Deserialize)]
 pub struct FifteenMinuteVehicle {
     pub recordnum: u32,
     #[row_value(rename = "countdate")]
@@ -367,7 +698,7 @@ impl FifteenMinuteVehicle {
 }
 
 /// The full metadata of a count, which corresponds to the "tc_header" table in the database.
-#[derive(Debug, Clone, PartialEq, RowValue, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, RowValue, Serialize, Deserialize)]
 pub struct Metadata {
     pub amending: Option<String>,
     pub ampeak: Option<f32>,
@@ -410,27 +741,200 @@ pub struct Metadata {
     pub sr: Option<String>,
     pub sri: Option<String>,
     pub stationid: Option<String>,
+    pub takenby: Option<String>,
     pub tolmt: Option<String>,
     pub trafdir: Option<RoadDirection>,
     pub x: Option<f32>,
     pub y: Option<f32>,
 }
 
+/// Name of a sidecar file containing the [`FieldMetadata`] of all input counts in its directory.
+pub const DIR_SIDECAR: &str = "metadata.json";
+
 /// The field metadata of an input count, which is a subset of the full [`Metadata`] and includes
 /// id, direction(s), count machine id, and - potentially - the speed limit.
 ///
 /// See the [import](../import/index.html) program for filename specification.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldMetadata {
     pub recordnum: u32,
     pub directions: Directions,
     pub counter_id: String,
     pub speed_limit: Option<u8>,
+    /// The direction and lane of each channel of the counter.
+    pub channels: BTreeMap<u8, Channel>,
 }
 
 impl FieldMetadata {
-    /// Get an input count's metadata from its path.
+    /// Create field metadata, with channels [mapped from directions][Directions::channels].
+    pub fn new(
+        recordnum: u32,
+        directions: Directions,
+        counter_id: String,
+        speed_limit: Option<u8>,
+    ) -> Self {
+        Self {
+            recordnum,
+            channels: directions.channels(),
+            directions,
+            counter_id,
+            speed_limit,
+        }
+    }
+
+    /// Get an input count's metadata from its [sidecar file][FieldMetadata::sidecar_path], if it
+    /// has one, or otherwise from its path.
     pub fn from_path(path: &Path) -> Result<Self, CountError> {
+        match Self::sidecar_path(path) {
+            Some(sidecar) => Self::from_sidecar(&sidecar),
+            None => Self::from_filename(path),
+        }
+    }
+
+    /// Find the sidecar metadata file of an input count, if there is one.
+    ///
+    /// This is a .json file with the same name as the input count (e.g. "count.json" for
+    /// "count.csv"), or, failing that, a [`DIR_SIDECAR`] file in the same directory.
+    pub fn sidecar_path(path: &Path) -> Option<PathBuf> {
+        let sidecar = path.with_extension("json");
+        if sidecar != path && sidecar.is_file() {
+            return Some(sidecar);
+        }
+        let sidecar = path.parent()?.join(DIR_SIDECAR);
+        sidecar.is_file().then_some(sidecar)
+    }
+
+    /// Get an input count's metadata from a sidecar metadata file.
+    ///
+    /// The file is JSON with the same fields as the filename specification, e.g.
+    /// `{"recordnum": 166905, "directions": "ew", "counter_id": "40972", "speed_limit": 35}`.
+    /// `speed_limit` can be null or omitted.
+    ///
+    /// For counters with more channels than directions in the filename specification can
+    /// describe, the direction and lane of each channel can also be included, e.g.
+    /// `"channels": {"1": {"direction": "n", "lane": 1}, "2": {"direction": "n", "lane": 2}, ...}`.
+    /// Otherwise, channels are [mapped from directions][Directions::channels]. Channels in the same
+    /// lane must be in the same direction.
+    pub fn from_sidecar(path: &Path) -> Result<Self, CountError> {
+        #[derive(Deserialize)]
+        struct SidecarChannel {
+            direction: String,
+            lane: u8,
+        }
+
+        #[derive(Deserialize)]
+        struct Sidecar {
+            recordnum: u32,
+            directions: String,
+            counter_id: String,
+            #[serde(default)]
+            speed_limit: Option<u8>,
+            channels: Option<BTreeMap<u8, SidecarChannel>>,
+        }
+
+        let sidecar: Sidecar = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+        let (directions, channels) = Self::parse_directions(&sidecar.directions)?;
+        let mut metadata = Self::new(
+            sidecar.recordnum,
+            directions,
+            sidecar.counter_id,
+            sidecar.speed_limit,
+        );
+        metadata.channels = channels;
+        if let Some(channels) = sidecar.channels {
+            metadata.channels = channels
+                .into_iter()
+                .map(|(channel, v)| {
+                    Ok((
+                        channel,
+                        Channel {
+                            direction: v.direction.parse()?,
+                            lane: v.lane,
+                        },
+                    ))
+                })
+                .collect::<Result<_, CountError>>()?;
+
+            // Counts are binned by lane, so each lane can only be in one direction.
+            let mut lanes = BTreeMap::new();
+            for channel in metadata.channels.values() {
+                let direction = *lanes.entry(channel.lane).or_insert(channel.direction);
+                if direction != channel.direction {
+                    return Err(CountError::InvalidField {
+                        field: "channels",
+                        value: format!(
+                            "lane {} is both {direction} and {}",
+                            channel.lane, channel.direction
+                        ),
+                    });
+                }
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// Describe how the speed limit and directions differ from those in `header`.
+    ///
+    /// Fields that `header` doesn't have are not compared. The first direction is compared to
+    /// `indir` and the second to `outdir`.
+    pub fn header_mismatches(&self, header: &Metadata) -> Vec<String> {
+        let mut mismatches = vec![];
+        if header.speedlimit.is_some() && header.speedlimit != self.speed_limit {
+            mismatches.push(format!(
+                "speed limit is {} but {} in TC_HEADER",
+                self.speed_limit
+                    .map_or("not given".to_string(), |v| v.to_string()),
+                header.speedlimit.unwrap_or_default(),
+            ));
+        }
+        if let Some(indir) = header.indir {
+            if indir != self.directions.direction1 {
+                mismatches.push(format!(
+                    "first direction is {} but indir is {indir} in TC_HEADER",
+                    self.directions.direction1
+                ));
+            }
+        }
+        if let Some(outdir) = header.outdir {
+            if Some(outdir) != self.directions.direction2 {
+                mismatches.push(format!(
+                    "second direction is {} but outdir is {outdir} in TC_HEADER",
+                    self.directions
+                        .direction2
+                        .map_or("not given".to_string(), |v| v.to_string())
+                ));
+            }
+        }
+        mismatches
+    }
+
+    /// Use the speed limit and directions from `header`, where it has them.
+    ///
+    /// If channels were [mapped from directions][Directions::channels_from], they are mapped
+    /// again. If the directions, with those from `header`, aren't [ones a count can
+    /// have][Directions::try_new], those from the filename are kept (but the speed limit is still
+    /// used), and the error is returned.
+    pub fn source_from_header(&mut self, header: &Metadata) -> Result<(), CountError> {
+        if header.speedlimit.is_some() {
+            self.speed_limit = header.speedlimit;
+        }
+        let first_channel = self.channels.keys().next().copied().unwrap_or(1);
+        let default_channels = self.channels == self.directions.channels_from(first_channel);
+        self.directions = Directions::try_new(
+            header.indir.unwrap_or(self.directions.direction1),
+            header.outdir.or(self.directions.direction2),
+            self.directions.direction3,
+        )?;
+        if default_channels {
+            self.channels = self.directions.channels_from(first_channel);
+        }
+        Ok(())
+    }
+
+    /// Get an input count's metadata from its filename.
+    pub fn from_filename(path: &Path) -> Result<Self, CountError> {
         let parts: Vec<&str> = path
             .file_stem()
             .ok_or(CountError::BadPath(path.to_owned()))?
@@ -462,40 +966,9 @@ impl FieldMetadata {
             }
         };
 
-        let directions: Directions = match parts[1] {
-            "nnn" => Directions::new(
-                LaneDirection::North,
-                Some(LaneDirection::North),
-                Some(LaneDirection::North),
-            ),
-            "sss" => Directions::new(
-                LaneDirection::South,
-                Some(LaneDirection::South),
-                Some(LaneDirection::South),
-            ),
-            "eee" => Directions::new(
-                LaneDirection::East,
-                Some(LaneDirection::East),
-                Some(LaneDirection::East),
-            ),
-            "www" => Directions::new(
-                LaneDirection::West,
-                Some(LaneDirection::West),
-                Some(LaneDirection::West),
-            ),
-            "ns" => Directions::new(LaneDirection::North, Some(LaneDirection::South), None),
-            "sn" => Directions::new(LaneDirection::South, Some(LaneDirection::North), None),
-            "ew" => Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
-            "we" => Directions::new(LaneDirection::West, Some(LaneDirection::East), None),
-            "nn" => Directions::new(LaneDirection::North, Some(LaneDirection::North), None),
-            "ss" => Directions::new(LaneDirection::South, Some(LaneDirection::South), None),
-            "ee" => Directions::new(LaneDirection::East, Some(LaneDirection::East), None),
-            "ww" => Directions::new(LaneDirection::West, Some(LaneDirection::West), None),
-            "n" => Directions::new(LaneDirection::North, None, None),
-            "s" => Directions::new(LaneDirection::South, None, None),
-            "e" => Directions::new(LaneDirection::East, None, None),
-            "w" => Directions::new(LaneDirection::West, None, None),
-            _ => {
+        let (directions, channels) = match Self::parse_directions(parts[1]) {
+            Ok(v) => v,
+            Err(_) => {
                 return Err(CountError::InvalidFileName {
                     problem: FileNameProblem::InvalidDirections,
                     path: path.to_owned(),
@@ -503,6 +976,12 @@ impl FieldMetadata {
             }
         };
 
+        if let Err(problem) = Self::check_counter_id(parts[2]) {
+            return Err(CountError::InvalidFileName {
+                problem,
+                path: path.to_owned(),
+            });
+        }
         let counter_id = parts[2].to_string();
 
         let speed_limit = if parts[3] == "na" {
@@ -519,24 +998,213 @@ impl FieldMetadata {
             }
         };
 
-        let metadata = Self {
-            recordnum,
-            directions,
-            counter_id,
-            speed_limit,
-        };
+        let mut metadata = Self::new(recordnum, directions, counter_id, speed_limit);
+        metadata.channels = channels;
 
         Ok(metadata)
     }
+
+    /// Check a counter ID as in the filename specification: it can't be empty, or contain the "-"
+    /// that separates the fields of the filename.
+    pub fn check_counter_id(counter_id: &str) -> Result<(), FileNameProblem> {
+        if counter_id.is_empty() || counter_id.contains('-') {
+            return Err(FileNameProblem::InvalidCounterId);
+        }
+        Ok(())
+    }
+
+    /// Create the name of a file of this count, to the filename specification, with `extension`
+    /// (e.g. "csv"); [`FieldMetadata::from_filename`] gets this metadata back from it.
+    ///
+    /// Only channels [mapped from directions][Directions::channels_from] can be described in a
+    /// filename; others need a [sidecar file][FieldMetadata::from_sidecar].
+    pub fn filename(&self, extension: &str) -> Result<String, CountError> {
+        Self::check_counter_id(&self.counter_id).map_err(|_| CountError::InvalidField {
+            field: "counter_id",
+            value: self.counter_id.clone(),
+        })?;
+        let first_channel = self.channels.keys().next().copied().unwrap_or(1);
+        if !(1..=8).contains(&first_channel)
+            || self.channels != self.directions.channels_from(first_channel)
+        {
+            return Err(CountError::ChannelsNotInFilename);
+        }
+
+        let directions = [
+            Some(self.directions.direction1),
+            self.directions.direction2,
+            self.directions.direction3,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|v| v.abbreviation())
+        .collect::<String>();
+        Ok(format!(
+            "{}-{}{directions}-{}-{}.{extension}",
+            self.recordnum,
+            "x".repeat(first_channel as usize - 1),
+            self.counter_id,
+            self.speed_limit.map_or("na".to_string(), |v| v.to_string()),
+        ))
+    }
+
+    /// Parse directions as in the filename specification, along with the channels they're on.
+    ///
+    /// The directions can be preceded by an "x" for each channel of the counter that isn't used,
+    /// e.g. "xe" for a count of one direction, east, on channel 2 (see
+    /// [`Directions::channels_from`]).
+    fn parse_directions(s: &str) -> Result<(Directions, BTreeMap<u8, Channel>), CountError> {
+        let directions = s.trim_start_matches(['x', 'X']);
+        let unused = u8::try_from(s.len() - directions.len())
+            .ok()
+            .filter(|v| *v < 8)
+            .ok_or_else(|| CountError::BadDirection(s.to_string()))?;
+        let directions: Directions = directions.parse()?;
+        let channels = directions.channels_from(unused + 1);
+        Ok((directions, channels))
+    }
+
+    /// Check that the records of a count, from the channels they're on, can be assigned a
+    /// direction and lane.
+    ///
+    /// This fails if there are records but none of them are on a channel that's mapped to a
+    /// direction, as when a count of one direction was taken on channel 2 but the file is named
+    /// as if it were on channel 1.
+    pub fn check_channels(&self, channels: impl IntoIterator<Item = u8>) -> Result<(), CountError> {
+        let mut unmapped = BTreeSet::new();
+        for channel in channels {
+            if self.channels.contains_key(&channel) {
+                return Ok(());
+            }
+            unmapped.insert(channel);
+        }
+        if unmapped.is_empty() {
+            return Ok(());
+        }
+        Err(CountError::UnmappedChannels(
+            unmapped
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ))
+    }
+
+    /// Find the records of a count, from the channel and time of each, that are on channels no
+    /// direction is mapped to (and so would be dropped).
+    pub fn unmapped_channels(
+        &self,
+        records: impl IntoIterator<Item = (u8, NaiveDateTime)>,
+    ) -> Vec<UnmappedChannel> {
+        let mut unmapped = BTreeMap::new();
+        for (channel, time) in records {
+            if !self.channels.contains_key(&channel) {
+                UnmappedChannel::add(&mut unmapped, channel, time);
+            }
+        }
+        unmapped.into_values().collect()
+    }
+}
+
+/// The records of a count on a channel that no direction is mapped to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnmappedChannel {
+    pub channel: u8,
+    pub records: usize,
+    /// The time of the first record.
+    pub first: NaiveDateTime,
+}
+
+impl UnmappedChannel {
+    /// Add a record on an unmapped channel to those of each channel.
+    fn add(unmapped: &mut BTreeMap<u8, Self>, channel: u8, time: NaiveDateTime) {
+        unmapped
+            .entry(channel)
+            .and_modify(|v| {
+                v.records += 1;
+                v.first = v.first.min(time);
+            })
+            .or_insert(Self {
+                channel,
+                records: 1,
+                first: time,
+            });
+    }
+}
+
+impl Display for UnmappedChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} records on channel {}, the first at {}",
+            self.records, self.channel, self.first
+        )
+    }
+}
+
+/// What to do when the speed limit or directions of an input count's [`FieldMetadata`] differ
+/// from those in its [`Metadata`] (TC_HEADER record).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderCheck {
+    /// Don't compare them.
+    #[default]
+    Off,
+    /// Warn about any differences, but use the field metadata.
+    Warn,
+    /// Don't import the count.
+    Error,
+    /// Use the speed limit and directions from TC_HEADER, where it has them.
+    Source,
+}
+
+impl FromStr for HeaderCheck {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(HeaderCheck::Off),
+            "warn" => Ok(HeaderCheck::Warn),
+            "error" => Ok(HeaderCheck::Error),
+            "source" => Ok(HeaderCheck::Source),
+            _ => Err(CountError::UnknownHeaderCheck(s.to_string())),
+        }
+    }
+}
+
+impl Display for HeaderCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let check = match self {
+            HeaderCheck::Off => "off",
+            HeaderCheck::Warn => "warn",
+            HeaderCheck::Error => "error",
+            HeaderCheck::Source => "source",
+        };
+        write!(f, "{}", check)
+    }
+}
+
+/// Strip the "bound" from a direction of travel, e.g. "nb" or "northbound".
+fn strip_bound(s: &str) -> Option<&str> {
+    s.strip_suffix("bound")
+        .or_else(|| s.strip_suffix('b'))
+        .filter(|v| !v.is_empty())
 }
 
 /// The direction of a road.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Deserialize)]
+///
+/// Like [`LaneDirection`], this is parsed from its name, abbreviation, or direction of travel, in
+/// any case (e.g. "north", "N", "NB", "northbound"), and serialized as its name ("North").
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum RoadDirection {
+    #[serde(alias = "north")]
     North,
+    #[serde(alias = "east")]
     East,
+    #[serde(alias = "south")]
     South,
+    #[serde(alias = "west")]
     West,
+    #[serde(alias = "both")]
     Both,
 }
 
@@ -544,7 +1212,8 @@ impl FromStr for RoadDirection {
     type Err = CountError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
+        let s_lower = s.trim().to_lowercase();
+        match strip_bound(&s_lower).unwrap_or(&s_lower) {
             "north" | "n" => Ok(RoadDirection::North),
             "east" | "e" => Ok(RoadDirection::East),
             "south" | "s" => Ok(RoadDirection::South),
@@ -567,29 +1236,107 @@ impl Display for RoadDirection {
         write!(f, "{}", dir)
     }
 }
+
+impl TryFrom<&str> for RoadDirection {
+    type Error = CountError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for RoadDirection {
+    type Error = CountError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RoadDirection> for String {
+    fn from(direction: RoadDirection) -> Self {
+        direction.to_string()
+    }
+}
+
 /// The direction of a lane.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Deserialize)]
+///
+/// This is the one place directions are converted from and to strings - filenames, sidecar and
+/// header files, database values, and the API all go through its [`FromStr`] and [`Display`]
+/// impls. It's parsed from its name, abbreviation, or direction of travel, in any case (e.g.
+/// "north", "N", "NB", "northbound"), and displayed as its name ("north").
+///
+/// It's serialized as its name capitalized ("North"), as it always has been in exports and the
+/// API, and deserialized from that or its name in lowercase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum LaneDirection {
+    #[serde(alias = "north")]
     North,
+    #[serde(alias = "east")]
     East,
+    #[serde(alias = "south")]
     South,
+    #[serde(alias = "west")]
     West,
+    #[serde(alias = "northeast")]
+    Northeast,
+    #[serde(alias = "northwest")]
+    Northwest,
+    #[serde(alias = "southeast")]
+    Southeast,
+    #[serde(alias = "southwest")]
+    Southwest,
 }
 
 impl FromStr for LaneDirection {
     type Err = CountError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
+        let s_lower = s.trim().to_lowercase();
+        match strip_bound(&s_lower).unwrap_or(&s_lower) {
             "north" | "n" => Ok(LaneDirection::North),
             "east" | "e" => Ok(LaneDirection::East),
             "south" | "s" => Ok(LaneDirection::South),
             "west" | "w" => Ok(LaneDirection::West),
+            "northeast" | "ne" => Ok(LaneDirection::Northeast),
+            "northwest" | "nw" => Ok(LaneDirection::Northwest),
+            "southeast" | "se" => Ok(LaneDirection::Southeast),
+            "southwest" | "sw" => Ok(LaneDirection::Southwest),
             _ => Err(CountError::BadDirection(s.to_string())),
         }
     }
 }
 
+impl LaneDirection {
+    /// Get the abbreviation of the direction, as used in filenames (e.g. "n" or "ne").
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            LaneDirection::North => "n",
+            LaneDirection::East => "e",
+            LaneDirection::South => "s",
+            LaneDirection::West => "w",
+            LaneDirection::Northeast => "ne",
+            LaneDirection::Northwest => "nw",
+            LaneDirection::Southeast => "se",
+            LaneDirection::Southwest => "sw",
+        }
+    }
+
+    /// Get the opposite direction.
+    pub fn opposite(&self) -> Self {
+        match self {
+            LaneDirection::North => LaneDirection::South,
+            LaneDirection::East => LaneDirection::West,
+            LaneDirection::South => LaneDirection::North,
+            LaneDirection::West => LaneDirection::East,
+            LaneDirection::Northeast => LaneDirection::Southwest,
+            LaneDirection::Northwest => LaneDirection::Southeast,
+            LaneDirection::Southeast => LaneDirection::Northwest,
+            LaneDirection::Southwest => LaneDirection::Northeast,
+        }
+    }
+}
+
 impl Display for LaneDirection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let dir = match self {
@@ -597,20 +1344,86 @@ impl Display for LaneDirection {
             LaneDirection::East => "east".to_string(),
             LaneDirection::South => "south".to_string(),
             LaneDirection::West => "west".to_string(),
+            LaneDirection::Northeast => "northeast".to_string(),
+            LaneDirection::Northwest => "northwest".to_string(),
+            LaneDirection::Southeast => "southeast".to_string(),
+            LaneDirection::Southwest => "southwest".to_string(),
         };
         write!(f, "{}", dir)
     }
 }
 
+impl TryFrom<&str> for LaneDirection {
+    type Error = CountError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for LaneDirection {
+    type Error = CountError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<LaneDirection> for String {
+    fn from(direction: LaneDirection) -> Self {
+        direction.to_string()
+    }
+}
+
 /// The [`LaneDirection`]s that a count could contain.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Directions {
     pub direction1: LaneDirection,
     pub direction2: Option<LaneDirection>,
     pub direction3: Option<LaneDirection>,
 }
 
+impl FromStr for Directions {
+    type Err = CountError;
+
+    /// Parse directions as in the filename specification, e.g. "ew", "eee", or "nesw".
+    ///
+    /// A count can have one direction, two that are the same or opposite, or three that are the
+    /// same.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_direction = || CountError::BadDirection(s.to_string());
+        let s_lower = s.to_lowercase();
+
+        // Split into individual directions, preferring intermediate directions (e.g. "ne") to
+        // cardinal ones. (Directions of travel, e.g. "nb", aren't used here, as "b" isn't a
+        // direction of its own.)
+        let mut directions = vec![];
+        let mut rest = s_lower.as_str();
+        while !rest.is_empty() {
+            let pair = rest.get(..2).filter(|v| !v.ends_with('b'));
+            let (direction, remaining) = match pair.map(LaneDirection::from_str) {
+                Some(Ok(v)) => (v, &rest[2..]),
+                _ => match rest.get(..1).map(LaneDirection::from_str) {
+                    Some(Ok(v)) => (v, &rest[1..]),
+                    _ => return Err(bad_direction()),
+                },
+            };
+            directions.push(direction);
+            rest = remaining;
+        }
+
+        match directions[..] {
+            [d1] => Directions::try_new(d1, None, None),
+            [d1, d2] => Directions::try_new(d1, Some(d2), None),
+            [d1, d2, d3] => Directions::try_new(d1, Some(d2), Some(d3)),
+            _ => Err(bad_direction()),
+        }
+        .map_err(|_| bad_direction())
+    }
+}
+
 impl Directions {
+    /// Create directions without checking them; see [`Directions::try_new`].
     pub fn new(
         direction1: LaneDirection,
         direction2: Option<LaneDirection>,
@@ -622,6 +1435,59 @@ impl Directions {
             direction3,
         }
     }
+
+    /// Create directions, checking that they're ones a count can have: one direction, two that
+    /// are the same or opposite, or three that are the same.
+    pub fn try_new(
+        direction1: LaneDirection,
+        direction2: Option<LaneDirection>,
+        direction3: Option<LaneDirection>,
+    ) -> Result<Self, CountError> {
+        let valid = match (direction2, direction3) {
+            (None, None) => true,
+            (Some(d2), None) => d2 == direction1 || d2 == direction1.opposite(),
+            (Some(d2), Some(d3)) => d2 == direction1 && d3 == direction1,
+            (None, Some(_)) => false,
+        };
+        if !valid {
+            return Err(CountError::InvalidDirections(
+                [Some(direction1), direction2, direction3]
+                    .into_iter()
+                    .flatten()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+        Ok(Self::new(direction1, direction2, direction3))
+    }
+
+    /// Map the channels of a counter to directions, in order, with each in its own lane:
+    /// channel 1 is `direction1` in lane 1, channel 2 is `direction2` in lane 2, and so on.
+    pub fn channels(&self) -> BTreeMap<u8, Channel> {
+        self.channels_from(1)
+    }
+
+    /// Map the channels of a counter from `first` on to directions, in order, with each in its
+    /// own lane: channel `first` is `direction1` in lane 1, the next channel is `direction2` in
+    /// lane 2, and so on. (The channels before `first` aren't used.)
+    pub fn channels_from(&self, first: u8) -> BTreeMap<u8, Channel> {
+        [Some(self.direction1), self.direction2, self.direction3]
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, direction)| {
+                let lane = i as u8 + 1;
+                direction.map(|direction| (first + i as u8, Channel { direction, lane }))
+            })
+            .collect()
+    }
+}
+
+/// The direction and lane that a channel of a counter records.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Channel {
+    pub direction: LaneDirection,
+    pub lane: u8,
 }
 
 /// Names of the 15 classifications from the FWA.
@@ -634,8 +1500,10 @@ impl Directions {
 ///  * <https://www.fhwa.dot.gov/policyinformation/vehclass.cfm>
 ///  * <https://www.fhwa.dot.gov/policyinformation/tmguide/tmg_2013/vehicle-types.cfm>
 ///  * <https://www.fhwa.dot.gov/publications/research/infrastructure/pavements/ltpp/13091/002.cfm>
+///
+/// Classes are ordered by their number, and displayed by their name.
 #[repr(u8)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum VehicleClass {
     Motorcycles = 1,
     PassengerCars = 2,
@@ -654,32 +1522,79 @@ pub enum VehicleClass {
 }
 
 impl VehicleClass {
+    /// All the classes, in order.
+    pub const ALL: [VehicleClass; 14] = [
+        VehicleClass::Motorcycles,
+        VehicleClass::PassengerCars,
+        VehicleClass::OtherFourTireSingleUnitVehicles,
+        VehicleClass::Buses,
+        VehicleClass::TwoAxleSixTireSingleUnitTrucks,
+        VehicleClass::ThreeAxleSingleUnitTrucks,
+        VehicleClass::FourOrMoreAxleSingleUnitTrucks,
+        VehicleClass::FourOrFewerAxleSingleTrailerTrucks,
+        VehicleClass::FiveAxleSingleTrailerTrucks,
+        VehicleClass::SixOrMoreAxleSingleTrailerTrucks,
+        VehicleClass::FiveOrFewerAxleMultiTrailerTrucks,
+        VehicleClass::SixAxleMultiTrailerTrucks,
+        VehicleClass::SevenOrMoreAxleMultiTrailerTrucks,
+        VehicleClass::UnclassifiedVehicle,
+    ];
+
+    /// Iterate over all the classes, in order.
+    pub fn iter() -> impl Iterator<Item = VehicleClass> {
+        Self::ALL.into_iter()
+    }
+
     /// Create a VehicleClass from a number.
     pub fn from_num(num: u8) -> Result<Self, CountError> {
         match num {
-            1 => Ok(VehicleClass::Motorcycles),
-            2 => Ok(VehicleClass::PassengerCars),
-            3 => Ok(VehicleClass::OtherFourTireSingleUnitVehicles),
-            4 => Ok(VehicleClass::Buses),
-            5 => Ok(VehicleClass::TwoAxleSixTireSingleUnitTrucks),
-            6 => Ok(VehicleClass::ThreeAxleSingleUnitTrucks),
-            7 => Ok(VehicleClass::FourOrMoreAxleSingleUnitTrucks),
-            8 => Ok(VehicleClass::FourOrFewerAxleSingleTrailerTrucks),
-            9 => Ok(VehicleClass::FiveAxleSingleTrailerTrucks),
-            10 => Ok(VehicleClass::SixOrMoreAxleSingleTrailerTrucks),
-            11 => Ok(VehicleClass::FiveOrFewerAxleMultiTrailerTrucks),
-            12 => Ok(VehicleClass::SixAxleMultiTrailerTrucks),
-            13 => Ok(VehicleClass::SevenOrMoreAxleMultiTrailerTrucks),
-            0 | 14 | 15 => Ok(VehicleClass::UnclassifiedVehicle),
-            other => Err(CountError::BadVehicleClass(other)),
+            0 | 14 => Ok(VehicleClass::UnclassifiedVehicle),
+            _ => Self::iter()
+                .find(|v| v.as_num() == num)
+                .ok_or(CountError::BadVehicleClass(num)),
         }
     }
+
+    /// Get the number of the class (15 for unclassified vehicles).
+    pub fn as_num(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl Display for VehicleClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let class = match self {
+            VehicleClass::Motorcycles => "motorcycles",
+            VehicleClass::PassengerCars => "passenger cars",
+            VehicleClass::OtherFourTireSingleUnitVehicles => "other four-tire single unit vehicles",
+            VehicleClass::Buses => "buses",
+            VehicleClass::TwoAxleSixTireSingleUnitTrucks => "two-axle, six-tire single unit trucks",
+            VehicleClass::ThreeAxleSingleUnitTrucks => "three-axle single unit trucks",
+            VehicleClass::FourOrMoreAxleSingleUnitTrucks => "four or more axle single unit trucks",
+            VehicleClass::FourOrFewerAxleSingleTrailerTrucks => {
+                "four or fewer axle single trailer trucks"
+            }
+            VehicleClass::FiveAxleSingleTrailerTrucks => "five-axle single trailer trucks",
+            VehicleClass::SixOrMoreAxleSingleTrailerTrucks => {
+                "six or more axle single trailer trucks"
+            }
+            VehicleClass::FiveOrFewerAxleMultiTrailerTrucks => {
+                "five or fewer axle multi-trailer trucks"
+            }
+            VehicleClass::SixAxleMultiTrailerTrucks => "six-axle multi-trailer trucks",
+            VehicleClass::SevenOrMoreAxleMultiTrailerTrucks => {
+                "seven or more axle multi-trailer trucks"
+            }
+            VehicleClass::UnclassifiedVehicle => "unclassified vehicles",
+        };
+        write!(f, "{}", class)
+    }
 }
 
-/// Count of [vehicles by class][`VehicleClass`], binned into 15-minute or hourly intervals.
+/// Count of [vehicles by class][`VehicleClass`], binned into [time intervals][TimeInterval].
 ///
-/// We almost always want fifteen-minute counts, but hourly is also an option.
-#[derive(Debug, Clone, RowValue)]
+/// We almost always want fifteen-minute counts, but 5-minute, 30-minute and hourly are also options.
+#[derive(Debug, Clone, RowValue, Serialize, Deserialize)]
 pub struct TimeBinnedVehicleClassCount {
     #[row_value(rename = "countdate")]
     pub date: NaiveDate,
@@ -721,10 +1636,32 @@ pub struct TimeBinnedVehicleClassCount {
     pub total: u32,
 }
 
-/// Count of vehicles by speed range, binned into 15-minute or hourly intervals.
+impl TimeBinnedVehicleClassCount {
+    /// Get the number of vehicles of a class.
+    pub fn class(&self, class: VehicleClass) -> u32 {
+        match class {
+            VehicleClass::Motorcycles => self.c1,
+            VehicleClass::PassengerCars => self.c2,
+            VehicleClass::OtherFourTireSingleUnitVehicles => self.c3,
+            VehicleClass::Buses => self.c4,
+            VehicleClass::TwoAxleSixTireSingleUnitTrucks => self.c5,
+            VehicleClass::ThreeAxleSingleUnitTrucks => self.c6,
+            VehicleClass::FourOrMoreAxleSingleUnitTrucks => self.c7,
+            VehicleClass::FourOrFewerAxleSingleTrailerTrucks => self.c8,
+            VehicleClass::FiveAxleSingleTrailerTrucks => self.c9,
+            VehicleClass::SixOrMoreAxleSingleTrailerTrucks => self.c10,
+            VehicleClass::FiveOrFewerAxleMultiTrailerTrucks => self.c11,
+            VehicleClass::SixAxleMultiTrailerTrucks => self.c12,
+            VehicleClass::SevenOrMoreAxleMultiTrailerTrucks => self.c13,
+            VehicleClass::UnclassifiedVehicle => self.c15.unwrap_or_default(),
+        }
+    }
+}
+
+/// Count of vehicles by speed range, binned into [time intervals][TimeInterval].
 ///
-/// We almost always want fifteen-minute counts, but hourly is also an option.
-#[derive(Debug, Clone, RowValue)]
+/// We almost always want fifteen-minute counts, but 5-minute, 30-minute and hourly are also options.
+#[derive(Debug, Clone, RowValue, Serialize, Deserialize)]
 pub struct TimeBinnedSpeedRangeCount {
     #[row_value(rename = "countdate")]
     pub date: NaiveDate,
@@ -753,39 +1690,48 @@ pub struct TimeBinnedSpeedRangeCount {
 }
 
 /// Create time-binned speed and class counts from [`IndividualVehicle`]s.
+///
+/// The vehicles are only iterated over once, so they can be streamed from a file (with an
+/// [`IndividualVehicleIter`][extract_from_file::IndividualVehicleIter]) rather than all loaded
+/// into memory first.
 pub fn create_speed_and_class_count(
     interval: TimeInterval,
     metadata: FieldMetadata,
-    mut counts: Vec<IndividualVehicle>,
+    counts: impl IntoIterator<Item = IndividualVehicle>,
 ) -> (
     Vec<TimeBinnedSpeedRangeCount>,
     Vec<TimeBinnedVehicleClassCount>,
 ) {
-    if counts.is_empty() {
-        return (vec![], vec![]);
-    }
-
-    let mut speed_range_map: HashMap<BinnedCountKey, SpeedRangeCount> = HashMap::new();
-    let mut vehicle_class_map: HashMap<BinnedCountKey, VehicleClassCount> = HashMap::new();
+    let mut speed_range_map: BTreeMap<BinnedCountKey, SpeedRangeCount> = BTreeMap::new();
+    let mut vehicle_class_map: BTreeMap<BinnedCountKey, VehicleClassCount> = BTreeMap::new();
+    // The dates/times of the first and last vehicles.
+    let mut span: Option<(NaiveDateTime, NaiveDateTime)> = None;
+
+    // Vehicles on channels without a direction, which are dropped.
+    let mut unmapped = BTreeMap::new();
+
+    for count in counts {
+        let datetime = NaiveDateTime::new(count.date, count.time.time());
+        span = match span {
+            Some((first, last)) => Some((first.min(datetime), last.max(datetime))),
+            None => Some((datetime, datetime)),
+        };
 
-    for count in counts.clone() {
-        // Get the direction from the lane of count/metadata of filename.
-        let direction = match count.lane {
-            1 => metadata.directions.direction1,
-            2 => metadata.directions.direction2.unwrap(),
-            3 => metadata.directions.direction3.unwrap(),
-            _ => {
-                error!("Unable to determine lane/direction.");
+        // Get the direction and lane from the channel of the count.
+        let Channel { direction, lane } = match metadata.channels.get(&count.lane) {
+            Some(v) => *v,
+            None => {
+                UnmappedChannel::add(&mut unmapped, count.lane, datetime);
                 continue;
             }
         };
 
-        // Create a key for the Hashmap for time intervals
+        // Create a key for the map for time intervals
         let time_part = bin_time(count.time.time(), interval);
         let key = BinnedCountKey {
             date: count.date,
             time: NaiveDateTime::new(count.date, time_part),
-            lane: count.lane,
+            lane,
         };
 
         // Add new entry to 15-min speed range map or increment existing one.
@@ -801,66 +1747,48 @@ pub fn create_speed_and_class_count(
         // Add new entry to 15-min vehicle class map or increment existing one.
         vehicle_class_map
             .entry(key)
-            .and_modify(|c| c.insert(count.class.clone()))
+            .and_modify(|c| c.insert(count.class))
             .or_insert(VehicleClassCount::first(
                 metadata.recordnum,
                 direction,
                 count.class,
             ));
     }
+    for channel in unmapped.values() {
+        error!("Dropped {channel}, as no direction is mapped to it.");
+    }
 
     /*
       If there was some time period (whose length is `TimeInterval`) where no vehicle was counted,
-      there will be no corresponding entry in our HashMap for it. However, that's because of the
+      there will be no corresponding entry in our map for it. However, that's because of the
       data we are using - `IndividualVehicle`s, which are vehicles that were counted - not because
       there is missing data for that time period. So create those where necessary.
     */
 
-    // Sort counts by date and time, get range, check if number of records is less than expected
-    // for every period to be included, insert any missing.
-    counts.sort_unstable_by_key(|c| (c.date, c.time.time()));
-
-    let first_dt = NaiveDateTime::new(
-        counts.first().unwrap().date,
-        counts.first().unwrap().time.time(),
-    );
-    let last_dt = NaiveDateTime::new(
-        counts.last().unwrap().date,
-        counts.last().unwrap().time.time(),
-    );
+    // Get the range of the counts, check if number of records is less than expected for every
+    // period to be included, insert any missing.
+    let Some((first_dt, last_dt)) = span else {
+        return (vec![], vec![]);
+    };
 
     let all_datetimes = create_time_bins(first_dt, last_dt, interval);
 
-    let mut all_keys = vec![];
-    let all_lanes = if metadata.directions.direction3.is_some() {
-        vec![1, 2, 3]
-    } else if metadata.directions.direction3.is_none() && metadata.directions.direction2.is_some() {
-        vec![1, 2]
-    } else {
-        vec![1]
-    };
-
     // construct all possible keys
+    let mut all_keys = vec![];
     for datetime in all_datetimes.clone() {
-        for lane in all_lanes.iter() {
-            all_keys.push(BinnedCountKey {
-                date: datetime.date(),
-                time: datetime,
-                lane: *lane,
-            })
+        for channel in metadata.channels.values() {
+            all_keys.push((
+                BinnedCountKey {
+                    date: datetime.date(),
+                    time: datetime,
+                    lane: channel.lane,
+                },
+                channel.direction,
+            ))
         }
     }
     // Add missing periods for speed range count
-    for key in all_keys {
-        let direction = match key.lane {
-            1 => metadata.directions.direction1,
-            2 => metadata.directions.direction2.unwrap(),
-            3 => metadata.directions.direction3.unwrap(),
-            _ => {
-                error!("Unable to determine lane/direction.");
-                continue;
-            }
-        };
+    for (key, direction) in all_keys {
         speed_range_map
             .entry(key)
             .or_insert(SpeedRangeCount::new(metadata.recordnum, direction));
@@ -869,7 +1797,7 @@ pub fn create_speed_and_class_count(
             .or_insert(VehicleClassCount::new(metadata.recordnum, direction));
     }
 
-    // Convert speed range count from HashMap to Vec.
+    // Convert speed range count from BTreeMap to Vec (so ordered by date/time and lane).
     let mut speed_range_count = vec![];
     for (key, value) in speed_range_map {
         speed_range_count.push(TimeBinnedSpeedRangeCount {
@@ -896,7 +1824,7 @@ pub fn create_speed_and_class_count(
         });
     }
 
-    // Convert vehicle class from HashMap to Vec.
+    // Convert vehicle class from BTreeMap to Vec (so ordered by date/time and lane).
     let mut vehicle_class_count = vec![];
     for (key, value) in vehicle_class_map {
         vehicle_class_count.push(TimeBinnedVehicleClassCount {
@@ -926,6 +1854,128 @@ pub fn create_speed_and_class_count(
     (speed_range_count, vehicle_class_count)
 }
 
+/// The number of speed ranges vehicles are binned into (`s1`-`s14`).
+pub const NUM_SPEED_RANGES: usize = 14;
+/// The number of classes in a [`TimeBinnedClassBySpeedCount`] (1-13 and unclassified).
+pub const NUM_CLASSES: usize = 14;
+
+/// Count of vehicles by both [class][`VehicleClass`] and speed range, binned into
+/// [time intervals][TimeInterval] for each direction.
+///
+/// This is for studies that need speeds broken out by class, e.g. those of trucks compared to
+/// those of passenger cars. Unlike in [`TimeBinnedVehicleClassCount`], unclassified vehicles are
+/// only counted on their own, not also as class 2.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeBinnedClassBySpeedCount {
+    pub date: NaiveDate,
+    pub time: NaiveDateTime,
+    pub recordnum: u32,
+    pub direction: LaneDirection,
+    /// The number of vehicles of each class (1-13, then unclassified) in each
+    /// [speed range][intermediate::speed_range] (1-14).
+    pub counts: [[u32; NUM_SPEED_RANGES]; NUM_CLASSES],
+    pub total: u32,
+}
+
+impl TimeBinnedClassBySpeedCount {
+    /// Create one with 0 count for every class and speed range.
+    pub fn new(recordnum: u32, time: NaiveDateTime, direction: LaneDirection) -> Self {
+        Self {
+            date: time.date(),
+            time,
+            recordnum,
+            direction,
+            counts: [[0; NUM_SPEED_RANGES]; NUM_CLASSES],
+            total: 0,
+        }
+    }
+
+    /// Insert an individual vehicle's class and speed into the count.
+    ///
+    /// As in [`SpeedRangeCount`], a speed that isn't in any range is only included in the total.
+    pub fn insert(&mut self, class: &VehicleClass, speed: f32) {
+        if let Some(range) = speed_range(speed) {
+            self.counts[Self::class_index(class)][range - 1] += 1;
+        }
+        self.total += 1;
+    }
+
+    /// Get the number of vehicles of a class in a speed range (1-14).
+    pub fn get(&self, class: &VehicleClass, speed_range: usize) -> u32 {
+        self.counts[Self::class_index(class)][speed_range - 1]
+    }
+
+    fn class_index(class: &VehicleClass) -> usize {
+        match class {
+            VehicleClass::UnclassifiedVehicle => NUM_CLASSES - 1,
+            other => other.as_num() as usize - 1,
+        }
+    }
+}
+
+/// Create time-binned counts of vehicles by both class and speed range from
+/// [`IndividualVehicle`]s, for each direction.
+///
+/// As with [`create_speed_and_class_count`], periods without any vehicles are included, and the
+/// counts are in order (by time, then direction).
+pub fn create_class_by_speed_count(
+    interval: TimeInterval,
+    metadata: FieldMetadata,
+    counts: impl IntoIterator<Item = IndividualVehicle>,
+) -> Vec<TimeBinnedClassBySpeedCount> {
+    let mut count_map: BTreeMap<(NaiveDateTime, LaneDirection), TimeBinnedClassBySpeedCount> =
+        BTreeMap::new();
+    // The dates/times of the first and last vehicles.
+    let mut span: Option<(NaiveDateTime, NaiveDateTime)> = None;
+
+    // Vehicles on channels without a direction, which are dropped.
+    let mut unmapped = BTreeMap::new();
+
+    for count in counts {
+        let datetime = NaiveDateTime::new(count.date, count.time.time());
+        span = match span {
+            Some((first, last)) => Some((first.min(datetime), last.max(datetime))),
+            None => Some((datetime, datetime)),
+        };
+
+        let Some(Channel { direction, .. }) = metadata.channels.get(&count.lane).copied() else {
+            UnmappedChannel::add(&mut unmapped, count.lane, datetime);
+            continue;
+        };
+        let time = NaiveDateTime::new(count.date, bin_time(count.time.time(), interval));
+        count_map
+            .entry((time, direction))
+            .or_insert_with(|| {
+                TimeBinnedClassBySpeedCount::new(metadata.recordnum, time, direction)
+            })
+            .insert(&count.class, count.speed);
+    }
+    for channel in unmapped.values() {
+        error!("Dropped {channel}, as no direction is mapped to it.");
+    }
+
+    // Add periods without any vehicles.
+    let Some((first_dt, last_dt)) = span else {
+        return vec![];
+    };
+    let mut directions = metadata
+        .channels
+        .values()
+        .map(|channel| channel.direction)
+        .collect::<Vec<_>>();
+    directions.sort();
+    directions.dedup();
+    for time in create_time_bins(first_dt, last_dt, interval) {
+        for direction in &directions {
+            count_map.entry((time, *direction)).or_insert_with(|| {
+                TimeBinnedClassBySpeedCount::new(metadata.recordnum, time, *direction)
+            });
+        }
+    }
+
+    count_map.into_values().collect()
+}
+
 /// Create time-binned bicycle volume count.
 pub fn create_binned_bicycle_vol_count(
     interval: TimeInterval,
@@ -936,10 +1986,10 @@ pub fn create_binned_bicycle_vol_count(
         return vec![];
     }
 
-    // Create key and value structs to put in the hashmap. Together they combine to include
-    // all the structs of `FifteenMinuteBicycle`, but we need them separate for the hashmap
+    // Create key and value structs to put in the map. Together they combine to include
+    // all the structs of `FifteenMinuteBicycle`, but we need them separate for the map
     // in order to keep updating them as we go through all the counts.
-    #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
     pub struct DataKey {
         pub date: NaiveDate,
         pub time: NaiveDateTime,
@@ -953,7 +2003,7 @@ pub fn create_binned_bicycle_vol_count(
         pub outdir: Option<u16>,
     }
 
-    let mut count_map: HashMap<DataKey, DataValues> = HashMap::new();
+    let mut count_map: BTreeMap<DataKey, DataValues> = BTreeMap::new();
 
     for count in counts.clone() {
         // Create a key for the Hashmap for time intervals
@@ -1006,7 +2056,7 @@ pub fn create_binned_bicycle_vol_count(
 
     /*
       If there was some time period (whose length is `TimeInterval`) where no vehicle was counted,
-      there will be no corresponding entry in our HashMap for it. However, that's because of the
+      there will be no corresponding entry in our map for it. However, that's because of the
       data we are using - `IndividualBicycle`s, which are bicycles that were counted - not because
       there is missing data for that time period. So create those where necessary.
     */
@@ -1045,7 +2095,7 @@ pub fn create_binned_bicycle_vol_count(
         });
     }
 
-    // Convert from HashMap to Vec.
+    // Convert from BTreeMap to Vec (so ordered by date/time).
     let mut bicycle_vol_count = vec![];
     for (key, value) in count_map {
         bicycle_vol_count.push(FifteenMinuteBicycle {
@@ -1064,7 +2114,7 @@ pub fn create_binned_bicycle_vol_count(
 /// Possible weather values.
 // TODO: needs fixed - this is just a guess
 // TODO: eventually how weather is entered needs overhauled
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Weather {
     Fair,
     Rain,
@@ -1072,29 +2122,127 @@ pub enum Weather {
 }
 
 /// Time interval to bin data by.
-#[derive(Clone, Copy)]
+///
+/// Each interval evenly divides an hour, so bins always start on the hour.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TimeInterval {
     Hour,
+    ThirtyMin,
     FifteenMin,
+    FiveMin,
 }
 
-/// Bin time by fifteen-minute or hourly intervals by changing the minute.
-pub fn bin_time(time: NaiveTime, interval: TimeInterval) -> NaiveTime {
-    let time = time.with_second(0).unwrap();
+impl TimeInterval {
+    /// The length of the interval, in minutes.
+    pub fn minutes(&self) -> u32 {
+        match self {
+            TimeInterval::Hour => 60,
+            TimeInterval::ThirtyMin => 30,
+            TimeInterval::FifteenMin => 15,
+            TimeInterval::FiveMin => 5,
+        }
+    }
+}
 
-    match interval {
-        TimeInterval::Hour => time.with_minute(0).unwrap(),
-        TimeInterval::FifteenMin => {
-            match time.minute() {
-                0..=14 => time.with_minute(0).unwrap(),
-                15..=29 => time.with_minute(15).unwrap(),
-                30..=44 => time.with_minute(30).unwrap(),
-                _ => time.with_minute(45).unwrap(), // minute is always 0-59, so this is 45-59
-            }
+/// What to do with the partial periods at the start and end of a count when binning or
+/// aggregating it.
+///
+/// A count rarely starts and ends exactly on the boundaries of the periods it's binned into, so
+/// its first and last periods (and days) usually only cover part of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialPeriods {
+    /// Keep them, importing everything.
+    #[default]
+    Keep,
+    /// Drop the first and last periods (of 15 minutes, an hour, etc.) if they're partial.
+    Periods,
+    /// Drop the first and last days if they're partial.
+    Days,
+}
+
+impl FromStr for PartialPeriods {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(PartialPeriods::Keep),
+            "periods" => Ok(PartialPeriods::Periods),
+            "days" => Ok(PartialPeriods::Days),
+            _ => Err(CountError::UnknownPartialPeriods(s.to_string())),
         }
     }
 }
 
+impl Display for PartialPeriods {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let partial_periods = match self {
+            PartialPeriods::Keep => "keep",
+            PartialPeriods::Periods => "periods",
+            PartialPeriods::Days => "days",
+        };
+        write!(f, "{}", partial_periods)
+    }
+}
+
+impl PartialPeriods {
+    /// Drop the records of a count that fall in its partial first and last periods, when
+    /// binning or aggregating them by `interval`, returning the number dropped.
+    ///
+    /// `binned` is the interval the records are already binned by (each covering that long from
+    /// its time), if they are; otherwise, they are of individual vehicles/bicycles. The count is
+    /// taken to cover from the time of the first record to the end of the last.
+    pub fn trim<T>(
+        self,
+        records: &mut Vec<T>,
+        interval: TimeInterval,
+        binned: Option<TimeInterval>,
+        time: impl Fn(&T) -> NaiveDateTime,
+    ) -> usize {
+        let period = match self {
+            PartialPeriods::Keep => return 0,
+            PartialPeriods::Periods => TimeDelta::minutes(interval.minutes().into()),
+            PartialPeriods::Days => TimeDelta::days(1),
+        };
+        let record_len = binned.map_or(TimeDelta::zero(), |v| {
+            TimeDelta::minutes(v.minutes().into())
+        });
+        let (Some(first), Some(last)) = (
+            records.iter().map(&time).min(),
+            records.iter().map(&time).max(),
+        ) else {
+            return 0;
+        };
+
+        // Round down to the start of a period.
+        let floor = |dt: NaiveDateTime| {
+            let midnight = dt.date().and_time(NaiveTime::MIN);
+            let periods = (dt - midnight).num_seconds() / period.num_seconds();
+            midnight + TimeDelta::seconds(periods * period.num_seconds())
+        };
+        let start = if floor(first) == first {
+            first
+        } else {
+            floor(first) + period
+        };
+        let end = floor(last + record_len);
+
+        let num_records = records.len();
+        records.retain(|record| {
+            let time = time(record);
+            time >= start && time < end && time + record_len <= end
+        });
+        num_records - records.len()
+    }
+}
+
+/// Bin time by an interval by changing the minute to the start of the interval it falls in.
+pub fn bin_time(time: NaiveTime, interval: TimeInterval) -> NaiveTime {
+    let time = time.with_second(0).unwrap();
+    let minute = time.minute();
+    time.with_minute(minute - minute % interval.minutes())
+        .unwrap()
+}
+
 /// Create all intervals between (and including) a first and last datetime.
 pub fn create_time_bins(
     first_dt: NaiveDateTime,
@@ -1109,10 +2257,7 @@ pub fn create_time_bins(
 
     let mut current_bin = first_bin;
 
-    let time_to_add = match interval {
-        TimeInterval::Hour => TimeDelta::hours(1),
-        TimeInterval::FifteenMin => TimeDelta::minutes(15),
-    };
+    let time_to_add = TimeDelta::minutes(interval.minutes().into());
 
     while current_bin <= last_bin {
         dts.push(current_bin);
@@ -1125,11 +2270,15 @@ pub fn create_time_bins(
 ///
 /// Since db function is fallible, just log any failure with it to stdout/file.
 /// Mostly just a DRY convenience function.
+///
+/// The records are marked with the [`DB_TARGET`][db::record_log::DB_TARGET] target, so that a
+/// [`RecordLog`][db::record_log::RecordLog] doesn't enter them into the database again.
 pub fn log_msg(recordnum: u32, log: impl Log, level: Level, message: &str, conn: &Connection) {
     log.log(
         &Record::builder()
             .args(format_args!("{recordnum}: {message}"))
             .level(level)
+            .target(db::record_log::DB_TARGET)
             .build(),
     );
 
@@ -1143,6 +2292,7 @@ pub fn log_msg(recordnum: u32, log: impl Log, level: Level, message: &str, conn:
                     "{recordnum}: Error entering log into database: {e}"
                 ))
                 .level(Level::Error)
+                .target(db::record_log::DB_TARGET)
                 .build(),
         );
     }
@@ -1152,6 +2302,19 @@ pub fn log_msg(recordnum: u32, log: impl Log, level: Level, message: &str, conn:
 mod tests {
     use super::*;
 
+    #[test]
+    fn count_error_is_owned_and_thread_safe() {
+        fn assert_static_send_sync<T: Send + Sync + 'static>() {}
+        assert_static_send_sync::<CountError>();
+
+        let err = {
+            let path = PathBuf::from("test_files/vehicle/166905-ew-40972-35.txt");
+            CountError::BadPath(path)
+        };
+        let err = std::thread::spawn(move || err.to_string()).join().unwrap();
+        assert!(err.contains("166905-ew-40972-35.txt"));
+    }
+
     #[test]
     fn time_binning_fifteen_min_is_correct() {
         // 1st 15-minute bin
@@ -1228,6 +2391,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn time_binning_five_and_thirty_min_is_correct() {
+        let time = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        assert_eq!(bin_time(time, TimeInterval::FiveMin), time);
+        assert_eq!(bin_time(time, TimeInterval::ThirtyMin), time);
+
+        let time = NaiveTime::from_hms_opt(10, 4, 59).unwrap();
+        assert_eq!(
+            bin_time(time, TimeInterval::FiveMin),
+            NaiveTime::from_hms_opt(10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            bin_time(time, TimeInterval::ThirtyMin),
+            NaiveTime::from_hms_opt(10, 0, 0).unwrap()
+        );
+
+        let time = NaiveTime::from_hms_opt(10, 37, 12).unwrap();
+        assert_eq!(
+            bin_time(time, TimeInterval::FiveMin),
+            NaiveTime::from_hms_opt(10, 35, 0).unwrap()
+        );
+        assert_eq!(
+            bin_time(time, TimeInterval::ThirtyMin),
+            NaiveTime::from_hms_opt(10, 30, 0).unwrap()
+        );
+
+        let time = NaiveTime::from_hms_opt(10, 59, 59).unwrap();
+        assert_eq!(
+            bin_time(time, TimeInterval::FiveMin),
+            NaiveTime::from_hms_opt(10, 55, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn create_time_bins_five_and_thirty_min_correct() {
+        let first_dt = NaiveDateTime::parse_from_str("2024-04-08 7:03", "%Y-%m-%d %-H:%M").unwrap();
+        let last_dt = NaiveDateTime::parse_from_str("2024-04-08 8:01", "%Y-%m-%d %-H:%M").unwrap();
+        assert_eq!(
+            create_time_bins(first_dt, last_dt, TimeInterval::FiveMin).len(),
+            13
+        );
+        assert_eq!(
+            create_time_bins(first_dt, last_dt, TimeInterval::ThirtyMin).len(),
+            3
+        );
+    }
+
     #[test]
     fn create_time_bins_correct() {
         let first_dt = NaiveDateTime::parse_from_str("2024-04-08 7:00", "%Y-%m-%d %-H:%M").unwrap();
@@ -1288,4 +2498,319 @@ mod tests {
         assert_eq!(keys_15.len(), 5);
         assert_eq!(keys_hour.len(), 2);
     }
+
+    #[test]
+    fn header_mismatches_found_and_sourced_from_header() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let mut metadata = FieldMetadata::from_filename(path).unwrap();
+        let header: Metadata =
+            serde_json::from_str(r#"{"speedlimit": 25, "indir": "East", "outdir": "West"}"#)
+                .unwrap();
+        let mismatches = metadata.header_mismatches(&header);
+        assert_eq!(mismatches, vec!["speed limit is 35 but 25 in TC_HEADER"]);
+
+        let header: Metadata =
+            serde_json::from_str(r#"{"indir": "West", "outdir": "East"}"#).unwrap();
+        assert_eq!(metadata.header_mismatches(&header).len(), 2);
+        metadata.source_from_header(&header).unwrap();
+        assert_eq!(metadata.directions.direction1, LaneDirection::West);
+        assert_eq!(metadata.directions.direction2, Some(LaneDirection::East));
+        assert_eq!(metadata.speed_limit, Some(35));
+        assert_eq!(metadata.channels[&1].direction, LaneDirection::West);
+        assert!(metadata.header_mismatches(&header).is_empty());
+
+        // Directions a count can't have aren't used, but the speed limit still is.
+        let header: Metadata =
+            serde_json::from_str(r#"{"speedlimit": 25, "indir": "North"}"#).unwrap();
+        assert!(matches!(
+            metadata.source_from_header(&header),
+            Err(CountError::InvalidDirections(v)) if v == "north, east"
+        ));
+        assert_eq!(metadata.directions.direction1, LaneDirection::West);
+        assert_eq!(metadata.channels[&2].direction, LaneDirection::East);
+        assert_eq!(metadata.speed_limit, Some(25));
+    }
+
+    #[test]
+    fn vehicle_classes_round_trip_numbers_in_order() {
+        for class in VehicleClass::iter() {
+            assert_eq!(VehicleClass::from_num(class.as_num()).unwrap(), class);
+        }
+        let nums = VehicleClass::iter().map(|v| v.as_num()).collect::<Vec<_>>();
+        assert_eq!(nums, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 15]);
+        assert!(VehicleClass::Buses < VehicleClass::FiveAxleSingleTrailerTrucks);
+        assert_eq!(
+            VehicleClass::from_num(14).unwrap(),
+            VehicleClass::UnclassifiedVehicle
+        );
+        assert!(VehicleClass::from_num(16).is_err());
+        assert_eq!(VehicleClass::Buses.to_string(), "buses");
+    }
+
+    #[test]
+    fn raw_vehicles_binned_again_as_originally() {
+        use crate::extract_from_file::Extract;
+
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let raw_vehicles = counted_vehicles
+            .iter()
+            .map(|v| RawVehicle::new(166905, v, metadata.channels[&v.lane]))
+            .collect::<Vec<_>>();
+        assert_eq!(raw_vehicles[0].mapped_channel(), metadata.channels[&1]);
+        let rebinned = raw_vehicles
+            .into_iter()
+            .map(IndividualVehicle::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let (_, original) =
+            create_speed_and_class_count(TimeInterval::Hour, metadata.clone(), counted_vehicles);
+        let (_, binned_again) =
+            create_speed_and_class_count(TimeInterval::Hour, metadata, rebinned);
+        let totals = |counts: &[TimeBinnedVehicleClassCount]| {
+            counts
+                .iter()
+                .map(|v| (v.time, v.lane, v.direction, v.total))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(totals(&original), totals(&binned_again));
+    }
+
+    #[test]
+    fn directions_parsed_from_names_abbreviations_and_travel() {
+        for s in [
+            "north",
+            "North",
+            "N",
+            "n",
+            "NB",
+            "northbound",
+            " Northbound ",
+        ] {
+            assert_eq!(LaneDirection::from_str(s).unwrap(), LaneDirection::North);
+        }
+        assert_eq!(
+            LaneDirection::try_from("SWB").unwrap(),
+            LaneDirection::Southwest
+        );
+        assert_eq!(RoadDirection::try_from("b").unwrap(), RoadDirection::Both);
+        assert_eq!(RoadDirection::try_from("EB").unwrap(), RoadDirection::East);
+        assert!(LaneDirection::from_str("b").is_err());
+        assert!(LaneDirection::from_str("up").is_err());
+
+        assert_eq!(
+            serde_json::to_string(&LaneDirection::Northeast).unwrap(),
+            r#""Northeast""#
+        );
+        assert_eq!(
+            serde_json::to_string(&RoadDirection::Both).unwrap(),
+            r#""Both""#
+        );
+        for s in [r#""South""#, r#""south""#] {
+            let direction: LaneDirection = serde_json::from_str(s).unwrap();
+            assert_eq!(direction, LaneDirection::South);
+        }
+        assert!(serde_json::from_str::<LaneDirection>(r#""SB""#).is_err());
+        assert_eq!(
+            LaneDirection::West
+                .to_string()
+                .parse::<LaneDirection>()
+                .unwrap(),
+            LaneDirection::West
+        );
+
+        // Directions of travel aren't accepted in filenames.
+        assert!(FieldMetadata::from_filename(Path::new("data/vehicle/1-nb-4-35.csv")).is_err());
+    }
+
+    #[test]
+    fn directions_stored_as_values_allowed_by_db() {
+        // The values of the direction columns' check constraints (see db_migrations.sql).
+        let allowed = [
+            "north",
+            "east",
+            "west",
+            "south",
+            "northeast",
+            "northwest",
+            "southeast",
+            "southwest",
+        ];
+        for direction in [
+            LaneDirection::North,
+            LaneDirection::East,
+            LaneDirection::South,
+            LaneDirection::West,
+            LaneDirection::Northeast,
+            LaneDirection::Northwest,
+            LaneDirection::Southeast,
+            LaneDirection::Southwest,
+        ] {
+            // (Directions are bound and read as their Display/FromStr strings.)
+            let value = direction.to_string();
+            assert!(allowed.contains(&value.as_str()), "{value}");
+            assert_eq!(value.parse::<LaneDirection>().unwrap(), direction);
+        }
+    }
+
+    #[test]
+    fn unused_channels_skipped_in_directions() {
+        let path = Path::new("data/vehicle/166905-xe-40972-35.csv");
+        let mut metadata = FieldMetadata::from_filename(path).unwrap();
+        assert_eq!(metadata.directions.direction1, LaneDirection::East);
+        assert_eq!(metadata.directions.direction2, None);
+        assert_eq!(
+            metadata.channels.clone().into_iter().collect::<Vec<_>>(),
+            vec![(
+                2,
+                Channel {
+                    direction: LaneDirection::East,
+                    lane: 1
+                }
+            )]
+        );
+        assert!(metadata.check_channels([2, 2]).is_ok());
+        assert!(metadata.check_channels([1, 2]).is_ok());
+
+        let header: Metadata = serde_json::from_str(r#"{"indir": "West"}"#).unwrap();
+        metadata.source_from_header(&header).unwrap();
+        assert_eq!(metadata.channels[&2].direction, LaneDirection::West);
+
+        let metadata =
+            FieldMetadata::from_filename(Path::new("data/vehicle/166905-e-40972-35.csv")).unwrap();
+        assert!(matches!(
+            metadata.check_channels([2, 3]),
+            Err(CountError::UnmappedChannels(v)) if v == "2, 3"
+        ));
+        assert!(metadata.check_channels([]).is_ok());
+        assert!(FieldMetadata::from_filename(Path::new("data/vehicle/1-xx-4-35.csv")).is_err());
+    }
+
+    #[test]
+    fn filename_parsed_back_to_metadata() {
+        for filename in [
+            "166905-ew-40972-35.csv",
+            "165367-ee-40972-35.csv",
+            "123456-s-101-na.txt",
+            "166905-xxnesw-40972-45.csv",
+        ] {
+            let metadata = FieldMetadata::from_filename(Path::new(filename)).unwrap();
+            let extension = filename.rsplit('.').next().unwrap();
+            assert_eq!(metadata.filename(extension).unwrap(), filename);
+        }
+
+        let mut metadata =
+            FieldMetadata::from_filename(Path::new("166905-ee-40972-35.csv")).unwrap();
+        metadata.channels.get_mut(&2).unwrap().lane = 3;
+        assert!(matches!(
+            metadata.filename("csv"),
+            Err(CountError::ChannelsNotInFilename)
+        ));
+    }
+
+    #[test]
+    fn filename_and_parsing_agree_on_counter_ids() {
+        for counter_id in ["40972", "40.972", "A1"] {
+            let filename = format!("166905-e-{counter_id}-35.csv");
+            let metadata = FieldMetadata::from_filename(Path::new(&filename)).unwrap();
+            assert_eq!(metadata.counter_id, counter_id);
+            assert_eq!(metadata.filename("csv").unwrap(), filename);
+        }
+
+        for counter_id in ["", "40-972"] {
+            assert!(FieldMetadata::check_counter_id(counter_id).is_err());
+            let metadata = FieldMetadata::new(
+                166905,
+                Directions::new(LaneDirection::East, None, None),
+                counter_id.to_string(),
+                Some(35),
+            );
+            assert!(matches!(
+                metadata.filename("csv"),
+                Err(CountError::InvalidField {
+                    field: "counter_id",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn unmapped_channels_counted_from_first_record() {
+        let dt = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let metadata =
+            FieldMetadata::from_filename(Path::new("data/vehicle/166905-e-40972-35.csv")).unwrap();
+        let unmapped = metadata.unmapped_channels([
+            (1, dt("2024-04-08 10:00")),
+            (3, dt("2024-04-08 10:05")),
+            (2, dt("2024-04-08 10:10")),
+            (3, dt("2024-04-08 10:01")),
+        ]);
+        assert_eq!(
+            unmapped,
+            vec![
+                UnmappedChannel {
+                    channel: 2,
+                    records: 1,
+                    first: dt("2024-04-08 10:10"),
+                },
+                UnmappedChannel {
+                    channel: 3,
+                    records: 2,
+                    first: dt("2024-04-08 10:01"),
+                },
+            ]
+        );
+        assert_eq!(
+            unmapped[1].to_string(),
+            "2 records on channel 3, the first at 2024-04-08 10:01:00"
+        );
+    }
+
+    #[test]
+    fn partial_periods_trimmed() {
+        let dt = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let individual = ["2024-04-08 10:55", "2024-04-08 11:00", "2024-04-08 11:20"]
+            .map(dt)
+            .to_vec();
+
+        let mut records = individual.clone();
+        let trimmed =
+            PartialPeriods::Keep.trim(&mut records, TimeInterval::FifteenMin, None, |v| *v);
+        assert_eq!(trimmed, 0);
+
+        // 10:55 is in the partial first period and 11:20 in the partial last one.
+        let trimmed =
+            PartialPeriods::Periods.trim(&mut records, TimeInterval::FifteenMin, None, |v| *v);
+        assert_eq!(trimmed, 2);
+        assert_eq!(records, vec![dt("2024-04-08 11:00")]);
+
+        // Binned records cover whole periods, so only those in partial hours are dropped.
+        let mut records = create_time_bins(
+            dt("2024-04-08 10:30"),
+            dt("2024-04-09 23:45"),
+            TimeInterval::FifteenMin,
+        );
+        let mut days = records.clone();
+        let trimmed = PartialPeriods::Periods.trim(
+            &mut records,
+            TimeInterval::Hour,
+            Some(TimeInterval::FifteenMin),
+            |v| *v,
+        );
+        assert_eq!(trimmed, 2);
+        assert_eq!(records[0], dt("2024-04-08 11:00"));
+        assert_eq!(records.last(), Some(&dt("2024-04-09 23:45")));
+
+        let trimmed = PartialPeriods::Days.trim(
+            &mut days,
+            TimeInterval::Hour,
+            Some(TimeInterval::FifteenMin),
+            |v| *v,
+        );
+        assert_eq!(trimmed, 54);
+        assert_eq!(days.len(), 96);
+    }
 }
@@ -0,0 +1,266 @@
+//! Where counts were taken: road, municipality, county, and coordinates.
+//!
+//! A count's [location][Location] is mostly from its TC_HEADER record (the road and its prefix
+//! and suffix, its MCD, and its latitude and longitude), with the county from the first five
+//! digits of its MCD. Where those are missing or out of date, and to name the municipality, they
+//! can be filled in from a GeoJSON file of [locations][Locations], set by the `LOCATIONS_GEOJSON`
+//! env var: a FeatureCollection of Point features with the recordnum of each count (and any of
+//! "road", "municipality", and "county") in their properties, like:
+//! ```json
+//! {"type": "FeatureCollection", "features": [
+//!   {"type": "Feature", "geometry": {"type": "Point", "coordinates": [-75.16, 39.95]},
+//!    "properties": {"recordnum": 166905, "road": "Market St", "municipality": "Philadelphia"}}
+//! ]}
+//! ```
+//!
+//! (Our database has no location table other than TC_HEADER itself, so anything it doesn't have
+//! comes from the GeoJSON file.)
+//!
+//! Locations are served with a count's metadata by the API, and
+//! [exported][crate::export::export_location] alongside counts created from individual vehicles,
+//! and in Excel [workbooks][crate::workbook].
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CountError, Metadata};
+
+/// The names of the counties of the DVRPC region, by code.
+const COUNTIES: [(&str, &str); 9] = [
+    ("34005", "Burlington"),
+    ("34007", "Camden"),
+    ("34015", "Gloucester"),
+    ("34021", "Mercer"),
+    ("42017", "Bucks"),
+    ("42029", "Chester"),
+    ("42045", "Delaware"),
+    ("42091", "Montgomery"),
+    ("42101", "Philadelphia"),
+];
+
+/// Where a count was taken.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub recordnum: u32,
+    /// The road, with its prefix and suffix (e.g. "N Broad St").
+    pub road: Option<String>,
+    /// The municipality (minor civil division) of the count, as its 10-digit code.
+    pub mcd: Option<String>,
+    pub municipality: Option<String>,
+    /// The county of the count, as its 5-digit code.
+    pub county: Option<String>,
+    pub county_name: Option<String>,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+}
+
+impl Location {
+    /// Get the location of a count from its metadata.
+    pub fn from_metadata(recordnum: u32, metadata: &Metadata) -> Self {
+        let road = [&metadata.rdprefix, &metadata.road, &metadata.rdsuffix]
+            .into_iter()
+            .flatten()
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+        let county = metadata
+            .mcd
+            .as_ref()
+            .and_then(|v| v.get(..5))
+            .map(|v| v.to_string());
+        Self {
+            recordnum,
+            road: metadata.road.as_ref().map(|_| road.join(" ")),
+            mcd: metadata.mcd.clone(),
+            municipality: None,
+            county_name: county.as_deref().and_then(county_name),
+            county,
+            latitude: metadata.latitude,
+            longitude: metadata.longitude,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let county = self.county_name.as_ref().map(|v| format!("{v} County"));
+        let place = [&self.road, &self.municipality, &county]
+            .into_iter()
+            .flatten()
+            .map(|v| v.as_str())
+            .collect::<Vec<_>>();
+        write!(f, "{}: ", self.recordnum)?;
+        if place.is_empty() {
+            write!(f, "unknown location")?;
+        } else {
+            write!(f, "{}", place.join(", "))?;
+        }
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            write!(f, " ({latitude}, {longitude})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the name of a county in the DVRPC region from its code.
+pub fn county_name(code: &str) -> Option<String> {
+    COUNTIES
+        .iter()
+        .find(|(v, _)| *v == code)
+        .map(|(_, name)| name.to_string())
+}
+
+/// The locations of counts from a GeoJSON file, which fill in or replace those from TC_HEADER.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Locations {
+    locations: BTreeMap<u32, Location>,
+}
+
+#[derive(Deserialize)]
+struct FeatureCollection {
+    features: Vec<Feature>,
+}
+
+#[derive(Deserialize)]
+struct Feature {
+    geometry: Option<Geometry>,
+    properties: FeatureProperties,
+}
+
+#[derive(Deserialize)]
+struct Geometry {
+    #[serde(rename = "type")]
+    kind: String,
+    coordinates: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct FeatureProperties {
+    recordnum: u32,
+    road: Option<String>,
+    municipality: Option<String>,
+    county: Option<String>,
+}
+
+impl Locations {
+    /// Get the locations from a GeoJSON file.
+    pub fn from_file(path: &Path) -> Result<Self, CountError> {
+        let features: FeatureCollection =
+            serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let locations = features
+            .features
+            .into_iter()
+            .map(|feature| {
+                // Only points have a coordinate of their own.
+                let coordinates = feature
+                    .geometry
+                    .filter(|v| v.kind == "Point")
+                    .and_then(|v| serde_json::from_value::<[f32; 2]>(v.coordinates).ok());
+                let properties = feature.properties;
+                let location = Location {
+                    recordnum: properties.recordnum,
+                    road: properties.road,
+                    mcd: None,
+                    municipality: properties.municipality,
+                    county_name: properties.county.as_deref().and_then(county_name),
+                    county: properties.county,
+                    latitude: coordinates.map(|v| v[1]),
+                    longitude: coordinates.map(|v| v[0]),
+                };
+                (location.recordnum, location)
+            })
+            .collect();
+        Ok(Self { locations })
+    }
+
+    /// Get the locations from the file set by the `LOCATIONS_GEOJSON` env var, or none if it
+    /// isn't set.
+    pub fn from_env() -> Result<Self, CountError> {
+        match env::var("LOCATIONS_GEOJSON") {
+            Ok(path) => Self::from_file(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Get the location of a count from the GeoJSON file alone, if it's there.
+    pub fn get(&self, recordnum: u32) -> Option<&Location> {
+        self.locations.get(&recordnum)
+    }
+
+    /// Get the location of a count from its metadata, with any fields of its location here in
+    /// their place.
+    pub fn locate(&self, recordnum: u32, metadata: &Metadata) -> Location {
+        let mut location = Location::from_metadata(recordnum, metadata);
+        if let Some(v) = self.locations.get(&recordnum) {
+            location.road = v.road.clone().or(location.road);
+            location.municipality = v.municipality.clone().or(location.municipality);
+            if v.county.is_some() {
+                location.county = v.county.clone();
+                location.county_name = v.county_name.clone();
+            }
+            if v.latitude.is_some() && v.longitude.is_some() {
+                location.latitude = v.latitude;
+                location.longitude = v.longitude;
+            }
+        }
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_from_metadata_filled_in_from_geojson() {
+        let metadata = Metadata {
+            recordnum: Some(166905),
+            rdprefix: Some("N".to_string()),
+            road: Some("Broad".to_string()),
+            rdsuffix: Some("St".to_string()),
+            mcd: Some("4210160000".to_string()),
+            latitude: Some(39.96),
+            longitude: Some(-75.16),
+            ..Default::default()
+        };
+        let location = Locations::default().locate(166905, &metadata);
+        assert_eq!(location.road, Some("N Broad St".to_string()));
+        assert_eq!(location.county, Some("42101".to_string()));
+        assert_eq!(location.county_name, Some("Philadelphia".to_string()));
+        assert_eq!(location.municipality, None);
+        assert_eq!(location.latitude, Some(39.96));
+
+        let path = env::temp_dir().join("traffic_counts_locations.geojson");
+        std::fs::write(
+            &path,
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature",
+                 "geometry": {"type": "Point", "coordinates": [-75.163, 39.953]},
+                 "properties": {"recordnum": 166905, "municipality": "Philadelphia"}}
+            ]}"#,
+        )
+        .unwrap();
+        let locations = Locations::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let location = locations.locate(166905, &metadata);
+        assert_eq!(location.road, Some("N Broad St".to_string()));
+        assert_eq!(location.municipality, Some("Philadelphia".to_string()));
+        assert_eq!(location.latitude, Some(39.953));
+        assert_eq!(location.longitude, Some(-75.163));
+        assert_eq!(locations.get(166905).unwrap().road, None);
+        assert!(locations.get(1).is_none());
+        assert_eq!(
+            locations.locate(1, &Metadata::default()),
+            Location {
+                recordnum: 1,
+                ..Default::default()
+            }
+        );
+    }
+}
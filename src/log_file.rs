@@ -0,0 +1,202 @@
+//! A log file that is rotated monthly, and optionally once it reaches a maximum size.
+//!
+//! Each month is logged to its own file, named with the year and month (e.g.
+//! `import-2024-06.log`), so that no one file grows without bound. If a maximum size is set, a
+//! month's log is continued in numbered files (`import-2024-06-1.log`, `import-2024-06-2.log`,
+//! ...) once it reaches it. If a retention period is set, the logs of months before it are
+//! removed.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Local, Months, NaiveDate};
+
+/// A log file, rotated monthly and (optionally) by size, to be written to by a logger.
+pub struct RotatingLogFile {
+    dir: PathBuf,
+    name: String,
+    /// The maximum size of a file, in bytes.
+    max_size: Option<u64>,
+    /// The number of months of logs to keep, including the current one.
+    retention: Option<u32>,
+    /// The first day of the month being logged.
+    month: NaiveDate,
+    /// The number of the file being written to within the month.
+    part: u32,
+    file: File,
+    size: u64,
+}
+
+impl RotatingLogFile {
+    /// Open the current log file in `dir`, with files named after `name`, creating it if
+    /// necessary and removing any logs from before the retention period.
+    pub fn new(
+        dir: &Path,
+        name: &str,
+        max_size: Option<u64>,
+        retention: Option<u32>,
+    ) -> io::Result<Self> {
+        let month = first_of_month(Local::now().date_naive());
+        // Continue the last file of the month.
+        let mut part = 0;
+        while dir.join(file_name(name, month, part + 1)).exists() {
+            part += 1;
+        }
+        let (file, size) = open(&dir.join(file_name(name, month, part)))?;
+        let log_file = Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            max_size,
+            retention,
+            month,
+            part,
+            file,
+            size,
+        };
+        log_file.remove_expired()?;
+        Ok(log_file)
+    }
+
+    /// The path of the file currently being written to.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(file_name(&self.name, self.month, self.part))
+    }
+
+    /// Switch to a new file if the month has changed or the current file is full, or reopen the
+    /// current one if it has somehow been deleted.
+    fn rotate(&mut self) -> io::Result<()> {
+        let month = first_of_month(Local::now().date_naive());
+        if month != self.month {
+            self.month = month;
+            self.part = 0;
+        } else if self.max_size.is_some_and(|v| self.size >= v) {
+            self.part += 1;
+        } else if self.path().exists() {
+            return Ok(());
+        }
+        (self.file, self.size) = open(&self.path())?;
+        // Failing to remove old logs shouldn't prevent logging.
+        let _ = self.remove_expired();
+        Ok(())
+    }
+
+    /// Remove the files of months before the retention period.
+    fn remove_expired(&self) -> io::Result<()> {
+        let Some(retention) = self.retention else {
+            return Ok(());
+        };
+        let oldest = oldest_month_kept(self.month, retention);
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let month = path
+                .file_name()
+                .and_then(|v| v.to_str())
+                .and_then(|v| month_of_file(&self.name, v));
+            if month.is_some_and(|v| v < oldest) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.rotate()?;
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Open a file for appending, creating it if necessary, and get its current size.
+fn open(path: &Path) -> io::Result<(File, u64)> {
+    let file = OpenOptions::new().append(true).create(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+/// The name of a log file, e.g. "import-2024-06.log" or "import-2024-06-1.log".
+fn file_name(name: &str, month: NaiveDate, part: u32) -> String {
+    let month = month.format("%Y-%m");
+    if part == 0 {
+        format!("{name}-{month}.log")
+    } else {
+        format!("{name}-{month}-{part}.log")
+    }
+}
+
+/// Get the month of a log file from its name, if it is one.
+fn month_of_file(name: &str, file_name: &str) -> Option<NaiveDate> {
+    let rest = file_name.strip_prefix(name)?.strip_prefix('-')?;
+    let rest = rest.strip_suffix(".log")?;
+    let (month, part) = (rest.get(..7)?, &rest[7..]);
+    if !part.is_empty() && part.strip_prefix('-')?.parse::<u32>().is_err() {
+        return None;
+    }
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").ok()
+}
+
+/// The oldest month whose logs are kept, when keeping `retention` months including `month`.
+fn oldest_month_kept(month: NaiveDate, retention: u32) -> NaiveDate {
+    month
+        .checked_sub_months(Months::new(retention.saturating_sub(1)))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn month_of_file_parsed_only_from_log_files() {
+        let june = NaiveDate::from_ymd_opt(2024, 6, 1);
+        assert_eq!(month_of_file("import", "import-2024-06.log"), june);
+        assert_eq!(month_of_file("import", "import-2024-06-3.log"), june);
+        assert_eq!(month_of_file("import", "import-2024-06-x.log"), None);
+        assert_eq!(month_of_file("import", "import.log"), None);
+        assert_eq!(month_of_file("import", "data_check-2024-06.log"), None);
+        assert_eq!(month_of_file("import", "166905-ew-40972-35.txt"), None);
+    }
+
+    #[test]
+    fn oldest_month_kept_includes_current_month() {
+        let month = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(oldest_month_kept(month, 1), month);
+        assert_eq!(
+            oldest_month_kept(month, 6),
+            NaiveDate::from_ymd_opt(2023, 10, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn log_continued_in_new_file_once_full_and_expired_logs_removed() {
+        let dir = std::env::temp_dir().join("traffic_counts_rotating_log_file");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let expired = dir.join("import-2000-01.log");
+        fs::write(&expired, "old").unwrap();
+
+        let mut log_file = RotatingLogFile::new(&dir, "import", Some(10), Some(12)).unwrap();
+        assert!(!expired.exists());
+        let first = log_file.path();
+        log_file.write_all(b"0123456789").unwrap();
+        log_file.write_all(b"more").unwrap();
+        let second = log_file.path();
+
+        assert_ne!(first, second);
+        assert!(second.to_str().unwrap().ends_with("-1.log"));
+        assert_eq!(fs::read_to_string(first).unwrap(), "0123456789");
+        assert_eq!(fs::read_to_string(second).unwrap(), "more");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+//! A manifest of the inputs and outputs of a run of the import program.
+//!
+//! When a published AADT is questioned, we need to be able to show exactly what went into it: which
+//! file (by the [hash][crate::extract_from_file::file_hash] of its contents) a count was imported
+//! from, how many rows were extracted from it, how many were inserted into each table, and what
+//! warnings were raised. A [`Manifest`] of each run, created from its
+//! [summary][crate::import_summary::ImportSummary], records this in JSON, to be
+//! [written][Manifest::write] to a file and/or [stored][Manifest::store] in the IMPORT_MANIFEST
+//! table.
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime};
+use oracle::Connection;
+use serde::Serialize;
+
+use crate::{import_summary::ImportSummary, CountError};
+
+/// The inputs and outputs of one run of the import program.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    /// The version of the import program.
+    pub version: &'static str,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub files: Vec<ManifestFile>,
+}
+
+/// The input and outputs of one file in a run of the import program.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestFile {
+    pub path: PathBuf,
+    pub hash: Option<String>,
+    pub recordnum: Option<u32>,
+    pub imported: bool,
+    /// Why the file wasn't imported, if it wasn't.
+    pub reason: Option<String>,
+    /// The number of rows extracted from the file.
+    pub rows_extracted: Option<usize>,
+    /// The number of rows of the file that couldn't be parsed.
+    pub rows_skipped: Option<usize>,
+    /// The number of rows inserted, by table.
+    pub rows_inserted: BTreeMap<&'static str, usize>,
+    pub warnings: Vec<String>,
+}
+
+impl Manifest {
+    /// Create the manifest of a run, ending now, from its summary.
+    pub fn new(summary: &ImportSummary) -> Self {
+        let files = summary
+            .files
+            .iter()
+            .map(|file| {
+                let mut rows_inserted = BTreeMap::new();
+                for (table, num) in &file.inserted {
+                    *rows_inserted.entry(*table).or_default() += num;
+                }
+                ManifestFile {
+                    path: file.path.clone(),
+                    hash: file.hash.clone(),
+                    recordnum: file.recordnum,
+                    imported: file.imported,
+                    reason: file.reason.clone(),
+                    rows_extracted: file.extracted.map(|(rows, _)| rows),
+                    rows_skipped: file.extracted.map(|(_, skipped)| skipped),
+                    rows_inserted,
+                    warnings: file.warnings.clone(),
+                }
+            })
+            .collect();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            start: summary.start,
+            end: Local::now().naive_local(),
+            files,
+        }
+    }
+
+    /// Write the manifest as JSON to a file in `dir`, returning the path of the file.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, CountError> {
+        let path = dir.join(format!(
+            "import-manifest-{}.json",
+            self.start.format("%Y%m%d%H%M%S")
+        ));
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), self)?;
+        Ok(path)
+    }
+
+    /// Store the entry of each file with a recordnum in the IMPORT_MANIFEST table, as JSON,
+    /// returning the number of entries stored.
+    ///
+    /// (Files whose recordnum couldn't be determined can't be linked to a count, and so are only
+    /// in the manifest written to a file.) Either all entries are stored or none are.
+    pub fn store(&self, conn: &Connection) -> Result<usize, CountError> {
+        match self.insert(conn) {
+            Ok(stored) => {
+                conn.commit()?;
+                Ok(stored)
+            }
+            Err(e) => {
+                conn.rollback()?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Insert the entries [stored][Manifest::store], without committing them.
+    fn insert(&self, conn: &Connection) -> Result<usize, CountError> {
+        let mut stored = 0;
+        for file in &self.files {
+            let Some(recordnum) = file.recordnum else {
+                continue;
+            };
+            conn.execute(
+                "insert into import_manifest \
+                (runstart, recordnum, filename, hash, imported, manifest) \
+                values (:1, :2, :3, :4, :5, :6)",
+                &[
+                    &self.start,
+                    &recordnum,
+                    &file.path.to_string_lossy().to_string(),
+                    &file.hash,
+                    &(file.imported as u8),
+                    &serde_json::to_string(file)?,
+                ],
+            )?;
+            stored += 1;
+        }
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_totals_rows_inserted_by_table() {
+        let mut summary = ImportSummary::new();
+        summary.start_file(Path::new("vehicle/2-e-1-35.csv"), vec![]);
+        summary.set_recordnum(2);
+        summary.set_hash("abc");
+        summary.extracted(100, 3);
+        summary.inserted("tc_clacount", 10);
+        summary.inserted("tc_volcount", 2);
+        summary.inserted("tc_volcount", 1);
+        summary.imported();
+        summary.start_file(Path::new("vehicle/bad.csv"), vec![]);
+        summary.finish_file(vec![]);
+
+        let manifest = Manifest::new(&summary);
+        let file = &manifest.files[0];
+        assert_eq!(file.hash.as_deref(), Some("abc"));
+        assert_eq!(file.rows_extracted, Some(100));
+        assert_eq!(file.rows_skipped, Some(3));
+        assert_eq!(
+            file.rows_inserted,
+            BTreeMap::from([("tc_clacount", 10), ("tc_volcount", 3)])
+        );
+        assert_eq!(manifest.files[1].rows_extracted, None);
+        assert!(!manifest.files[1].imported);
+
+        let path = manifest.write(&std::env::temp_dir()).unwrap();
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(json["files"][0]["rows_inserted"]["tc_volcount"], 3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
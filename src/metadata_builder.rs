@@ -0,0 +1,193 @@
+//! Build the [`FieldMetadata`] of an input count from its fields, rather than its filename.
+//!
+//! Counts uploaded some other way than with a file named to the filename specification (e.g.
+//! through a form, with each field entered separately) have their metadata
+//! [built][FieldMetadataBuilder], with each field validated as it would be in a filename (by the
+//! same [`FieldMetadata`] functions), so that any metadata a filename can give can be built. The
+//! counter must also be one in the [inventory][CounterInventory] of counters.
+use std::collections::BTreeMap;
+
+use crate::{counter_inventory::CounterInventory, Channel, CountError, FieldMetadata};
+
+/// A builder of [`FieldMetadata`], validating each field.
+///
+/// The recordnum, directions, and counter are required; without channels, they're
+/// [mapped from the directions][crate::Directions::channels], as they are for filenames.
+#[derive(Debug, Clone, Default)]
+pub struct FieldMetadataBuilder {
+    recordnum: Option<u32>,
+    directions: Option<String>,
+    counter_id: Option<String>,
+    speed_limit: Option<u8>,
+    channels: Option<BTreeMap<u8, Channel>>,
+}
+
+impl FieldMetadataBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn recordnum(mut self, recordnum: u32) -> Self {
+        self.recordnum = Some(recordnum);
+        self
+    }
+
+    /// Set the directions as they'd be in a filename, e.g. "ew" or "xn".
+    pub fn directions(mut self, directions: &str) -> Self {
+        self.directions = Some(directions.to_string());
+        self
+    }
+
+    pub fn counter_id(mut self, counter_id: &str) -> Self {
+        self.counter_id = Some(counter_id.to_string());
+        self
+    }
+
+    pub fn speed_limit(mut self, speed_limit: Option<u8>) -> Self {
+        self.speed_limit = speed_limit;
+        self
+    }
+
+    /// Set the direction and lane of each channel of the counter.
+    pub fn channels(mut self, channels: BTreeMap<u8, Channel>) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Validate the fields and build the metadata, with the counter required to be in
+    /// `inventory`.
+    pub fn build(self, inventory: &CounterInventory) -> Result<FieldMetadata, CountError> {
+        let invalid = |field, value: &dyn ToString| CountError::InvalidField {
+            field,
+            value: value.to_string(),
+        };
+
+        // (As in a filename, any recordnum and speed limit are valid.)
+        let recordnum = self
+            .recordnum
+            .ok_or(CountError::MissingField("recordnum"))?;
+
+        let directions = self
+            .directions
+            .ok_or(CountError::MissingField("directions"))?;
+        let (directions, channels) = FieldMetadata::parse_directions(&directions)
+            .map_err(|_| invalid("directions", &directions))?;
+
+        let counter_id = self
+            .counter_id
+            .ok_or(CountError::MissingField("counter_id"))?;
+        let counter_id = counter_id.trim().to_string();
+        FieldMetadata::check_counter_id(&counter_id)
+            .map_err(|_| invalid("counter_id", &counter_id))?;
+        if !inventory.contains(&counter_id) {
+            return Err(CountError::UnknownCounter(counter_id));
+        }
+
+        // Channels can only be of the count's directions.
+        let counted = [
+            Some(directions.direction1),
+            directions.direction2,
+            directions.direction3,
+        ];
+        let channels = self.channels.unwrap_or(channels);
+        if let Some((channel, v)) = channels
+            .iter()
+            .find(|(_, v)| !counted.contains(&Some(v.direction)))
+        {
+            return Err(invalid("channels", &format!("{channel}: {}", v.direction)));
+        }
+
+        let mut metadata = FieldMetadata::new(recordnum, directions, counter_id, self.speed_limit);
+        metadata.channels = channels;
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::LaneDirection;
+
+    #[test]
+    fn built_metadata_same_as_from_filename() {
+        let metadata = FieldMetadataBuilder::new()
+            .recordnum(166905)
+            .directions("xe")
+            .counter_id("40972")
+            .speed_limit(Some(35))
+            .build(&CounterInventory::default())
+            .unwrap();
+        assert_eq!(
+            metadata,
+            FieldMetadata::from_filename(Path::new("166905-xe-40972-35.csv")).unwrap()
+        );
+    }
+
+    #[test]
+    fn fields_accepted_in_filenames_accepted() {
+        let metadata = FieldMetadataBuilder::new()
+            .recordnum(1_000_000)
+            .directions("e")
+            .counter_id("40972")
+            .speed_limit(Some(33))
+            .build(&CounterInventory::default())
+            .unwrap();
+        assert_eq!(
+            metadata,
+            FieldMetadata::from_filename(Path::new("1000000-e-40972-33.csv")).unwrap()
+        );
+        assert!(FieldMetadata::from_filename(Path::new("1-e--35.csv")).is_err());
+    }
+
+    #[test]
+    fn invalid_fields_refused() {
+        let builder = FieldMetadataBuilder::new()
+            .recordnum(166905)
+            .directions("ew")
+            .counter_id("40972");
+        let inventory: CounterInventory = toml::from_str("[counters.40972]").unwrap();
+        assert!(builder.clone().build(&inventory).is_ok());
+
+        assert!(matches!(
+            builder.clone().directions("eb").build(&inventory),
+            Err(CountError::InvalidField {
+                field: "directions",
+                ..
+            })
+        ));
+        assert!(matches!(
+            builder.clone().counter_id("409-72").build(&inventory),
+            Err(CountError::InvalidField {
+                field: "counter_id",
+                ..
+            })
+        ));
+        assert!(matches!(
+            builder.clone().counter_id("40973").build(&inventory),
+            Err(CountError::UnknownCounter(_))
+        ));
+        assert!(matches!(
+            builder.clone().counter_id(" ").build(&inventory),
+            Err(CountError::InvalidField {
+                field: "counter_id",
+                ..
+            })
+        ));
+        assert!(matches!(
+            FieldMetadataBuilder::new()
+                .directions("e")
+                .build(&inventory),
+            Err(CountError::MissingField("recordnum"))
+        ));
+        let channels = BTreeMap::from([(
+            1,
+            Channel {
+                direction: LaneDirection::North,
+                lane: 1,
+            },
+        )]);
+        assert!(builder.channels(channels).build(&inventory).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Metrics of the import program's health, for Prometheus.
+//!
+//! So that how the import program is doing can be tracked over time on a dashboard, rather than
+//! only read in its log or [summaries][crate::import_summary], it keeps [`Metrics`] of its runs:
+//! the files processed (by whether they were imported), the records inserted (by table), the rows
+//! of files that couldn't be parsed, the round-trip time to the database, and how long runs take.
+//!
+//! These are written, in Prometheus's text format, to a file for the node exporter's textfile
+//! collector (see [`Metrics::write`]). The counters are totals since the program started; the
+//! time of the last run is also included, so that a program that has stopped can be noticed.
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write as _};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::{import_summary::ImportSummary, CountError};
+
+/// The upper bounds, in seconds, of the buckets of the database's round-trip time.
+const DB_LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// The upper bounds, in seconds, of the buckets of the duration of runs.
+const RUN_DURATION_BUCKETS: &[f64] = &[1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0];
+
+/// A Prometheus histogram: how many observations fell at or below each bound, and their sum.
+#[derive(Debug, Clone, PartialEq)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    /// Write the histogram's buckets, sum, and count in Prometheus's text format.
+    fn render(&self, out: &mut String, name: &str) {
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum {}", self.sum);
+        let _ = writeln!(out, "{name}_count {}", self.count);
+    }
+}
+
+/// Metrics of the runs of the import program since it started.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    files_imported: u64,
+    files_not_imported: u64,
+    /// The number of records inserted, by table.
+    records_inserted: BTreeMap<&'static str, u64>,
+    parse_errors: u64,
+    db_latency: Histogram,
+    run_duration: Histogram,
+    /// When the last run finished, in seconds since the Unix epoch.
+    last_run: Option<i64>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            files_imported: 0,
+            files_not_imported: 0,
+            records_inserted: BTreeMap::new(),
+            parse_errors: 0,
+            db_latency: Histogram::new(DB_LATENCY_BUCKETS),
+            run_duration: Histogram::new(RUN_DURATION_BUCKETS),
+            last_run: None,
+        }
+    }
+
+    /// Add rows of a file that couldn't be parsed.
+    pub fn parse_errors(&mut self, rows: usize) {
+        self.parse_errors += rows as u64;
+    }
+
+    /// Add a round trip to the database.
+    pub fn db_latency(&mut self, latency: Duration) {
+        self.db_latency.observe(latency.as_secs_f64());
+    }
+
+    /// Add a run, with the files it processed and how long it took.
+    ///
+    /// Runs in which no files were processed only update the time of the last run.
+    pub fn run(&mut self, summary: &ImportSummary, duration: Duration) {
+        self.last_run = Some(Local::now().timestamp());
+        if summary.files.is_empty() {
+            return;
+        }
+        for file in &summary.files {
+            if file.imported {
+                self.files_imported += 1;
+            } else {
+                self.files_not_imported += 1;
+            }
+            for (table, num) in &file.inserted {
+                *self.records_inserted.entry(*table).or_default() += *num as u64;
+            }
+        }
+        self.run_duration.observe(duration.as_secs_f64());
+    }
+
+    /// Write the metrics to `path`, for the node exporter's textfile collector.
+    ///
+    /// The file should be in the collector's directory and end in ".prom". It is written to a
+    /// temporary file first and then renamed, so that the collector never reads half of it.
+    pub fn write(&self, path: &Path) -> Result<(), CountError> {
+        let tmp = path.with_extension("prom.tmp");
+        fs::write(&tmp, self.to_string())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for Metrics {
+    /// The metrics in Prometheus's text format.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+
+        out.push_str(
+            "# HELP traffic_counts_import_files_total Files processed by the import program.\n",
+        );
+        out.push_str("# TYPE traffic_counts_import_files_total counter\n");
+        let _ = writeln!(
+            out,
+            "traffic_counts_import_files_total{{outcome=\"imported\"}} {}",
+            self.files_imported
+        );
+        let _ = writeln!(
+            out,
+            "traffic_counts_import_files_total{{outcome=\"not_imported\"}} {}",
+            self.files_not_imported
+        );
+
+        out.push_str("# HELP traffic_counts_import_records_inserted_total Records inserted.\n");
+        out.push_str("# TYPE traffic_counts_import_records_inserted_total counter\n");
+        for (table, num) in &self.records_inserted {
+            let _ = writeln!(
+                out,
+                "traffic_counts_import_records_inserted_total{{table=\"{table}\"}} {num}"
+            );
+        }
+
+        out.push_str(
+            "# HELP traffic_counts_import_parse_errors_total Rows of files that couldn't be \
+            parsed.\n",
+        );
+        out.push_str("# TYPE traffic_counts_import_parse_errors_total counter\n");
+        let _ = writeln!(
+            out,
+            "traffic_counts_import_parse_errors_total {}",
+            self.parse_errors
+        );
+
+        out.push_str(
+            "# HELP traffic_counts_import_db_latency_seconds Round-trip time to the database.\n",
+        );
+        out.push_str("# TYPE traffic_counts_import_db_latency_seconds histogram\n");
+        self.db_latency
+            .render(&mut out, "traffic_counts_import_db_latency_seconds");
+
+        out.push_str(
+            "# HELP traffic_counts_import_run_duration_seconds Duration of runs that processed \
+            files.\n",
+        );
+        out.push_str("# TYPE traffic_counts_import_run_duration_seconds histogram\n");
+        self.run_duration
+            .render(&mut out, "traffic_counts_import_run_duration_seconds");
+
+        if let Some(last_run) = self.last_run {
+            out.push_str(
+                "# HELP traffic_counts_import_last_run_timestamp_seconds When the last run \
+                finished.\n",
+            );
+            out.push_str("# TYPE traffic_counts_import_last_run_timestamp_seconds gauge\n");
+            let _ = writeln!(
+                out,
+                "traffic_counts_import_last_run_timestamp_seconds {last_run}"
+            );
+        }
+
+        write!(f, "{out}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_accumulate_runs_in_prometheus_format() {
+        let mut summary = ImportSummary::new();
+        summary.start_file(Path::new("vehicle/1-e-1-35.csv"), vec![]);
+        summary.inserted("tc_clacount", 10);
+        summary.inserted("tc_volcount", 4);
+        summary.imported();
+        summary.start_file(Path::new("vehicle/2-e-1-35.csv"), vec![]);
+
+        let mut metrics = Metrics::new();
+        metrics.parse_errors(3);
+        metrics.db_latency(Duration::from_millis(20));
+        metrics.run(&summary, Duration::from_secs(12));
+        metrics.run(&summary, Duration::from_secs(40));
+        metrics.run(&ImportSummary::new(), Duration::from_secs(1));
+        let text = metrics.to_string();
+
+        assert!(text.contains("traffic_counts_import_files_total{outcome=\"imported\"} 2\n"));
+        assert!(text.contains("traffic_counts_import_files_total{outcome=\"not_imported\"} 2\n"));
+        assert!(text
+            .contains("traffic_counts_import_records_inserted_total{table=\"tc_clacount\"} 20\n"));
+        assert!(text.contains("traffic_counts_import_parse_errors_total 3\n"));
+        assert!(text.contains("traffic_counts_import_db_latency_seconds_bucket{le=\"0.01\"} 0\n"));
+        assert!(text.contains("traffic_counts_import_db_latency_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(text.contains("traffic_counts_import_run_duration_seconds_bucket{le=\"15\"} 1\n"));
+        assert!(text.contains("traffic_counts_import_run_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("traffic_counts_import_run_duration_seconds_sum 52\n"));
+        assert!(text.contains("traffic_counts_import_last_run_timestamp_seconds "));
+    }
+}
@@ -0,0 +1,125 @@
+//! Filter the files to import by glob patterns.
+//!
+//! Rather than processing everything under the data directory, a run can be limited to the
+//! files matching any of a set of [included][PathFilter::new] patterns (e.g.
+//! "vehicle/**/rc-*.txt"), and files matching any of a set of excluded ones (e.g. the
+//! subdirectory of a consultant whose files use a different format, "vehicle/consultant/**")
+//! can be skipped.
+//!
+//! Patterns are matched against paths relative to the data directory, with `/` separating their
+//! components on every platform. `*` and `?` don't match across components; `**` does. An
+//! archive is matched by its own path, not those of the files in it.
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::CountError;
+
+/// Included and excluded glob patterns of paths.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+    /// Whether any patterns were included; without any, all paths are.
+    any_included: bool,
+}
+
+impl Default for PathFilter {
+    fn default() -> Self {
+        Self {
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+            any_included: false,
+        }
+    }
+}
+
+impl PathFilter {
+    /// Create a filter of paths matching any of the `include` patterns (or any paths, if there
+    /// are none) and none of the `exclude` ones.
+    pub fn new<T: AsRef<str>>(include: &[T], exclude: &[T]) -> Result<Self, CountError> {
+        Ok(Self {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+            any_included: !include.is_empty(),
+        })
+    }
+
+    /// Check if a path (relative to the data directory) passes the filter.
+    pub fn is_match(&self, path: &Path) -> bool {
+        (!self.any_included || self.include.is_match(path)) && !self.exclude.is_match(path)
+    }
+
+    /// Check if a path within `dir` passes the filter, matching it relative to `dir`.
+    ///
+    /// A path not within `dir` is matched as it is.
+    pub fn is_match_within(&self, dir: &Path, path: &Path) -> bool {
+        self.is_match(path.strip_prefix(dir).unwrap_or(path))
+    }
+
+    /// Keep only the paths within `dir` that pass the filter, matching them relative to it.
+    pub fn retain_within(&self, dir: &Path, paths: &mut Vec<PathBuf>) {
+        paths.retain(|path| self.is_match_within(dir, path));
+    }
+}
+
+fn glob_set<T: AsRef<str>>(patterns: &[T]) -> Result<GlobSet, CountError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(glob(pattern.as_ref())?);
+    }
+    Ok(builder.build()?)
+}
+
+fn glob(pattern: &str) -> Result<Glob, CountError> {
+    Ok(GlobBuilder::new(pattern).literal_separator(true).build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_filtered_by_included_and_excluded_patterns() {
+        let dir = Path::new("data");
+        let paths = [
+            "data/vehicle/rc-166905-ew-40972-35.txt",
+            "data/vehicle/166905-ew-40972-35.txt",
+            "data/vehicle/consultant/rc-165367-ee-38397-45.txt",
+            "data/15minutevehicle/rc-168193-ew-39352-na.txt",
+        ]
+        .map(PathBuf::from);
+        let filtered = |filter: &PathFilter| {
+            let mut filtered = paths.to_vec();
+            filter.retain_within(dir, &mut filtered);
+            filtered
+        };
+
+        assert_eq!(filtered(&PathFilter::default()), paths);
+
+        let filter = PathFilter::new(&["vehicle/*.zip"], &[]).unwrap();
+        assert!(filter.is_match_within(dir, Path::new("data/vehicle/batch.zip")));
+        assert!(!filter.is_match_within(dir, Path::new("data/15minutevehicle/batch.zip")));
+
+        let filter = PathFilter::new(&["vehicle/**/rc-*.txt"], &[]).unwrap();
+        assert_eq!(filtered(&filter), [paths[0].clone(), paths[2].clone()]);
+
+        // `*` doesn't match across directories.
+        let filter = PathFilter::new(&["vehicle/*.txt"], &[]).unwrap();
+        assert_eq!(filtered(&filter), [paths[0].clone(), paths[1].clone()]);
+
+        let filter = PathFilter::new(&[], &["vehicle/consultant/**"]).unwrap();
+        assert_eq!(
+            filtered(&filter),
+            [paths[0].clone(), paths[1].clone(), paths[3].clone()]
+        );
+
+        let filter = PathFilter::new(&["**/rc-*"], &["vehicle/consultant/**"]).unwrap();
+        assert_eq!(filtered(&filter), [paths[0].clone(), paths[3].clone()]);
+
+        assert!(matches!(
+            PathFilter::new(&["vehicle/[rc-*"], &[]),
+            Err(CountError::GlobError(_))
+        ));
+    }
+}
@@ -0,0 +1,449 @@
+//! Peak hour and peak hour factor calculation.
+//!
+//! The peak hour is the 60-minute period - any four consecutive 15-minute periods - with the
+//! highest volume. The peak hour factor (PHF) is the volume of the peak hour divided by four
+//! times the volume of the busiest 15-minute period within it, and so is between 0.25 and 1.0.
+//!
+//! Peak hours are found separately for the morning and afternoon of each day of a count, for
+//! each direction. Lanes going the same direction are combined.
+//!
+//! The standard [design factors][DesignFactors] of each full day of a count are also derived from
+//! its peak hour: the K-factor (the share of the day's volume in its peak hour) and D-factor (the
+//! share of the peak hour's volume going in its busier direction), along with the day's
+//! directional split. For these, the peak hour is the busiest hour of the whole day, in both
+//! directions.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::{LaneDirection, TimeBinnedVehicleClassCount};
+
+/// The half of the day a peak hour is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum PeakPeriod {
+    Am,
+    Pm,
+}
+
+impl PeakPeriod {
+    /// Get the period a datetime falls in.
+    pub fn from_datetime(datetime: NaiveDateTime) -> Self {
+        if datetime.hour() < 12 {
+            PeakPeriod::Am
+        } else {
+            PeakPeriod::Pm
+        }
+    }
+}
+
+impl Display for PeakPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakPeriod::Am => write!(f, "AM"),
+            PeakPeriod::Pm => write!(f, "PM"),
+        }
+    }
+}
+
+/// The peak hour of one direction of a count, on a particular day and [`PeakPeriod`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeakHour {
+    pub recordnum: u32,
+    pub date: NaiveDate,
+    pub direction: LaneDirection,
+    pub period: PeakPeriod,
+    /// The start of the first 15-minute period in the hour.
+    pub start: NaiveDateTime,
+    /// The end of the last 15-minute period in the hour.
+    pub end: NaiveDateTime,
+    pub volume: u32,
+    /// The volume of the busiest 15-minute period in the hour.
+    pub peak_fifteen_min: u32,
+    pub factor: f32,
+}
+
+impl Display for PeakHour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} peak hour ({}): {}-{}, volume {}, PHF {:.2}",
+            self.recordnum,
+            self.date,
+            self.period,
+            self.direction,
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.volume,
+            self.factor
+        )
+    }
+}
+
+/// Find the AM and PM peak hours, per day and direction, from 15-minute
+/// [`TimeBinnedVehicleClassCount`]s.
+///
+/// Only windows of four consecutive 15-minute periods, all within the same [`PeakPeriod`], are
+/// considered. If two windows have the same volume, the earlier one is used. Periods without any
+/// volume do not have a peak hour.
+pub fn create_peak_hours(counts: &[TimeBinnedVehicleClassCount]) -> Vec<PeakHour> {
+    // Sum the volume of all lanes going the same direction, per 15-minute period.
+    let mut volumes: BTreeMap<
+        (u32, NaiveDate, LaneDirection, PeakPeriod),
+        BTreeMap<NaiveDateTime, u32>,
+    > = BTreeMap::new();
+
+    for count in counts {
+        let direction = match count.direction {
+            Some(v) => v,
+            None => continue,
+        };
+        let period = PeakPeriod::from_datetime(count.time);
+        *volumes
+            .entry((count.recordnum, count.date, direction, period))
+            .or_default()
+            .entry(count.time)
+            .or_insert(0) += count.total;
+    }
+
+    let mut peak_hours = vec![];
+    for ((recordnum, date, direction, period), periods) in volumes {
+        let periods = periods.into_iter().collect::<Vec<_>>();
+        let mut peak_hour: Option<PeakHour> = None;
+
+        for window in periods.windows(4) {
+            // The periods are unique and sorted, so this ensures they are consecutive.
+            if window[3].0 - window[0].0 != TimeDelta::minutes(45) {
+                continue;
+            }
+            let volume = window.iter().map(|(_, volume)| volume).sum::<u32>();
+            if volume == 0 || peak_hour.as_ref().is_some_and(|p| p.volume >= volume) {
+                continue;
+            }
+            let peak_fifteen_min = window.iter().map(|(_, volume)| *volume).max().unwrap();
+            peak_hour = Some(PeakHour {
+                recordnum,
+                date,
+                direction,
+                period,
+                start: window[0].0,
+                end: window[3].0 + TimeDelta::minutes(15),
+                volume,
+                peak_fifteen_min,
+                factor: volume as f32 / (4 * peak_fifteen_min) as f32,
+            });
+        }
+
+        if let Some(v) = peak_hour {
+            peak_hours.push(v);
+        }
+    }
+    peak_hours
+}
+
+/// The directional split, K-factor, and D-factor of one day of a count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DesignFactors {
+    pub recordnum: u32,
+    pub date: NaiveDate,
+    /// The day's volume in each direction.
+    pub volumes: BTreeMap<LaneDirection, u32>,
+    /// The start of the day's peak hour (both directions combined).
+    pub peak_hour_start: NaiveDateTime,
+    /// The volume of the peak hour in each direction.
+    pub peak_hour_volumes: BTreeMap<LaneDirection, u32>,
+    /// The share of the day's volume in its peak hour.
+    pub k_factor: f32,
+    /// The direction with the most volume in the peak hour.
+    pub peak_direction: LaneDirection,
+    /// The share of the peak hour's volume going in the peak direction.
+    pub d_factor: f32,
+}
+
+impl DesignFactors {
+    /// The day's volume, in all directions.
+    pub fn volume(&self) -> u32 {
+        self.volumes.values().sum()
+    }
+
+    /// The share of the day's volume going in a direction.
+    pub fn directional_split(&self, direction: LaneDirection) -> f32 {
+        self.volumes.get(&direction).copied().unwrap_or(0) as f32 / self.volume() as f32
+    }
+}
+
+impl Display for DesignFactors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let split = self
+            .volumes
+            .keys()
+            .map(|direction| {
+                format!(
+                    "{direction} {:.0}%",
+                    self.directional_split(*direction) * 100.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        write!(
+            f,
+            "{} {} design factors: split {split}, K {:.3}, D {:.3} ({}), peak hour {}",
+            self.recordnum,
+            self.date,
+            self.k_factor,
+            self.d_factor,
+            self.peak_direction,
+            self.peak_hour_start.format("%H:%M"),
+        )
+    }
+}
+
+/// The number of 15-minute periods in a day.
+const PERIODS_PER_DAY: usize = 96;
+
+/// Compute the [`DesignFactors`] of each day of a count from its 15-minute
+/// [`TimeBinnedVehicleClassCount`]s.
+///
+/// As with [`create_peak_hours`], the peak hour is four consecutive 15-minute periods, and the
+/// earlier of two with the same volume is used. K-factors are only meaningful for full days of
+/// counts, so only days with every 15-minute period counted (and some volume) have design factors.
+pub fn create_design_factors(counts: &[TimeBinnedVehicleClassCount]) -> Vec<DesignFactors> {
+    // The volume of each direction, per 15-minute period of each day.
+    let mut volumes: BTreeMap<
+        (u32, NaiveDate),
+        BTreeMap<NaiveDateTime, BTreeMap<LaneDirection, u32>>,
+    > = BTreeMap::new();
+
+    for count in counts {
+        let Some(direction) = count.direction else {
+            continue;
+        };
+        *volumes
+            .entry((count.recordnum, count.date))
+            .or_default()
+            .entry(count.time)
+            .or_default()
+            .entry(direction)
+            .or_insert(0) += count.total;
+    }
+
+    let mut design_factors = vec![];
+    for ((recordnum, date), periods) in volumes {
+        if periods.len() != PERIODS_PER_DAY {
+            continue;
+        }
+        let periods = periods.into_iter().collect::<Vec<_>>();
+        let mut day_volumes: BTreeMap<LaneDirection, u32> = BTreeMap::new();
+        for (direction, volume) in periods.iter().flat_map(|(_, v)| v) {
+            *day_volumes.entry(*direction).or_insert(0) += volume;
+        }
+        let day_volume = day_volumes.values().sum::<u32>();
+
+        let mut peak_hour: Option<(NaiveDateTime, u32, BTreeMap<LaneDirection, u32>)> = None;
+        for window in periods.windows(4) {
+            // The periods are unique and sorted, so this ensures they are consecutive.
+            if window[3].0 - window[0].0 != TimeDelta::minutes(45) {
+                continue;
+            }
+            let mut hour_volumes: BTreeMap<LaneDirection, u32> = BTreeMap::new();
+            for (direction, volume) in window.iter().flat_map(|(_, v)| v) {
+                *hour_volumes.entry(*direction).or_insert(0) += volume;
+            }
+            let volume = hour_volumes.values().sum::<u32>();
+            if volume == 0 || peak_hour.as_ref().is_some_and(|(_, v, _)| *v >= volume) {
+                continue;
+            }
+            peak_hour = Some((window[0].0, volume, hour_volumes));
+        }
+
+        let Some((peak_hour_start, peak_hour_volume, peak_hour_volumes)) = peak_hour else {
+            continue;
+        };
+        // The first direction with the most volume.
+        let (peak_direction, peak_direction_volume) = peak_hour_volumes
+            .iter()
+            .rev()
+            .max_by_key(|(_, volume)| **volume)
+            .map(|(direction, volume)| (*direction, *volume))
+            .unwrap();
+        design_factors.push(DesignFactors {
+            recordnum,
+            date,
+            volumes: day_volumes,
+            peak_hour_start,
+            k_factor: peak_hour_volume as f32 / day_volume as f32,
+            peak_direction,
+            d_factor: peak_direction_volume as f32 / peak_hour_volume as f32,
+            peak_hour_volumes,
+        });
+    }
+    design_factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(
+        time: &str,
+        lane: u8,
+        direction: LaneDirection,
+        total: u32,
+    ) -> TimeBinnedVehicleClassCount {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        TimeBinnedVehicleClassCount {
+            date: time.date(),
+            time,
+            lane: Some(lane),
+            recordnum: 123,
+            direction: Some(direction),
+            c1: 0,
+            c2: total,
+            c3: 0,
+            c4: 0,
+            c5: 0,
+            c6: 0,
+            c7: 0,
+            c8: 0,
+            c9: 0,
+            c10: 0,
+            c11: 0,
+            c12: 0,
+            c13: 0,
+            c15: Some(0),
+            total,
+        }
+    }
+
+    #[test]
+    fn peak_hour_and_factor_correct() {
+        let counts = vec![
+            count("2024-04-08 07:00", 1, LaneDirection::East, 10),
+            count("2024-04-08 07:15", 1, LaneDirection::East, 20),
+            count("2024-04-08 07:30", 1, LaneDirection::East, 30),
+            count("2024-04-08 07:45", 1, LaneDirection::East, 40),
+            count("2024-04-08 08:00", 1, LaneDirection::East, 50),
+            count("2024-04-08 08:15", 1, LaneDirection::East, 10),
+        ];
+        let peak_hours = create_peak_hours(&counts);
+        assert_eq!(peak_hours.len(), 1);
+
+        let peak_hour = &peak_hours[0];
+        assert_eq!(peak_hour.period, PeakPeriod::Am);
+        assert_eq!(peak_hour.volume, 140);
+        assert_eq!(peak_hour.peak_fifteen_min, 50);
+        assert_eq!(format!("{:.2}", peak_hour.factor), "0.70");
+        assert_eq!(
+            peak_hour.start,
+            NaiveDateTime::parse_from_str("2024-04-08 07:15", "%Y-%m-%d %H:%M").unwrap()
+        );
+        assert_eq!(
+            peak_hour.end,
+            NaiveDateTime::parse_from_str("2024-04-08 08:15", "%Y-%m-%d %H:%M").unwrap()
+        );
+    }
+
+    #[test]
+    fn peak_hour_combines_lanes_and_separates_directions_and_periods() {
+        let mut counts = vec![];
+        for time in [
+            "07:00", "07:15", "07:30", "07:45", "16:00", "16:15", "16:30", "16:45",
+        ] {
+            let time = format!("2024-04-08 {time}");
+            counts.push(count(&time, 1, LaneDirection::East, 5));
+            counts.push(count(&time, 2, LaneDirection::East, 5));
+            counts.push(count(&time, 3, LaneDirection::West, 1));
+        }
+        let peak_hours = create_peak_hours(&counts);
+        assert_eq!(peak_hours.len(), 4);
+
+        let east_am = peak_hours
+            .iter()
+            .find(|p| p.direction == LaneDirection::East && p.period == PeakPeriod::Am)
+            .unwrap();
+        assert_eq!(east_am.volume, 40);
+        assert_eq!(east_am.factor, 1.0);
+
+        let west_pm = peak_hours
+            .iter()
+            .find(|p| p.direction == LaneDirection::West && p.period == PeakPeriod::Pm)
+            .unwrap();
+        assert_eq!(west_pm.volume, 4);
+    }
+
+    #[test]
+    fn peak_hour_requires_consecutive_periods() {
+        let counts = vec![
+            count("2024-04-08 07:00", 1, LaneDirection::East, 10),
+            count("2024-04-08 07:15", 1, LaneDirection::East, 10),
+            count("2024-04-08 07:30", 1, LaneDirection::East, 10),
+            count("2024-04-08 08:00", 1, LaneDirection::East, 10),
+        ];
+        assert!(create_peak_hours(&counts).is_empty());
+    }
+
+    /// A full day of counts, with nothing counted in the periods not given.
+    fn full_day(date: &str, volumes: &[(&str, u32, u32)]) -> Vec<TimeBinnedVehicleClassCount> {
+        let mut counts = vec![];
+        for period in 0..PERIODS_PER_DAY {
+            let time = format!("{date} {:02}:{:02}", period / 4, period % 4 * 15);
+            let (east, west) = volumes
+                .iter()
+                .find(|(t, _, _)| time.ends_with(t))
+                .map(|(_, east, west)| (*east, *west))
+                .unwrap_or((0, 0));
+            counts.push(count(&time, 1, LaneDirection::East, east));
+            counts.push(count(&time, 2, LaneDirection::West, west));
+        }
+        counts
+    }
+
+    #[test]
+    fn design_factors_correct() {
+        let counts = full_day(
+            "2024-04-08",
+            &[
+                ("06:45", 10, 10),
+                ("07:00", 30, 10),
+                ("07:15", 30, 10),
+                ("07:30", 30, 10),
+                ("07:45", 30, 10),
+                ("12:00", 20, 20),
+            ],
+        );
+        let design_factors = create_design_factors(&counts);
+        assert_eq!(design_factors.len(), 1);
+
+        let factors = &design_factors[0];
+        assert_eq!(factors.volume(), 220);
+        assert_eq!(
+            factors.directional_split(LaneDirection::East),
+            150.0 / 220.0
+        );
+        assert_eq!(
+            factors.peak_hour_start,
+            NaiveDateTime::parse_from_str("2024-04-08 07:00", "%Y-%m-%d %H:%M").unwrap()
+        );
+        assert_eq!(factors.k_factor, 160.0 / 220.0);
+        assert_eq!(factors.peak_direction, LaneDirection::East);
+        assert_eq!(factors.d_factor, 0.75);
+    }
+
+    #[test]
+    fn design_factors_only_for_full_days() {
+        let mut counts = full_day("2024-04-08", &[("07:00", 30, 10)]);
+        // Only the morning of the next day.
+        counts.extend(
+            full_day("2024-04-09", &[("07:00", 30, 10)])
+                .into_iter()
+                .filter(|count| count.time.hour() < 12),
+        );
+        let design_factors = create_design_factors(&counts);
+        assert_eq!(design_factors.len(), 1);
+        assert_eq!(
+            design_factors[0].date,
+            NaiveDate::from_ymd_opt(2024, 4, 8).unwrap()
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Bin a count again from its stored raw vehicles.
+//!
+//! Counts imported with their [raw vehicles][crate::RawVehicle] stored (see `IMPORT_STORE_RAW`)
+//! can have their class, speed, and hourly volume counts (the TC_CLACOUNT, TC_SPECOUNT, and
+//! TC_VOLCOUNT tables) created again without their original files - e.g. after the speed ranges
+//! or the handling of unclassified vehicles change. The existing rows are replaced in a single
+//! transaction: if anything fails, nothing is changed.
+//!
+//! Each vehicle is stored with the direction and lane its channel was mapped to when it was
+//! imported, and the mapping of all of the count's channels is stored with them, so that the count
+//! is binned again with the same mapping - whether its directions were mapped from its first
+//! channel or a custom mapping, and even if a channel recorded nothing.
+//! Vehicles are stored before any partial periods are dropped, so these are dropped again as
+//! when importing (see [`PartialPeriods`]).
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+use oracle::Connection;
+
+use crate::{
+    combined_directions::combine_hourly_counts,
+    create_speed_and_class_count,
+    db::{self, crud::Crud},
+    denormalize::{Denormalize, NonNormalVolCount},
+    heavy_vehicles::create_heavy_vehicle_summary,
+    CountError, Directions, FieldMetadata, IndividualVehicle, PartialPeriods, RawChannel,
+    RawVehicle, TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount, TimeInterval,
+};
+
+/// The number of rows of a count deleted from and inserted into each table by [`rebin`].
+pub type Rebinned = Vec<(&'static str, u64, usize)>;
+
+/// Replace the class, speed, and hourly volume counts of a count with ones binned again from its
+/// stored raw vehicles.
+///
+/// Partial periods are dropped from the start and end of the count as `partial_periods` says,
+/// which should be as they were when it was imported. With `combined_directions`, rows of both
+/// directions combined are also inserted into the hourly volume table, as when importing. The
+/// count's share of heavy vehicles is updated too.
+pub fn rebin(
+    conn: &Connection,
+    recordnum: u32,
+    partial_periods: PartialPeriods,
+    combined_directions: bool,
+    batch_size: NonZeroUsize,
+) -> Result<Rebinned, CountError> {
+    let raw_vehicles = db::get_raw_vehicles(conn, recordnum)?;
+    let raw_channels = db::get_raw_channels(conn, recordnum)?;
+    let metadata = raw_metadata(recordnum, &raw_channels, &raw_vehicles)?;
+    let mut vehicles = raw_vehicles
+        .into_iter()
+        .map(IndividualVehicle::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    partial_periods.trim(&mut vehicles, TimeInterval::FifteenMin, None, |v| v.time);
+
+    let (speed_range_count, vehicle_class_count) =
+        create_speed_and_class_count(TimeInterval::FifteenMin, metadata, vehicles);
+
+    match replace(
+        conn,
+        recordnum,
+        &vehicle_class_count,
+        &speed_range_count,
+        combined_directions,
+        batch_size,
+    ) {
+        Ok(v) => {
+            conn.commit()?;
+            Ok(v)
+        }
+        Err(e) => {
+            conn.rollback()?;
+            Err(e)
+        }
+    }
+}
+
+/// The metadata to bin a count's raw vehicles with: each of its channels mapped to the direction
+/// and lane it was when the count was imported.
+///
+/// The channels are those stored with the vehicles, so that channels that recorded nothing are
+/// zero-filled as they were when importing. (Counts whose raw vehicles were stored before their
+/// channels were have none, and so only have the channels their vehicles were counted on.)
+fn raw_metadata(
+    recordnum: u32,
+    channels: &[RawChannel],
+    vehicles: &[RawVehicle],
+) -> Result<FieldMetadata, CountError> {
+    if vehicles.is_empty() {
+        return Err(CountError::NoRawVehicles(recordnum));
+    }
+    let mut channels = channels
+        .iter()
+        .map(|v| (v.channel, v.mapped_channel()))
+        .collect::<BTreeMap<_, _>>();
+    for vehicle in vehicles {
+        channels
+            .entry(vehicle.channel)
+            .or_insert_with(|| vehicle.mapped_channel());
+    }
+
+    // The directions of the count aren't used to bin it, but are taken from its lanes anyway.
+    let mut lanes = channels.values().collect::<Vec<_>>();
+    lanes.sort_by_key(|v| v.lane);
+    lanes.dedup_by_key(|v| v.lane);
+    let direction = |i: usize| lanes.get(i).map(|v| v.direction);
+    let direction1 = direction(0).ok_or(CountError::NoRawVehicles(recordnum))?;
+
+    Ok(FieldMetadata {
+        recordnum,
+        directions: Directions::new(direction1, direction(1), direction(2)),
+        counter_id: String::new(),
+        speed_limit: None,
+        channels,
+    })
+}
+
+/// Replace the rows of a count, without committing.
+fn replace(
+    conn: &Connection,
+    recordnum: u32,
+    vehicle_class_count: &[TimeBinnedVehicleClassCount],
+    speed_range_count: &[TimeBinnedSpeedRangeCount],
+    combined_directions: bool,
+    batch_size: NonZeroUsize,
+) -> Result<Rebinned, CountError> {
+    let mut rebinned = vec![];
+
+    let deleted = TimeBinnedVehicleClassCount::delete(conn, recordnum)?;
+    TimeBinnedVehicleClassCount::insert_batch(conn, vehicle_class_count, batch_size)?;
+    rebinned.push((
+        TimeBinnedVehicleClassCount::COUNT_TABLE,
+        deleted,
+        vehicle_class_count.len(),
+    ));
+
+    let deleted = TimeBinnedSpeedRangeCount::delete(conn, recordnum)?;
+    TimeBinnedSpeedRangeCount::insert_batch(conn, speed_range_count, batch_size)?;
+    rebinned.push((
+        TimeBinnedSpeedRangeCount::COUNT_TABLE,
+        deleted,
+        speed_range_count.len(),
+    ));
+
+    // (The class counts just inserted are visible to this transaction, and so are what's
+    // denormalized.)
+    let deleted = NonNormalVolCount::delete(conn, recordnum)?;
+    let mut volume_count = TimeBinnedVehicleClassCount::denormalize_vol_count(recordnum, conn)?;
+    if combined_directions {
+        volume_count.extend(combine_hourly_counts(&volume_count));
+    }
+    NonNormalVolCount::insert_batch(conn, &volume_count, batch_size)?;
+    rebinned.push((NonNormalVolCount::COUNT_TABLE, deleted, volume_count.len()));
+
+    if let Some(percent) = create_heavy_vehicle_summary(vehicle_class_count)
+        .and_then(|heavy_vehicles| heavy_vehicles.overall.percent())
+    {
+        db::update_heavy_vehicle_percent(conn, recordnum, percent)?;
+    }
+
+    Ok(rebinned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Channel, LaneDirection};
+    use chrono::NaiveDate;
+
+    fn vehicle(channel: u8, direction: LaneDirection, lane: u8) -> RawVehicle {
+        let date = NaiveDate::from_ymd_opt(2024, 4, 8).unwrap();
+        RawVehicle {
+            recordnum: 1,
+            date,
+            time: date.and_hms_opt(10, 0, 0).unwrap(),
+            channel,
+            direction,
+            lane,
+            class: 2,
+            speed: 30.0,
+        }
+    }
+
+    fn channel(channel: u8, direction: LaneDirection, lane: u8) -> RawChannel {
+        RawChannel {
+            recordnum: 1,
+            channel,
+            direction,
+            lane,
+        }
+    }
+
+    #[test]
+    fn raw_vehicles_binned_with_channels_as_imported() {
+        // Nothing was counted on channel 1 (mapped to lane 1), nor on the unused channel 3.
+        let channels = [
+            channel(1, LaneDirection::East, 1),
+            channel(2, LaneDirection::West, 2),
+            channel(4, LaneDirection::East, 3),
+        ];
+        let vehicles = [
+            vehicle(2, LaneDirection::West, 2),
+            vehicle(4, LaneDirection::East, 3),
+        ];
+
+        let metadata = raw_metadata(1, &channels, &vehicles).unwrap();
+        assert_eq!(
+            metadata.channels,
+            BTreeMap::from([
+                (
+                    1,
+                    Channel {
+                        direction: LaneDirection::East,
+                        lane: 1
+                    }
+                ),
+                (
+                    2,
+                    Channel {
+                        direction: LaneDirection::West,
+                        lane: 2
+                    }
+                ),
+                (
+                    4,
+                    Channel {
+                        direction: LaneDirection::East,
+                        lane: 3
+                    }
+                ),
+            ])
+        );
+        assert!(matches!(
+            raw_metadata(1, &channels, &[]),
+            Err(CountError::NoRawVehicles(1))
+        ));
+
+        // Without stored channels, those the vehicles were counted on are used.
+        let metadata = raw_metadata(1, &[], &vehicles).unwrap();
+        assert_eq!(metadata.channels.keys().collect::<Vec<_>>(), [&2, &4]);
+    }
+
+    #[test]
+    fn channel_without_vehicles_zero_filled_when_binned_again() {
+        let channels = [
+            channel(1, LaneDirection::East, 1),
+            channel(2, LaneDirection::West, 2),
+        ];
+        let vehicles = [vehicle(2, LaneDirection::West, 2)];
+        let metadata = raw_metadata(1, &channels, &vehicles).unwrap();
+        let vehicles = vehicles
+            .into_iter()
+            .map(IndividualVehicle::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let (speed_range_count, vehicle_class_count) =
+            create_speed_and_class_count(TimeInterval::FifteenMin, metadata, vehicles);
+        let lane = |lane| {
+            vehicle_class_count
+                .iter()
+                .filter(move |v| v.lane == Some(lane))
+                .map(|v| v.total)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lane(1), [0]);
+        assert_eq!(lane(2), [1]);
+        assert_eq!(
+            speed_range_count
+                .iter()
+                .filter(|v| v.lane == Some(1))
+                .map(|v| v.total)
+                .collect::<Vec<_>>(),
+            [0]
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Summary reports of counts, as delivered to municipalities.
+//!
+//! A [report][CountReport] of a count in the database has its metadata and location, the
+//! volume of each full day, its [hourly profile][crate::volume_profile] (as a table, and as JSON
+//! for charting), the share of vehicles of each [class][VehicleClass], statistics of their
+//! speeds, and the issues found by the [data checks][crate::check_data]. It's
+//! [written][write_html] as an HTML file, which can also be [converted][write_pdf] to PDF by an
+//! external program - by default `wkhtmltopdf`, or whatever is set by the `REPORT_PDF_CONVERTER`
+//! env var (which is run with the paths of the HTML and PDF files).
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::{Datelike, NaiveDate};
+use oracle::Connection;
+use rinja::Template;
+use serde::Serialize;
+
+use crate::{
+    aadt::daily_volumes,
+    check_data::{check, speed_percentile, speed_ranges, SPEED_RANGE_LOWER_BOUNDS},
+    db::{self, LocatedMetadata},
+    location::Locations,
+    volume_profile::{create_volume_profile, DayType, VolumeProfile},
+    CountError, LaneDirection, VehicleClass,
+};
+
+/// The number and share of vehicles of a class.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassShare {
+    pub class: VehicleClass,
+    pub vehicles: u32,
+    pub percent: f32,
+}
+
+/// Statistics of the speeds of a count's vehicles, from the number in each speed range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpeedStatistics {
+    pub vehicles: u32,
+    /// The number of vehicles in each speed range (s1-s14).
+    pub ranges: [u32; 14],
+    /// The estimated median speed.
+    pub p50: Option<f32>,
+    /// The estimated 85th percentile speed.
+    pub p85: Option<f32>,
+}
+
+/// A summary report of a count.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CountReport {
+    pub recordnum: u32,
+    pub metadata: LocatedMetadata,
+    /// The volume of each full day counted.
+    pub daily: BTreeMap<NaiveDate, u32>,
+    pub profile: Option<VolumeProfile>,
+    /// The share of vehicles of each class (unclassified vehicles are also counted as class 2).
+    pub classes: Vec<ClassShare>,
+    pub speeds: Option<SpeedStatistics>,
+    /// The issues found by the data checks (or why they couldn't be done).
+    pub warnings: Vec<String>,
+}
+
+/// Create the report of a count in the database.
+pub fn create_report(
+    conn: &Connection,
+    recordnum: u32,
+    locations: &Locations,
+) -> Result<CountReport, CountError> {
+    let metadata = db::get_located_metadata(conn, recordnum, locations)?;
+
+    let volumes = db::get_volume_count(conn, recordnum)?;
+    let daily = daily_volumes(&volumes);
+    let profile = create_volume_profile(&volumes);
+
+    let class_counts = db::get_class_count(conn, recordnum)?;
+    let total = class_counts.iter().map(|v| v.total).sum::<u32>();
+    let classes = if total == 0 {
+        vec![]
+    } else {
+        VehicleClass::iter()
+            .map(|class| {
+                let vehicles = class_counts.iter().map(|v| v.class(class)).sum();
+                ClassShare {
+                    class,
+                    vehicles,
+                    percent: vehicles as f32 / total as f32 * 100.0,
+                }
+            })
+            .collect()
+    };
+
+    let ranges = speed_ranges(&db::get_speed_count(conn, recordnum)?);
+    let vehicles = ranges.iter().sum::<u32>();
+    let speeds = (vehicles > 0).then(|| SpeedStatistics {
+        vehicles,
+        ranges,
+        p50: speed_percentile(&ranges, 0.5),
+        p85: speed_percentile(&ranges, 0.85),
+    });
+
+    let warnings = match check(recordnum, conn) {
+        Ok(report) => report
+            .findings()
+            .map(|v| format!("{}: {}", v.level, v.message))
+            .collect(),
+        Err(e) => vec![format!("Data checks not done: {e}")],
+    };
+
+    Ok(CountReport {
+        recordnum,
+        metadata,
+        daily,
+        profile,
+        classes,
+        speeds,
+        warnings,
+    })
+}
+
+/// The report, formatted for its HTML template.
+#[derive(Template)]
+#[template(path = "report.html")]
+struct ReportTemplate {
+    recordnum: u32,
+    /// The name and value of each field of metadata.
+    fields: Vec<(&'static str, String)>,
+    /// The date, day of the week, and volume of each day.
+    daily: Vec<(String, String, u32)>,
+    /// The direction and type of day of each column of the hourly profile.
+    profile_columns: Vec<String>,
+    /// The hour and the average volume of each column of each row of the hourly profile.
+    profile_rows: Vec<(String, Vec<String>)>,
+    /// The hourly profile as JSON, for charting.
+    profile_json: String,
+    /// The number, name, count, and percentage of each class.
+    classes: Vec<(u8, String, u32, String)>,
+    /// The name and value of each speed statistic.
+    speed_statistics: Vec<(&'static str, String)>,
+    /// The speed range and count of each speed range.
+    speed_ranges: Vec<(String, u32)>,
+    warnings: Vec<String>,
+}
+
+/// The name and value of each field of a report's metadata, with "-" for those that aren't known.
+pub(crate) fn fields(report: &CountReport) -> Vec<(&'static str, String)> {
+    let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    let metadata = &report.metadata.metadata;
+    let location = &report.metadata.location;
+    vec![
+        (
+            "Type",
+            or_dash(metadata.count_kind.as_ref().map(|v| v.to_string())),
+        ),
+        ("Road", or_dash(location.road.clone())),
+        (
+            "Municipality",
+            or_dash(location.municipality.clone().or(location.mcd.clone())),
+        ),
+        (
+            "County",
+            or_dash(location.county_name.clone().or(location.county.clone())),
+        ),
+        (
+            "Coordinates",
+            or_dash(
+                location
+                    .latitude
+                    .zip(location.longitude)
+                    .map(|(lat, lon)| format!("{lat}, {lon}")),
+            ),
+        ),
+        ("Counter", or_dash(metadata.counter_id.clone())),
+        (
+            "Speed limit",
+            or_dash(metadata.speedlimit.map(|v| format!("{v} mph"))),
+        ),
+        ("Taken by", or_dash(metadata.takenby.clone())),
+        (
+            "Last counted",
+            or_dash(metadata.datelastcounted.map(|v| v.to_string())),
+        ),
+    ]
+}
+
+/// The name of each speed range (s1-s14), e.g. "15-20 mph".
+pub(crate) fn speed_range_names() -> Vec<String> {
+    SPEED_RANGE_LOWER_BOUNDS
+        .iter()
+        .enumerate()
+        .map(|(i, lower)| match SPEED_RANGE_LOWER_BOUNDS.get(i + 1) {
+            Some(upper) => format!("{lower}-{upper} mph"),
+            None => format!("over {lower} mph"),
+        })
+        .collect()
+}
+
+impl ReportTemplate {
+    fn new(report: &CountReport) -> Result<Self, CountError> {
+        let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+        let fields = fields(report);
+
+        let daily = report
+            .daily
+            .iter()
+            .map(|(date, volume)| (date.to_string(), date.weekday().to_string(), *volume))
+            .collect();
+
+        let mut series: BTreeMap<(Option<LaneDirection>, DayType), [Option<f32>; 24]> =
+            BTreeMap::new();
+        for hour in report.profile.iter().flat_map(|v| &v.hours) {
+            series
+                .entry((hour.direction, hour.day_type))
+                .or_insert([None; 24])[hour.hour as usize] = Some(hour.average);
+        }
+        let profile_columns = series
+            .keys()
+            .map(|(direction, day_type)| {
+                let direction = direction.map_or("both".to_string(), |v| v.to_string());
+                format!("{direction} {day_type}")
+            })
+            .collect();
+        let profile_rows = if series.is_empty() {
+            vec![]
+        } else {
+            (0..24)
+                .map(|hour| {
+                    let averages = series
+                        .values()
+                        .map(|v| or_dash(v[hour].map(|v| format!("{v:.0}"))))
+                        .collect();
+                    (format!("{hour:02}:00"), averages)
+                })
+                .collect()
+        };
+        // (Escaped so that it can't end the script element it's in.)
+        let profile_json =
+            serde_json::to_string(&report.profile.as_ref().map(|v| &v.hours).unwrap_or(&vec![]))?
+                .replace("</", "<\\/");
+
+        let classes = report
+            .classes
+            .iter()
+            .map(|v| {
+                (
+                    v.class.as_num(),
+                    v.class.to_string(),
+                    v.vehicles,
+                    format!("{:.1}%", v.percent),
+                )
+            })
+            .collect();
+
+        let speed = |v: Option<f32>| or_dash(v.map(|v| format!("{v:.1} mph")));
+        let (speed_statistics, speed_ranges) = match &report.speeds {
+            Some(speeds) => (
+                vec![
+                    ("Vehicles", speeds.vehicles.to_string()),
+                    ("Median speed", speed(speeds.p50)),
+                    ("85th percentile speed", speed(speeds.p85)),
+                ],
+                speed_range_names().into_iter().zip(speeds.ranges).collect(),
+            ),
+            None => (vec![], vec![]),
+        };
+
+        Ok(Self {
+            recordnum: report.recordnum,
+            fields,
+            daily,
+            profile_columns,
+            profile_rows,
+            profile_json,
+            classes,
+            speed_statistics,
+            speed_ranges,
+            warnings: report.warnings.clone(),
+        })
+    }
+}
+
+/// Render a report as HTML.
+pub fn render_html(report: &CountReport) -> Result<String, CountError> {
+    ReportTemplate::new(report)?
+        .render()
+        .map_err(|e| CountError::ReportError(e.to_string()))
+}
+
+/// Write a report as an HTML file in `dir`, returning the path of the file.
+pub fn write_html(report: &CountReport, dir: &Path) -> Result<PathBuf, CountError> {
+    let path = dir.join(format!("{}-report.html", report.recordnum));
+    fs::write(&path, render_html(report)?)?;
+    Ok(path)
+}
+
+/// Convert an HTML report to PDF (alongside it), returning the path of the PDF file.
+pub fn write_pdf(html: &Path) -> Result<PathBuf, CountError> {
+    let converter = env::var("REPORT_PDF_CONVERTER").unwrap_or_else(|_| "wkhtmltopdf".to_string());
+    let pdf = html.with_extension("pdf");
+    let output = Command::new(&converter)
+        .arg(html)
+        .arg(&pdf)
+        .output()
+        .map_err(|e| CountError::ReportError(format!("unable to run {converter}: {e}")))?;
+    if !output.status.success() {
+        return Err(CountError::ReportError(format!(
+            "{converter} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{location::Location, volume_profile::HourlyVolume, Metadata};
+
+    #[test]
+    fn report_rendered_as_html() {
+        let report = CountReport {
+            recordnum: 166905,
+            metadata: LocatedMetadata {
+                metadata: Metadata {
+                    recordnum: Some(166905),
+                    speedlimit: Some(35),
+                    ..Default::default()
+                },
+                location: Location {
+                    recordnum: 166905,
+                    road: Some("N Broad St".to_string()),
+                    county_name: Some("Philadelphia".to_string()),
+                    ..Default::default()
+                },
+            },
+            daily: BTreeMap::from([(NaiveDate::from_ymd_opt(2024, 4, 9).unwrap(), 12345)]),
+            profile: Some(VolumeProfile {
+                recordnum: 166905,
+                hours: vec![HourlyVolume {
+                    recordnum: 166905,
+                    direction: Some(LaneDirection::North),
+                    day_type: DayType::Weekday,
+                    hour: 7,
+                    days: 1,
+                    average: 640.0,
+                }],
+            }),
+            classes: vec![ClassShare {
+                class: VehicleClass::Buses,
+                vehicles: 10,
+                percent: 2.5,
+            }],
+            speeds: None,
+            warnings: vec!["WARN: 3 gaps in the data </script>".to_string()],
+        };
+        let html = render_html(&report).unwrap();
+        assert!(html.contains("N Broad St"));
+        assert!(html.contains("12345"));
+        assert!(html.contains("north weekday"));
+        assert!(html.contains("640"));
+        assert!(html.contains("buses"));
+        assert!(html.contains("2.5%"));
+        assert!(html.contains("3 gaps in the data"));
+        assert!(!html.contains("data </script>"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Running the import program as a Windows service.
+//!
+//! Rather than being started again and again by the Task Scheduler, the import program can run
+//! continuously as a service, started with Windows (and restarted, if so configured, should it
+//! fail). The Service Control Manager starts it by running it, which should then call [`run`]
+//! with what to do; stopping the service and changing its parameters are passed on to it. When it
+//! stops on its own, it reports a non-zero exit code, so that the service's recovery actions (if
+//! set to apply to failures other than crashes) are taken. This module is only built on Windows.
+use std::ffi::OsString;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+};
+
+use crate::CountError;
+
+/// The name the service is installed under.
+pub const SERVICE_NAME: &str = "traffic-counts-import";
+
+/// What the service does, and how it stops and reloads its configuration.
+struct Service {
+    run: Mutex<Option<Box<dyn FnOnce() -> u32 + Send>>>,
+    stop: fn(),
+    reload: fn(),
+}
+
+static SERVICE: OnceLock<Service> = OnceLock::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Run as a service: `run` until it returns, calling `stop` when the service is asked to stop
+/// (`run` should then return soon after) and `reload` when its parameters have changed.
+///
+/// `run` returns the exit code to report once stopped: 0 if it stopped because it was asked to,
+/// or otherwise a non-zero code, which is reported as specific to the service.
+///
+/// This blocks until the service has stopped. It fails if the program wasn't started by the
+/// Service Control Manager.
+pub fn run(
+    run: impl FnOnce() -> u32 + Send + 'static,
+    stop: fn(),
+    reload: fn(),
+) -> Result<(), CountError> {
+    let _ = SERVICE.set(Service {
+        run: Mutex::new(Some(Box::new(run))),
+        stop,
+        reload,
+    });
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .map_err(|e| CountError::ServiceError(e.to_string()))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let Some(service) = SERVICE.get() else {
+        return;
+    };
+    let handler = move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            (service.stop)();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::ParamChange => {
+            (service.reload)();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let Ok(status_handle) = service_control_handler::register(SERVICE_NAME, handler) else {
+        return;
+    };
+    let status = |current_state, controls_accepted, exit_code| ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    };
+
+    let _ = status_handle.set_service_status(status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP
+            | ServiceControlAccept::SHUTDOWN
+            | ServiceControlAccept::PARAM_CHANGE,
+        ServiceExitCode::Win32(0),
+    ));
+    let code = match service.run.lock().ok().and_then(|mut v| v.take()) {
+        Some(run) => run(),
+        None => 0,
+    };
+    let exit_code = match code {
+        0 => ServiceExitCode::Win32(0),
+        code => ServiceExitCode::ServiceSpecific(code),
+    };
+    let _ = status_handle.set_service_status(status(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        exit_code,
+    ));
+}
@@ -0,0 +1,428 @@
+//! Fetch files to import from where counters' files are uploaded.
+//!
+//! Files are often uploaded somewhere other than the data directory - an SFTP server or a
+//! network share - from which someone would otherwise have to copy them. An [`Ingestion`] copies
+//! new files from such a [`Source`] into the data directory (keeping the same subdirectories, and
+//! so the same filename and metadata conventions), where they are imported like any other file.
+//! Once a file has been imported, it can then be deleted from the source, or moved to an archive
+//! directory there. Files left in place are [recorded][Ingestion::new], by their path and the hash
+//! of their contents, so that they aren't fetched and imported again - unless they change.
+//!
+//! A source is given as either a directory - including a network share, e.g.
+//! `\\server\share\counts` - or, with the `sftp` feature, an SFTP URL, e.g.
+//! `sftp://user@host:22/counts`. The password of an SFTP server is read from the `SFTP_PASSWORD`
+//! env var, or, if `SFTP_KEY` is set, the private key at that path is used instead.
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::error;
+
+use crate::{extract_from_file::file_hash, CountError};
+
+/// Somewhere files to import can be fetched from.
+///
+/// Paths are relative to the root of the source, e.g. "vehicle/166905-ew-40972-35.txt".
+pub trait Source {
+    /// List all files in the source.
+    fn list(&mut self) -> Result<Vec<PathBuf>, CountError>;
+    /// Copy a file from the source to `dest`.
+    fn fetch(&mut self, file: &Path, dest: &Path) -> Result<(), CountError>;
+    /// Delete a file from the source.
+    fn remove(&mut self, file: &Path) -> Result<(), CountError>;
+    /// Move a file into a directory of the source (relative to its root, unless absolute),
+    /// keeping its subdirectories.
+    fn archive(&mut self, file: &Path, archive_dir: &Path) -> Result<(), CountError>;
+}
+
+/// Open a source: a directory, or an SFTP URL (`sftp://user@host[:port]/path`).
+pub fn open(source: &str) -> Result<Box<dyn Source>, CountError> {
+    match source.strip_prefix("sftp://") {
+        #[cfg(feature = "sftp")]
+        Some(url) => Ok(Box::new(SftpSource::connect(url)?)),
+        #[cfg(not(feature = "sftp"))]
+        Some(_) => Err(CountError::SourceError(
+            "SFTP sources require the `sftp` feature".to_string(),
+        )),
+        None => Ok(Box::new(DirSource::new(PathBuf::from(source)))),
+    }
+}
+
+/// A directory, either local or a network share.
+pub struct DirSource {
+    root: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn list_dir(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), CountError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.list_dir(&path, files)?;
+            } else if let Ok(file) = path.strip_prefix(&self.root) {
+                files.push(file.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source for DirSource {
+    fn list(&mut self) -> Result<Vec<PathBuf>, CountError> {
+        let mut files = vec![];
+        self.list_dir(&self.root, &mut files)?;
+        Ok(files)
+    }
+
+    fn fetch(&mut self, file: &Path, dest: &Path) -> Result<(), CountError> {
+        fs::copy(self.root.join(file), dest)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, file: &Path) -> Result<(), CountError> {
+        Ok(fs::remove_file(self.root.join(file))?)
+    }
+
+    fn archive(&mut self, file: &Path, archive_dir: &Path) -> Result<(), CountError> {
+        let dest = self.root.join(archive_dir).join(file);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Renaming fails across filesystems, so copy and remove in that case.
+        if fs::rename(self.root.join(file), &dest).is_err() {
+            fs::copy(self.root.join(file), &dest)?;
+            fs::remove_file(self.root.join(file))?;
+        }
+        Ok(())
+    }
+}
+
+/// A directory on an SFTP server.
+#[cfg(feature = "sftp")]
+pub struct SftpSource {
+    sftp: ssh2::Sftp,
+    root: PathBuf,
+}
+
+#[cfg(feature = "sftp")]
+impl SftpSource {
+    /// Connect to an SFTP server, from the part of its URL after "sftp://".
+    pub fn connect(url: &str) -> Result<Self, CountError> {
+        let invalid = || CountError::SourceError(format!("invalid SFTP URL 'sftp://{url}'"));
+        let (user, rest) = url.split_once('@').ok_or_else(invalid)?;
+        let (address, root) = rest.split_once('/').ok_or_else(invalid)?;
+        let (host, port) = match address.split_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (address, 22),
+        };
+
+        let mut session = ssh2::Session::new()?;
+        session.set_tcp_stream(std::net::TcpStream::connect((host, port))?);
+        session.handshake()?;
+        match std::env::var("SFTP_KEY") {
+            Ok(key) => session.userauth_pubkey_file(user, None, Path::new(&key), None)?,
+            Err(_) => {
+                let password = std::env::var("SFTP_PASSWORD").map_err(|e| {
+                    CountError::SourceError(format!("unable to load SFTP_PASSWORD: {e}"))
+                })?;
+                session.userauth_password(user, &password)?
+            }
+        }
+        Ok(Self {
+            sftp: session.sftp()?,
+            root: Path::new("/").join(root),
+        })
+    }
+
+    fn list_dir(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), CountError> {
+        for (path, stat) in self.sftp.readdir(dir)? {
+            if stat.is_dir() {
+                self.list_dir(&path, files)?;
+            } else if let Ok(file) = path.strip_prefix(&self.root) {
+                files.push(file.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a directory and any of its parents that don't exist.
+    fn create_dir_all(&self, dir: &Path) -> Result<(), CountError> {
+        if self.sftp.stat(dir).is_ok() {
+            return Ok(());
+        }
+        if let Some(parent) = dir.parent() {
+            self.create_dir_all(parent)?;
+        }
+        Ok(self.sftp.mkdir(dir, 0o755)?)
+    }
+}
+
+#[cfg(feature = "sftp")]
+impl Source for SftpSource {
+    fn list(&mut self) -> Result<Vec<PathBuf>, CountError> {
+        let mut files = vec![];
+        self.list_dir(&self.root, &mut files)?;
+        Ok(files)
+    }
+
+    fn fetch(&mut self, file: &Path, dest: &Path) -> Result<(), CountError> {
+        let mut remote = self.sftp.open(self.root.join(file))?;
+        let mut local = fs::File::create(dest)?;
+        std::io::copy(&mut remote, &mut local)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, file: &Path) -> Result<(), CountError> {
+        Ok(self.sftp.unlink(&self.root.join(file))?)
+    }
+
+    fn archive(&mut self, file: &Path, archive_dir: &Path) -> Result<(), CountError> {
+        let dest = self.root.join(archive_dir).join(file);
+        if let Some(parent) = dest.parent() {
+            self.create_dir_all(parent)?;
+        }
+        Ok(self.sftp.rename(&self.root.join(file), &dest, None)?)
+    }
+}
+
+#[cfg(feature = "sftp")]
+impl From<ssh2::Error> for CountError {
+    fn from(value: ssh2::Error) -> Self {
+        CountError::SourceError(value.to_string())
+    }
+}
+
+/// Copies new files from a [`Source`] into the data directory, and deletes or archives them in
+/// the source once they have been imported.
+pub struct Ingestion {
+    location: String,
+    source: Option<Box<dyn Source>>,
+    /// Delete files from the source once they've been imported.
+    remove: bool,
+    /// The directory of the source to move files to once they've been imported, if any.
+    archive_dir: Option<PathBuf>,
+    /// The files fetched (by their path in the data directory) and their paths in the source.
+    fetched: HashMap<PathBuf, PathBuf>,
+    /// The file that every file fetched is recorded in, unless they're deleted from the source.
+    record: Option<PathBuf>,
+    /// The files recorded as fetched, by their path in the source and the hash of their contents.
+    recorded: HashSet<(PathBuf, String)>,
+}
+
+impl Ingestion {
+    /// Create an `Ingestion` from the source at `location` (see [`open`]).
+    ///
+    /// Unless files are to be deleted from the source once imported, those fetched are recorded
+    /// in `record` (one per line, as the hash of its contents and its path in the source,
+    /// separated by a tab), so that they aren't fetched again - by this or a later run - unless
+    /// their contents change.
+    pub fn new(
+        location: String,
+        remove: bool,
+        archive_dir: Option<PathBuf>,
+        record: PathBuf,
+    ) -> Result<Self, CountError> {
+        let (record, recorded) = if remove {
+            (None, HashSet::new())
+        } else {
+            let recorded = match fs::read_to_string(&record) {
+                Ok(v) => v
+                    .lines()
+                    .filter_map(|line| line.split_once('\t'))
+                    .map(|(hash, file)| (PathBuf::from(file), hash.to_string()))
+                    .collect(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+                Err(e) => return Err(e.into()),
+            };
+            (Some(record), recorded)
+        };
+        Ok(Self {
+            location,
+            source: None,
+            remove,
+            archive_dir,
+            fetched: HashMap::new(),
+            record,
+            recorded,
+        })
+    }
+
+    /// Copy the files in the source that haven't already been fetched into the data directory,
+    /// returning the number copied.
+    ///
+    /// Files that can't be copied are logged and skipped. If the source can't be listed, it is
+    /// reopened (e.g. reconnecting to an SFTP server) the next time this is called.
+    ///
+    /// Files that are recorded are fetched to a temporary file first, to compare their contents
+    /// with those recorded, so that nothing is written to the data directory unless it's new.
+    pub fn fetch_new(&mut self, data_dir: &Path) -> Result<usize, CountError> {
+        let mut source = match self.source.take() {
+            Some(v) => v,
+            None => open(&self.location)?,
+        };
+        let files = source.list()?;
+
+        let mut num_fetched = 0;
+        for file in files {
+            let dest = data_dir.join(&file);
+            let archived = self
+                .archive_dir
+                .as_ref()
+                .is_some_and(|v| file.starts_with(v));
+            if archived || dest.exists() || self.fetched.values().any(|v| *v == file) {
+                continue;
+            }
+            match self.fetch(source.as_mut(), &file, &dest) {
+                Ok(true) => {
+                    self.fetched.insert(dest, file);
+                    num_fetched += 1;
+                }
+                Ok(false) => (),
+                Err(e) => error!("{file:?} not fetched from {}: {e}", self.location),
+            }
+        }
+        self.source = Some(source);
+        Ok(num_fetched)
+    }
+
+    /// Copy a file from the source to `dest`, unless it's recorded as already fetched, returning
+    /// whether it was copied.
+    fn fetch(
+        &mut self,
+        source: &mut dyn Source,
+        file: &Path,
+        dest: &Path,
+    ) -> Result<bool, CountError> {
+        let Some(record) = &self.record else {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            source.fetch(file, dest)?;
+            return Ok(true);
+        };
+
+        let temp = std::env::temp_dir().join(format!(
+            "traffic_counts_fetch_{}_{}",
+            std::process::id(),
+            file.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        source.fetch(file, &temp)?;
+        let fetched = file_hash(&temp).and_then(|hash| {
+            let entry = (file.to_path_buf(), hash);
+            if self.recorded.contains(&entry) {
+                return Ok(false);
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&temp, dest)?;
+            let mut record = OpenOptions::new().create(true).append(true).open(record)?;
+            writeln!(record, "{}\t{}", entry.1, entry.0.display())?;
+            self.recorded.insert(entry);
+            Ok(true)
+        });
+        let _ = fs::remove_file(&temp);
+        fetched
+    }
+
+    /// Delete or archive the file in the source that a file in the data directory was fetched
+    /// from, if it was and if configured to.
+    pub fn imported(&mut self, path: &Path) -> Result<(), CountError> {
+        let Some(file) = self.fetched.remove(path) else {
+            return Ok(());
+        };
+        let Some(source) = &mut self.source else {
+            return Err(CountError::SourceError(format!(
+                "not connected to {}",
+                self.location
+            )));
+        };
+        match &self.archive_dir {
+            Some(archive_dir) => source.archive(&file, archive_dir),
+            None if self.remove => source.remove(&file),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_files_fetched_once_and_archived_once_imported() {
+        let dir = std::env::temp_dir().join("traffic_counts_source");
+        let _ = fs::remove_dir_all(&dir);
+        let (remote, data_dir) = (dir.join("remote"), dir.join("data"));
+        fs::create_dir_all(remote.join("vehicle")).unwrap();
+        fs::copy(
+            "test_files/vehicle/101-eee-21-35.csv",
+            remote.join("vehicle/101-eee-21-35.csv"),
+        )
+        .unwrap();
+
+        let mut ingestion = Ingestion::new(
+            remote.to_str().unwrap().to_string(),
+            false,
+            Some(PathBuf::from("archive")),
+            dir.join("fetched.log"),
+        )
+        .unwrap();
+        assert_eq!(ingestion.fetch_new(&data_dir).unwrap(), 1);
+        let local = data_dir.join("vehicle/101-eee-21-35.csv");
+        assert!(local.is_file());
+
+        // Not fetched again, even if removed from the data directory without being imported.
+        fs::remove_file(&local).unwrap();
+        assert_eq!(ingestion.fetch_new(&data_dir).unwrap(), 0);
+
+        ingestion.imported(&local).unwrap();
+        assert!(!remote.join("vehicle/101-eee-21-35.csv").exists());
+        assert!(remote.join("archive/vehicle/101-eee-21-35.csv").is_file());
+        assert_eq!(ingestion.fetch_new(&data_dir).unwrap(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_left_in_source_fetched_again_only_if_changed() {
+        let dir = std::env::temp_dir().join("traffic_counts_source_left");
+        let _ = fs::remove_dir_all(&dir);
+        let (remote, data_dir) = (dir.join("remote"), dir.join("data"));
+        fs::create_dir_all(remote.join("vehicle")).unwrap();
+        let remote_file = remote.join("vehicle/101-eee-21-35.csv");
+        fs::copy("test_files/vehicle/101-eee-21-35.csv", &remote_file).unwrap();
+        let ingestion = || {
+            Ingestion::new(
+                remote.to_str().unwrap().to_string(),
+                false,
+                None,
+                dir.join("fetched.log"),
+            )
+            .unwrap()
+        };
+
+        let mut first = ingestion();
+        assert_eq!(first.fetch_new(&data_dir).unwrap(), 1);
+        let local = data_dir.join("vehicle/101-eee-21-35.csv");
+        first.imported(&local).unwrap();
+        assert!(remote_file.is_file());
+        fs::remove_file(&local).unwrap();
+        assert_eq!(first.fetch_new(&data_dir).unwrap(), 0);
+
+        // Nor by a later run, unless the file has changed.
+        let mut second = ingestion();
+        assert_eq!(second.fetch_new(&data_dir).unwrap(), 0);
+        assert!(!local.exists());
+        fs::write(&remote_file, "changed").unwrap();
+        assert_eq!(second.fetch_new(&data_dir).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&local).unwrap(), "changed");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+//! How well vehicles comply with the posted speed limit.
+//!
+//! Municipalities ask, with nearly every speed study, how many vehicles exceed the speed limit and
+//! by how much. From the records of [individual vehicles][IndividualVehicle] and the count's
+//! [speed limit][FieldMetadata::speed_limit], the vehicles over the limit, and at least 5, 10, and
+//! 15 mph over it, are counted for each hour and for the count as a whole, by direction.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::{NaiveDateTime, Timelike};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::{FieldMetadata, IndividualVehicle, LaneDirection};
+
+/// The number of vehicles, and of those over the speed limit (by at least some amount), in some
+/// period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeedCompliance {
+    pub vehicles: u32,
+    /// Vehicles going faster than the speed limit.
+    pub over: u32,
+    /// Vehicles going at least 5 mph over the speed limit.
+    pub over_5: u32,
+    /// Vehicles going at least 10 mph over the speed limit.
+    pub over_10: u32,
+    /// Vehicles going at least 15 mph over the speed limit.
+    pub over_15: u32,
+}
+
+impl SpeedCompliance {
+    fn add_vehicle(&mut self, speed: f32, speed_limit: u8) {
+        let over = speed - speed_limit as f32;
+        self.vehicles += 1;
+        self.over += (over > 0.0) as u32;
+        self.over_5 += (over >= 5.0) as u32;
+        self.over_10 += (over >= 10.0) as u32;
+        self.over_15 += (over >= 15.0) as u32;
+    }
+
+    fn add(&mut self, other: Self) {
+        self.vehicles += other.vehicles;
+        self.over += other.over;
+        self.over_5 += other.over_5;
+        self.over_10 += other.over_10;
+        self.over_15 += other.over_15;
+    }
+
+    /// The percentage of vehicles that `num` is, if there were any vehicles.
+    pub fn percent(&self, num: u32) -> Option<f32> {
+        if self.vehicles == 0 {
+            None
+        } else {
+            Some(num as f32 / self.vehicles as f32 * 100.0)
+        }
+    }
+}
+
+impl Display for SpeedCompliance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} vehicles", self.vehicles)?;
+        if let (Some(over), Some(over_5), Some(over_10), Some(over_15)) = (
+            self.percent(self.over),
+            self.percent(self.over_5),
+            self.percent(self.over_10),
+            self.percent(self.over_15),
+        ) {
+            write!(
+                f,
+                ", {over:.1}% over, {over_5:.1}% 5+ mph over, {over_10:.1}% 10+ mph over, \
+                {over_15:.1}% 15+ mph over"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Speed compliance of a count, by direction: for each hour and overall.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedComplianceReport {
+    pub recordnum: u32,
+    pub speed_limit: u8,
+    /// The start of each hour, and its compliance, by direction.
+    pub hourly: BTreeMap<LaneDirection, BTreeMap<NaiveDateTime, SpeedCompliance>>,
+    pub by_direction: BTreeMap<LaneDirection, SpeedCompliance>,
+    pub overall: SpeedCompliance,
+}
+
+/// A row of a [`SpeedComplianceReport`], for export: one for each direction and hour, and then
+/// one for each direction for the count as a whole (without an hour).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedComplianceRow {
+    pub recordnum: u32,
+    pub direction: LaneDirection,
+    pub hour: Option<NaiveDateTime>,
+    pub speed_limit: u8,
+    pub vehicles: u32,
+    pub over: u32,
+    pub over_5: u32,
+    pub over_10: u32,
+    pub over_15: u32,
+    pub percent_over: Option<f32>,
+    pub percent_over_5: Option<f32>,
+    pub percent_over_10: Option<f32>,
+    pub percent_over_15: Option<f32>,
+}
+
+impl SpeedComplianceReport {
+    /// The report flattened into [rows][SpeedComplianceRow].
+    pub fn rows(&self) -> Vec<SpeedComplianceRow> {
+        let row = |direction, hour, compliance: &SpeedCompliance| SpeedComplianceRow {
+            recordnum: self.recordnum,
+            direction,
+            hour,
+            speed_limit: self.speed_limit,
+            vehicles: compliance.vehicles,
+            over: compliance.over,
+            over_5: compliance.over_5,
+            over_10: compliance.over_10,
+            over_15: compliance.over_15,
+            percent_over: compliance.percent(compliance.over),
+            percent_over_5: compliance.percent(compliance.over_5),
+            percent_over_10: compliance.percent(compliance.over_10),
+            percent_over_15: compliance.percent(compliance.over_15),
+        };
+        let mut rows = vec![];
+        for (direction, hourly) in &self.hourly {
+            for (hour, compliance) in hourly {
+                rows.push(row(*direction, Some(*hour), compliance));
+            }
+        }
+        for (direction, compliance) in &self.by_direction {
+            rows.push(row(*direction, None, compliance));
+        }
+        rows
+    }
+}
+
+impl Display for SpeedComplianceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} speed compliance (limit {} mph): {}",
+            self.recordnum, self.speed_limit, self.overall
+        )?;
+        for (direction, compliance) in &self.by_direction {
+            write!(f, "; {direction:?}: {compliance}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Report the speed compliance of a count from its [`IndividualVehicle`]s.
+///
+/// Returns `None` if the count's speed limit isn't known or there are no vehicles.
+pub fn create_speed_compliance(
+    metadata: &FieldMetadata,
+    vehicles: &[IndividualVehicle],
+) -> Option<SpeedComplianceReport> {
+    let speed_limit = metadata.speed_limit?;
+    if vehicles.is_empty() {
+        return None;
+    }
+    let mut hourly: BTreeMap<LaneDirection, BTreeMap<NaiveDateTime, SpeedCompliance>> =
+        BTreeMap::new();
+    let mut by_direction: BTreeMap<LaneDirection, SpeedCompliance> = BTreeMap::new();
+
+    for vehicle in vehicles {
+        let Some(channel) = metadata.channels.get(&vehicle.lane) else {
+            error!(
+                "Unable to determine lane/direction of channel {}.",
+                vehicle.lane
+            );
+            continue;
+        };
+        let hour = vehicle
+            .date
+            .and_hms_opt(vehicle.time.hour(), 0, 0)
+            .expect("hour of a valid time is valid");
+        hourly
+            .entry(channel.direction)
+            .or_default()
+            .entry(hour)
+            .or_default()
+            .add_vehicle(vehicle.speed, speed_limit);
+    }
+
+    let mut overall = SpeedCompliance::default();
+    for (direction, hours) in &hourly {
+        let total = by_direction.entry(*direction).or_default();
+        for compliance in hours.values() {
+            total.add(*compliance);
+        }
+        overall.add(*total);
+    }
+
+    Some(SpeedComplianceReport {
+        recordnum: metadata.recordnum,
+        speed_limit,
+        hourly,
+        by_direction,
+        overall,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Directions, VehicleClass};
+
+    fn vehicle(time: &str, lane: u8, speed: f32) -> IndividualVehicle {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        IndividualVehicle {
+            date: time.date(),
+            time,
+            lane,
+            class: VehicleClass::Motorcycles,
+            speed,
+            axles: None,
+            gap: None,
+            headway: None,
+            length: None,
+        }
+    }
+
+    #[test]
+    fn vehicles_over_limit_counted_by_hour_and_direction() {
+        let mut metadata = FieldMetadata::new(
+            123,
+            Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
+            "1".to_string(),
+            Some(35),
+        );
+        let vehicles = vec![
+            vehicle("2024-04-08 07:05", 1, 30.0),
+            vehicle("2024-04-08 07:10", 1, 35.0),
+            vehicle("2024-04-08 07:20", 1, 40.0),
+            vehicle("2024-04-08 07:30", 1, 51.0),
+            vehicle("2024-04-08 08:00", 1, 45.0),
+            vehicle("2024-04-08 07:15", 2, 36.0),
+        ];
+        let report = create_speed_compliance(&metadata, &vehicles).unwrap();
+
+        let seven = NaiveDateTime::parse_from_str("2024-04-08 07:00", "%Y-%m-%d %H:%M").unwrap();
+        let east = &report.hourly[&LaneDirection::East];
+        assert_eq!(east.len(), 2);
+        assert_eq!(
+            east[&seven],
+            SpeedCompliance {
+                vehicles: 4,
+                over: 2,
+                over_5: 2,
+                over_10: 1,
+                over_15: 1,
+            }
+        );
+        assert_eq!(report.by_direction[&LaneDirection::East].over_10, 2);
+        assert_eq!(report.by_direction[&LaneDirection::West].over, 1);
+        assert_eq!(report.overall.vehicles, 6);
+        assert_eq!(report.overall.over, 4);
+        let west = report.by_direction[&LaneDirection::West];
+        assert_eq!(west.percent(west.over), Some(100.0));
+        assert_eq!(west.percent(west.over_5), Some(0.0));
+        assert_eq!(report.rows().len(), 5);
+
+        metadata.speed_limit = None;
+        assert!(create_speed_compliance(&metadata, &vehicles).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+//! The status of the running import program, served over HTTP.
+//!
+//! So that whether the import program is running, and what it's doing, can be checked (e.g. by a
+//! monitoring service, or by someone wondering why a file hasn't been imported yet) without
+//! reading its log, it keeps a [`Status`]. This can be [served][serve] as JSON at `/status`,
+//! along with its [metrics][crate::metrics], in Prometheus's text format, at `/metrics`.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{Local, NaiveDateTime};
+use log::error;
+use serde::Serialize;
+
+use crate::{import_summary::ImportSummary, metrics::Metrics, CountError};
+
+/// What the import program is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activity {
+    /// Waiting for files to be uploaded.
+    Waiting,
+    Importing,
+    /// Checking the data of counts already imported again, on a schedule.
+    Checking,
+}
+
+/// The status of the running import program.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub started: NaiveDateTime,
+    /// When the configuration was last (re)loaded.
+    pub config_loaded: NaiveDateTime,
+    pub activity: Activity,
+    /// The file being imported, if any.
+    pub current_file: Option<PathBuf>,
+    /// The summary of the last run in which any files were processed.
+    pub last_summary: Option<ImportSummary>,
+    #[serde(skip)]
+    pub metrics: Metrics,
+}
+
+/// A [`Status`] shared between the import program and the server.
+pub type SharedStatus = Arc<Mutex<Status>>;
+
+impl Status {
+    pub fn new() -> Self {
+        let now = Local::now().naive_local();
+        Self {
+            started: now,
+            config_loaded: now,
+            activity: Activity::Waiting,
+            current_file: None,
+            last_summary: None,
+            metrics: Metrics::new(),
+        }
+    }
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Update a shared status (unless the server panicked while reading it).
+pub fn update(status: &SharedStatus, f: impl FnOnce(&mut Status)) {
+    if let Ok(mut status) = status.lock() {
+        f(&mut status);
+    }
+}
+
+/// Serve the status at `address`, on a thread of its own, for as long as the program runs.
+///
+/// Nothing is served until the address has been bound to, so an error binding to it is returned
+/// rather than logged.
+pub fn serve(address: SocketAddr, status: SharedStatus) -> Result<(), CountError> {
+    let listener = std::net::TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    thread::spawn(move || {
+        runtime.block_on(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Unable to serve status: {e}");
+                    return;
+                }
+            };
+            let app = Router::new()
+                .route("/status", get(get_status))
+                .route("/metrics", get(get_metrics))
+                .with_state(status);
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("Unable to serve status: {e}");
+            }
+        })
+    });
+    Ok(())
+}
+
+async fn get_status(State(status): State<SharedStatus>) -> Response {
+    match status.lock() {
+        Ok(status) => Json(status.clone()).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn get_metrics(State(status): State<SharedStatus>) -> Response {
+    match status.lock() {
+        Ok(status) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            status.metrics.to_string(),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_serialized_without_metrics() {
+        let mut status = Status::new();
+        status.activity = Activity::Importing;
+        status.current_file = Some(PathBuf::from("vehicle/166905-ew-40972-35.txt"));
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["activity"], "importing");
+        assert_eq!(json["current_file"], "vehicle/166905-ew-40972-35.txt");
+        assert!(json["last_summary"].is_null());
+        assert!(json.get("metrics").is_none());
+    }
+}
@@ -0,0 +1,547 @@
+//! Export counts in the FHWA Traffic Monitoring Guide (TMG) formats.
+//!
+//! This is for submitting counts to PennDOT/TMAS without a separate conversion tool. Counts are
+//! exported as fixed-width [records of a type][TmgRecordType], using the count's [`Metadata`]
+//! (TC_HEADER) to identify the station.
+//!
+//! ## Volume records
+//!
+//! Each [`NonNormalVolCount`] (i.e. TC_VOLCOUNT) day is written as a TMG hourly volume record, of
+//! 141 columns:
+//!
+//! | Columns | Field                                                          |
+//! |---------|----------------------------------------------------------------|
+//! | 1       | Record type ("3")                                              |
+//! | 2-3     | FIPS state code, from the first two digits of the MCD          |
+//! | 4-5     | Functional classification (FC and "R"ural/"U"rban, e.g. "3U")  |
+//! | 6-11    | Station ID (TC_HEADER's station ID, or else the recordnum)     |
+//! | 12      | Direction of travel                                            |
+//! | 13      | Lane of travel ("0", all lanes combined)                       |
+//! | 14-15   | Year                                                           |
+//! | 16-17   | Month                                                          |
+//! | 18-19   | Day                                                            |
+//! | 20      | Day of week (1 is Sunday)                                      |
+//! | 21-140  | Volume of each hour, starting at midnight, five columns each   |
+//! | 141     | Restrictions ("0", none)                                       |
+//!
+//! TMG volume records are of whole days only, so partial days (typically the first and last of a
+//! count) are left out.
+//!
+//! ## Classification records
+//!
+//! Each hour of [`TimeBinnedVehicleClassCount`]s (i.e. TC_CLACOUNT) is written as a TMG
+//! vehicle classification record, of 89 columns:
+//!
+//! | Columns | Field                                                          |
+//! |---------|----------------------------------------------------------------|
+//! | 1       | Record type ("C")                                              |
+//! | 2-3     | FIPS state code                                                |
+//! | 4-9     | Station ID                                                     |
+//! | 10      | Direction of travel                                            |
+//! | 11      | Lane of travel ("0", all lanes combined)                       |
+//! | 12-13   | Year                                                           |
+//! | 14-15   | Month                                                          |
+//! | 16-17   | Day                                                            |
+//! | 18-19   | Hour (starting at 00)                                          |
+//! | 20-24   | Total volume (including unclassified vehicles)                 |
+//! | 25-89   | Volume of each of the 13 FHWA classes, five columns each       |
+//!
+//! ## Speed records
+//!
+//! Each hour of [`TimeBinnedSpeedRangeCount`]s (i.e. TC_SPECOUNT) is written as a TMG speed
+//! record, of 94 columns:
+//!
+//! | Columns | Field                                                          |
+//! |---------|----------------------------------------------------------------|
+//! | 1       | Record type ("S")                                              |
+//! | 2-19    | As in classification records                                   |
+//! | 20-24   | Total volume                                                   |
+//! | 25-94   | Volume of each of the 14 speed bins, five columns each         |
+//!
+//! The speed records of a count are preceded by a declaration of the bins they use, of 50
+//! columns:
+//!
+//! | Columns | Field                                                          |
+//! |---------|----------------------------------------------------------------|
+//! | 1       | Record type ("B")                                              |
+//! | 2-3     | FIPS state code                                                |
+//! | 4-9     | Station ID                                                     |
+//! | 10-11   | Number of speed bins ("14")                                    |
+//! | 12-50   | Upper bound of each bin but the last (mph), three columns each |
+//!
+//! Partial hours at the start and end of a count are left out of both classification and speed
+//! records.
+//!
+//! In all records, lanes going the same direction are combined.
+//!
+//! TMG records identify a count only by its state and station ID, so its
+//! [location][crate::location] isn't exported; TMAS has the location of each station.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use oracle::Connection;
+
+use crate::{
+    db::{self, crud::Crud},
+    denormalize::NonNormalVolCount,
+    CountError, LaneDirection, Metadata, PartialPeriods, RoadDirection, TimeBinnedSpeedRangeCount,
+    TimeBinnedVehicleClassCount, TimeInterval,
+};
+
+/// The upper bound (in mph) of each of the speed bins of [`TimeBinnedSpeedRangeCount`]s but the
+/// last, which has none.
+const SPEED_BIN_UPPER_BOUNDS: [u8; 13] = [15, 20, 25, 30, 35, 40, 45, 50, 55, 60, 65, 70, 75];
+
+/// The types of TMG records counts can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TmgRecordType {
+    /// Hourly volume ("3") records.
+    Volume,
+    /// Vehicle classification ("C") records.
+    Class,
+    /// Speed ("S") records, preceded by the declaration ("B") of their speed bins.
+    Speed,
+}
+
+impl FromStr for TmgRecordType {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "volume" => Ok(TmgRecordType::Volume),
+            "class" => Ok(TmgRecordType::Class),
+            "speed" => Ok(TmgRecordType::Speed),
+            _ => Err(CountError::UnknownTmgRecordType(s.to_string())),
+        }
+    }
+}
+
+impl Display for TmgRecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let record_type = match self {
+            TmgRecordType::Volume => "volume",
+            TmgRecordType::Class => "class",
+            TmgRecordType::Speed => "speed",
+        };
+        write!(f, "{}", record_type)
+    }
+}
+
+/// Create TMG hourly volume records from the hourly volume counts of a count.
+pub fn volume_records(
+    metadata: &Metadata,
+    counts: &[NonNormalVolCount],
+) -> Result<Vec<String>, CountError> {
+    let state = state_code(metadata)?;
+    let fc = functional_class(metadata)?;
+    let station_id = station_id(metadata)?;
+
+    // Combine the lanes of each direction, leaving out any hour missing from any lane.
+    let mut days: BTreeMap<(NaiveDate, Option<LaneDirection>), [Option<u32>; 24]> = BTreeMap::new();
+    for count in counts {
+        let hours = count.hourly();
+        days.entry((count.date, count.direction))
+            .and_modify(|day| {
+                for (total, volume) in day.iter_mut().zip(hours) {
+                    *total = total.zip(volume).map(|(total, volume)| total + volume);
+                }
+            })
+            .or_insert(hours);
+    }
+
+    let mut records = vec![];
+    for ((date, direction), hours) in days {
+        // Only whole days are included.
+        let Some(hours) = hours.into_iter().collect::<Option<Vec<_>>>() else {
+            continue;
+        };
+        let mut record = format!(
+            "3{state}{fc}{station_id}{}0{:02}{:02}{:02}{}",
+            direction_code(direction, metadata)?,
+            date.year() % 100,
+            date.month(),
+            date.day(),
+            date.weekday().number_from_sunday(),
+        );
+        for volume in hours {
+            record.push_str(&format!("{volume:05}"));
+        }
+        record.push('0');
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Create TMG vehicle classification records from the 15-minute class counts of a count.
+pub fn class_records(
+    metadata: &Metadata,
+    counts: &[TimeBinnedVehicleClassCount],
+) -> Result<Vec<String>, CountError> {
+    let hours = hourly_volumes(
+        counts,
+        |count| (count.time, count.direction),
+        |count| {
+            [
+                count.total,
+                count.c1,
+                count.c2,
+                count.c3,
+                count.c4,
+                count.c5,
+                count.c6,
+                count.c7,
+                count.c8,
+                count.c9,
+                count.c10,
+                count.c11,
+                count.c12,
+                count.c13,
+            ]
+        },
+    );
+    hours
+        .into_iter()
+        .map(|((hour, direction), volumes)| hourly_record('C', metadata, hour, direction, &volumes))
+        .collect()
+}
+
+/// Create TMG speed records from the 15-minute speed range counts of a count, preceded by the
+/// declaration of the speed bins they use.
+pub fn speed_records(
+    metadata: &Metadata,
+    counts: &[TimeBinnedSpeedRangeCount],
+) -> Result<Vec<String>, CountError> {
+    let mut declaration = format!(
+        "B{}{}{:02}",
+        state_code(metadata)?,
+        station_id(metadata)?,
+        SPEED_BIN_UPPER_BOUNDS.len() + 1
+    );
+    for upper_bound in SPEED_BIN_UPPER_BOUNDS {
+        declaration.push_str(&format!("{upper_bound:03}"));
+    }
+
+    let hours = hourly_volumes(
+        counts,
+        |count| (count.time, count.direction),
+        |count| {
+            [
+                count.total,
+                count.s1,
+                count.s2,
+                count.s3,
+                count.s4,
+                count.s5,
+                count.s6,
+                count.s7,
+                count.s8,
+                count.s9,
+                count.s10,
+                count.s11,
+                count.s12,
+                count.s13,
+                count.s14,
+            ]
+        },
+    );
+    let mut records = vec![declaration];
+    for ((hour, direction), volumes) in hours {
+        records.push(hourly_record('S', metadata, hour, direction, &volumes)?);
+    }
+    Ok(records)
+}
+
+/// Sum the volumes of 15-minute counts by hour and direction (combining lanes), leaving out
+/// partial hours at the start and end of the count.
+///
+/// `key` gets the time and direction of a count and `volumes` its volumes.
+fn hourly_volumes<T: Clone, const N: usize>(
+    counts: &[T],
+    key: impl Fn(&T) -> (NaiveDateTime, Option<LaneDirection>),
+    volumes: impl Fn(&T) -> [u32; N],
+) -> BTreeMap<(NaiveDateTime, Option<LaneDirection>), [u32; N]> {
+    let mut counts = counts.to_vec();
+    PartialPeriods::Periods.trim(
+        &mut counts,
+        TimeInterval::Hour,
+        Some(TimeInterval::FifteenMin),
+        |count| key(count).0,
+    );
+
+    let mut hours = BTreeMap::new();
+    for count in &counts {
+        let (time, direction) = key(count);
+        let hour = time.date().and_hms_opt(time.hour(), 0, 0).unwrap();
+        let totals = hours.entry((hour, direction)).or_insert([0; N]);
+        for (total, volume) in totals.iter_mut().zip(volumes(count)) {
+            *total += volume;
+        }
+    }
+    hours
+}
+
+/// Create an hourly record (of classification or speed) of a type.
+fn hourly_record(
+    record_type: char,
+    metadata: &Metadata,
+    hour: NaiveDateTime,
+    direction: Option<LaneDirection>,
+    volumes: &[u32],
+) -> Result<String, CountError> {
+    let mut record = format!(
+        "{record_type}{}{}{}0{:02}{:02}{:02}{:02}",
+        state_code(metadata)?,
+        station_id(metadata)?,
+        direction_code(direction, metadata)?,
+        hour.year() % 100,
+        hour.month(),
+        hour.day(),
+        hour.hour(),
+    );
+    for volume in volumes {
+        record.push_str(&format!("{volume:05}"));
+    }
+    Ok(record)
+}
+
+/// Export counts in the database to a file of TMG records of a type, returning the number of
+/// records written.
+///
+/// Any existing file at `path` is overwritten.
+pub fn export_records(
+    conn: &Connection,
+    record_type: TmgRecordType,
+    recordnums: &[u32],
+    path: &Path,
+) -> Result<usize, CountError> {
+    let mut records = vec![];
+    for &recordnum in recordnums {
+        let metadata = db::get_metadata(conn, recordnum)?;
+        match record_type {
+            TmgRecordType::Volume => {
+                // (Without rows of both directions combined, which would count volumes twice.)
+                let counts = db::get_volume_count(conn, recordnum)?;
+                records.extend(volume_records(&metadata, &counts)?);
+            }
+            TmgRecordType::Class => {
+                let counts = TimeBinnedVehicleClassCount::select(conn, recordnum)?;
+                records.extend(class_records(&metadata, &counts)?);
+            }
+            TmgRecordType::Speed => {
+                let counts = TimeBinnedSpeedRangeCount::select(conn, recordnum)?;
+                records.extend(speed_records(&metadata, &counts)?);
+            }
+        }
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    for record in &records {
+        writeln!(file, "{record}")?;
+    }
+    file.flush()?;
+    Ok(records.len())
+}
+
+/// The FIPS code of the state of a count, which is the first two digits of its MCD.
+fn state_code(metadata: &Metadata) -> Result<String, CountError> {
+    let mcd = metadata
+        .mcd
+        .as_ref()
+        .ok_or(CountError::MissingMetadata("MCD"))?;
+    mcd.get(..2)
+        .filter(|state| state.chars().all(|c| c.is_ascii_digit()))
+        .map(|state| state.to_string())
+        .ok_or_else(|| CountError::InvalidMcd(mcd.clone()))
+}
+
+/// The functional classification of the road of a count: its functional class and whether it's
+/// rural or urban, e.g. "3U".
+fn functional_class(metadata: &Metadata) -> Result<String, CountError> {
+    let fc = metadata
+        .fc
+        .filter(|fc| (1..=7).contains(fc))
+        .ok_or(CountError::MissingMetadata("functional classification"))?;
+    let area = match metadata.isurban.as_deref() {
+        Some("Y" | "y") => 'U',
+        Some(_) => 'R',
+        None => return Err(CountError::MissingMetadata("urban/rural")),
+    };
+    Ok(format!("{fc}{area}"))
+}
+
+/// The six-character station ID of a count: its station ID in TC_HEADER, if it has one, or
+/// otherwise its recordnum.
+fn station_id(metadata: &Metadata) -> Result<String, CountError> {
+    let station_id = match &metadata.stationid {
+        Some(v) if !v.trim().is_empty() => v.trim().to_string(),
+        _ => metadata
+            .recordnum
+            .ok_or(CountError::MissingMetadata("recordnum"))?
+            .to_string(),
+    };
+    if station_id.len() > 6 {
+        return Err(CountError::InvalidStationId(station_id));
+    }
+
+    Ok(format!("{station_id:0>6}"))
+}
+
+/// The TMG code for the direction of travel.
+///
+/// Counts without a direction are of both directions combined, which is determined by the
+/// direction of the road.
+fn direction_code(
+    direction: Option<LaneDirection>,
+    metadata: &Metadata,
+) -> Result<char, CountError> {
+    let code = match direction {
+        Some(LaneDirection::North) => '1',
+        Some(LaneDirection::Northeast) => '2',
+        Some(LaneDirection::East) => '3',
+        Some(LaneDirection::Southeast) => '4',
+        Some(LaneDirection::South) => '5',
+        Some(LaneDirection::Southwest) => '6',
+        Some(LaneDirection::West) => '7',
+        Some(LaneDirection::Northwest) => '8',
+        None => match metadata.cntdir {
+            Some(RoadDirection::North | RoadDirection::South) => '9',
+            Some(RoadDirection::East | RoadDirection::West) => '0',
+            _ => return Err(CountError::MissingMetadata("direction")),
+        },
+    };
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        create_speed_and_class_count, denormalize::create_non_normal_bicycle_vol_count,
+        extract_from_file::Extract, FieldMetadata, FifteenMinuteBicycle, IndividualVehicle,
+    };
+
+    fn metadata() -> Metadata {
+        serde_json::from_str(
+            r#"{"recordnum": 167607, "mcd": "4210160000", "fc": 3, "isurban": "Y"}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn volume_records_correct() {
+        let path = Path::new("test_files/15minutebicycle/167607-ns-4175-na.csv");
+        let field_metadata = FieldMetadata::from_path(path).unwrap();
+        let counts = FifteenMinuteBicycle::extract(path).unwrap();
+        let hourly_counts = create_non_normal_bicycle_vol_count(&field_metadata, &counts);
+
+        // five days, two directions
+        let records = volume_records(&metadata(), &hourly_counts).unwrap();
+        assert_eq!(records.len(), 10);
+        assert!(records.iter().all(|record| record.len() == 141));
+
+        // Friday, September 22, 2023, northbound
+        assert_eq!(&records[0][..20], "3423U167607102309226");
+        let total: u32 = records
+            .iter()
+            .map(|record| {
+                (0..24)
+                    .map(|hour| record[20 + hour * 5..25 + hour * 5].parse::<u32>().unwrap())
+                    .sum::<u32>()
+            })
+            .sum();
+        assert_eq!(total, 511);
+    }
+
+    #[test]
+    fn volume_records_excludes_partial_days() {
+        let path = Path::new("test_files/15minutebicycle/167607-ns-4175-na.csv");
+        let field_metadata = FieldMetadata::from_path(path).unwrap();
+        let counts = FifteenMinuteBicycle::extract(path).unwrap();
+        let hourly_counts = create_non_normal_bicycle_vol_count(&field_metadata, &counts[4..]);
+
+        let records = volume_records(&metadata(), &hourly_counts).unwrap();
+        assert_eq!(records.len(), 8);
+    }
+
+    #[test]
+    fn volume_records_errs_without_station_metadata() {
+        let mut metadata = self::metadata();
+        metadata.mcd = None;
+        assert!(matches!(
+            volume_records(&metadata, &[]),
+            Err(CountError::MissingMetadata(_))
+        ));
+
+        let mut metadata = self::metadata();
+        metadata.stationid = Some("1234567".to_string());
+        assert!(matches!(
+            volume_records(&metadata, &[]),
+            Err(CountError::InvalidStationId(_))
+        ));
+    }
+
+    #[test]
+    fn class_records_correct() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let field_metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let (_, class_counts) = create_speed_and_class_count(
+            TimeInterval::FifteenMin,
+            field_metadata,
+            counted_vehicles,
+        );
+        let mut metadata = metadata();
+        metadata.recordnum = Some(166905);
+
+        // 48 whole hours, from 11am on November 6 to 11am on November 8, two directions
+        let records = class_records(&metadata, &class_counts).unwrap();
+        assert_eq!(records.len(), 96);
+        assert!(records.iter().all(|record| record.len() == 89));
+        assert_eq!(&records[0][..19], "C421669053023110611");
+
+        let volumes = |record: &String| {
+            (0..14)
+                .map(|i| record[19 + i * 5..24 + i * 5].parse::<u32>().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let total: u32 = records.iter().map(|record| volumes(record)[0]).sum();
+        assert_eq!(total, 8702);
+        assert!(records
+            .iter()
+            .all(|record| volumes(record)[1..].iter().sum::<u32>() <= volumes(record)[0]));
+    }
+
+    #[test]
+    fn speed_records_correct() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let field_metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let (speed_counts, _) = create_speed_and_class_count(
+            TimeInterval::FifteenMin,
+            field_metadata,
+            counted_vehicles,
+        );
+        let mut metadata = metadata();
+        metadata.recordnum = Some(166905);
+
+        // the declaration of the bins, and then 48 hours, two directions
+        let records = speed_records(&metadata, &speed_counts).unwrap();
+        assert_eq!(records.len(), 97);
+        assert_eq!(
+            records[0],
+            "B4216690514015020025030035040045050055060065070075"
+        );
+        assert!(records[1..].iter().all(|record| record.len() == 94));
+
+        let total: u32 = records[1..]
+            .iter()
+            .map(|record| record[19..24].parse::<u32>().unwrap())
+            .sum();
+        assert_eq!(total, 8702);
+    }
+}
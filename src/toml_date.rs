@@ -0,0 +1,66 @@
+//! Deserializing dates in TOML files.
+//!
+//! TOML has its own type of date (e.g. `start = 2023-09-22`), which [`NaiveDate`] can't be
+//! deserialized from, so fields of them are deserialized with [`deserialize`] (or
+//! [`option::deserialize`], for optional ones).
+use chrono::NaiveDate;
+use serde::{de::Error, Deserialize, Deserializer};
+use toml::value::Datetime;
+
+/// Deserialize a TOML date.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+    to_date(Datetime::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Deserializing optional dates.
+pub mod option {
+    use super::*;
+
+    /// Deserialize a TOML date, if there is one. (The field must also be `#[serde(default)]`,
+    /// for it to be none when it's missing.)
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDate>, D::Error> {
+        Option::<Datetime>::deserialize(deserializer)?
+            .map(to_date)
+            .transpose()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Convert a TOML date to a [`NaiveDate`]; it's an error for it to have a time.
+fn to_date(datetime: Datetime) -> Result<NaiveDate, String> {
+    match (datetime.date, datetime.time) {
+        (Some(date), None) => {
+            NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())
+                .ok_or_else(|| format!("{datetime} is not a valid date"))
+        }
+        _ => Err(format!("{datetime} is not a date (without a time)")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Dates {
+        #[serde(deserialize_with = "deserialize")]
+        start: NaiveDate,
+        #[serde(default, deserialize_with = "option::deserialize")]
+        end: Option<NaiveDate>,
+    }
+
+    #[test]
+    fn dates_deserialized() {
+        let dates: Dates = toml::from_str("start = 2023-09-22\nend = 2023-09-26").unwrap();
+        assert_eq!(dates.start, NaiveDate::from_ymd_opt(2023, 9, 22).unwrap());
+        assert_eq!(dates.end, NaiveDate::from_ymd_opt(2023, 9, 26));
+
+        let dates: Dates = toml::from_str("start = 2023-09-22").unwrap();
+        assert_eq!(dates.end, None);
+
+        assert!(toml::from_str::<Dates>("start = 2023-09-22T10:00:00").is_err());
+        assert!(toml::from_str::<Dates>("start = \"2023-09-22\"").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Unpack archives of count files.
+//!
+//! Technicians often upload several files for a site at once, in a zip archive, or compress a
+//! single large file with gzip. The files in an archive are [unpacked][unpack] into a directory -
+//! usually the one the archive is in - where they are imported like any other file, and so their
+//! names must follow the same filename specification.
+//!
+//! Directories within a zip archive are ignored; every file in it is unpacked directly into the
+//! directory, as if it had been uploaded there itself. A gzip file is unpacked to its name without
+//! the ".gz" extension.
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::CountError;
+
+/// Check if a file is an archive that can be unpacked, by its extension.
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("zip") || x.eq_ignore_ascii_case("gz"))
+}
+
+/// Unpack the files in an archive into a directory, returning their paths.
+///
+/// Existing files are not overwritten; if any file in the archive already exists in the
+/// directory, nothing more is unpacked and an error is returned.
+pub fn unpack(path: &Path, dir: &Path) -> Result<Vec<PathBuf>, CountError> {
+    fs::create_dir_all(dir)?;
+    if path
+        .extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("gz"))
+    {
+        let name = path
+            .file_stem()
+            .ok_or_else(|| CountError::BadPath(path.to_owned()))?;
+        let dest = dir.join(name);
+        copy_new(&mut GzDecoder::new(File::open(path)?), &dest)?;
+        return Ok(vec![dest]);
+    }
+
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut paths = vec![];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        // Only the file's name is used, which also keeps it from being unpacked outside `dir`.
+        let Some(name) = file
+            .enclosed_name()
+            .and_then(|v| v.file_name().map(PathBuf::from))
+        else {
+            return Err(CountError::BadPath(PathBuf::from(file.name())));
+        };
+        let dest = dir.join(name);
+        copy_new(&mut file, &dest)?;
+        paths.push(dest);
+    }
+    Ok(paths)
+}
+
+/// Copy the contents of a reader to a new file.
+fn copy_new(reader: &mut impl io::Read, dest: &Path) -> Result<(), CountError> {
+    let mut file = File::create_new(dest)?;
+    io::copy(reader, &mut file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    #[test]
+    fn zip_and_gzip_files_unpacked() {
+        let dir = std::env::temp_dir().join("traffic_counts_unpack");
+        let _ = fs::remove_dir_all(&dir);
+        let unpacked = dir.join("vehicle");
+        fs::create_dir_all(&dir).unwrap();
+        let contents = fs::read("test_files/vehicle/166905-ew-40972-35.txt").unwrap();
+
+        let zip_path = dir.join("bundle.zip");
+        let mut zip = ZipWriter::new(File::create(&zip_path).unwrap());
+        zip.add_directory("site/", SimpleFileOptions::default())
+            .unwrap();
+        for name in ["site/166905-ew-40972-35.txt", "166906-ew-40972-35.txt"] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(&contents).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let gz_path = dir.join("166907-ew-40972-35.txt.gz");
+        let mut gz = GzEncoder::new(File::create(&gz_path).unwrap(), Compression::default());
+        gz.write_all(&contents).unwrap();
+        gz.finish().unwrap();
+
+        assert!(is_archive(&zip_path) && is_archive(&gz_path));
+        assert!(!is_archive(Path::new("166905-ew-40972-35.txt")));
+        assert_eq!(
+            unpack(&zip_path, &unpacked).unwrap(),
+            vec![
+                unpacked.join("166905-ew-40972-35.txt"),
+                unpacked.join("166906-ew-40972-35.txt")
+            ]
+        );
+        let paths = unpack(&gz_path, &unpacked).unwrap();
+        assert_eq!(paths, vec![unpacked.join("166907-ew-40972-35.txt")]);
+        assert_eq!(fs::read(&paths[0]).unwrap(), contents);
+
+        // Files aren't overwritten.
+        assert!(unpack(&gz_path, &unpacked).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! The average volume of each hour of the day.
+//!
+//! Traffic engineers graph nearly every count as a time-of-day profile: the average volume of
+//! each hour of the day, separately for weekdays and weekends, in each direction. This is created
+//! from a count's hourly volumes ([`NonNormalVolCount`]s), which every kind of count has. Lanes in
+//! the same direction are combined, and only the hours of a day that were counted in all of them
+//! are averaged, so that the partial first and last days of a count don't drag the averages down.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{denormalize::NonNormalVolCount, LaneDirection};
+
+/// Whether a day is a weekday or on the weekend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DayType {
+    Weekday,
+    Weekend,
+}
+
+impl DayType {
+    pub fn from_date(date: NaiveDate) -> Self {
+        match date.weekday() {
+            Weekday::Sat | Weekday::Sun => DayType::Weekend,
+            _ => DayType::Weekday,
+        }
+    }
+}
+
+impl Display for DayType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let day_type = match self {
+            DayType::Weekday => "weekday",
+            DayType::Weekend => "weekend",
+        };
+        write!(f, "{}", day_type)
+    }
+}
+
+/// The average volume of one hour of the day, in one direction, on weekdays or weekend days.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HourlyVolume {
+    pub recordnum: u32,
+    pub direction: Option<LaneDirection>,
+    pub day_type: DayType,
+    /// The hour of the day, from 0 (12am) to 23 (11pm).
+    pub hour: u8,
+    /// The number of days this hour was counted on.
+    pub days: u32,
+    pub average: f32,
+}
+
+/// The time-of-day volume profile of a count.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeProfile {
+    pub recordnum: u32,
+    /// The average volume of each hour counted, in order by direction, day type, and hour.
+    pub hours: Vec<HourlyVolume>,
+}
+
+impl Display for VolumeProfile {
+    /// A table of the profile, with a row for each hour and a column for each direction and day
+    /// type.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut columns: BTreeMap<(Option<LaneDirection>, DayType), [Option<f32>; 24]> =
+            BTreeMap::new();
+        for hour in &self.hours {
+            columns
+                .entry((hour.direction, hour.day_type))
+                .or_insert([None; 24])[hour.hour as usize] = Some(hour.average);
+        }
+
+        write!(f, "{} volume profile\nhour", self.recordnum)?;
+        for (direction, day_type) in columns.keys() {
+            match direction {
+                Some(direction) => write!(f, "\t{direction:?} {day_type}")?,
+                None => write!(f, "\t{day_type}")?,
+            }
+        }
+        for hour in 0..24 {
+            write!(f, "\n{hour}")?;
+            for averages in columns.values() {
+                match averages[hour] {
+                    Some(average) => write!(f, "\t{average:.1}")?,
+                    None => write!(f, "\t")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Create the time-of-day volume profile of a count from its [`NonNormalVolCount`]s.
+///
+/// Returns `None` if there are no counts.
+pub fn create_volume_profile(counts: &[NonNormalVolCount]) -> Option<VolumeProfile> {
+    let recordnum = counts.first()?.recordnum;
+
+    // The volume of each hour of each day in each direction, if it was counted in every lane.
+    let mut days: BTreeMap<(Option<LaneDirection>, NaiveDate), [Option<u32>; 24]> = BTreeMap::new();
+    for count in counts {
+        let hours = count.hourly();
+        let day = days
+            .entry((count.direction, count.date))
+            .or_insert([Some(0); 24]);
+        for (volume, lane_volume) in day.iter_mut().zip(hours) {
+            *volume = volume.zip(lane_volume).map(|(a, b)| a + b);
+        }
+    }
+
+    // The total volume of each hour of the day, and the number of days it was counted on.
+    let mut totals: BTreeMap<(Option<LaneDirection>, DayType, u8), (u32, u32)> = BTreeMap::new();
+    for ((direction, date), volumes) in days {
+        for (hour, volume) in volumes.into_iter().enumerate() {
+            if let Some(volume) = volume {
+                let total = totals
+                    .entry((direction, DayType::from_date(date), hour as u8))
+                    .or_default();
+                total.0 += volume;
+                total.1 += 1;
+            }
+        }
+    }
+
+    let hours = totals
+        .into_iter()
+        .map(
+            |((direction, day_type, hour), (volume, days))| HourlyVolume {
+                recordnum,
+                direction,
+                day_type,
+                hour,
+                days,
+                average: volume as f32 / days as f32,
+            },
+        )
+        .collect();
+    Some(VolumeProfile { recordnum, hours })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A day's hourly volumes in one lane, with `volume` in each hour from `first` on.
+    fn count(date: &str, lane: u8, first: usize, volume: u32) -> NonNormalVolCount {
+        let mut hours = [None; 24];
+        for hour in hours.iter_mut().skip(first) {
+            *hour = Some(volume);
+        }
+        NonNormalVolCount {
+            recordnum: 123,
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            direction: Some(LaneDirection::East),
+            lane: Some(lane),
+            setflag: None,
+            totalcount: None,
+            am12: hours[0],
+            am1: hours[1],
+            am2: hours[2],
+            am3: hours[3],
+            am4: hours[4],
+            am5: hours[5],
+            am6: hours[6],
+            am7: hours[7],
+            am8: hours[8],
+            am9: hours[9],
+            am10: hours[10],
+            am11: hours[11],
+            pm12: hours[12],
+            pm1: hours[13],
+            pm2: hours[14],
+            pm3: hours[15],
+            pm4: hours[16],
+            pm5: hours[17],
+            pm6: hours[18],
+            pm7: hours[19],
+            pm8: hours[20],
+            pm9: hours[21],
+            pm10: hours[22],
+            pm11: hours[23],
+        }
+    }
+
+    #[test]
+    fn hours_averaged_over_weekdays_and_weekends_counted() {
+        // Friday (from 10am in lane 1, all day in lane 2), Saturday, and Monday.
+        let counts = vec![
+            count("2024-04-05", 1, 10, 10),
+            count("2024-04-05", 2, 0, 20),
+            count("2024-04-06", 1, 0, 5),
+            count("2024-04-08", 1, 0, 40),
+        ];
+        let profile = create_volume_profile(&counts).unwrap();
+        assert_eq!(profile.hours.len(), 48);
+
+        let hour = |day_type, hour| {
+            profile
+                .hours
+                .iter()
+                .find(|v| v.day_type == day_type && v.hour == hour)
+                .unwrap()
+        };
+        // Before 10am, Friday wasn't counted in lane 1, so only Monday is averaged.
+        assert_eq!(hour(DayType::Weekday, 9).days, 1);
+        assert_eq!(hour(DayType::Weekday, 9).average, 40.0);
+        assert_eq!(hour(DayType::Weekday, 10).days, 2);
+        assert_eq!(hour(DayType::Weekday, 10).average, 35.0);
+        assert_eq!(hour(DayType::Weekend, 23).average, 5.0);
+
+        assert!(create_volume_profile(&[]).is_none());
+    }
+}
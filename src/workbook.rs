@@ -0,0 +1,359 @@
+//! Export counts in the database as Excel workbooks.
+//!
+//! This is the deliverable member governments expect: one workbook per count, named
+//! `{recordnum}-counts.xlsx`, with these sheets:
+//!   - "Summary" - as in the count's [report][crate::report]: its metadata and location, the
+//!     volume of each full day, the share of vehicles of each class, statistics of their speeds,
+//!     and the issues found by the data checks,
+//!   - "15-minute class" - its [class counts][TimeBinnedVehicleClassCount] (TC_CLACOUNT),
+//!   - "Speed" - its [speed range counts][TimeBinnedSpeedRangeCount] (TC_SPECOUNT),
+//!   - "Hourly volume" - its [hourly volumes][NonNormalVolCount] (TC_VOLCOUNT), a row per day.
+//!
+//! The sheets of counts a count doesn't have are left with just their header.
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use oracle::Connection;
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+
+use crate::{
+    db,
+    denormalize::NonNormalVolCount,
+    location::Locations,
+    report::{create_report, fields, speed_range_names, CountReport},
+    CountError, LaneDirection, TimeBinnedSpeedRangeCount, TimeBinnedVehicleClassCount,
+    VehicleClass,
+};
+
+/// The formats of the cells of a workbook.
+struct Formats {
+    header: Format,
+    date: Format,
+    time: Format,
+    percent: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        Self {
+            header: Format::new().set_bold(),
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+            time: Format::new().set_num_format("hh:mm"),
+            percent: Format::new().set_num_format("0.0%"),
+        }
+    }
+}
+
+/// Export a count in the database as a workbook in `dir`, returning the path of the file.
+///
+/// Any existing file with the same path is overwritten.
+pub fn export_workbook(
+    conn: &Connection,
+    recordnum: u32,
+    locations: &Locations,
+    dir: &Path,
+) -> Result<PathBuf, CountError> {
+    let report = create_report(conn, recordnum, locations)?;
+    let path = dir.join(format!("{recordnum}-counts.xlsx"));
+    write_workbook(
+        &report,
+        &db::get_class_count(conn, recordnum)?,
+        &db::get_speed_count(conn, recordnum)?,
+        &db::get_volume_count(conn, recordnum)?,
+        &path,
+    )?;
+    Ok(path)
+}
+
+/// Write the summary and counts of a count to a workbook at `path`.
+pub fn write_workbook(
+    report: &CountReport,
+    class_counts: &[TimeBinnedVehicleClassCount],
+    speed_counts: &[TimeBinnedSpeedRangeCount],
+    volume_counts: &[NonNormalVolCount],
+    path: &Path,
+) -> Result<(), CountError> {
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+    write_summary(
+        workbook.add_worksheet().set_name("Summary")?,
+        report,
+        &formats,
+    )?;
+    write_class_counts(
+        workbook.add_worksheet().set_name("15-minute class")?,
+        class_counts,
+        &formats,
+    )?;
+    write_speed_counts(
+        workbook.add_worksheet().set_name("Speed")?,
+        speed_counts,
+        &formats,
+    )?;
+    write_volume_counts(
+        workbook.add_worksheet().set_name("Hourly volume")?,
+        volume_counts,
+        &formats,
+    )?;
+    workbook.save(path)?;
+    Ok(())
+}
+
+/// The name of a direction, with `None` being both directions combined.
+fn direction_name(direction: Option<LaneDirection>) -> String {
+    direction.map_or("both".to_string(), |v| v.to_string())
+}
+
+/// Write a row of headers, from the first column.
+fn write_header<T: AsRef<str>>(
+    sheet: &mut Worksheet,
+    row: u32,
+    headers: &[T],
+    formats: &Formats,
+) -> Result<(), CountError> {
+    for (col, header) in headers.iter().enumerate() {
+        sheet.write_with_format(row, col as u16, header.as_ref(), &formats.header)?;
+    }
+    Ok(())
+}
+
+/// Write the date, time, lane, and direction of a count in the first four columns of a row.
+fn write_time_bin(
+    sheet: &mut Worksheet,
+    row: u32,
+    date: &NaiveDate,
+    time: &NaiveDateTime,
+    lane: Option<u8>,
+    direction: Option<LaneDirection>,
+    formats: &Formats,
+) -> Result<(), CountError> {
+    sheet.write_datetime_with_format(row, 0, date, &formats.date)?;
+    sheet.write_datetime_with_format(row, 1, time.time(), &formats.time)?;
+    if let Some(lane) = lane {
+        sheet.write(row, 2, lane)?;
+    }
+    sheet.write(row, 3, direction_name(direction))?;
+    Ok(())
+}
+
+fn write_summary(
+    sheet: &mut Worksheet,
+    report: &CountReport,
+    formats: &Formats,
+) -> Result<(), CountError> {
+    sheet.set_column_width(0, 24)?;
+    sheet.set_column_width(1, 36)?;
+    sheet.write_with_format(0, 0, format!("Count {}", report.recordnum), &formats.header)?;
+    let mut row = 1;
+    for (name, value) in fields(report) {
+        sheet.write(row, 0, name)?;
+        sheet.write(row, 1, value)?;
+        row += 1;
+    }
+
+    row += 1;
+    write_header(sheet, row, &["Date", "Day", "Volume"], formats)?;
+    for (date, volume) in &report.daily {
+        row += 1;
+        sheet.write_datetime_with_format(row, 0, date, &formats.date)?;
+        sheet.write(row, 1, date.format("%A").to_string())?;
+        sheet.write(row, 2, *volume)?;
+    }
+
+    if !report.classes.is_empty() {
+        row += 2;
+        write_header(sheet, row, &["Class", "Name", "Vehicles", "Share"], formats)?;
+        for class in &report.classes {
+            row += 1;
+            sheet.write(row, 0, class.class.as_num())?;
+            sheet.write(row, 1, class.class.to_string())?;
+            sheet.write(row, 2, class.vehicles)?;
+            sheet.write_with_format(row, 3, class.percent / 100.0, &formats.percent)?;
+        }
+    }
+
+    if let Some(speeds) = &report.speeds {
+        row += 2;
+        write_header(sheet, row, &["Speed", "Vehicles"], formats)?;
+        for (range, vehicles) in speed_range_names().into_iter().zip(speeds.ranges) {
+            row += 1;
+            sheet.write(row, 0, range)?;
+            sheet.write(row, 1, vehicles)?;
+        }
+        for (name, speed) in [
+            ("Median speed (mph)", speeds.p50),
+            ("85th percentile speed (mph)", speeds.p85),
+        ] {
+            row += 1;
+            sheet.write(row, 0, name)?;
+            if let Some(speed) = speed {
+                sheet.write(row, 1, speed)?;
+            }
+        }
+    }
+
+    row += 2;
+    write_header(sheet, row, &["Data checks"], formats)?;
+    if report.warnings.is_empty() {
+        sheet.write(row + 1, 0, "No issues found")?;
+    }
+    for warning in &report.warnings {
+        row += 1;
+        sheet.write(row, 0, warning)?;
+    }
+    Ok(())
+}
+
+fn write_class_counts(
+    sheet: &mut Worksheet,
+    counts: &[TimeBinnedVehicleClassCount],
+    formats: &Formats,
+) -> Result<(), CountError> {
+    let mut headers = ["Date", "Time", "Lane", "Direction"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    headers.extend(VehicleClass::iter().map(|v| v.to_string()));
+    headers.push("Total".to_string());
+    write_header(sheet, 0, &headers, formats)?;
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.set_column_width(0, 12)?;
+
+    for (row, count) in (1..).zip(counts) {
+        write_time_bin(
+            sheet,
+            row,
+            &count.date,
+            &count.time,
+            count.lane,
+            count.direction,
+            formats,
+        )?;
+        for (col, class) in (4..).zip(VehicleClass::iter()) {
+            sheet.write(row, col, count.class(class))?;
+        }
+        sheet.write(row, headers.len() as u16 - 1, count.total)?;
+    }
+    Ok(())
+}
+
+fn write_speed_counts(
+    sheet: &mut Worksheet,
+    counts: &[TimeBinnedSpeedRangeCount],
+    formats: &Formats,
+) -> Result<(), CountError> {
+    let mut headers = ["Date", "Time", "Lane", "Direction"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    headers.extend(speed_range_names());
+    headers.push("Total".to_string());
+    write_header(sheet, 0, &headers, formats)?;
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.set_column_width(0, 12)?;
+
+    for (row, count) in (1..).zip(counts) {
+        write_time_bin(
+            sheet,
+            row,
+            &count.date,
+            &count.time,
+            count.lane,
+            count.direction,
+            formats,
+        )?;
+        let ranges = [
+            count.s1, count.s2, count.s3, count.s4, count.s5, count.s6, count.s7, count.s8,
+            count.s9, count.s10, count.s11, count.s12, count.s13, count.s14,
+        ];
+        for (col, vehicles) in (4..).zip(ranges) {
+            sheet.write(row, col, vehicles)?;
+        }
+        sheet.write(row, headers.len() as u16 - 1, count.total)?;
+    }
+    Ok(())
+}
+
+fn write_volume_counts(
+    sheet: &mut Worksheet,
+    counts: &[NonNormalVolCount],
+    formats: &Formats,
+) -> Result<(), CountError> {
+    let mut headers = ["Date", "Direction", "Lane"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    headers.extend((0..24).map(|hour| format!("{hour:02}:00")));
+    headers.push("Total".to_string());
+    write_header(sheet, 0, &headers, formats)?;
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.set_column_width(0, 12)?;
+
+    for (row, count) in (1..).zip(counts) {
+        sheet.write_datetime_with_format(row, 0, count.date, &formats.date)?;
+        sheet.write(row, 1, direction_name(count.direction))?;
+        if let Some(lane) = count.lane {
+            sheet.write(row, 2, lane)?;
+        }
+        let hours = count.hourly();
+        // Hours not counted (at the start and end of a count) are left blank.
+        for (col, volume) in (3..).zip(hours) {
+            if let Some(volume) = volume {
+                sheet.write(row, col, volume)?;
+            }
+        }
+        if let Some(total) = count.totalcount {
+            sheet.write(row, headers.len() as u16 - 1, total)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::{
+        create_speed_and_class_count, db::LocatedMetadata, extract_from_file::Extract,
+        location::Location, FieldMetadata, IndividualVehicle, Metadata, TimeInterval,
+    };
+
+    #[test]
+    fn workbook_written_with_sheet_of_each_kind_of_count() {
+        let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+        let metadata = FieldMetadata::from_path(path).unwrap();
+        let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+        let (speed_counts, class_counts) =
+            create_speed_and_class_count(TimeInterval::FifteenMin, metadata, counted_vehicles);
+        let report = CountReport {
+            recordnum: 166905,
+            metadata: LocatedMetadata {
+                metadata: Metadata::default(),
+                location: Location::default(),
+            },
+            daily: Default::default(),
+            profile: None,
+            classes: vec![],
+            speeds: None,
+            warnings: vec![],
+        };
+
+        let path = std::env::temp_dir().join("166905-counts.xlsx");
+        write_workbook(&report, &class_counts, &speed_counts, &[], &path).unwrap();
+
+        let mut workbook = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut sheets = String::new();
+        workbook
+            .by_name("xl/workbook.xml")
+            .unwrap()
+            .read_to_string(&mut sheets)
+            .unwrap();
+        for name in ["Summary", "15-minute class", "Speed", "Hourly volume"] {
+            assert!(sheets.contains(&format!("name=\"{name}\"")));
+        }
+        assert!(workbook.by_name("xl/worksheets/sheet4.xml").is_ok());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+//! Running the import pipeline - extracting data from a file, creating counts from it, and
+//! storing them - with a SQLite store rather than our database.
+#![cfg(feature = "sqlite")]
+
+use std::path::Path;
+
+use traffic_counts::{
+    db::{
+        sqlite::SqliteStore,
+        store::{store_vehicle_counts, CountStore, StoredCount},
+    },
+    denormalize::*,
+    extract_from_file::Extract,
+    *,
+};
+
+fn roundtrip<T: StoredCount>(store: &SqliteStore, recordnum: u32, records: &[T]) {
+    store.insert(records).unwrap();
+    let stored = store.get::<T>(recordnum).unwrap();
+    assert_eq!(stored.len(), records.len());
+    assert_eq!(
+        serde_json::to_string(&stored).unwrap(),
+        serde_json::to_string(records).unwrap()
+    );
+}
+
+#[test]
+fn individual_vehicle_counts_stored_and_retrieved() {
+    let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+    let metadata = FieldMetadata::from_path(path).unwrap();
+    let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+    let (speed_range_count, vehicle_class_count) = create_speed_and_class_count(
+        TimeInterval::FifteenMin,
+        metadata.clone(),
+        counted_vehicles.clone(),
+    );
+    let non_normal_speedavg_count = create_non_normal_speedavg_count(metadata, counted_vehicles);
+
+    let store = SqliteStore::open_in_memory().unwrap();
+    roundtrip(&store, 166905, &speed_range_count);
+    roundtrip(&store, 166905, &vehicle_class_count);
+    roundtrip(&store, 166905, &non_normal_speedavg_count);
+
+    store.delete::<TimeBinnedVehicleClassCount>(166905).unwrap();
+    assert!(store
+        .get::<TimeBinnedVehicleClassCount>(166905)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        store
+            .get::<TimeBinnedSpeedRangeCount>(166905)
+            .unwrap()
+            .len(),
+        speed_range_count.len()
+    );
+}
+
+#[test]
+fn vehicle_counts_replaced_when_stored_again() {
+    let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+    let metadata = FieldMetadata::from_path(path).unwrap();
+    let counted_vehicles = IndividualVehicle::extract(path).unwrap();
+    let store = SqliteStore::open_in_memory().unwrap();
+
+    let stored = store_vehicle_counts(&store, &metadata, &counted_vehicles).unwrap();
+    assert_eq!(
+        store_vehicle_counts(&store, &metadata, &counted_vehicles).unwrap(),
+        stored
+    );
+    let tables = stored.iter().map(|(table, _)| *table).collect::<Vec<_>>();
+    assert_eq!(tables, ["tc_clacount", "tc_specount", "tc_spesum"]);
+    assert_eq!(
+        store
+            .get::<TimeBinnedVehicleClassCount>(166905)
+            .unwrap()
+            .len(),
+        stored[0].1
+    );
+}
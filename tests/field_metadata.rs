@@ -7,40 +7,40 @@ fn field_metadata_parse_from_path_ok() {
     let path = Path::new("some/path/166905-e-40972-35.txt");
     let field_metadata = FieldMetadata::from_path(path).unwrap();
     let expected_field_metadata = {
-        FieldMetadata {
-            recordnum: 166905,
-            directions: Directions::new(LaneDirection::East, None, None),
-            counter_id: 40972.to_string(),
-            speed_limit: Some(35),
-        }
+        FieldMetadata::new(
+            166905,
+            Directions::new(LaneDirection::East, None, None),
+            40972.to_string(),
+            Some(35),
+        )
     };
     assert_eq!(field_metadata, expected_field_metadata);
 
     let path = Path::new("some/path/166905-ew-40972-35.txt");
     let field_metadata = FieldMetadata::from_path(path).unwrap();
     let expected_field_metadata = {
-        FieldMetadata {
-            recordnum: 166905,
-            directions: Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
-            counter_id: 40972.to_string(),
-            speed_limit: Some(35),
-        }
+        FieldMetadata::new(
+            166905,
+            Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
+            40972.to_string(),
+            Some(35),
+        )
     };
     assert_eq!(field_metadata, expected_field_metadata);
 
     let path = Path::new("some/path/166905-eee-40972-35.txt");
     let field_metadata = FieldMetadata::from_path(path).unwrap();
     let expected_field_metadata = {
-        FieldMetadata {
-            recordnum: 166905,
-            directions: Directions::new(
+        FieldMetadata::new(
+            166905,
+            Directions::new(
                 LaneDirection::East,
                 Some(LaneDirection::East),
                 Some(LaneDirection::East),
             ),
-            counter_id: 40972.to_string(),
-            speed_limit: Some(35),
-        }
+            40972.to_string(),
+            Some(35),
+        )
     };
     assert_eq!(field_metadata, expected_field_metadata);
 }
@@ -50,12 +50,12 @@ fn field_metadata_parse_from_path_ok_with_na_speed_limit() {
     let path = Path::new("some/path/166905-ew-40972-na.txt");
     let field_metadata = FieldMetadata::from_path(path).unwrap();
     let expected_field_metadata = {
-        FieldMetadata {
-            recordnum: 166905,
-            directions: Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
-            counter_id: 40972.to_string(),
-            speed_limit: None,
-        }
+        FieldMetadata::new(
+            166905,
+            Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
+            40972.to_string(),
+            None,
+        )
     };
     assert_eq!(field_metadata, expected_field_metadata)
 }
@@ -151,3 +151,139 @@ fn field_metadata_parse_from_path_errs_if_speedlimit_bad() {
         })
     ))
 }
+
+#[test]
+fn field_metadata_json_roundtrip() {
+    let path = Path::new("some/path/166905-ew-40972-35.txt");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    let json = serde_json::to_string(&field_metadata).unwrap();
+    assert!(json.contains(r#""direction1":"East""#));
+    let deserialized: FieldMetadata = serde_json::from_str(&json).unwrap();
+    assert_eq!(field_metadata, deserialized);
+}
+
+#[test]
+fn field_metadata_from_sidecar_file_ok() {
+    let path = Path::new("test_files/sidecar/166905.csv");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    let expected_field_metadata = FieldMetadata::new(
+        166905,
+        Directions::new(LaneDirection::East, Some(LaneDirection::West), None),
+        40972.to_string(),
+        Some(35),
+    );
+    assert_eq!(field_metadata, expected_field_metadata);
+}
+
+#[test]
+fn field_metadata_from_directory_sidecar_file_ok() {
+    let path = Path::new("test_files/sidecar/dir/any-name.csv");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    let expected_field_metadata = FieldMetadata::new(
+        165367,
+        Directions::new(LaneDirection::North, None, None),
+        38397.to_string(),
+        None,
+    );
+    assert_eq!(field_metadata, expected_field_metadata);
+}
+
+#[test]
+fn field_metadata_parse_from_path_ok_with_intermediate_directions() {
+    let path = Path::new("some/path/166905-ne-40972-35.txt");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    assert_eq!(
+        field_metadata.directions,
+        Directions::new(LaneDirection::Northeast, None, None)
+    );
+
+    let path = Path::new("some/path/166905-nesw-40972-35.txt");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    assert_eq!(
+        field_metadata.directions,
+        Directions::new(
+            LaneDirection::Northeast,
+            Some(LaneDirection::Southwest),
+            None
+        )
+    );
+
+    let path = Path::new("some/path/166905-sesese-40972-35.txt");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    assert_eq!(
+        field_metadata.directions,
+        Directions::new(
+            LaneDirection::Southeast,
+            Some(LaneDirection::Southeast),
+            Some(LaneDirection::Southeast)
+        )
+    );
+}
+
+#[test]
+fn field_metadata_parse_from_path_errs_if_intermediate_directions_bad() {
+    for directions in ["nee", "nese", "nwsw", "nenesw"] {
+        let path = format!("some/path/166905-{directions}-40972-35.txt");
+        assert!(matches!(
+            FieldMetadata::from_path(Path::new(&path)),
+            Err(CountError::InvalidFileName {
+                problem: FileNameProblem::InvalidDirections,
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn field_metadata_from_sidecar_file_with_channels_ok() {
+    let path = Path::new("test_files/sidecar/multilane/count.csv");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    assert_eq!(field_metadata.channels.len(), 4);
+    assert_eq!(
+        field_metadata.channels[&2],
+        Channel {
+            direction: LaneDirection::North,
+            lane: 2
+        }
+    );
+    assert_eq!(
+        field_metadata.channels[&4],
+        Channel {
+            direction: LaneDirection::South,
+            lane: 4
+        }
+    );
+}
+
+#[test]
+fn field_metadata_channels_default_to_directions() {
+    let path = Path::new("some/path/166905-ew-40972-35.txt");
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+    assert_eq!(field_metadata.channels.len(), 2);
+    assert_eq!(
+        field_metadata.channels[&1],
+        Channel {
+            direction: LaneDirection::East,
+            lane: 1
+        }
+    );
+    assert_eq!(
+        field_metadata.channels[&2],
+        Channel {
+            direction: LaneDirection::West,
+            lane: 2
+        }
+    );
+}
+
+#[test]
+fn field_metadata_from_sidecar_file_errs_if_lane_has_two_directions() {
+    let path = Path::new("test_files/sidecar/conflicting_lanes/count.csv");
+    assert!(matches!(
+        FieldMetadata::from_path(path),
+        Err(CountError::InvalidField {
+            field: "channels",
+            ..
+        })
+    ));
+}
@@ -305,3 +305,116 @@ fn counts_created_correctly_101() {
     assert_eq!(speed_range_count.last().unwrap().total, 5);
     assert_eq!(vehicle_class_count.last().unwrap().total, 5);
 }
+
+#[test]
+fn counts_created_correctly_with_channel_mapping() {
+    let mut field_metadata = FieldMetadata::new(
+        170000,
+        Directions::new(LaneDirection::North, Some(LaneDirection::South), None),
+        40972.to_string(),
+        Some(45),
+    );
+    field_metadata.channels = [
+        (1, LaneDirection::North, 1),
+        (2, LaneDirection::North, 2),
+        (3, LaneDirection::South, 3),
+        (4, LaneDirection::South, 4),
+    ]
+    .into_iter()
+    .map(|(channel, direction, lane)| (channel, Channel { direction, lane }))
+    .collect();
+
+    let time = NaiveDateTime::parse_from_str("2024-04-08 10:05", "%Y-%m-%d %H:%M").unwrap();
+    let individual_vehicles = [1, 2, 2, 3, 4, 4, 4]
+        .into_iter()
+        .map(|channel| IndividualVehicle::new(time.date(), time, channel, 2, 40.0).unwrap())
+        .collect::<Vec<_>>();
+
+    let (speed_range_count, mut vehicle_class_count) = create_speed_and_class_count(
+        TimeInterval::FifteenMin,
+        field_metadata,
+        individual_vehicles,
+    );
+    vehicle_class_count.sort_unstable_by_key(|count| count.lane);
+
+    assert_eq!(speed_range_count.len(), 4);
+    assert_eq!(vehicle_class_count.len(), 4);
+    let totals = vehicle_class_count
+        .iter()
+        .map(|c| (c.lane.unwrap(), c.direction.unwrap(), c.total))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        totals,
+        vec![
+            (1, LaneDirection::North, 1),
+            (2, LaneDirection::North, 2),
+            (3, LaneDirection::South, 1),
+            (4, LaneDirection::South, 3),
+        ]
+    );
+}
+
+#[test]
+fn counts_ordered_by_time_and_lane_166905() {
+    let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+    let individual_vehicles = IndividualVehicle::extract(path).unwrap();
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+
+    let (speed_range_count, vehicle_class_count) = create_speed_and_class_count(
+        TimeInterval::FifteenMin,
+        field_metadata,
+        individual_vehicles,
+    );
+
+    // No sorting is needed: they are returned in order.
+    assert!(speed_range_count
+        .windows(2)
+        .all(|v| (v[0].time, v[0].lane) < (v[1].time, v[1].lane)));
+    assert!(vehicle_class_count
+        .windows(2)
+        .all(|v| (v[0].time, v[0].lane) < (v[1].time, v[1].lane)));
+}
+
+#[test]
+fn class_by_speed_consistent_with_class_and_speed_counts_166905() {
+    let path = Path::new("test_files/vehicle/166905-ew-40972-35.txt");
+    let individual_vehicles = IndividualVehicle::extract(path).unwrap();
+    let field_metadata = FieldMetadata::from_path(path).unwrap();
+
+    let (speed_range_count, _) = create_speed_and_class_count(
+        TimeInterval::FifteenMin,
+        field_metadata.clone(),
+        individual_vehicles.clone(),
+    );
+    let class_by_speed = create_class_by_speed_count(
+        TimeInterval::FifteenMin,
+        field_metadata,
+        individual_vehicles.clone(),
+    );
+
+    // One per period and direction (each direction of this count has one lane).
+    assert_eq!(class_by_speed.len(), speed_range_count.len());
+    assert_eq!(
+        class_by_speed.iter().map(|c| c.total).sum::<u32>(),
+        individual_vehicles.len() as u32
+    );
+
+    // Each speed range's count is the sum of those of each class in it.
+    for (cross_tab, speeds) in class_by_speed.iter().zip(&speed_range_count) {
+        assert_eq!(cross_tab.time, speeds.time);
+        let in_s5 = cross_tab.counts.iter().map(|c| c[4]).sum::<u32>();
+        assert_eq!(in_s5, speeds.s5);
+    }
+
+    // The count of one class in one speed range.
+    let cars_in_s6 = class_by_speed
+        .iter()
+        .map(|c| c.get(&VehicleClass::PassengerCars, 6))
+        .sum::<u32>();
+    let expected = individual_vehicles
+        .iter()
+        .filter(|v| matches!(v.class, VehicleClass::PassengerCars))
+        .filter(|v| intermediate::speed_range(v.speed) == Some(6))
+        .count() as u32;
+    assert_eq!(cars_in_s6, expected);
+}